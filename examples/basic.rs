@@ -1,6 +1,6 @@
 include!("common/common.rs");
 
-use libtxc::{LogLevel, Stream, TransaqConnector};
+use libtxc::{ConnectCommand, LogLevel, Stream, TransaqConnector};
 use tracing::info;
 
 // запуск примера:
@@ -19,24 +19,18 @@ fn main() -> anyhow::Result<()> {
     В этом примере поступающие сообщения выводятся в терминал.
     */
 
-    txc.input_stream().subscribe(|buf| info!("{buf}"));
+    txc.input_stream().subscribe(|buf| info!("{buf}"))?;
 
     // Создание канала для отправки команд
     let sender = txc.sender();
 
     // Отправка команды подключения
-    let connect = format!(
-        "<command id=\"connect\">
-            <login>{login}</login>
-            <password>{password}</password>
-            <host>tr1.finam.ru</host>
-            <port>3900</port>
-        </command>\0"
-    );
+    let connect =
+        ConnectCommand::new().login(login).password(password).host("tr1.finam.ru").port(3900);
 
     info!("Sending 'connect' command");
 
-    info!("{}", unsafe { sender.send(connect)? });
+    info!("{}", unsafe { sender.send(connect.build())? });
 
     // При успешном подключении сервер начнёт отправку чудовищного массива данных,
     // это займёт до 20 сек.
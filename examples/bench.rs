@@ -1,8 +1,14 @@
 include!("common/common.rs");
 
-use std::time::Instant;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use libtxc::{LogLevel, Stream, TransaqConnector};
+use libtxc::{AllTradesResponse, LogLevel, OwnedMessage, Stream, TransaqConnector};
 use tracing::info;
 
 // запуск примера:
@@ -30,7 +36,7 @@ fn main() -> anyhow::Result<()> {
         let delta = (now - std::mem::replace(&mut prev, now)).as_micros();
         unsafe { *deltas.as_mut_ptr().add(i) = delta as usize };
         i += 1;
-    });
+    })?;
 
     let get_version = "<command id = \"get_connector_version\"/>\0";
 
@@ -60,5 +66,67 @@ fn main() -> anyhow::Result<()> {
         );
     }
 
+    // Пропускная способность: обработчик в потоке коннектора и в отдельном потоке, сообщения
+    // в который передаются через очередь `subscribe_spsc`
+    for spsc in [false, true] {
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&received);
+        let handler = move |_: OwnedMessage| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        };
+
+        let stream = txc.input_stream().map(|buf| buf.to_owned_message());
+        if spsc {
+            stream.subscribe_spsc(handler, 1 << 12)?;
+        } else {
+            stream.subscribe(handler)?;
+        }
+
+        let start = Instant::now();
+        for _ in 0..N {
+            let _ = unsafe { sender.send(get_version) };
+        }
+        while received.load(Ordering::Relaxed) < N && start.elapsed() < Duration::from_secs(10) {
+            std::thread::yield_now();
+        }
+
+        info!(
+            "{}: {:.0} msg/s",
+            if spsc { "spsc" } else { "inline" },
+            received.load(Ordering::Relaxed) as f64 / start.elapsed().as_secs_f64()
+        );
+    }
+
+    // Скорость разбора `<alltrades>`, 5000 сделок в сообщении
+    let mut alltrades = String::from("<alltrades>");
+    for i in 0..5000 {
+        alltrades.push_str(&format!(
+            "<trade secid=\"3\"><seccode>SBER</seccode><board>TQBR</board>\
+             <tradeno>{}</tradeno><time>17.04.2023 10:{:02}:{:02}.{:03}</time>\
+             <price>{}.{:02}</price><quantity>{}</quantity><buysell>{}</buysell>\
+             <period>N</period></trade>",
+            7_000_000_000u64 + i,
+            i / 60 % 60,
+            i % 60,
+            i % 1000,
+            230 + i % 7,
+            i % 100,
+            1 + i % 50,
+            if i % 2 == 0 { 'B' } else { 'S' },
+        ));
+    }
+    alltrades.push_str("</alltrades>");
+
+    const ROUNDS: usize = 200;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let trades = AllTradesResponse::parse(alltrades.as_bytes())?;
+        assert_eq!(trades.trades.len(), 5000);
+    }
+    info!(
+        "alltrades: {:.0} MB/s",
+        (alltrades.len() * ROUNDS) as f64 / start.elapsed().as_secs_f64() / 1e6
+    );
+
     Ok(())
 }
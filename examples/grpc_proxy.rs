@@ -0,0 +1,32 @@
+include!("common/common.rs");
+
+use libtxc::{GrpcService, LogLevel, TransaqConnector};
+use tracing::info;
+
+// запуск примера:
+// cargo run --release --features grpc --example grpc_proxy
+//
+// gRPC сервер, предоставляющий доступ к коннектору другим процессам, описание сервиса в
+// `proto/txc.proto`. Адрес сервера задаётся переменной `TXC_GRPC_ADDR`, по умолчанию
+// `127.0.0.1:50051`.
+fn main() -> anyhow::Result<()> {
+    let (_, _, lib, logdir) = init()?;
+    init_logging();
+
+    let addr = std::env::var("TXC_GRPC_ADDR").unwrap_or_else(|_| "127.0.0.1:50051".into());
+    let addr = addr.parse()?;
+
+    let mut txc = TransaqConnector::new(lib.into(), logdir.into(), LogLevel::Minimum)?;
+
+    // Сервис заменяет обработчик входящих сообщений и передаёт их подписчикам `Subscribe`,
+    // команды `Send` отправляются через `Sender` коннектора.
+    let service = GrpcService::new(&mut txc)?;
+
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    info!("Listening on {addr}");
+    rt.block_on(
+        tonic::transport::Server::builder().add_service(service.into_server()).serve(addr),
+    )?;
+
+    Ok(())
+}
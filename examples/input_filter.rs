@@ -1,6 +1,6 @@
 include!("common/common.rs");
 
-use libtxc::{LogLevel, Stream, TransaqConnector};
+use libtxc::{ConnectCommand, LogLevel, Stream, TransaqConnector};
 use tracing::info;
 
 // запуск примера:
@@ -15,28 +15,25 @@ fn main() -> anyhow::Result<()> {
 
     // Обьект, возвращённый `TransaqConnector::input_stream()`, реализует `libtxc::Stream`,
     // содержащий методы для компоновки конвейера обработки входящих сoобщений, этот пример
-    // демонстрирует использование комбинаторов `map` и `filter` для фильтрации на основе xml тэга.
+    // демонстрирует использование комбинатора `filter_tags` для фильтрации на основе xml тэга,
+    // без преобразования каждого сообщения в строку.
     //
     // см. `libtxc::Stream` для списка доступных комбинаторов.
 
-    let is_result = |msg: &str| msg.starts_with("<result");
-    let is_error = |msg: &str| msg.starts_with("<error");
-    let is_server_status = |msg: &str| msg.starts_with("<server_status");
-
     txc.input_stream()
+        .filter_tags(["result", "error", "server_status"])
         .map(|buf| buf.to_string_lossy().to_string())
-        .filter(move |msg| is_result(msg) || is_error(msg) || is_server_status(msg))
-        .subscribe(|msg| info!("{msg}"));
+        .subscribe(|msg| info!("{msg}"))?;
 
     unsafe {
-        txc.sender().send(format!(
-            "<command id=\"connect\">
-                <login>{login}</login>
-                <password>{password}</password>
-                <host>tr1.finam.ru</host>
-                <port>3900</port>
-            </command>\0"
-        ))?
+        txc.sender().send(
+            ConnectCommand::new()
+                .login(login)
+                .password(password)
+                .host("tr1.finam.ru")
+                .port(3900)
+                .build(),
+        )?
     };
 
     std::thread::sleep(std::time::Duration::from_secs(20));
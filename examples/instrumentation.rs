@@ -1,6 +1,6 @@
 include!("common/common.rs");
 
-use libtxc::{LogLevel, Stream, TransaqConnector};
+use libtxc::{ConnectCommand, LogLevel, Stream, TransaqConnector};
 use tracing_subscriber::layer::SubscriberExt;
 
 // запуск примера:
@@ -24,18 +24,15 @@ fn main() -> anyhow::Result<()> {
 
     let mut txc = TransaqConnector::new(lib.into(), logdir.into(), LogLevel::Minimum)?;
 
-    txc.input_stream().subscribe(|buf| println!("{buf}"));
+    txc.input_stream().subscribe(|buf| println!("{buf}"))?;
 
     let sender = txc.sender();
-    let connect = format!(
-        r#"
-    <command id="connect">
-        <login>{login}</login>
-        <password>{password}</password>
-        <host>tr1.finam.ru</host>
-        <port>3900</port>
-    </command>"#,
-    );
+    let connect = ConnectCommand::new()
+        .login(login)
+        .password(password)
+        .host("tr1.finam.ru")
+        .port(3900)
+        .build();
 
     unsafe { sender.send(connect) }?;
     // на данном этапе 'tracy' начнёт получать метрики и обновлять GUI.
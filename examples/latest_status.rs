@@ -0,0 +1,44 @@
+include!("common/common.rs");
+
+use libtxc::{ConnectCommand, LogLevel, ServerStatusResponse, Stream, TransaqConnector};
+use std::time::Duration;
+use tracing::info;
+
+// запуск примера:
+// cargo run --release --example latest_status
+//
+// Чтение последнего состояния подключения из потока стратегии, без канала сообщений.
+fn main() -> anyhow::Result<()> {
+    let (login, password, lib, logdir) = init()?;
+    init_logging();
+
+    let mut txc = TransaqConnector::new(lib.into(), logdir.into(), LogLevel::Minimum)?;
+
+    let status =
+        txc.input_stream().filter_map(|buf| ServerStatusResponse::try_from(&buf).ok()).latest()?;
+
+    // поток стратегии опрашивает последнее состояние подключения в своём цикле
+    let strategy = std::thread::spawn(move || {
+        for _ in 0..30 {
+            if status.changed(Duration::from_secs(1)) {
+                info!("server_status changed: {:?}", status.get());
+            }
+            let connected = status.get().map_or(false, |status| status.is_connected());
+            info!("strategy tick, connected: {connected}");
+        }
+    });
+
+    let sender = txc.sender();
+    let connect = ConnectCommand::new()
+        .login(login)
+        .password(password)
+        .host("tr1.finam.ru")
+        .port(3900)
+        .build();
+    unsafe { sender.send(connect) }?;
+
+    strategy.join().expect("strategy thread panicked");
+    unsafe { sender.send("<command id=\"disconnect\"/>") }?;
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+include!("common/common.rs");
+
+use libtxc::{ConnectCommand, LogLevel, Stream, TransaqConnector};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// запуск примера:
+// cargo run --release --example metrics
+//
+// Оценка пропускной способности конвейера обработки входящих сообщений.
+//
+// Счётчики `StreamMetrics` обновляются в потоке коннектора и опрашиваются раз в секунду
+// из основного потока.
+fn main() -> anyhow::Result<()> {
+    let (login, password, lib, logdir) = init()?;
+    init_logging();
+
+    let mut txc = TransaqConnector::new(lib.into(), logdir.into(), LogLevel::Minimum)?;
+
+    let (stream, metrics) = txc.input_stream().metered();
+    stream.subscribe(|_| {})?;
+
+    let sender = txc.sender();
+    let connect = ConnectCommand::new()
+        .login(login)
+        .password(password)
+        .host("tr1.finam.ru")
+        .port(3900)
+        .build();
+    unsafe { sender.send(connect) }?;
+
+    let (mut messages, mut bytes) = (0, 0);
+    for _ in 0..30 {
+        std::thread::sleep(Duration::from_secs(1));
+        let (m, b) = (metrics.messages(), metrics.bytes());
+        let idle = metrics.last_arrival().map(|t| Instant::now() - t);
+        info!("{} msg/s, {} KiB/s, idle {:?}", m - messages, (b - bytes) / 1024, idle);
+        messages = m;
+        bytes = b;
+    }
+
+    unsafe { sender.send("<command id=\"disconnect\"/>") }?;
+
+    Ok(())
+}
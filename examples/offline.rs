@@ -22,7 +22,7 @@ fn main() -> anyhow::Result<()> {
     */
     txc.input_stream().subscribe(move |buf| {
         let _ = tx.send(buf.to_string_lossy().to_string());
-    });
+    })?;
 
     // Создание канала для отправки команд
     let sender = txc.sender();
@@ -0,0 +1,39 @@
+include!("common/common.rs");
+
+use libtxc::{ConnectCommand, Decimal, LogLevel, Stream, TradesResponse, TransaqConnector};
+use tracing::info;
+
+// запуск примера:
+// cargo run --release --example rolling_mean
+//
+// Скользящее среднее цены собственных сделок по последним 20 сделкам.
+fn main() -> anyhow::Result<()> {
+    let (login, password, lib, logdir) = init()?;
+    init_logging();
+
+    let mut txc = TransaqConnector::new(lib.into(), logdir.into(), LogLevel::Minimum)?;
+
+    txc.input_stream()
+        // цена последней сделки сообщения `<trades>`
+        .filter_map(|buf| {
+            let trades = TradesResponse::try_from(&buf).ok()?;
+            trades.trades.into_iter().filter_map(|trade| trade.price).next_back()
+        })
+        .window(20)
+        .map(|window| window.iter().sum::<Decimal>() / Decimal::from(window.len()))
+        .subscribe(|mean| info!("rolling mean: {mean}"))?;
+
+    let sender = txc.sender();
+    let connect = ConnectCommand::new()
+        .login(login)
+        .password(password)
+        .host("tr1.finam.ru")
+        .port(3900)
+        .build();
+    unsafe { sender.send(connect) }?;
+
+    std::thread::sleep(std::time::Duration::from_secs(60));
+    unsafe { sender.send("<command id=\"disconnect\"/>") }?;
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 include!("common/common.rs");
 
-use libtxc::{LogLevel, Stream, TCStr, TransaqConnector};
+use libtxc::{
+    ConnectCommand, ConnectionStatus, LogLevel, ServerStatusResponse, Stream, TCStr,
+    TransaqConnector,
+};
 use tracing::info;
 
 // запуск примера:
@@ -42,14 +45,12 @@ fn main() -> anyhow::Result<()> {
         Version(u8),
     }
 
-    let cmd_connect = format!(
-        "<command id=\"connect\">
-            <login>{login}</login>
-            <password>{password}</password>
-            <host>tr1.finam.ru</host>
-            <port>3900</port>
-        </command>\0"
-    );
+    let cmd_connect = ConnectCommand::new()
+        .login(login)
+        .password(password)
+        .host("tr1.finam.ru")
+        .port(3900)
+        .build();
     let cmd_disconnect = "<command id=\"disconnect\"/>\0";
     let cmd_get_version = "<command id = \"get_connector_version\"/>\0";
 
@@ -82,7 +83,7 @@ fn main() -> anyhow::Result<()> {
             info!("disconnected");
             std::thread::sleep(std::time::Duration::from_secs(5));
             info!("sending 'connect'");
-            unsafe { s2.send(cmd.as_bytes()).unwrap() };
+            unsafe { s2.send(&cmd).unwrap() };
         }
     });
 
@@ -94,16 +95,13 @@ fn main() -> anyhow::Result<()> {
         if bytes.starts_with(b"<connector_version") {
             // <connector_version>Номер_версии_коннектора</connector_version>
             Some(Message::Version(bytes[19]))
-        } else if bytes.starts_with(b"<server_status") {
-            // <server_status .. connected="true/false" ..
-            let i = bytes.iter().skip(14).position(|b| b'c'.eq(b));
-            if i.is_none() {
-                // <server_status connected="error"..
-                info!("{}", buf.to_string_lossy());
+        } else if let Ok(status) = ServerStatusResponse::parse(bytes) {
+            // <server_status .. connected="true/false/error" ..
+            if status.connected == ConnectionStatus::Error {
+                info!("{}", status.text.unwrap_or_default());
                 return None;
             }
-            let connected = bytes[i.unwrap() + 14 + 11] == b't';
-            Some(Message::Status(connected))
+            Some(Message::Status(status.is_connected()))
         } else {
             None
         }
@@ -114,12 +112,12 @@ fn main() -> anyhow::Result<()> {
         let _ = tx3.try_send(msg);
     };
 
-    txc.input_stream().filter_map(status_version_filter).subscribe(dispatch);
+    txc.input_stream().filter_map(status_version_filter).subscribe(dispatch)?;
 
     // start
     unsafe {
         s3.send(cmd_get_version)?;
-        s3.send(cmd_connect.as_bytes())?;
+        s3.send(&cmd_connect)?;
     }
 
     // 3
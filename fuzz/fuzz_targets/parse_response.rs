@@ -0,0 +1,16 @@
+#![no_main]
+
+// запуск:
+// cargo fuzz run parse_response
+//
+// Ответ коннектора произвольного содержания не должен приводить к панике или
+// `undefined behaviour` при включенной опции `safe_buffers`. Успешным считается ответ вида
+// `<result success="true"..`, при этом ответ распознаётся по отдельным байтам, а не разбирается
+// полностью.
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(bytes) = libtxc::fuzz_parse_send_response(data) {
+        assert!(bytes.len() >= 23 && bytes[1] == b'r' && bytes[17] == b't', "{bytes:?}");
+    }
+});
@@ -1,5 +1,7 @@
-use super::{ffi, Error};
-use std::{ffi::CStr, fmt, ops::Deref, ptr::NonNull};
+#[cfg(feature = "encoding")]
+use super::encoding::decode_cp1251;
+use super::{ffi, lang, Error, ErrorResponse};
+use std::{borrow::Cow, ffi::CStr, fmt, ops::Deref, ptr::NonNull};
 #[cfg(feature = "tracing")]
 use tracing::instrument;
 
@@ -10,7 +12,7 @@ pub fn as_nonnull_txc_buf(p: *mut u8) -> Result<NonNull<u8>, Error> {
     if super::likely(!p.is_null()) {
         Ok(unsafe { NonNull::new_unchecked(p) })
     } else {
-        Err(Error::Internal("Коннектор вернул нулевой указатель".into()))
+        Err(Error::Internal(lang::NULL_POINTER.into()))
     }
 }
 
@@ -38,6 +40,238 @@ impl TCStr<'_> {
     pub(crate) fn new(ptr: NonNull<u8>, free_mem: ffi::FreeMemory) -> Self {
         Self(ptr, free_mem, std::marker::PhantomData)
     }
+
+    // Rebinds the buffer to another lifetime, the connector must outlive the returned value
+    #[inline(always)]
+    pub(crate) fn rebind<'b>(self) -> TCStr<'b> {
+        let this = std::mem::ManuallyDrop::new(self);
+        TCStr::new(this.0, this.1)
+    }
+
+    /// Имя корневого XML тэга сообщения
+    ///
+    /// Возвращает срез байт между `<` и первым пробельным символом, `/` или `>`, без копирования
+    /// и аллокаций. Если сообщение не начинается с `<`, возвращает `None`.
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let buf: TCStr = /*<server_status connected="true"/>*/;
+    /// assert_eq!(buf.xml_tag_name(), Some(&b"server_status"[..]));
+    /// ```
+    #[inline]
+    pub fn xml_tag_name(&self) -> Option<&[u8]> {
+        xml_tag_name(self.to_bytes())
+    }
+
+    /// Значение атрибута корневого XML тэга сообщения
+    ///
+    /// Возвращает срез байт между кавычками(`"` или `'`) значения атрибута **attr_name**, без
+    /// копирования и аллокаций. Поиск выполняется только среди атрибутов корневого тэга,
+    /// атрибуты вложенных элементов не учитываются. Значение возвращается как есть,
+    /// без раскрытия XML сущностей(`&amp;` etc.).
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let buf: TCStr = /*<server_status id="3" connected="true" recover="false"/>*/;
+    /// assert_eq!(buf.xml_attr(b"connected"), Some(&b"true"[..]));
+    /// assert_eq!(buf.xml_attr(b"server_tz"), None);
+    /// ```
+    #[inline]
+    pub fn xml_attr(&self, attr_name: &[u8]) -> Option<&[u8]> {
+        xml_attr(self.to_bytes(), attr_name)
+    }
+
+    /// Копирует содержимое буфера в [`OwnedMessage`]
+    #[inline]
+    pub fn to_owned_message(&self) -> OwnedMessage {
+        OwnedMessage(self.to_bytes().into())
+    }
+
+    /// Содержимое буфера в кодировке windows-1251 в виде строки
+    ///
+    /// Сообщения в кодировке windows-1251, например новости некоторых брокеров, при чтении
+    /// [`CStr::to_string_lossy`] искажаются. Содержимое в кодировке ASCII возвращается без
+    /// копирования.
+    ///
+    /// Требует опции **encoding**.
+    #[cfg(feature = "encoding")]
+    #[inline]
+    pub fn decode_cp1251(&self) -> Cow<'_, str> {
+        decode_cp1251(self.to_bytes())
+    }
+}
+
+#[inline]
+pub(crate) fn xml_tag_name(bytes: &[u8]) -> Option<&[u8]> {
+    let name = bytes.strip_prefix(b"<")?;
+    let end = name
+        .iter()
+        .position(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n' | b'/' | b'>'))
+        .unwrap_or(name.len());
+
+    Some(&name[..end]).filter(|name| !name.is_empty())
+}
+
+// root tag name, preceded by an optional XML declaration `<?xml ..?>`
+#[inline]
+pub(crate) fn xml_root_tag_name(bytes: &[u8]) -> Option<&[u8]> {
+    let bytes = match bytes.strip_prefix(b"<?") {
+        Some(decl) => {
+            let end = decl.windows(2).position(|w| w == b"?>")? + 2;
+            let rest = &decl[end..];
+            &rest[rest.iter().take_while(|b| b.is_ascii_whitespace()).count()..]
+        }
+        None => bytes,
+    };
+    xml_tag_name(bytes)
+}
+
+// text content of the root element `<tag ..>text</tag>`
+#[inline]
+pub(crate) fn xml_text(bytes: &[u8]) -> Option<&[u8]> {
+    let tag = xml_tag_name(bytes)?;
+    let start = bytes.iter().position(|b| b'>'.eq(b))? + 1;
+    let end = bytes.len() - bytes.iter().rev().position(|b| b'<'.eq(b))? - 1;
+    (start <= end && bytes[end..].strip_prefix(b"</")?.starts_with(tag)).then(|| &bytes[start..end])
+}
+
+#[inline]
+pub(crate) fn xml_attr<'a>(bytes: &'a [u8], attr_name: &[u8]) -> Option<&'a [u8]> {
+    let is_space = |b: &u8| matches!(b, b' ' | b'\t' | b'\r' | b'\n');
+    let skip_spaces = |i: usize| i + bytes[i..].iter().take_while(|b| is_space(b)).count();
+
+    // only the attributes of the root start tag are scanned, it ends on the first '>' or '/'
+    // outside of the quoted value
+    let mut i = 1 + xml_tag_name(bytes)?.len();
+    loop {
+        i = skip_spaces(i);
+        if matches!(bytes.get(i)?, b'>' | b'/') {
+            return None;
+        }
+
+        let key_len = bytes[i..]
+            .iter()
+            .position(|b| is_space(b) || matches!(b, b'=' | b'>' | b'/'))
+            .unwrap_or(bytes.len() - i);
+        let key = &bytes[i..i + key_len];
+
+        i = skip_spaces(i + key_len);
+        if b'='.ne(bytes.get(i)?) {
+            return None;
+        }
+        i = skip_spaces(i + 1);
+
+        let quote = *bytes.get(i)?;
+        if quote != b'"' && quote != b'\'' {
+            return None;
+        }
+        let value_len = bytes[i + 1..].iter().position(|b| quote.eq(b))?;
+        let value = &bytes[i + 1..i + 1 + value_len];
+
+        if key == attr_name {
+            return Some(value);
+        }
+        i += value_len + 2;
+    }
+}
+
+// elements `<tag ..>..</tag>` or `<tag ../>` nested at any depth, elements of the same name are
+// expected not to be nested into each other
+pub(crate) fn xml_elements<'a>(bytes: &'a [u8], tag: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+    let mut pos = 0;
+    std::iter::from_fn(move || loop {
+        let start = pos + bytes[pos..].iter().position(|b| b'<'.eq(b))?;
+        pos = start + 1;
+        if xml_tag_name(&bytes[start..]) != Some(tag) {
+            continue;
+        }
+
+        let open_end = start + bytes[start..].iter().position(|b| b'>'.eq(b))?;
+        let end = if bytes[open_end - 1] == b'/' {
+            open_end + 1
+        } else {
+            open_end + 1 + xml_closing_tag(&bytes[open_end + 1..], tag)?
+        };
+        pos = end;
+        return Some(&bytes[start..end]);
+    })
+}
+
+// offset past the closing tag `</tag>`
+fn xml_closing_tag(bytes: &[u8], tag: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    loop {
+        let start = pos + bytes[pos..].windows(2).position(|w| w == b"</")?;
+        let rest = &bytes[start + 2..];
+        match rest.strip_prefix(tag) {
+            Some(rest) if rest.first() == Some(&b'>') => return Some(start + 3 + tag.len()),
+            _ => pos = start + 2,
+        }
+    }
+}
+
+// text content of the first child element `<tag>text</tag>`
+#[inline]
+pub(crate) fn xml_child_text<'a>(bytes: &'a [u8], tag: &'a [u8]) -> Option<&'a [u8]> {
+    xml_elements(bytes, tag).next().and_then(xml_text)
+}
+
+// leaf elements `<tag>text</tag>` nested at any depth in document order, as pairs of the tag
+// name and the text, one pass alternative to the repeated `xml_child_text` lookups
+pub(crate) fn xml_leaf_elements(bytes: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    // past the root start tag
+    let mut pos = bytes.iter().position(|b| b'>'.eq(b)).map_or(bytes.len(), |i| i + 1);
+    std::iter::from_fn(move || loop {
+        let start = pos + bytes[pos..].iter().position(|b| b'<'.eq(b))?;
+        let open_end = start + bytes[start..].iter().position(|b| b'>'.eq(b))?;
+        pos = open_end + 1;
+        let tag = match xml_tag_name(&bytes[start..]) {
+            // closing tags have no name, empty elements have no text
+            Some(tag) if bytes[open_end - 1] != b'/' => tag,
+            _ => continue,
+        };
+
+        let text_end = pos + bytes[pos..].iter().position(|b| b'<'.eq(b))?;
+        let close = &bytes[text_end..];
+        let is_closing = close.get(1) == Some(&b'/')
+            && close[2..].starts_with(tag)
+            && close.get(2 + tag.len()) == Some(&b'>');
+        if is_closing {
+            let text = &bytes[pos..text_end];
+            pos = text_end + 3 + tag.len();
+            return Some((tag, text));
+        }
+    })
+}
+
+// replaces the predefined XML entities, invalid UTF-8 sequences are replaced as well
+pub(crate) fn xml_unescape(bytes: &[u8]) -> Cow<'_, str> {
+    let text = String::from_utf8_lossy(bytes);
+    if !text.contains('&') {
+        return text;
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = &*text;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let entity =
+            [("&amp;", '&'), ("&lt;", '<'), ("&gt;", '>'), ("&quot;", '"'), ("&apos;", '\'')]
+                .iter()
+                .find(|(name, _)| rest.starts_with(name));
+        match entity {
+            Some((name, c)) => {
+                out.push(*c);
+                rest = &rest[name.len()..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
 }
 
 impl Drop for TCStr<'_> {
@@ -46,11 +280,7 @@ impl Drop for TCStr<'_> {
     fn drop(&mut self) {
         let result = unsafe { (self.1)(self.as_ptr() as _) };
         if super::unlikely(!result) {
-            eprintln!(
-                "Операция очистки txc буфера FreeMemory(*) завершилась неудачно, \
-                  это - недокументированная ситуация и возможно всякое. \
-                  Cоздайте issue на github если вам удалось добиться воспроизводимости."
-            );
+            eprintln!("{}", lang::FREE_MEMORY_FAILED);
         }
     }
 }
@@ -63,6 +293,12 @@ impl Deref for TCStr<'_> {
         unsafe { CStr::from_ptr(self.0.as_ptr() as _) }
     }
 }
+impl AsRef<[u8]> for TCStr<'_> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.to_bytes()
+    }
+}
 impl fmt::Debug for TCStr<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_tuple("TCStr").field(&self.0).finish()
@@ -74,6 +310,107 @@ impl fmt::Display for TCStr<'_> {
     }
 }
 
+/// Копия сообщения коннектора
+///
+/// В отличие от [`TCStr`], не связана с буфером коннектора, не ограничена временем жизни
+/// и может быть передана между потоками. Завершающий нулевой байт не сохраняется.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct OwnedMessage(Box<[u8]>);
+
+impl OwnedMessage {
+    /// Содержимое сообщения
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Содержимое сообщения в виде строки, см. [`String::from_utf8_lossy`]
+    #[inline]
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+
+    /// Содержимое сообщения в кодировке windows-1251 в виде строки, см. [`TCStr::decode_cp1251`]
+    #[cfg(feature = "encoding")]
+    #[inline]
+    pub fn decode_cp1251(&self) -> Cow<'_, str> {
+        decode_cp1251(&self.0)
+    }
+
+    /// Имя корневого XML тэга сообщения, см. [`TCStr::xml_tag_name`]
+    #[inline]
+    pub fn xml_tag_name(&self) -> Option<&[u8]> {
+        xml_tag_name(&self.0)
+    }
+
+    /// Значение атрибута корневого XML тэга сообщения, см. [`TCStr::xml_attr`]
+    #[inline]
+    pub fn xml_attr(&self, attr_name: &[u8]) -> Option<&[u8]> {
+        xml_attr(&self.0, attr_name)
+    }
+
+    /// Возвращает буфер сообщения
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0.into_vec()
+    }
+}
+impl From<TCStr<'_>> for OwnedMessage {
+    #[inline]
+    fn from(buf: TCStr<'_>) -> Self {
+        buf.to_owned_message()
+    }
+}
+impl From<&TCStr<'_>> for OwnedMessage {
+    #[inline]
+    fn from(buf: &TCStr<'_>) -> Self {
+        buf.to_owned_message()
+    }
+}
+impl From<&[u8]> for OwnedMessage {
+    #[inline]
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.into())
+    }
+}
+impl From<Vec<u8>> for OwnedMessage {
+    #[inline]
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes.into_boxed_slice())
+    }
+}
+impl AsRef<[u8]> for OwnedMessage {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+impl fmt::Debug for OwnedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OwnedMessage").field(&self.to_string_lossy()).finish()
+    }
+}
+impl fmt::Display for OwnedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+// Buffer allocated on the Rust side in place of the connector one, released by `free_buffer`
+#[cfg(any(test, feature = "testing", feature = "fuzzing"))]
+pub(crate) fn alloc_buffer(bytes: &[u8]) -> NonNull<u8> {
+    let buf = std::ffi::CString::new(bytes).expect(lang::NO_NUL);
+    // `CString::into_raw` never returns null
+    unsafe { NonNull::new_unchecked(buf.into_raw() as *mut u8) }
+}
+
+// `FreeMemory` counterpart for the buffers allocated by `alloc_buffer`
+#[cfg(any(test, feature = "testing", feature = "fuzzing"))]
+pub(crate) unsafe extern "C" fn free_buffer(ptr: *const u8) -> bool {
+    drop(std::ffi::CString::from_raw(ptr as *mut std::os::raw::c_char));
+    true
+}
+
 /* Response might be of three forms:
  * Success:   <result success=”true” ... />
  * Error:     <result success=”false”>...</result>
@@ -93,6 +430,15 @@ fn is_success(bytes: &[u8]) -> bool {
     b't'.eq(unsafe { bytes.get_unchecked(RESULT_BOOL_START) })
 }
 
+// `<error>` document is reported with its text, other responses as is
+#[cold]
+fn internal_error(bytes: &[u8], msg: String) -> Error {
+    match ErrorResponse::parse(bytes) {
+        Ok(error) if !error.text.is_empty() => Error::Internal(error.text.into()),
+        _ => Error::Internal(msg.into()),
+    }
+}
+
 #[cfg(feature = "safe_buffers")]
 #[inline(always)]
 pub fn parse_send_response(buf: TCStr) -> super::Result<TCStr> {
@@ -100,17 +446,20 @@ pub fn parse_send_response(buf: TCStr) -> super::Result<TCStr> {
     let len = bytes.len();
 
     if len < MIN_RESPONSE_LENGTH || (is_result(bytes) && len < MIN_RESULT_LENGTH) {
-        return Err(Error::Internal(format!(
-            "Коннектор вернул неожиданное сообщение \"{}\"",
-            buf.to_string_lossy()
-        )));
+        return Err(Error::Internal(
+            format!("{} \"{}\"", lang::UNEXPECTED_MESSAGE, buf.to_string_lossy()).into(),
+        ));
     }
 
     if super::likely(is_result(bytes) && is_success(bytes)) {
         Ok(buf)
     } else {
         let msg = buf.to_string_lossy().to_string();
-        Err(if is_result(bytes) { Error::InvalidCommand(msg) } else { Error::Internal(msg) })
+        Err(if is_result(bytes) {
+            Error::InvalidCommand(msg.into())
+        } else {
+            internal_error(bytes, msg)
+        })
     }
 }
 
@@ -126,7 +475,122 @@ pub fn parse_send_response(buf: TCStr) -> super::Result<TCStr> {
         } else {
             let bytes = buf.to_bytes();
             let msg = std::str::from_utf8_unchecked(bytes).to_string();
-            Err(if is_result(bytes) { Error::InvalidCommand(msg) } else { Error::Internal(msg) })
+            Err(if is_result(bytes) {
+                Error::InvalidCommand(msg.into())
+            } else {
+                internal_error(bytes, msg)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buf(msg: &str) -> TCStr<'static> {
+        TCStr::new(alloc_buffer(msg.as_bytes()), free_buffer)
+    }
+
+    #[test]
+    fn xml_tag_name_self_closing() {
+        assert_eq!(buf("<result/>").xml_tag_name(), Some(&b"result"[..]));
+        assert_eq!(buf("<result success=\"true\"/>").xml_tag_name(), Some(&b"result"[..]));
+    }
+
+    #[test]
+    fn xml_tag_name_with_attributes() {
+        assert_eq!(buf("<tag attr=\"val\">").xml_tag_name(), Some(&b"tag"[..]));
+        assert_eq!(buf("<tag\tattr=\"val\"/>").xml_tag_name(), Some(&b"tag"[..]));
+        assert_eq!(buf("<tag\r\n attr=\"val\"/>").xml_tag_name(), Some(&b"tag"[..]));
+    }
+
+    #[test]
+    fn xml_tag_name_open_tag() {
+        assert_eq!(buf("<tag>").xml_tag_name(), Some(&b"tag"[..]));
+        assert_eq!(buf("<tag>text</tag>").xml_tag_name(), Some(&b"tag"[..]));
+        // unterminated tag is the whole remainder
+        assert_eq!(buf("<tag").xml_tag_name(), Some(&b"tag"[..]));
+    }
+
+    #[test]
+    fn xml_tag_name_invalid() {
+        assert_eq!(buf("").xml_tag_name(), None);
+        assert_eq!(buf("<").xml_tag_name(), None);
+        assert_eq!(buf("<>").xml_tag_name(), None);
+        assert_eq!(buf("</>").xml_tag_name(), None);
+        assert_eq!(buf(" <tag/>").xml_tag_name(), None);
+        assert_eq!(buf("tag").xml_tag_name(), None);
+    }
+
+    #[test]
+    fn xml_tag_name_owned_message() {
+        let msg = buf("<server_status connected=\"true\"/>").to_owned_message();
+        assert_eq!(msg.xml_tag_name(), Some(&b"server_status"[..]));
+        assert_eq!(OwnedMessage::from(&b""[..]).xml_tag_name(), None);
+    }
+
+    #[test]
+    fn xml_attr_boolean_values() {
+        let buf = buf("<server_status id=\"3\" connected=\"true\" recover='false'/>");
+        assert_eq!(buf.xml_attr(b"connected"), Some(&b"true"[..]));
+        assert_eq!(buf.xml_attr(b"recover"), Some(&b"false"[..]));
+        assert_eq!(buf.xml_attr(b"id"), Some(&b"3"[..]));
+    }
+
+    #[test]
+    fn xml_attr_missing() {
+        let buf = buf("<server_status connected=\"true\"/>");
+        assert_eq!(buf.xml_attr(b"recover"), None);
+        assert_eq!(buf.xml_attr(b"connect"), None);
+        assert_eq!(buf.xml_attr(b"connected=\"true\""), None);
+        assert_eq!(buf.xml_attr(b""), None);
+        assert_eq!(xml_attr(b"<result/>", b"success"), None);
+        assert_eq!(xml_attr(b"", b"success"), None);
+    }
+
+    #[test]
+    fn xml_attr_child_element() {
+        let buf = buf("<news_body id=\"1\"><text lang=\"ru\">lang=\"en\"</text></news_body>");
+        assert_eq!(buf.xml_attr(b"id"), Some(&b"1"[..]));
+        assert_eq!(buf.xml_attr(b"lang"), None);
+    }
+
+    #[test]
+    fn xml_attr_value_is_not_unescaped() {
+        let buf = buf("<error text = \"a &amp; b\" note=\"x/>y\"/>");
+        assert_eq!(buf.xml_attr(b"text"), Some(&b"a &amp; b"[..]));
+        assert_eq!(buf.xml_attr(b"note"), Some(&b"x/>y"[..]));
+        assert_eq!(buf.to_owned_message().xml_attr(b"note"), Some(&b"x/>y"[..]));
+    }
+
+    // the messages are at least `MIN_RESULT_LENGTH` long, as the connector responses are
+    #[test]
+    fn parse_send_response_errors() {
+        let ok = parse_send_response(buf("<result success=\"true\"/>")).unwrap();
+        assert_eq!(ok.to_bytes(), b"<result success=\"true\"/>");
+
+        let rejected = "<result success=\"false\"><message>rejected</message></result>";
+        match parse_send_response(buf(rejected)) {
+            Err(Error::InvalidCommand(msg)) => assert_eq!(msg.message(), rejected),
+            ret => panic!("{ret:?}"),
+        }
+
+        // `<error>` text is reported without the markup
+        match parse_send_response(buf("<error>Нет подключения &amp; сессии</error>"))
+        {
+            Err(Error::Internal(msg)) => assert_eq!(msg.message(), "Нет подключения & сессии"),
+            ret => panic!("{ret:?}"),
+        }
+        match parse_send_response(buf("<error></error><!-- padding -->")) {
+            Err(Error::Internal(msg)) => {
+                assert_eq!(msg.message(), "<error></error><!-- padding -->")
+            }
+            ret => panic!("{ret:?}"),
+        }
+        match parse_send_response(buf("<unexpected response=\"1\"/>")) {
+            Err(Error::Internal(msg)) => assert_eq!(msg.message(), "<unexpected response=\"1\"/>"),
+            ret => panic!("{ret:?}"),
         }
     }
 }
@@ -1,7 +1,25 @@
-use super::buffers::as_nonnull_txc_buf;
-use super::ffi::CallbackEx;
-use super::stream::Stream;
-use std::{ffi::c_void, mem, ptr::NonNull};
+use super::buffers::{as_nonnull_txc_buf, TCStr};
+use super::ffi::{CallbackEx, FreeMemory};
+#[cfg(feature = "catch_unwind")]
+use super::lang;
+use super::stats::SessionStats;
+use super::stream::{Stream, Subscription};
+use super::Result;
+use std::{
+    cell::Cell,
+    ffi::c_void,
+    mem::{self, ManuallyDrop},
+    ptr::{self, NonNull},
+    sync::{
+        atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// how long the replaced callback is waited to complete before its drop is deferred to the reaper
+const QUIESCENCE_TIMEOUT: Duration = Duration::from_millis(100);
 
 macro_rules! debug_assert_T_ptr {
     ($T:ty, $p:expr) => {
@@ -17,67 +35,332 @@ macro_rules! eprintln_abort {
     }};
 }
 
-#[repr(transparent)]
-pub struct InputStream<T>(pub T);
+pub struct InputStream<T> {
+    pub subscribe_fn: T,
+    pub free_memory: FreeMemory,
+    pub panic_policy: Arc<Mutex<PanicPolicy>>,
+    pub stats: Arc<SessionStats>,
+    pub gates: Arc<Gates>,
+}
 
 impl<T> Stream for InputStream<T>
 where
-    T: FnMut(CallbackEx, BoxT) + Send + Sync,
+    T: FnMut(CallbackEx, Callback) -> Result<Subscription> + Send + Sync,
 {
     type Output = NonNull<u8>;
 
-    fn subscribe<F: FnMut(Self::Output) + Sync + Send + 'static>(mut self, f: F) {
-        self.0(trampoline::<F>, BoxT::new(f))
+    fn subscribe_guarded<F: FnMut(Self::Output) + Sync + Send + 'static>(
+        mut self,
+        f: F,
+    ) -> Result<Subscription> {
+        let handler = BoxT::new(Handler {
+            invoke: invoke::<F>,
+            f,
+            free_memory: self.free_memory,
+            panic_policy: self.panic_policy,
+            stats: self.stats,
+            poisoned: false,
+        });
+        let gate = self.gates.acquire(handler.as_raw_ptr(), self.free_memory);
+        let callback = Callback(Some(Registration { gate, handler, gates: self.gates }));
+        (self.subscribe_fn)(trampoline, callback)
     }
 }
 
+/// Реакция на панику в функции обратного вызова
+///
+/// Учитывается только при включенной опции **catch_unwind**, см. [`crate`]. Без неё паника
+/// в функции обратного вызова приводит к аварийному завершению процесса.
+#[derive(Clone, Default)]
+pub enum PanicPolicy {
+    /// Вывести сообщение и аварийно завершить процесс(по-умолчанию)
+    #[default]
+    Abort,
+    /// Вывести сообщение и продолжить работу
+    ///
+    /// Состояние обработчика, в котором произошла паника, может быть нарушено, поэтому он больше
+    /// не вызывается; поступающие сообщения освобождаются без обработки до установки нового
+    /// обработчика.
+    LogAndContinue,
+    /// Передать информацию о панике в пользовательскую функцию и продолжить работу
+    ///
+    /// Как и в случае [`PanicPolicy::LogAndContinue`], обработчик больше не вызывается.
+    /// Функция выполняется в потоке коннектора, и, например, для отправки команды `disconnect`
+    /// перед завершением процесса, её следует передать в другой поток.
+    Custom(Arc<dyn Fn(CallbackPanic) + Send + Sync>),
+}
+
+/// Информация о панике в функции обратного вызова, см. [`PanicPolicy::Custom`]
+#[derive(Debug, Clone)]
+pub struct CallbackPanic {
+    message: String,
+}
+
+impl CallbackPanic {
+    /// Сообщение паники
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+// Invokes the type erased `Handler<F>`
+type Invoke = unsafe fn(*mut c_void, NonNull<u8>);
+
+// Handler state, referenced by the `Gate` registered alongside the 'trampoline'
+#[cfg_attr(not(feature = "catch_unwind"), allow(dead_code))]
+#[repr(C)]
+struct Handler<F> {
+    // the first field, read by the 'trampoline' not knowing `F`
+    invoke: Invoke,
+    f: F,
+    free_memory: FreeMemory,
+    panic_policy: Arc<Mutex<PanicPolicy>>,
+    stats: Arc<SessionStats>,
+    poisoned: bool,
+}
+
+/// Результат обработки входящего сообщения, см.
+/// [`TransaqConnector::subscribe_with_ack`](crate::TransaqConnector::subscribe_with_ack)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallbackAck {
+    /// Сообщение принято, буфер освобождается `libtxc`
+    Consumed,
+    /// Сообщение отклонено, буфер не освобождается `libtxc`, см.
+    /// [`TransaqConnector::subscribe_with_ack`](crate::TransaqConnector::subscribe_with_ack)
+    Rejected,
+}
+
+thread_local! {
+    // raised by the handler for the 'trampoline' to report the buffer as not consumed
+    static REJECTED: Cell<bool> = const { Cell::new(false) };
+}
+
+// Reports the buffer being handled on the current thread as not consumed, the caller must not
+// release the buffer
+#[inline]
+pub fn reject_buffer() {
+    REJECTED.with(|rejected| rejected.set(true));
+}
+
 // 'trampoline' is registered as a 'callback' via `txc::set_callback_ex` and get's directly
 // executed by the library within the C-language runtime.
-extern "C" fn trampoline<F: FnMut(NonNull<u8>)>(buffer: *const u8, callback: *mut c_void) -> bool {
+extern "C" fn trampoline(buffer: *const u8, gate: *mut c_void) -> bool {
+    REJECTED.with(|rejected| rejected.set(false));
     let f = || match as_nonnull_txc_buf(buffer as _) {
-        Ok(ptr) => invoke_callback::<F>(callback, ptr),
+        Ok(ptr) => {
+            debug_assert_T_ptr!(Gate, gate);
+            let gate = unsafe { &*gate.cast::<Gate>() };
+            // the handler is read once the invocation is accounted, see `Registration::retire`
+            gate.in_flight.fetch_add(1, Ordering::SeqCst);
+            let handler = gate.handler.load(Ordering::SeqCst);
+            if handler.is_null() {
+                // the handler is released, the connector hasn't waited for the replacement
+                unsafe { (gate.free_memory)(ptr.as_ptr()) };
+            } else {
+                unsafe { (*handler.cast::<Invoke>())(handler, ptr) };
+            }
+            gate.in_flight.fetch_sub(1, Ordering::SeqCst);
+        }
         Err(err) => eprintln_abort!("{}", err.to_string()),
     };
 
     #[cfg(feature = "tracing")]
-    tracing::debug_span!("trampoline").in_scope(f);
+    tracing::debug_span!(
+        "trampoline",
+        messages = tracing::field::Empty,
+        latency_us = tracing::field::Empty
+    )
+    .in_scope(f);
     #[cfg(not(feature = "tracing"))]
     f();
 
+    !REJECTED.with(Cell::take)
+}
+
+// Registered in place of the released subscription, the payload is `FreeMemory` itself
+pub extern "C" fn release_trampoline(buffer: *const u8, free_memory: *mut c_void) -> bool {
+    if !buffer.is_null() {
+        let free_memory: FreeMemory = unsafe { mem::transmute(free_memory) };
+        unsafe { free_memory(buffer) };
+    }
     true
 }
 
+unsafe fn invoke<F: FnMut(NonNull<u8>)>(handler: *mut c_void, buffer: NonNull<u8>) {
+    debug_assert_T_ptr!(Handler<F>, handler);
+    let handler = &mut *handler.cast::<Handler<F>>();
+    let start = Instant::now();
+    invoke_callback(handler, buffer);
+    handler.stats.record_message(start.elapsed());
+}
+
 #[cfg(not(feature = "catch_unwind"))]
 #[inline(always)]
-fn invoke_callback<F: FnMut(NonNull<u8>)>(callback: *mut c_void, buffer: NonNull<u8>) {
-    unsafe { invoke_fn_ptr::<F>(callback, buffer) };
+fn invoke_callback<F: FnMut(NonNull<u8>)>(handler: &mut Handler<F>, buffer: NonNull<u8>) {
+    (handler.f)(buffer)
 }
 
 #[cfg(feature = "catch_unwind")]
 #[inline(always)]
-fn invoke_callback<F: FnMut(NonNull<u8>)>(callback: *mut c_void, buffer: NonNull<u8>) {
+fn invoke_callback<F: FnMut(NonNull<u8>)>(handler: &mut Handler<F>, buffer: NonNull<u8>) {
     #[cold]
-    #[inline]
-    fn closure_panic_abort(err: Box<dyn std::any::Any + Send>) -> ! {
-        let panic_info = err
+    #[inline(never)]
+    fn on_panic(panic_policy: &Mutex<PanicPolicy>, err: Box<dyn std::any::Any + Send>) {
+        let message = err
             .downcast::<String>()
             .map(|v| *v)
             .or_else(|e| e.downcast::<&str>().map(|v| v.to_string()))
-            .unwrap_or_else(|_| "Неизвестная причина".to_string());
-        eprintln_abort!("Паника в ffi коде: {panic_info:?}\n");
+            .unwrap_or_else(|_| lang::PANIC_UNKNOWN.to_string());
+
+        // the policy is not locked while the custom function runs, it may replace the policy
+        let policy = panic_policy.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match policy {
+            PanicPolicy::Abort => eprintln_abort!("{}: {message:?}\n", lang::PANIC),
+            PanicPolicy::LogAndContinue => {
+                eprintln!("{}: {message:?}, {}\n", lang::PANIC, lang::HANDLER_DISABLED)
+            }
+            PanicPolicy::Custom(f) => f(CallbackPanic { message }),
+        }
     }
 
-    if let Err(err) = std::panic::catch_unwind(|| {
-        debug_assert_T_ptr!(F, callback);
-        unsafe { invoke_fn_ptr::<F>(callback, buffer) };
-    }) {
-        closure_panic_abort(err)
+    if super::unlikely(handler.poisoned) {
+        unsafe { (handler.free_memory)(buffer.as_ptr()) };
+        return;
+    }
+
+    // the handler is never called again after the panic, only dropped, so its state being
+    // broken by the unwinding can't be observed
+    let f = std::panic::AssertUnwindSafe(&mut handler.f);
+    if let Err(err) = std::panic::catch_unwind(move || { f }.0(buffer)) {
+        handler.poisoned = true;
+        handler.stats.record_error();
+        on_panic(&handler.panic_policy, err)
     }
 }
 
-#[inline(always)]
-unsafe fn invoke_fn_ptr<F: FnMut(NonNull<u8>)>(f: *mut c_void, arg: NonNull<u8>) {
-    (*f.cast::<F>())(arg);
+// Payload registered with the connector in place of the handler.
+// The connector may call the 'trampoline' with the payload of the replaced handler, if it doesn't
+// wait for the running callback to complete, so the gate outlives the handler: it's reused for
+// the later handlers and released along with the connector, see `Gates`.
+struct Gate {
+    // invocations in progress, raised before the handler is read
+    in_flight: AtomicUsize,
+    // `Handler<F>`, null once the handler is released
+    handler: AtomicPtr<c_void>,
+    // releases the buffers delivered after the handler is released
+    free_memory: FreeMemory,
+}
+
+// Gates of the released handlers, the connector must be released before the gates.
+// Boxed as the connector keeps pointers to them while the pool grows
+#[derive(Default)]
+#[allow(clippy::vec_box)]
+pub struct Gates(Mutex<Vec<Box<Gate>>>);
+
+impl Gates {
+    fn acquire(&self, handler: *mut c_void, free_memory: FreeMemory) -> Box<Gate> {
+        let gate = self.0.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let gate = gate.unwrap_or_else(|| {
+            Box::new(Gate {
+                in_flight: AtomicUsize::new(0),
+                handler: AtomicPtr::new(ptr::null_mut()),
+                free_memory,
+            })
+        });
+        gate.handler.store(handler, Ordering::SeqCst);
+        gate
+    }
+
+    fn release(&self, gate: Box<Gate>) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(gate);
+    }
+}
+
+// Handler registered with the connector, it's released on drop once it's not executing
+pub struct Callback(Option<Registration>);
+
+struct Registration {
+    gate: Box<Gate>,
+    handler: BoxT,
+    gates: Arc<Gates>,
+}
+
+impl Callback {
+    #[inline]
+    pub fn as_raw_ptr(&self) -> *mut c_void {
+        self.0.as_ref().map_or(ptr::null_mut(), |r| &*r.gate as *const Gate as *mut c_void)
+    }
+
+    #[inline]
+    pub fn is_in_flight(&self) -> bool {
+        self.0.as_ref().map_or(false, Registration::is_in_flight)
+    }
+
+    // Releases the replaced handler, see `Registration::retire`
+    #[inline]
+    pub fn retire(mut self) {
+        if let Some(registration) = self.0.take() {
+            registration.retire();
+        }
+    }
+}
+
+impl Drop for Callback {
+    fn drop(&mut self) {
+        if let Some(registration) = self.0.take() {
+            registration.retire();
+        }
+    }
+}
+
+impl Registration {
+    #[inline]
+    fn is_in_flight(&self) -> bool {
+        self.gate.in_flight.load(Ordering::SeqCst) != 0
+    }
+
+    // Drops the replaced handler once it's not executing.
+    // `set_callback_ex` already waits for the running callback to complete, this guards against
+    // the connector not keeping its promise. The handler is unlinked from the gate first, the
+    // 'trampoline' accounts the invocation before reading it, so once the gate is observed idle
+    // the handler is not referenced anymore. The handler is waited for `QUIESCENCE_TIMEOUT` and
+    // then its drop is deferred to the background reaper, instead of blocking the caller forever.
+    fn retire(self) {
+        self.gate.handler.store(ptr::null_mut(), Ordering::SeqCst);
+        let deadline = Instant::now() + QUIESCENCE_TIMEOUT;
+        let mut spins = 0u32;
+        while self.is_in_flight() {
+            if Instant::now() >= deadline {
+                return self.reap();
+            }
+            if spins < 64 {
+                std::hint::spin_loop();
+                spins += 1;
+            } else {
+                thread::yield_now();
+            }
+        }
+        self.release();
+    }
+
+    fn release(self) {
+        let Self { gate, handler, gates } = self;
+        gates.release(gate);
+        drop(handler);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn reap(self) {
+        // leaked rather than dropped while executing, if the thread could not be spawned
+        let this = ManuallyDrop::new(self);
+        let _ = thread::Builder::new().name("txc-callback-reaper".into()).spawn(move || {
+            while this.is_in_flight() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            ManuallyDrop::into_inner(this).release();
+        });
+    }
 }
 
 // `Box<T>` with types erased
@@ -112,3 +395,267 @@ unsafe fn drop_t<T>(ptr: *mut c_void) {
     debug_assert_T_ptr!(T, ptr);
     let _ = Box::from_raw(ptr.cast::<T>());
 }
+
+// Temporary observers of the input stream, executed ahead of the user subscription.
+// Tap returns `true` once it's done and should be removed.
+pub type Tap = Box<dyn FnMut(&TCStr) -> bool + Send>;
+
+#[derive(Default)]
+pub struct Taps {
+    // fast path for the common case of no taps installed, avoids locking on every message
+    len: AtomicUsize,
+    next_id: AtomicU64,
+    taps: Mutex<Vec<(u64, Tap)>>,
+}
+
+impl Taps {
+    pub fn insert(&self, tap: Tap) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut taps = self.taps.lock().unwrap_or_else(|e| e.into_inner());
+        taps.push((id, tap));
+        self.len.store(taps.len(), Ordering::Release);
+        id
+    }
+
+    pub fn remove(&self, id: u64) {
+        let mut taps = self.taps.lock().unwrap_or_else(|e| e.into_inner());
+        taps.retain(|(tap_id, _)| *tap_id != id);
+        self.len.store(taps.len(), Ordering::Release);
+    }
+
+    #[inline(always)]
+    pub fn dispatch(&self, buf: &TCStr) {
+        if super::likely(self.len.load(Ordering::Acquire) == 0) {
+            return;
+        }
+        let mut taps = self.taps.lock().unwrap_or_else(|e| e.into_inner());
+        let mut i = 0;
+        while i < taps.len() {
+            if (taps[i].1)(buf) {
+                drop(taps.swap_remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        self.len.store(taps.len(), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffers::{alloc_buffer, free_buffer};
+    use std::sync::{atomic::AtomicBool, mpsc};
+
+    // Handler registered through the `InputStream`, the 'trampoline' is called directly in place
+    // of the connector
+    struct Registered {
+        trampoline: CallbackEx,
+        callback: Callback,
+    }
+
+    impl Registered {
+        fn deliver(&self, msg: &str) -> bool {
+            let buf = alloc_buffer(msg.as_bytes()).as_ptr();
+            (self.trampoline)(buf, self.callback.as_raw_ptr())
+        }
+    }
+
+    fn subscribe<F>(panic_policy: &Arc<Mutex<PanicPolicy>>, f: F) -> (Registered, Arc<SessionStats>)
+    where
+        F: FnMut(NonNull<u8>) + Send + Sync + 'static,
+    {
+        let slot = Arc::new(Mutex::new(None));
+        let stats = Arc::new(SessionStats::default());
+        let registered = Arc::clone(&slot);
+        let input = InputStream {
+            subscribe_fn: move |trampoline, callback| {
+                *registered.lock().unwrap() = Some(Registered { trampoline, callback });
+                Ok(Subscription::detached())
+            },
+            free_memory: free_buffer,
+            panic_policy: Arc::clone(panic_policy),
+            stats: Arc::clone(&stats),
+            gates: Default::default(),
+        };
+        drop(input.subscribe_guarded(f).unwrap());
+        let registered = slot.lock().unwrap().take().unwrap();
+        (registered, stats)
+    }
+
+    // Raised once the handler state is dropped
+    struct Dropped(Arc<AtomicBool>);
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn wait_until<F: Fn() -> bool>(f: F) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !f() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    // Handler reporting the invocation and blocking until it's released
+    fn blocking(
+        dropped: &Arc<AtomicBool>,
+    ) -> (impl FnMut(NonNull<u8>) + Send + Sync + 'static, mpsc::Receiver<()>, mpsc::Sender<()>)
+    {
+        let (entered_tx, entered) = mpsc::channel();
+        let (release, release_rx) = mpsc::channel();
+        let (entered_tx, release_rx) = (Mutex::new(entered_tx), Mutex::new(release_rx));
+        let state = Dropped(Arc::clone(dropped));
+        let f = move |ptr| {
+            let _buf = crate::TCStr::new(ptr, free_buffer);
+            entered_tx.lock().unwrap().send(()).unwrap();
+            release_rx.lock().unwrap().recv().unwrap();
+            assert!(!state.0.load(Ordering::SeqCst));
+        };
+        (f, entered, release)
+    }
+
+    // Starts the handler invocation on another thread, as the connector does
+    fn deliver_async(trampoline: CallbackEx, callback: &Callback) -> thread::JoinHandle<bool> {
+        let payload = callback.as_raw_ptr() as usize;
+        thread::spawn(move || {
+            let buf = alloc_buffer(b"<quotes/>").as_ptr();
+            trampoline(buf, payload as *mut c_void)
+        })
+    }
+
+    #[test]
+    fn retire_waits_for_running_handler() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let (f, entered, release) = blocking(&dropped);
+        let (Registered { trampoline, callback }, _) = subscribe(&Default::default(), f);
+
+        let delivery = deliver_async(trampoline, &callback);
+        entered.recv().unwrap();
+        let retired = thread::spawn(move || callback.retire());
+        thread::sleep(Duration::from_millis(10));
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        release.send(()).unwrap();
+        assert!(delivery.join().unwrap());
+        retired.join().unwrap();
+        assert!(wait_until(|| dropped.load(Ordering::SeqCst)));
+    }
+
+    #[test]
+    fn retire_defers_drop_to_reaper() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let (f, entered, release) = blocking(&dropped);
+        let (Registered { trampoline, callback }, _) = subscribe(&Default::default(), f);
+
+        let delivery = deliver_async(trampoline, &callback);
+        entered.recv().unwrap();
+        // returns once `QUIESCENCE_TIMEOUT` elapses, the handler is still executing
+        callback.retire();
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        release.send(()).unwrap();
+        assert!(delivery.join().unwrap());
+        assert!(wait_until(|| dropped.load(Ordering::SeqCst)));
+    }
+
+    #[test]
+    fn retire_idle_handler() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let state = Dropped(Arc::clone(&dropped));
+        let (registered, _) = subscribe(&Default::default(), move |ptr| {
+            let _buf = crate::TCStr::new(ptr, free_buffer);
+            let _ = &state;
+        });
+
+        assert!(registered.deliver("<quotes/>"));
+        registered.callback.retire();
+        assert!(wait_until(|| dropped.load(Ordering::SeqCst)));
+    }
+
+    #[cfg(feature = "catch_unwind")]
+    fn panicking(calls: &Arc<AtomicUsize>) -> impl FnMut(NonNull<u8>) + Send + Sync + 'static {
+        let calls = Arc::clone(calls);
+        move |ptr| {
+            let _buf = crate::TCStr::new(ptr, free_buffer);
+            calls.fetch_add(1, Ordering::SeqCst);
+            panic!("boom {}", calls.load(Ordering::SeqCst))
+        }
+    }
+
+    // `PanicPolicy::Abort` terminates the process and is not covered
+
+    #[cfg(feature = "catch_unwind")]
+    #[test]
+    fn panic_policy_log_and_continue() {
+        let policy = Arc::new(Mutex::new(PanicPolicy::LogAndContinue));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (registered, stats) = subscribe(&policy, panicking(&calls));
+
+        assert!(registered.deliver("<a/>"));
+        // the handler is disabled, the buffers are released without the call
+        assert!(registered.deliver("<b/>"));
+        assert!(registered.deliver("<c/>"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(stats.errors(), 1);
+        assert_eq!(stats.messages(), 3);
+    }
+
+    #[cfg(feature = "catch_unwind")]
+    #[test]
+    fn panic_policy_custom() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let policy = Arc::new(Mutex::new(PanicPolicy::Custom(Arc::new(move |panic| {
+            tx.lock().unwrap().send(panic.message().to_string()).unwrap()
+        }))));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (registered, stats) = subscribe(&policy, panicking(&calls));
+
+        assert!(registered.deliver("<a/>"));
+        assert!(registered.deliver("<b/>"));
+        assert_eq!(rx.try_iter().collect::<Vec<String>>(), ["boom 1"]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(stats.errors(), 1);
+    }
+
+    #[cfg(feature = "catch_unwind")]
+    #[test]
+    fn panic_policy_custom_unknown_payload() {
+        let message = Arc::new(Mutex::new(String::new()));
+        let out = Arc::clone(&message);
+        let policy = Arc::new(Mutex::new(PanicPolicy::Custom(Arc::new(move |panic| {
+            *out.lock().unwrap() = panic.message().to_string()
+        }))));
+        let (registered, _) = subscribe(&policy, |ptr| {
+            let _buf = crate::TCStr::new(ptr, free_buffer);
+            std::panic::panic_any(42)
+        });
+
+        assert!(registered.deliver("<a/>"));
+        assert_eq!(*message.lock().unwrap(), lang::PANIC_UNKNOWN);
+    }
+
+    #[cfg(feature = "catch_unwind")]
+    #[test]
+    fn panic_policy_replaced_by_custom() {
+        let policy = Arc::new(Mutex::new(PanicPolicy::Abort));
+        let shared = Arc::clone(&policy);
+        // would deadlock if the policy was locked for the duration of the call
+        *policy.lock().unwrap() = PanicPolicy::Custom(Arc::new(move |_| {
+            *shared.lock().unwrap() = PanicPolicy::LogAndContinue;
+        }));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (registered, stats) = subscribe(&policy, panicking(&calls));
+
+        assert!(registered.deliver("<a/>"));
+        assert!(matches!(*policy.lock().unwrap(), PanicPolicy::LogAndContinue));
+        assert_eq!(stats.errors(), 1);
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{RecvError, RecvTimeoutError, TryRecvError},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+/// Поведение очереди при переполнении
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Ожидать освобождения места в очереди(по-умолчанию)
+    ///
+    /// Блокирует поток коннектора до тех пор, пока получатель не освободит место, что
+    /// останавливает доставку всех входящих сообщений.
+    #[default]
+    Block,
+    /// Отбросить новое сообщение
+    DropNewest,
+    /// Отбросить самое старое сообщение в очереди
+    DropOldest,
+}
+
+struct Shared<T> {
+    queue: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    dropped: AtomicU64,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    sender_alive: bool,
+    receiver_alive: bool,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub(crate) fn bounded<T>(
+    capacity: usize,
+    overflow: OverflowPolicy,
+) -> (ChannelSender<T>, Receiver<T>) {
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(State {
+            items: VecDeque::with_capacity(capacity),
+            sender_alive: true,
+            receiver_alive: true,
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        capacity,
+        overflow,
+        dropped: AtomicU64::new(0),
+    });
+    (ChannelSender(Arc::clone(&shared)), Receiver(shared))
+}
+
+// Producer half, lives inside the subscription and gets dropped alongside it
+pub(crate) struct ChannelSender<T>(Arc<Shared<T>>);
+
+impl<T> ChannelSender<T> {
+    pub fn send(&self, item: T) {
+        let shared = &*self.0;
+        let mut state = shared.lock();
+        if !state.receiver_alive {
+            return;
+        }
+
+        if state.items.len() == shared.capacity {
+            match shared.overflow {
+                OverflowPolicy::Block => {
+                    while state.receiver_alive && state.items.len() == shared.capacity {
+                        state = shared.not_full.wait(state).unwrap_or_else(|e| e.into_inner());
+                    }
+                    if !state.receiver_alive {
+                        return;
+                    }
+                }
+                OverflowPolicy::DropNewest => {
+                    shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    state.items.pop_front();
+                    shared.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        state.items.push_back(item);
+        drop(state);
+        shared.not_empty.notify_one();
+    }
+}
+
+impl<T> Drop for ChannelSender<T> {
+    fn drop(&mut self) {
+        self.0.lock().sender_alive = false;
+        self.0.not_empty.notify_all();
+    }
+}
+
+/// Получатель сообщений из ограниченной очереди
+///
+/// Повторяет интерфейс [`std::sync::mpsc::Receiver`]. Очередь закрывается при замене обработчика
+/// входящих сообщений или освобождении коннектора; оставшиеся в очереди сообщения могут быть
+/// прочитаны и после этого.
+pub struct Receiver<T>(Arc<Shared<T>>);
+
+impl<T> Receiver<T> {
+    /// Ожидает следующее сообщение
+    ///
+    /// Возвращает ошибку, если очередь пуста и закрыта.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.0.lock();
+        loop {
+            if let Some(item) = self.pop(&mut state) {
+                return Ok(item);
+            }
+            if !state.sender_alive {
+                return Err(RecvError);
+            }
+            state = self.0.not_empty.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Возвращает следующее сообщение, не блокируя поток
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.0.lock();
+        match self.pop(&mut state) {
+            Some(item) => Ok(item),
+            None if state.sender_alive => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+
+    /// Ожидает следующее сообщение не дольше **timeout**
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.0.lock();
+        loop {
+            if let Some(item) = self.pop(&mut state) {
+                return Ok(item);
+            }
+            if !state.sender_alive {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .0
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Блокирующий итератор по сообщениям, завершается при закрытии очереди
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    /// Итератор по сообщениям, доступным без ожидания
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok())
+    }
+
+    /// Количество сообщений в очереди
+    pub fn len(&self) -> usize {
+        self.0.lock().items.len()
+    }
+
+    /// Очередь пуста
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Количество сообщений, отброшенных из-за переполнения очереди
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn pop(&self, state: &mut State<T>) -> Option<T> {
+        let item = state.items.pop_front();
+        if item.is_some() {
+            self.0.not_full.notify_one();
+        }
+        item
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.receiver_alive = false;
+        state.items.clear();
+        drop(state);
+        self.0.not_full.notify_all();
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+/// Блокирующий итератор по сообщениям, см. [`Receiver::iter`]
+pub struct IntoIter<T>(Receiver<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.recv().ok()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("len", &self.len())
+            .field("capacity", &self.0.capacity)
+            .field("overflow", &self.0.overflow)
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+
+impl<T> fmt::Debug for IntoIter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.0).finish()
+    }
+}
@@ -0,0 +1,2373 @@
+use super::{lang, Timestamp};
+use rust_decimal::Decimal;
+use std::fmt;
+
+/// Ошибка проверки параметров команды
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValidationError {
+    /// Значение параметра содержит специальные символы XML `<`, `>`, `&`, `"`
+    InvalidCharacters {
+        /// Имя параметра
+        field: &'static str,
+        /// Значение параметра
+        value: String,
+    },
+    /// Не указан обязательный параметр
+    MissingField(&'static str),
+    /// Не указана цена лимитной заявки
+    MissingPrice,
+    /// Нулевой идентификатор транзакции
+    ZeroTransactionId,
+    /// Указаны взаимоисключающие параметры, например цена и `bymarket`
+    ConflictingFields(&'static str, &'static str),
+    /// Длина значения параметра превышает допустимую
+    TooLong {
+        /// Имя параметра
+        field: &'static str,
+        /// Допустимая длина, символов
+        max: usize,
+    },
+    /// Значение параметра вне допустимого диапазона
+    InvalidValue {
+        /// Имя параметра
+        field: &'static str,
+        /// Значение параметра
+        value: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::InvalidCharacters { field, value } => {
+                write!(f, "{} '{field}': \"{value}\"", lang::INVALID_CHARACTERS)
+            }
+            ValidationError::MissingField(field) => {
+                write!(f, "{} '{field}'", lang::MISSING_PARAMETER)
+            }
+            ValidationError::MissingPrice => f.write_str(lang::MISSING_PRICE),
+            ValidationError::ZeroTransactionId => f.write_str(lang::ZERO_TRANSACTION_ID),
+            ValidationError::ConflictingFields(a, b) => {
+                write!(f, "{} '{a}', '{b}'", lang::CONFLICTING_PARAMETERS)
+            }
+            ValidationError::TooLong { field, max } => {
+                write!(f, "{} '{field}': > {max}", lang::PARAMETER_TOO_LONG)
+            }
+            ValidationError::InvalidValue { field, value } => {
+                write!(f, "{} '{field}': \"{value}\"", lang::INVALID_PARAMETER)
+            }
+        }
+    }
+}
+impl std::error::Error for ValidationError {}
+
+// Rejects values which would break the structure of the command
+fn check_chars(field: &'static str, value: &str) -> Result<(), ValidationError> {
+    if value.bytes().any(|b| matches!(b, b'<' | b'>' | b'&' | b'"')) {
+        Err(ValidationError::InvalidCharacters { field, value: value.to_string() })
+    } else {
+        Ok(())
+    }
+}
+
+// Rejects empty and malformed values of the required parameters
+fn check_required(field: &'static str, value: &str) -> Result<(), ValidationError> {
+    if value.is_empty() {
+        return Err(ValidationError::MissingField(field));
+    }
+    check_chars(field, value)
+}
+
+fn check_transaction_id(transactionid: u64) -> Result<(), ValidationError> {
+    if transactionid == 0 {
+        Err(ValidationError::ZeroTransactionId)
+    } else {
+        Ok(())
+    }
+}
+
+// Appends `s` with XML special characters replaced by entities
+fn push_escaped(buf: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\'' => buf.push_str("&apos;"),
+            c => buf.push(c),
+        }
+    }
+}
+
+fn push_element(buf: &mut String, tag: &str, value: impl fmt::Display) {
+    use fmt::Write;
+    let _ = write!(buf, "<{tag}>{value}</{tag}>");
+}
+
+fn push_escaped_element(buf: &mut String, tag: &str, value: &str) {
+    buf.push('<');
+    buf.push_str(tag);
+    buf.push('>');
+    push_escaped(buf, value);
+    buf.push_str("</");
+    buf.push_str(tag);
+    buf.push('>');
+}
+
+// Completes the command with the terminating nul byte, as expected by `Sender::send`
+fn terminate(mut buf: String) -> Vec<u8> {
+    buf.push('\0');
+    buf.into_bytes()
+}
+
+/// Команда подключения к серверу `connect`
+///
+/// Значения подставляются в команду с заменой специальных символов XML.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::ConnectCommand;
+///
+/// let connect = ConnectCommand::new()
+///     .login("login")
+///     .password("password")
+///     .host("tr1.finam.ru")
+///     .port(3900)
+///     .build();
+/// let result = unsafe { sender.send(connect)? };
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ConnectCommand {
+    login: String,
+    password: String,
+    host: String,
+    port: u16,
+    milliseconds: bool,
+    autopos: bool,
+    rqdelay: u16,
+}
+
+impl Default for ConnectCommand {
+    fn default() -> Self {
+        Self {
+            login: String::new(),
+            password: String::new(),
+            host: String::new(),
+            port: 0,
+            milliseconds: false,
+            autopos: true,
+            rqdelay: 100,
+        }
+    }
+}
+
+impl ConnectCommand {
+    /// Создаёт команду со значениями по-умолчанию: `autopos=true`, `milliseconds=false`,
+    /// `rqdelay=100`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Идентификатор пользователя
+    pub fn login<S: Into<String>>(mut self, login: S) -> Self {
+        self.login = login.into();
+        self
+    }
+
+    /// Пароль пользователя
+    pub fn password<S: Into<String>>(mut self, password: S) -> Self {
+        self.password = password.into();
+        self
+    }
+
+    /// Адрес сервера
+    pub fn host<S: Into<String>>(mut self, host: S) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Порт сервера
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Передавать время с точностью до миллисекунд
+    pub fn milliseconds(mut self, milliseconds: bool) -> Self {
+        self.milliseconds = milliseconds;
+        self
+    }
+
+    /// Автоматически запрашивать информацию о позициях при изменении
+    pub fn autopos(mut self, autopos: bool) -> Self {
+        self.autopos = autopos;
+        self
+    }
+
+    /// Период агрегирования данных, мс
+    pub fn rqdelay(mut self, rqdelay: u16) -> Self {
+        self.rqdelay = rqdelay;
+        self
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    pub fn build(&self) -> Vec<u8> {
+        let mut buf = String::with_capacity(256);
+        buf.push_str("<command id=\"connect\">");
+        push_escaped_element(&mut buf, "login", &self.login);
+        push_escaped_element(&mut buf, "password", &self.password);
+        push_escaped_element(&mut buf, "host", &self.host);
+        push_element(&mut buf, "port", self.port);
+        push_element(&mut buf, "milliseconds", self.milliseconds);
+        push_element(&mut buf, "autopos", self.autopos);
+        push_element(&mut buf, "rqdelay", self.rqdelay);
+        buf.push_str("</command>");
+        terminate(buf)
+    }
+}
+
+impl fmt::Debug for ConnectCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectCommand")
+            .field("login", &self.login)
+            .field("password", &"***")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("milliseconds", &self.milliseconds)
+            .field("autopos", &self.autopos)
+            .field("rqdelay", &self.rqdelay)
+            .finish()
+    }
+}
+
+/// Команда подписки на сделки по инструментам `subscribe_ticks`
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::SubscribeTicksCommand;
+///
+/// let mut cmd = SubscribeTicksCommand::new().security("TQBR", "SBER").security("TQBR", "GAZP");
+/// cmd.remove("TQBR", "GAZP");
+/// let result = unsafe { sender.send(cmd.build()?)? };
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct SubscribeTicksCommand {
+    securities: Vec<(String, String)>,
+}
+
+impl SubscribeTicksCommand {
+    /// Создаёт команду без инструментов
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляет инструмент **seccode** режима торгов **board**
+    pub fn security<B: Into<String>, S: Into<String>>(mut self, board: B, seccode: S) -> Self {
+        self.add(board, seccode);
+        self
+    }
+
+    /// Добавляет инструмент **seccode** режима торгов **board**, если он ещё не добавлен
+    pub fn add<B: Into<String>, S: Into<String>>(&mut self, board: B, seccode: S) -> &mut Self {
+        let security = (board.into(), seccode.into());
+        if !self.securities.contains(&security) {
+            self.securities.push(security);
+        }
+        self
+    }
+
+    /// Удаляет инструмент, возвращает `false`, если инструмент не был добавлен
+    pub fn remove(&mut self, board: &str, seccode: &str) -> bool {
+        let len = self.securities.len();
+        self.securities.retain(|(b, s)| !(b == board && s == seccode));
+        len != self.securities.len()
+    }
+
+    /// Добавленные инструменты, пары `(board, seccode)`
+    pub fn securities(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.securities.iter().map(|(b, s)| (b.as_str(), s.as_str()))
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    ///
+    /// # Errors
+    /// - [`ValidationError::InvalidCharacters`] - `board` или `seccode` содержат специальные
+    ///   символы XML
+    pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+        for (board, seccode) in &self.securities {
+            check_chars("board", board)?;
+            check_chars("seccode", seccode)?;
+        }
+        Ok(self.build_unchecked())
+    }
+
+    /// Формирует команду без проверки параметров
+    pub fn build_unchecked(&self) -> Vec<u8> {
+        let mut buf = String::with_capacity(64 + self.securities.len() * 64);
+        buf.push_str("<command id=\"subscribe_ticks\"><securities>");
+        for (board, seccode) in &self.securities {
+            buf.push_str("<security>");
+            push_element(&mut buf, "board", board);
+            push_element(&mut buf, "seccode", seccode);
+            buf.push_str("</security>");
+        }
+        buf.push_str("</securities></command>");
+        terminate(buf)
+    }
+}
+
+// Securities of the `subscribe` and `unsubscribe` commands, grouped by the kind of market data
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+struct MarketDataSections {
+    all_trades: Vec<(String, String)>,
+    quotations: Vec<(String, String)>,
+    quotes: Vec<(String, String)>,
+}
+
+impl MarketDataSections {
+    fn sections(&self) -> [(&'static str, &[(String, String)]); 3] {
+        [
+            ("alltrades", &self.all_trades),
+            ("quotations", &self.quotations),
+            ("quotes", &self.quotes),
+        ]
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        let sections = self.sections();
+        if sections.iter().all(|(_, securities)| securities.is_empty()) {
+            return Err(ValidationError::MissingField("security"));
+        }
+        for (board, seccode) in sections.iter().flat_map(|(_, securities)| securities.iter()) {
+            check_required("board", board)?;
+            check_required("seccode", seccode)?;
+        }
+        Ok(())
+    }
+
+    fn build(&self, id: &str) -> Vec<u8> {
+        let sections = self.sections();
+        let count: usize = sections.iter().map(|(_, securities)| securities.len()).sum();
+        let mut buf = String::with_capacity(64 + count * 64);
+        buf.push_str("<command id=\"");
+        buf.push_str(id);
+        buf.push_str("\">");
+        for (tag, securities) in sections.iter().filter(|(_, securities)| !securities.is_empty()) {
+            buf.push('<');
+            buf.push_str(tag);
+            buf.push('>');
+            for (board, seccode) in securities.iter() {
+                buf.push_str("<security>");
+                push_escaped_element(&mut buf, "board", board);
+                push_escaped_element(&mut buf, "seccode", seccode);
+                buf.push_str("</security>");
+            }
+            buf.push_str("</");
+            buf.push_str(tag);
+            buf.push('>');
+        }
+        buf.push_str("</command>");
+        terminate(buf)
+    }
+}
+
+fn extend_securities<I, B, S>(section: &mut Vec<(String, String)>, securities: I)
+where
+    I: IntoIterator<Item = (B, S)>,
+    B: Into<String>,
+    S: Into<String>,
+{
+    for (board, seccode) in securities {
+        let security = (board.into(), seccode.into());
+        if !section.contains(&security) {
+            section.push(security);
+        }
+    }
+}
+
+macro_rules! market_data_command {
+    ($(#[$meta:meta])* $name:ident, $id:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+        pub struct $name(MarketDataSections);
+
+        impl $name {
+            /// Создаёт команду без инструментов
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Добавляет инструменты, пары `(board, seccode)`, в раздел `<alltrades>` - все сделки
+            pub fn all_trades<I, B, S>(mut self, securities: I) -> Self
+            where
+                I: IntoIterator<Item = (B, S)>,
+                B: Into<String>,
+                S: Into<String>,
+            {
+                extend_securities(&mut self.0.all_trades, securities);
+                self
+            }
+
+            /// Добавляет инструменты, пары `(board, seccode)`, в раздел `<quotations>` -
+            /// котировки
+            pub fn quotations<I, B, S>(mut self, securities: I) -> Self
+            where
+                I: IntoIterator<Item = (B, S)>,
+                B: Into<String>,
+                S: Into<String>,
+            {
+                extend_securities(&mut self.0.quotations, securities);
+                self
+            }
+
+            /// Добавляет инструменты, пары `(board, seccode)`, в раздел `<quotes>` - стакан
+            pub fn quotes<I, B, S>(mut self, securities: I) -> Self
+            where
+                I: IntoIterator<Item = (B, S)>,
+                B: Into<String>,
+                S: Into<String>,
+            {
+                extend_securities(&mut self.0.quotes, securities);
+                self
+            }
+
+            /// Формирует команду, завершённую нулевым байтом
+            ///
+            /// Разделы без инструментов не включаются в команду.
+            ///
+            /// # Errors
+            /// - [`ValidationError::MissingField`] - не указано ни одного инструмента, или
+            ///   инструмент с пустым `board` или `seccode`
+            /// - [`ValidationError::InvalidCharacters`] - `board` или `seccode` содержат
+            ///   специальные символы XML
+            pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+                self.0.validate()?;
+                Ok(self.build_unchecked())
+            }
+
+            /// Формирует команду без проверки параметров, специальные символы XML заменяются
+            pub fn build_unchecked(&self) -> Vec<u8> {
+                self.0.build($id)
+            }
+        }
+    };
+}
+
+market_data_command!(
+    /// Команда подписки на биржевую информацию `subscribe`
+    ///
+    /// # Пример
+    /// ```no_run
+    /// use libtxc::SubscribeCommand;
+    ///
+    /// let cmd = SubscribeCommand::new()
+    ///     .all_trades([("TQBR", "SBER")])
+    ///     .quotations([("TQBR", "SBER"), ("TQBR", "GAZP")])
+    ///     .quotes([("FUT", "SiZ6")]);
+    /// let ack = sender.subscribe(&cmd)?;
+    /// ```
+    SubscribeCommand,
+    "subscribe"
+);
+
+market_data_command!(
+    /// Команда отмены подписки на биржевую информацию `unsubscribe`, см. [`SubscribeCommand`]
+    ///
+    /// # Пример
+    /// ```no_run
+    /// use libtxc::UnsubscribeCommand;
+    ///
+    /// let cmd = UnsubscribeCommand::new().quotes([("FUT", "SiZ6")]);
+    /// let ack = sender.unsubscribe(&cmd)?;
+    /// ```
+    UnsubscribeCommand,
+    "unsubscribe"
+);
+
+/// Команда запроса исторических данных `gethistorydata`
+///
+/// Ответ передаётся сообщениями `<candles>`, см. [`Candles`](crate::Candles) и
+/// [`HistoryFetcher`](crate::HistoryFetcher).
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::HistoryDataCommand;
+///
+/// let cmd = HistoryDataCommand::new("TQBR", "SBER", 1, 500).reset(false);
+/// let result = unsafe { sender.send(cmd.build()?)? };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HistoryDataCommand {
+    board: String,
+    seccode: String,
+    period: u32,
+    count: u32,
+    reset: bool,
+}
+
+impl HistoryDataCommand {
+    /// Создаёт запрос **count** последних свечей периода **period** инструмента **seccode**
+    /// режима торгов **board**
+    pub fn new<B: Into<String>, S: Into<String>>(
+        board: B,
+        seccode: S,
+        period: u32,
+        count: u32,
+    ) -> Self {
+        Self { board: board.into(), seccode: seccode.into(), period, count, reset: true }
+    }
+
+    /// `true`(по-умолчанию) - запросить последние свечи, `false` - продолжить с места,
+    /// на котором завершился предыдущий запрос, т.е. запросить более ранние свечи
+    pub fn reset(mut self, reset: bool) -> Self {
+        self.reset = reset;
+        self
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    ///
+    /// # Errors
+    /// - [`ValidationError::MissingField`] - не указан `board`, `seccode` или `count`
+    /// - [`ValidationError::InvalidCharacters`] - `board` или `seccode` содержат специальные
+    ///   символы XML
+    pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+        check_required("board", &self.board)?;
+        check_required("seccode", &self.seccode)?;
+        if self.count == 0 {
+            return Err(ValidationError::MissingField("count"));
+        }
+        Ok(self.build_unchecked())
+    }
+
+    /// Формирует команду без проверки параметров
+    pub fn build_unchecked(&self) -> Vec<u8> {
+        let mut buf = String::with_capacity(192);
+        buf.push_str("<command id=\"gethistorydata\"><security>");
+        push_element(&mut buf, "board", &self.board);
+        push_element(&mut buf, "seccode", &self.seccode);
+        buf.push_str("</security>");
+        push_element(&mut buf, "period", self.period);
+        push_element(&mut buf, "count", self.count);
+        push_element(&mut buf, "reset", self.reset);
+        buf.push_str("</command>");
+        terminate(buf)
+    }
+}
+
+/// Направление заявки
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BuySell {
+    /// Покупка
+    Buy,
+    /// Продажа
+    Sell,
+}
+
+impl BuySell {
+    /// Значение параметра `buysell` команды
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuySell::Buy => "B",
+            BuySell::Sell => "S",
+        }
+    }
+}
+
+impl fmt::Display for BuySell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// `brokerref` is limited by the connector to 20 characters
+const BROKERREF_MAX_LEN: usize = 20;
+
+fn check_brokerref(brokerref: &str) -> Result<(), ValidationError> {
+    check_chars("brokerref", brokerref)?;
+    if brokerref.chars().count() > BROKERREF_MAX_LEN {
+        return Err(ValidationError::TooLong { field: "brokerref", max: BROKERREF_MAX_LEN });
+    }
+    Ok(())
+}
+
+fn check_positive(field: &'static str, value: Decimal) -> Result<(), ValidationError> {
+    if value.is_sign_positive() && !value.is_zero() {
+        Ok(())
+    } else {
+        Err(ValidationError::InvalidValue { field, value: value.to_string() })
+    }
+}
+
+// The connector accepts the time in seconds, without milliseconds
+fn check_time(field: &'static str, time: &Timestamp) -> Result<(), ValidationError> {
+    let valid = (1..=12).contains(&time.month)
+        && (1..=31).contains(&time.day)
+        && time.hour < 24
+        && time.minute < 60
+        && time.second < 60
+        && time.millisecond == 0;
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::InvalidValue { field, value: time.to_string() })
+    }
+}
+
+/// Способ исполнения лимитной заявки, параметр `unfilled` команды `neworder`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Unfilled {
+    /// Неисполненная часть заявки помещается в очередь заявок(по-умолчанию)
+    #[default]
+    PutInQueue,
+    /// Заявка исполняется полностью или снимается
+    FillOrKill,
+    /// Неисполненная сразу часть заявки снимается
+    ImmediateOrCancel,
+}
+
+impl Unfilled {
+    /// Значение параметра `unfilled` команды
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unfilled::PutInQueue => "PutInQueue",
+            Unfilled::FillOrKill => "FOK",
+            Unfilled::ImmediateOrCancel => "IOC",
+        }
+    }
+}
+
+/// Срок начала действия условной заявки, параметр `validafter`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidAfter {
+    /// С момента выставления(по-умолчанию)
+    #[default]
+    Now,
+    /// С указанного времени
+    At(Timestamp),
+}
+
+impl fmt::Display for ValidAfter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidAfter::Now => f.write_str("0"),
+            ValidAfter::At(time) => time.fmt(f),
+        }
+    }
+}
+
+/// Срок действия условной или стоп-заявки, параметры `validbefore` и `validfor`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidBefore {
+    /// До конца торговой сессии(по-умолчанию)
+    #[default]
+    EndOfSession,
+    /// До отмены
+    TillCanceled,
+    /// До указанного времени
+    At(Timestamp),
+}
+
+impl fmt::Display for ValidBefore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidBefore::EndOfSession => f.write_str("0"),
+            ValidBefore::TillCanceled => f.write_str("till_canceled"),
+            ValidBefore::At(time) => time.fmt(f),
+        }
+    }
+}
+
+/// Условие активации условной заявки, параметры `cond_type` и `cond_value` команды
+/// `newcondorder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Condition {
+    /// Лучшая цена покупки не выше значения
+    Bid(Decimal),
+    /// Лучшая цена покупки или цена последней сделки не выше значения
+    BidOrLast(Decimal),
+    /// Лучшая цена продажи не ниже значения
+    Ask(Decimal),
+    /// Лучшая цена продажи или цена последней сделки не ниже значения
+    AskOrLast(Decimal),
+    /// Наступление указанного времени
+    Time(Timestamp),
+    /// Обеспеченность ниже значения
+    CovDown(Decimal),
+    /// Обеспеченность выше значения
+    CovUp(Decimal),
+    /// Цена последней сделки выше значения
+    LastUp(Decimal),
+    /// Цена последней сделки ниже значения
+    LastDown(Decimal),
+}
+
+impl Condition {
+    /// Значение параметра `cond_type` команды
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Condition::Bid(_) => "Bid",
+            Condition::BidOrLast(_) => "BidOrLast",
+            Condition::Ask(_) => "Ask",
+            Condition::AskOrLast(_) => "AskOrLast",
+            Condition::Time(_) => "Time",
+            Condition::CovDown(_) => "CovDown",
+            Condition::CovUp(_) => "CovUp",
+            Condition::LastUp(_) => "LastUp",
+            Condition::LastDown(_) => "LastDown",
+        }
+    }
+
+    fn check(&self) -> Result<(), ValidationError> {
+        match self {
+            Condition::Time(time) => check_time("cond_value", time),
+            Condition::Bid(value)
+            | Condition::BidOrLast(value)
+            | Condition::Ask(value)
+            | Condition::AskOrLast(value)
+            | Condition::LastUp(value)
+            | Condition::LastDown(value) => check_positive("cond_value", *value),
+            // coverage is compared as is, negative values are meaningful
+            Condition::CovDown(_) | Condition::CovUp(_) => Ok(()),
+        }
+    }
+
+    fn push(&self, buf: &mut String) {
+        push_element(buf, "cond_type", self.as_str());
+        match self {
+            Condition::Time(time) => push_element(buf, "cond_value", time),
+            Condition::Bid(value)
+            | Condition::BidOrLast(value)
+            | Condition::Ask(value)
+            | Condition::AskOrLast(value)
+            | Condition::CovDown(value)
+            | Condition::CovUp(value)
+            | Condition::LastUp(value)
+            | Condition::LastDown(value) => push_element(buf, "cond_value", value),
+        }
+    }
+}
+
+// Instrument, account and direction, shared by all of the order commands
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+struct OrderTarget {
+    board: String,
+    seccode: String,
+    client: String,
+    union: String,
+    buysell: Option<BuySell>,
+}
+
+impl OrderTarget {
+    fn new(buysell: BuySell, board: String, seccode: String) -> Self {
+        Self { board, seccode, buysell: Some(buysell), ..Self::default() }
+    }
+
+    fn check(&self) -> Result<BuySell, ValidationError> {
+        check_required("board", &self.board)?;
+        check_required("seccode", &self.seccode)?;
+        match (self.client.is_empty(), self.union.is_empty()) {
+            (true, true) => return Err(ValidationError::MissingField("client")),
+            (false, false) => return Err(ValidationError::ConflictingFields("client", "union")),
+            (false, true) => check_chars("client", &self.client)?,
+            (true, false) => check_chars("union", &self.union)?,
+        }
+        self.buysell.ok_or(ValidationError::MissingField("buysell"))
+    }
+
+    // `<security>` and the account, either `<client>` or `<union>`
+    fn push(&self, buf: &mut String) {
+        buf.push_str("<security>");
+        push_element(buf, "board", &self.board);
+        push_element(buf, "seccode", &self.seccode);
+        buf.push_str("</security>");
+        if self.union.is_empty() {
+            push_element(buf, "client", &self.client);
+        } else {
+            push_element(buf, "union", &self.union);
+        }
+    }
+}
+
+// Parameters of the order placed by `neworder` and `newcondorder`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+struct OrderParams {
+    quantity: u32,
+    price: Option<Decimal>,
+    hidden: Option<u32>,
+    bymarket: bool,
+    brokerref: String,
+    usecredit: bool,
+    nosplit: bool,
+}
+
+impl OrderParams {
+    fn check(&self) -> Result<(), ValidationError> {
+        if self.quantity == 0 {
+            return Err(ValidationError::MissingField("quantity"));
+        }
+        match (self.bymarket, self.price) {
+            (true, Some(_)) => return Err(ValidationError::ConflictingFields("bymarket", "price")),
+            (false, None) => return Err(ValidationError::MissingPrice),
+            (false, Some(price)) => check_positive("price", price)?,
+            (true, None) => {}
+        }
+        if let Some(hidden) = self.hidden {
+            if self.bymarket {
+                return Err(ValidationError::ConflictingFields("bymarket", "hidden"));
+            }
+            // the visible part must not be empty
+            if hidden >= self.quantity {
+                return Err(ValidationError::InvalidValue {
+                    field: "hidden",
+                    value: hidden.to_string(),
+                });
+            }
+        }
+        check_brokerref(&self.brokerref)
+    }
+
+    // price, quantity, direction and the order type, in the order of the connector's reference
+    fn push(&self, buf: &mut String, buysell: BuySell) {
+        if let Some(price) = self.price {
+            push_element(buf, "price", price);
+        }
+        if let Some(hidden) = self.hidden {
+            push_element(buf, "hidden", hidden);
+        }
+        push_element(buf, "quantity", self.quantity);
+        push_element(buf, "buysell", buysell);
+        if self.bymarket {
+            buf.push_str("<bymarket/>");
+        }
+        if !self.brokerref.is_empty() {
+            push_element(buf, "brokerref", &self.brokerref);
+        }
+    }
+
+    fn push_flags(&self, buf: &mut String) {
+        if self.usecredit {
+            buf.push_str("<usecredit/>");
+        }
+        if self.nosplit {
+            buf.push_str("<nosplit/>");
+        }
+    }
+}
+
+// Setters of `OrderTarget` in the `target` field of the command
+macro_rules! order_target_setters {
+    () => {
+        /// Режим торгов
+        pub fn board<S: Into<String>>(mut self, board: S) -> Self {
+            self.target.board = board.into();
+            self
+        }
+
+        /// Код инструмента
+        pub fn seccode<S: Into<String>>(mut self, seccode: S) -> Self {
+            self.target.seccode = seccode.into();
+            self
+        }
+
+        /// Клиент, не указывается вместе с [`union`](Self::union)
+        pub fn client<S: Into<String>>(mut self, client: S) -> Self {
+            self.target.client = client.into();
+            self
+        }
+
+        /// Код юниона(единого денежного счёта), не указывается вместе с [`client`](Self::client)
+        pub fn union<S: Into<String>>(mut self, union: S) -> Self {
+            self.target.union = union.into();
+            self
+        }
+
+        /// Направление заявки
+        pub fn buysell(mut self, buysell: BuySell) -> Self {
+            self.target.buysell = Some(buysell);
+            self
+        }
+    };
+}
+
+// Setters of `OrderParams` in the `order` field of the command
+macro_rules! order_params_setters {
+    () => {
+        /// Количество, лотов
+        pub fn quantity(mut self, quantity: u32) -> Self {
+            self.order.quantity = quantity;
+            self
+        }
+
+        /// Цена лимитной заявки, не указывается вместе с [`bymarket`](Self::bymarket)
+        pub fn price(mut self, price: Decimal) -> Self {
+            self.order.price = Some(price);
+            self
+        }
+
+        /// Скрытое количество айсберг-заявки, лотов, меньше общего количества
+        pub fn hidden(mut self, hidden: u32) -> Self {
+            self.order.hidden = Some(hidden);
+            self
+        }
+
+        /// Выставить заявку по рыночной цене, цена заявки при этом не указывается
+        pub fn bymarket(mut self, bymarket: bool) -> Self {
+            self.order.bymarket = bymarket;
+            self
+        }
+
+        /// Примечание, не более 20 символов
+        pub fn brokerref<S: Into<String>>(mut self, brokerref: S) -> Self {
+            self.order.brokerref = brokerref.into();
+            self
+        }
+
+        /// Использовать кредит
+        pub fn usecredit(mut self, usecredit: bool) -> Self {
+            self.order.usecredit = usecredit;
+            self
+        }
+
+        /// Не разделять заявку по ценам
+        pub fn nosplit(mut self, nosplit: bool) -> Self {
+            self.order.nosplit = nosplit;
+            self
+        }
+    };
+}
+
+/// Команда выставления заявки `neworder`
+///
+/// Лимитная заявка требует указания цены, рыночная(`bymarket`) выставляется без цены.
+/// Параметры проверяются при формировании команды, см. [`PlaceOrderCommand::build`].
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{Decimal, PlaceOrderCommand, Unfilled};
+///
+/// let limit = PlaceOrderCommand::buy("TQBR", "SBER")
+///     .client("client")
+///     .quantity(10)
+///     .price(Decimal::new(25050, 2))
+///     .unfilled(Unfilled::ImmediateOrCancel)
+///     .brokerref("strategy-1");
+/// let ack = sender.place(&limit)?;
+/// println!("{}", ack.transactionid);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct PlaceOrderCommand {
+    target: OrderTarget,
+    order: OrderParams,
+    unfilled: Option<Unfilled>,
+}
+
+impl PlaceOrderCommand {
+    /// Создаёт команду без параметров
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Создаёт заявку на покупку инструмента **seccode** в режиме торгов **board**
+    pub fn buy<B: Into<String>, S: Into<String>>(board: B, seccode: S) -> Self {
+        Self { target: OrderTarget::new(BuySell::Buy, board.into(), seccode.into()), ..Self::new() }
+    }
+
+    /// Создаёт заявку на продажу инструмента **seccode** в режиме торгов **board**
+    pub fn sell<B: Into<String>, S: Into<String>>(board: B, seccode: S) -> Self {
+        Self {
+            target: OrderTarget::new(BuySell::Sell, board.into(), seccode.into()),
+            ..Self::new()
+        }
+    }
+
+    order_target_setters!();
+    order_params_setters!();
+
+    /// Способ исполнения заявки, не указывается для рыночной заявки
+    pub fn unfilled(mut self, unfilled: Unfilled) -> Self {
+        self.unfilled = Some(unfilled);
+        self
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    ///
+    /// # Errors
+    /// - [`ValidationError::MissingField`] - не указан `board`, `seccode`, `client`(или
+    ///   `union`), `buysell` или `quantity`
+    /// - [`ValidationError::MissingPrice`] - не указана цена лимитной заявки
+    /// - [`ValidationError::ConflictingFields`] - указаны `client` и `union`; цена, `hidden`
+    ///   или `unfilled` рыночной заявки
+    /// - [`ValidationError::InvalidValue`] - цена не положительна, `hidden` не меньше `quantity`
+    /// - [`ValidationError::TooLong`] - `brokerref` длиннее 20 символов
+    /// - [`ValidationError::InvalidCharacters`] - параметры содержат специальные символы XML
+    pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+        let buysell = self.target.check()?;
+        self.order.check()?;
+        if self.order.bymarket && self.unfilled.is_some() {
+            return Err(ValidationError::ConflictingFields("bymarket", "unfilled"));
+        }
+
+        let mut buf = String::with_capacity(320);
+        buf.push_str("<command id=\"neworder\">");
+        self.target.push(&mut buf);
+        self.order.push(&mut buf, buysell);
+        if let Some(unfilled) = self.unfilled {
+            push_element(&mut buf, "unfilled", unfilled.as_str());
+        }
+        self.order.push_flags(&mut buf);
+        buf.push_str("</command>");
+        Ok(terminate(buf))
+    }
+}
+
+/// Команда выставления условной заявки `newcondorder`
+///
+/// Заявка выставляется на биржу при выполнении условия [`Condition`] в течение срока действия
+/// [`validafter`](Self::validafter) - [`validbefore`](Self::validbefore).
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{CondOrderCommand, Condition, Decimal, ValidBefore};
+///
+/// let cond = CondOrderCommand::sell("TQBR", "SBER")
+///     .client("client")
+///     .quantity(10)
+///     .price(Decimal::new(26000, 2))
+///     .condition(Condition::LastUp(Decimal::new(25990, 2)))
+///     .validbefore(ValidBefore::TillCanceled);
+/// let ack = sender.place_cond(&cond)?;
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct CondOrderCommand {
+    target: OrderTarget,
+    order: OrderParams,
+    condition: Option<Condition>,
+    validafter: ValidAfter,
+    validbefore: ValidBefore,
+    within_pos: bool,
+    expdate: Option<Timestamp>,
+}
+
+impl CondOrderCommand {
+    /// Создаёт команду без параметров
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Создаёт заявку на покупку инструмента **seccode** в режиме торгов **board**
+    pub fn buy<B: Into<String>, S: Into<String>>(board: B, seccode: S) -> Self {
+        Self { target: OrderTarget::new(BuySell::Buy, board.into(), seccode.into()), ..Self::new() }
+    }
+
+    /// Создаёт заявку на продажу инструмента **seccode** в режиме торгов **board**
+    pub fn sell<B: Into<String>, S: Into<String>>(board: B, seccode: S) -> Self {
+        Self {
+            target: OrderTarget::new(BuySell::Sell, board.into(), seccode.into()),
+            ..Self::new()
+        }
+    }
+
+    order_target_setters!();
+    order_params_setters!();
+
+    /// Условие выставления заявки
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Начало срока действия
+    pub fn validafter(mut self, validafter: ValidAfter) -> Self {
+        self.validafter = validafter;
+        self
+    }
+
+    /// Окончание срока действия
+    pub fn validbefore(mut self, validbefore: ValidBefore) -> Self {
+        self.validbefore = validbefore;
+        self
+    }
+
+    /// Выставить заявку только в пределах имеющейся позиции
+    pub fn within_pos(mut self, within_pos: bool) -> Self {
+        self.within_pos = within_pos;
+        self
+    }
+
+    /// Дата экспирации, только для фьючерсов
+    pub fn expdate(mut self, expdate: Timestamp) -> Self {
+        self.expdate = Some(expdate);
+        self
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    ///
+    /// # Errors
+    /// - [`ValidationError::MissingField`] - не указан `board`, `seccode`, `client`(или
+    ///   `union`), `buysell`, `quantity` или условие `cond_type`
+    /// - [`ValidationError::MissingPrice`] - не указана цена лимитной заявки
+    /// - [`ValidationError::ConflictingFields`] - указаны `client` и `union`; цена или `hidden`
+    ///   рыночной заявки
+    /// - [`ValidationError::InvalidValue`] - цена или ценовое условие не положительны, `hidden` не
+    ///   меньше `quantity`, время указано с миллисекундами или вне допустимого диапазона,
+    ///   `validbefore` не позже `validafter`
+    /// - [`ValidationError::TooLong`] - `brokerref` длиннее 20 символов
+    /// - [`ValidationError::InvalidCharacters`] - параметры содержат специальные символы XML
+    pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+        let buysell = self.target.check()?;
+        self.order.check()?;
+        let condition = self.condition.ok_or(ValidationError::MissingField("cond_type"))?;
+        condition.check()?;
+        if let ValidAfter::At(time) = &self.validafter {
+            check_time("validafter", time)?;
+        }
+        if let ValidBefore::At(time) = &self.validbefore {
+            check_time("validbefore", time)?;
+            if matches!(self.validafter, ValidAfter::At(after) if after >= *time) {
+                return Err(ValidationError::InvalidValue {
+                    field: "validbefore",
+                    value: time.to_string(),
+                });
+            }
+        }
+        if let Some(expdate) = &self.expdate {
+            check_time("expdate", expdate)?;
+        }
+
+        let mut buf = String::with_capacity(448);
+        buf.push_str("<command id=\"newcondorder\">");
+        self.target.push(&mut buf);
+        self.order.push(&mut buf, buysell);
+        condition.push(&mut buf);
+        push_element(&mut buf, "validafter", self.validafter);
+        push_element(&mut buf, "validbefore", self.validbefore);
+        self.order.push_flags(&mut buf);
+        if self.within_pos {
+            buf.push_str("<within_pos/>");
+        }
+        if let Some(expdate) = &self.expdate {
+            push_element(&mut buf, "expdate", expdate);
+        }
+        buf.push_str("</command>");
+        Ok(terminate(buf))
+    }
+}
+
+/// Параметры стоп-лосса команды `newstoporder`
+///
+/// Заявка выставляется при достижении цены активации по цене [`orderprice`](Self::orderprice)
+/// или по рыночной цене.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StopLossParams {
+    activationprice: Decimal,
+    orderprice: Option<Decimal>,
+    bymarket: bool,
+    quantity: u32,
+    usecredit: bool,
+    brokerref: String,
+}
+
+impl StopLossParams {
+    /// Создаёт стоп-лосс с ценой активации **activationprice**
+    pub fn new(activationprice: Decimal) -> Self {
+        Self {
+            activationprice,
+            orderprice: None,
+            bymarket: false,
+            quantity: 0,
+            usecredit: false,
+            brokerref: String::new(),
+        }
+    }
+
+    /// Цена выставляемой заявки, не указывается вместе с [`bymarket`](Self::bymarket)
+    pub fn orderprice(mut self, orderprice: Decimal) -> Self {
+        self.orderprice = Some(orderprice);
+        self
+    }
+
+    /// Выставить заявку по рыночной цене
+    pub fn bymarket(mut self, bymarket: bool) -> Self {
+        self.bymarket = bymarket;
+        self
+    }
+
+    /// Количество, лотов
+    pub fn quantity(mut self, quantity: u32) -> Self {
+        self.quantity = quantity;
+        self
+    }
+
+    /// Использовать кредит
+    pub fn usecredit(mut self, usecredit: bool) -> Self {
+        self.usecredit = usecredit;
+        self
+    }
+
+    /// Примечание, не более 20 символов
+    pub fn brokerref<S: Into<String>>(mut self, brokerref: S) -> Self {
+        self.brokerref = brokerref.into();
+        self
+    }
+
+    fn check(&self) -> Result<(), ValidationError> {
+        check_positive("activationprice", self.activationprice)?;
+        match (self.bymarket, self.orderprice) {
+            (true, Some(_)) => {
+                return Err(ValidationError::ConflictingFields("bymarket", "orderprice"))
+            }
+            (false, None) => return Err(ValidationError::MissingPrice),
+            (false, Some(price)) => check_positive("orderprice", price)?,
+            (true, None) => {}
+        }
+        if self.quantity == 0 {
+            return Err(ValidationError::MissingField("quantity"));
+        }
+        check_brokerref(&self.brokerref)
+    }
+
+    fn push(&self, buf: &mut String) {
+        buf.push_str("<stoploss>");
+        push_element(buf, "activationprice", self.activationprice);
+        if let Some(orderprice) = self.orderprice {
+            push_element(buf, "orderprice", orderprice);
+        }
+        if self.bymarket {
+            buf.push_str("<bymarket/>");
+        }
+        push_element(buf, "quantity", self.quantity);
+        if self.usecredit {
+            buf.push_str("<usecredit/>");
+        }
+        if !self.brokerref.is_empty() {
+            push_element(buf, "brokerref", &self.brokerref);
+        }
+        buf.push_str("</stoploss>");
+    }
+}
+
+/// Параметры тейк-профита команды `newstoporder`
+///
+/// При достижении цены активации и последующей коррекции цены на величину
+/// [`correction`](Self::correction) выставляется заявка с защитным спредом
+/// [`spread`](Self::spread) или по рыночной цене.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TakeProfitParams {
+    activationprice: Decimal,
+    quantity: u32,
+    correction: Option<Decimal>,
+    spread: Option<Decimal>,
+    bymarket: bool,
+    usecredit: bool,
+    brokerref: String,
+}
+
+impl TakeProfitParams {
+    /// Создаёт тейк-профит с ценой активации **activationprice**
+    pub fn new(activationprice: Decimal) -> Self {
+        Self {
+            activationprice,
+            quantity: 0,
+            correction: None,
+            spread: None,
+            bymarket: false,
+            usecredit: false,
+            brokerref: String::new(),
+        }
+    }
+
+    /// Количество, лотов
+    pub fn quantity(mut self, quantity: u32) -> Self {
+        self.quantity = quantity;
+        self
+    }
+
+    /// Коррекция цены
+    pub fn correction(mut self, correction: Decimal) -> Self {
+        self.correction = Some(correction);
+        self
+    }
+
+    /// Защитный спред, не указывается вместе с [`bymarket`](Self::bymarket)
+    pub fn spread(mut self, spread: Decimal) -> Self {
+        self.spread = Some(spread);
+        self
+    }
+
+    /// Выставить заявку по рыночной цене
+    pub fn bymarket(mut self, bymarket: bool) -> Self {
+        self.bymarket = bymarket;
+        self
+    }
+
+    /// Использовать кредит
+    pub fn usecredit(mut self, usecredit: bool) -> Self {
+        self.usecredit = usecredit;
+        self
+    }
+
+    /// Примечание, не более 20 символов
+    pub fn brokerref<S: Into<String>>(mut self, brokerref: S) -> Self {
+        self.brokerref = brokerref.into();
+        self
+    }
+
+    fn check(&self) -> Result<(), ValidationError> {
+        check_positive("activationprice", self.activationprice)?;
+        if self.quantity == 0 {
+            return Err(ValidationError::MissingField("quantity"));
+        }
+        if self.bymarket && self.spread.is_some() {
+            return Err(ValidationError::ConflictingFields("bymarket", "spread"));
+        }
+        for (field, value) in [("correction", self.correction), ("spread", self.spread)] {
+            match value {
+                Some(value) if value.is_sign_negative() && !value.is_zero() => {
+                    return Err(ValidationError::InvalidValue { field, value: value.to_string() })
+                }
+                _ => {}
+            }
+        }
+        check_brokerref(&self.brokerref)
+    }
+
+    fn push(&self, buf: &mut String) {
+        buf.push_str("<takeprofit>");
+        push_element(buf, "activationprice", self.activationprice);
+        push_element(buf, "quantity", self.quantity);
+        if self.usecredit {
+            buf.push_str("<usecredit/>");
+        }
+        if !self.brokerref.is_empty() {
+            push_element(buf, "brokerref", &self.brokerref);
+        }
+        if let Some(correction) = self.correction {
+            push_element(buf, "correction", correction);
+        }
+        if let Some(spread) = self.spread {
+            push_element(buf, "spread", spread);
+        }
+        if self.bymarket {
+            buf.push_str("<bymarket/>");
+        }
+        buf.push_str("</takeprofit>");
+    }
+}
+
+/// Команда выставления стоп-заявки `newstoporder`
+///
+/// Заявка содержит стоп-лосс, тейк-профит или оба условия. Связанная заявка
+/// [`linkedorderno`](Self::linkedorderno) снимает стоп-заявку при своём снятии.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{Decimal, StopLossParams, StopOrderCommand, TakeProfitParams, ValidBefore};
+///
+/// let stop = StopOrderCommand::sell("TQBR", "SBER")
+///     .client("client")
+///     .stoploss(StopLossParams::new(Decimal::new(24500, 2)).bymarket(true).quantity(10))
+///     .takeprofit(TakeProfitParams::new(Decimal::new(27000, 2)).quantity(10))
+///     .validfor(ValidBefore::TillCanceled);
+/// let ack = sender.place_stop(&stop)?;
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct StopOrderCommand {
+    target: OrderTarget,
+    linkedorderno: Option<u64>,
+    validfor: ValidBefore,
+    expdate: Option<Timestamp>,
+    stoploss: Option<StopLossParams>,
+    takeprofit: Option<TakeProfitParams>,
+}
+
+impl StopOrderCommand {
+    /// Создаёт команду без параметров
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Создаёт стоп-заявку на покупку инструмента **seccode** в режиме торгов **board**
+    pub fn buy<B: Into<String>, S: Into<String>>(board: B, seccode: S) -> Self {
+        Self { target: OrderTarget::new(BuySell::Buy, board.into(), seccode.into()), ..Self::new() }
+    }
+
+    /// Создаёт стоп-заявку на продажу инструмента **seccode** в режиме торгов **board**
+    pub fn sell<B: Into<String>, S: Into<String>>(board: B, seccode: S) -> Self {
+        Self {
+            target: OrderTarget::new(BuySell::Sell, board.into(), seccode.into()),
+            ..Self::new()
+        }
+    }
+
+    order_target_setters!();
+
+    /// Биржевой номер связанной заявки
+    pub fn linkedorderno(mut self, linkedorderno: u64) -> Self {
+        self.linkedorderno = Some(linkedorderno);
+        self
+    }
+
+    /// Срок действия
+    pub fn validfor(mut self, validfor: ValidBefore) -> Self {
+        self.validfor = validfor;
+        self
+    }
+
+    /// Дата экспирации, только для фьючерсов
+    pub fn expdate(mut self, expdate: Timestamp) -> Self {
+        self.expdate = Some(expdate);
+        self
+    }
+
+    /// Стоп-лосс
+    pub fn stoploss(mut self, stoploss: StopLossParams) -> Self {
+        self.stoploss = Some(stoploss);
+        self
+    }
+
+    /// Тейк-профит
+    pub fn takeprofit(mut self, takeprofit: TakeProfitParams) -> Self {
+        self.takeprofit = Some(takeprofit);
+        self
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    ///
+    /// # Errors
+    /// - [`ValidationError::MissingField`] - не указан `board`, `seccode`, `client`(или
+    ///   `union`), `buysell`, ни стоп-лосс, ни тейк-профит, или `quantity` одного из них
+    /// - [`ValidationError::MissingPrice`] - не указана цена лимитной заявки стоп-лосса
+    /// - [`ValidationError::ConflictingFields`] - указаны `client` и `union`; `orderprice`
+    ///   рыночного стоп-лосса, `spread` рыночного тейк-профита
+    /// - [`ValidationError::InvalidValue`] - цены не положительны, отрицательные `correction`
+    ///   или `spread`, нулевой `linkedorderno`, время указано с миллисекундами или вне
+    ///   допустимого диапазона
+    /// - [`ValidationError::TooLong`] - `brokerref` длиннее 20 символов
+    /// - [`ValidationError::InvalidCharacters`] - параметры содержат специальные символы XML
+    pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+        let buysell = self.target.check()?;
+        if self.stoploss.is_none() && self.takeprofit.is_none() {
+            return Err(ValidationError::MissingField("stoploss"));
+        }
+        if let Some(stoploss) = &self.stoploss {
+            stoploss.check()?;
+        }
+        if let Some(takeprofit) = &self.takeprofit {
+            takeprofit.check()?;
+        }
+        if self.linkedorderno == Some(0) {
+            return Err(ValidationError::InvalidValue {
+                field: "linkedorderno",
+                value: "0".to_string(),
+            });
+        }
+        if let ValidBefore::At(time) = &self.validfor {
+            check_time("validfor", time)?;
+        }
+        if let Some(expdate) = &self.expdate {
+            check_time("expdate", expdate)?;
+        }
+
+        let mut buf = String::with_capacity(512);
+        buf.push_str("<command id=\"newstoporder\">");
+        self.target.push(&mut buf);
+        push_element(&mut buf, "buysell", buysell);
+        if let Some(linkedorderno) = self.linkedorderno {
+            push_element(&mut buf, "linkedorderno", linkedorderno);
+        }
+        push_element(&mut buf, "validfor", self.validfor);
+        if let Some(expdate) = &self.expdate {
+            push_element(&mut buf, "expdate", expdate);
+        }
+        if let Some(stoploss) = &self.stoploss {
+            stoploss.push(&mut buf);
+        }
+        if let Some(takeprofit) = &self.takeprofit {
+            takeprofit.push(&mut buf);
+        }
+        buf.push_str("</command>");
+        Ok(terminate(buf))
+    }
+}
+
+/// Команда снятия заявки `cancelorder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CancelOrderCommand {
+    transactionid: u64,
+}
+
+impl CancelOrderCommand {
+    /// Создаёт команду снятия заявки с идентификатором транзакции **transactionid**
+    pub fn new(transactionid: u64) -> Self {
+        Self { transactionid }
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    ///
+    /// # Errors
+    /// [`ValidationError::ZeroTransactionId`] - нулевой идентификатор транзакции
+    pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+        check_transaction_id(self.transactionid)?;
+
+        let mut buf = String::with_capacity(96);
+        buf.push_str("<command id=\"cancelorder\">");
+        push_element(&mut buf, "transactionid", self.transactionid);
+        buf.push_str("</command>");
+        Ok(terminate(buf))
+    }
+}
+
+/// Изменяемые параметры заявки в команде `moveorder`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MoveFlag {
+    /// Изменить цену, количество не меняется(по-умолчанию)
+    #[default]
+    Price,
+    /// Изменить количество
+    Quantity,
+    /// Изменить цену и количество
+    PriceAndQuantity,
+}
+
+impl MoveFlag {
+    #[inline]
+    fn moves_price(self) -> bool {
+        matches!(self, MoveFlag::Price | MoveFlag::PriceAndQuantity)
+    }
+
+    #[inline]
+    fn moves_quantity(self) -> bool {
+        matches!(self, MoveFlag::Quantity | MoveFlag::PriceAndQuantity)
+    }
+}
+
+/// Команда изменения заявки `moveorder`
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{Decimal, MoveFlag, MoveOrderCommand};
+///
+/// let cmd = MoveOrderCommand::new(transactionid)
+///     .price(Decimal::new(25100, 2))
+///     .quantity(5)
+///     .moveflag(MoveFlag::PriceAndQuantity)
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MoveOrderCommand {
+    transactionid: u64,
+    price: Option<Decimal>,
+    quantity: Option<u32>,
+    moveflag: MoveFlag,
+}
+
+impl MoveOrderCommand {
+    /// Создаёт команду изменения заявки с идентификатором транзакции **transactionid**
+    pub fn new(transactionid: u64) -> Self {
+        Self { transactionid, price: None, quantity: None, moveflag: MoveFlag::default() }
+    }
+
+    /// Новая цена
+    pub fn price(mut self, price: Decimal) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    /// Новое количество, лотов
+    pub fn quantity(mut self, quantity: u32) -> Self {
+        self.quantity = Some(quantity);
+        self
+    }
+
+    /// Изменяемые параметры заявки
+    pub fn moveflag(mut self, moveflag: MoveFlag) -> Self {
+        self.moveflag = moveflag;
+        self
+    }
+
+    /// Формирует команду, завершённую нулевым байтом
+    ///
+    /// # Errors
+    /// - [`ValidationError::ZeroTransactionId`] - нулевой идентификатор транзакции
+    /// - [`ValidationError::MissingPrice`] - не указана цена при изменении цены
+    /// - [`ValidationError::MissingField`] - не указано количество при изменении количества
+    pub fn build(&self) -> Result<Vec<u8>, ValidationError> {
+        check_transaction_id(self.transactionid)?;
+        let price = match (self.moveflag.moves_price(), self.price) {
+            (true, None) => return Err(ValidationError::MissingPrice),
+            (true, price) => price,
+            (false, _) => None,
+        };
+        let quantity = match (self.moveflag.moves_quantity(), self.quantity) {
+            (true, None) | (true, Some(0)) => {
+                return Err(ValidationError::MissingField("quantity"))
+            }
+            (true, quantity) => quantity,
+            (false, _) => None,
+        };
+
+        let mut buf = String::with_capacity(160);
+        buf.push_str("<command id=\"moveorder\">");
+        push_element(&mut buf, "transactionid", self.transactionid);
+        if let Some(price) = price {
+            push_element(&mut buf, "price", price);
+        }
+        // 0 - keep the quantity, 1 - set the quantity to the given value
+        push_element(&mut buf, "moveflag", u8::from(self.moveflag.moves_quantity()));
+        if let Some(quantity) = quantity {
+            push_element(&mut buf, "quantity", quantity);
+        }
+        buf.push_str("</command>");
+        Ok(terminate(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn assert_command(cmd: Vec<u8>, expected: &str) {
+        assert_eq!(cmd.last(), Some(&b'\0'));
+        assert_eq!(std::str::from_utf8(&cmd[..cmd.len() - 1]), Ok(expected));
+    }
+
+    #[test]
+    fn connect() {
+        let cmd = ConnectCommand::new()
+            .login("user")
+            .password("secret")
+            .host("tr1.finam.ru")
+            .port(3900)
+            .build();
+        assert_command(
+            cmd,
+            "<command id=\"connect\"><login>user</login><password>secret</password>\
+             <host>tr1.finam.ru</host><port>3900</port><milliseconds>false</milliseconds>\
+             <autopos>true</autopos><rqdelay>100</rqdelay></command>",
+        );
+
+        let cmd = ConnectCommand::new()
+            .login("user")
+            .password("secret")
+            .host("tr1.finam.ru")
+            .port(3900)
+            .milliseconds(true)
+            .autopos(false)
+            .rqdelay(500)
+            .build();
+        assert_command(
+            cmd,
+            "<command id=\"connect\"><login>user</login><password>secret</password>\
+             <host>tr1.finam.ru</host><port>3900</port><milliseconds>true</milliseconds>\
+             <autopos>false</autopos><rqdelay>500</rqdelay></command>",
+        );
+    }
+
+    #[test]
+    fn connect_escapes_values() {
+        let cmd = ConnectCommand::new().login("a&b").password("<\"'>").host("h").port(1).build();
+        assert_command(
+            cmd,
+            "<command id=\"connect\"><login>a&amp;b</login>\
+             <password>&lt;&quot;&apos;&gt;</password><host>h</host><port>1</port>\
+             <milliseconds>false</milliseconds><autopos>true</autopos><rqdelay>100</rqdelay>\
+             </command>",
+        );
+    }
+
+    #[test]
+    fn connect_debug_hides_password() {
+        let cmd = ConnectCommand::new().login("user").password("secret");
+        let debug = format!("{cmd:?}");
+        assert!(debug.contains("\"user\""));
+        assert!(!debug.contains("secret"));
+    }
+
+    #[test]
+    fn subscribe_ticks() {
+        let cmd = SubscribeTicksCommand::new()
+            .security("TQBR", "SBER")
+            .security("TQBR", "GAZP")
+            .security("FUT", "SiZ4");
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"subscribe_ticks\"><securities>\
+             <security><board>TQBR</board><seccode>SBER</seccode></security>\
+             <security><board>TQBR</board><seccode>GAZP</seccode></security>\
+             <security><board>FUT</board><seccode>SiZ4</seccode></security>\
+             </securities></command>",
+        );
+        assert_eq!(cmd.build().unwrap(), cmd.build_unchecked());
+    }
+
+    #[test]
+    fn subscribe_ticks_incremental() {
+        let mut cmd = SubscribeTicksCommand::new().security("TQBR", "SBER");
+        cmd.add("TQBR", "GAZP").add("TQBR", "SBER");
+        assert_eq!(cmd.securities().collect::<Vec<_>>(), [("TQBR", "SBER"), ("TQBR", "GAZP")]);
+
+        assert!(cmd.remove("TQBR", "SBER"));
+        assert!(!cmd.remove("TQBR", "SBER"));
+        assert!(!cmd.remove("FUT", "GAZP"));
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"subscribe_ticks\"><securities>\
+             <security><board>TQBR</board><seccode>GAZP</seccode></security>\
+             </securities></command>",
+        );
+
+        assert!(cmd.remove("TQBR", "GAZP"));
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"subscribe_ticks\"><securities></securities></command>",
+        );
+    }
+
+    #[test]
+    fn subscribe_ticks_rejects_special_characters() {
+        for c in ["<", ">", "&", "\""] {
+            let board = format!("TQ{c}BR");
+            let cmd =
+                SubscribeTicksCommand::new().security("TQBR", "SBER").security(&*board, "SBER");
+            assert_eq!(
+                cmd.build(),
+                Err(ValidationError::InvalidCharacters { field: "board", value: board.clone() })
+            );
+
+            let seccode = format!("SB{c}ER");
+            let cmd = SubscribeTicksCommand::new().security("TQBR", &*seccode);
+            assert_eq!(
+                cmd.build(),
+                Err(ValidationError::InvalidCharacters { field: "seccode", value: seccode })
+            );
+        }
+        // apostrophe doesn't break the element content
+        assert!(SubscribeTicksCommand::new().security("TQBR", "SB'ER").build().is_ok());
+    }
+
+    fn limit_order() -> PlaceOrderCommand {
+        PlaceOrderCommand::buy("TQBR", "SBER")
+            .client("C1")
+            .quantity(10)
+            .price(Decimal::new(25050, 2))
+    }
+
+    #[test]
+    fn place_limit_order() {
+        assert_command(
+            limit_order().build().unwrap(),
+            "<command id=\"neworder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><price>250.50</price><quantity>10</quantity>\
+             <buysell>B</buysell></command>",
+        );
+
+        let cmd = limit_order().buysell(BuySell::Sell).usecredit(true);
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"neworder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><price>250.50</price><quantity>10</quantity>\
+             <buysell>S</buysell><usecredit/></command>",
+        );
+    }
+
+    #[test]
+    fn place_market_order() {
+        let cmd = PlaceOrderCommand::sell("TQBR", "SBER").client("C1").quantity(1).bymarket(true);
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"neworder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><quantity>1</quantity><buysell>S</buysell>\
+             <bymarket/></command>",
+        );
+    }
+
+    #[test]
+    fn place_order_from_setters() {
+        let cmd = PlaceOrderCommand::new()
+            .board("TQBR")
+            .seccode("SBER")
+            .client("C1")
+            .buysell(BuySell::Buy)
+            .quantity(10)
+            .price(Decimal::new(25050, 2));
+        assert_eq!(cmd, limit_order());
+        assert_eq!(cmd.build(), limit_order().build());
+    }
+
+    #[test]
+    fn place_order_rejects_missing_fields() {
+        let missing = |cmd: PlaceOrderCommand| cmd.build().unwrap_err();
+        assert_eq!(missing(limit_order().board("")), ValidationError::MissingField("board"));
+        assert_eq!(missing(limit_order().seccode("")), ValidationError::MissingField("seccode"));
+        assert_eq!(missing(limit_order().client("")), ValidationError::MissingField("client"));
+        assert_eq!(missing(limit_order().quantity(0)), ValidationError::MissingField("quantity"));
+        assert_eq!(missing(PlaceOrderCommand::new()), ValidationError::MissingField("board"));
+
+        let cmd = PlaceOrderCommand::new()
+            .board("TQBR")
+            .seccode("SBER")
+            .client("C1")
+            .quantity(10)
+            .price(Decimal::ONE);
+        assert_eq!(missing(cmd), ValidationError::MissingField("buysell"));
+
+        let cmd = PlaceOrderCommand::buy("TQBR", "SBER").client("C1").quantity(10);
+        assert_eq!(missing(cmd.clone()), ValidationError::MissingPrice);
+        assert!(cmd.bymarket(true).build().is_ok());
+    }
+
+    #[test]
+    fn place_order_rejects_special_characters() {
+        for (field, cmd) in [
+            ("board", limit_order().board("TQ<BR")),
+            ("seccode", limit_order().seccode("SB&ER")),
+            ("client", limit_order().client("C\"1")),
+        ] {
+            match cmd.build() {
+                Err(ValidationError::InvalidCharacters { field: f, .. }) => assert_eq!(f, field),
+                ret => panic!("{field}: {ret:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn cancel_order() {
+        assert_command(
+            CancelOrderCommand::new(42).build().unwrap(),
+            "<command id=\"cancelorder\"><transactionid>42</transactionid></command>",
+        );
+        assert_command(
+            CancelOrderCommand::new(u64::MAX).build().unwrap(),
+            "<command id=\"cancelorder\"><transactionid>18446744073709551615</transactionid>\
+             </command>",
+        );
+        assert_eq!(CancelOrderCommand::new(0).build(), Err(ValidationError::ZeroTransactionId));
+    }
+
+    #[test]
+    fn move_order() {
+        let cmd = MoveOrderCommand::new(42).price(Decimal::new(25100, 2)).quantity(5);
+        assert_command(
+            cmd.clone().build().unwrap(),
+            "<command id=\"moveorder\"><transactionid>42</transactionid><price>251.00</price>\
+             <moveflag>0</moveflag></command>",
+        );
+        assert_command(
+            cmd.clone().moveflag(MoveFlag::Quantity).build().unwrap(),
+            "<command id=\"moveorder\"><transactionid>42</transactionid><moveflag>1</moveflag>\
+             <quantity>5</quantity></command>",
+        );
+        assert_command(
+            cmd.moveflag(MoveFlag::PriceAndQuantity).build().unwrap(),
+            "<command id=\"moveorder\"><transactionid>42</transactionid><price>251.00</price>\
+             <moveflag>1</moveflag><quantity>5</quantity></command>",
+        );
+    }
+
+    #[test]
+    fn move_order_rejects_missing_fields() {
+        let price = Decimal::new(25100, 2);
+        let cmd = MoveOrderCommand::new(0).price(price).quantity(5);
+        for moveflag in [MoveFlag::Price, MoveFlag::Quantity, MoveFlag::PriceAndQuantity] {
+            let ret = cmd.clone().moveflag(moveflag).build();
+            assert_eq!(ret, Err(ValidationError::ZeroTransactionId));
+        }
+
+        assert_eq!(MoveOrderCommand::new(42).build(), Err(ValidationError::MissingPrice));
+        let cmd = MoveOrderCommand::new(42).quantity(5).moveflag(MoveFlag::PriceAndQuantity);
+        assert_eq!(cmd.build(), Err(ValidationError::MissingPrice));
+
+        let missing = Err(ValidationError::MissingField("quantity"));
+        let cmd = MoveOrderCommand::new(42).moveflag(MoveFlag::Quantity);
+        assert_eq!(cmd.clone().build(), missing);
+        assert_eq!(cmd.quantity(0).build(), missing);
+        let cmd = MoveOrderCommand::new(42).price(price).moveflag(MoveFlag::PriceAndQuantity);
+        assert_eq!(cmd.build(), missing);
+    }
+
+    #[test]
+    fn place_order_with_all_parameters() {
+        let cmd = limit_order()
+            .hidden(3)
+            .brokerref("strategy-1")
+            .unfilled(Unfilled::ImmediateOrCancel)
+            .usecredit(true)
+            .nosplit(true);
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"neworder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><price>250.50</price><hidden>3</hidden>\
+             <quantity>10</quantity><buysell>B</buysell><brokerref>strategy-1</brokerref>\
+             <unfilled>IOC</unfilled><usecredit/><nosplit/></command>",
+        );
+    }
+
+    #[test]
+    fn place_order_unfilled() {
+        for (unfilled, expected) in [
+            (Unfilled::PutInQueue, "PutInQueue"),
+            (Unfilled::FillOrKill, "FOK"),
+            (Unfilled::ImmediateOrCancel, "IOC"),
+        ] {
+            assert_eq!(unfilled.as_str(), expected);
+            let cmd = String::from_utf8(limit_order().unfilled(unfilled).build().unwrap()).unwrap();
+            assert!(cmd.contains(&format!("<unfilled>{expected}</unfilled>")), "{cmd}");
+        }
+        assert_eq!(Unfilled::default(), Unfilled::PutInQueue);
+
+        let cmd = PlaceOrderCommand::buy("TQBR", "SBER").client("C1").quantity(1).bymarket(true);
+        assert_eq!(
+            cmd.unfilled(Unfilled::FillOrKill).build(),
+            Err(ValidationError::ConflictingFields("bymarket", "unfilled"))
+        );
+    }
+
+    #[test]
+    fn place_order_union() {
+        let cmd = limit_order().client("").union("U1");
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"neworder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><union>U1</union><price>250.50</price><quantity>10</quantity>\
+             <buysell>B</buysell></command>",
+        );
+        assert_eq!(
+            limit_order().union("U1").build(),
+            Err(ValidationError::ConflictingFields("client", "union"))
+        );
+        assert_eq!(
+            limit_order().client("").union("U<1").build(),
+            Err(ValidationError::InvalidCharacters { field: "union", value: "U<1".into() })
+        );
+    }
+
+    #[test]
+    fn place_order_rejects_conflicting_fields() {
+        assert_eq!(
+            limit_order().bymarket(true).build(),
+            Err(ValidationError::ConflictingFields("bymarket", "price"))
+        );
+        let market =
+            PlaceOrderCommand::buy("TQBR", "SBER").client("C1").quantity(10).bymarket(true);
+        assert_eq!(
+            market.hidden(3).build(),
+            Err(ValidationError::ConflictingFields("bymarket", "hidden"))
+        );
+    }
+
+    #[test]
+    fn place_order_rejects_invalid_values() {
+        for price in [Decimal::ZERO, Decimal::NEGATIVE_ONE, Decimal::new(-25050, 2)] {
+            assert_eq!(
+                limit_order().price(price).build(),
+                Err(ValidationError::InvalidValue { field: "price", value: price.to_string() })
+            );
+        }
+        // the visible part must not be empty
+        for hidden in [10, 11] {
+            assert_eq!(
+                limit_order().hidden(hidden).build(),
+                Err(ValidationError::InvalidValue { field: "hidden", value: hidden.to_string() })
+            );
+        }
+        assert!(limit_order().hidden(9).build().is_ok());
+        assert!(limit_order().hidden(0).build().is_ok());
+    }
+
+    #[test]
+    fn place_order_brokerref() {
+        // the length is counted in characters
+        for brokerref in ["12345678901234567890", "примечание-примечани", ""] {
+            assert!(limit_order().brokerref(brokerref).build().is_ok(), "{brokerref}");
+        }
+        for brokerref in ["123456789012345678901", "примечание-примечание"] {
+            assert_eq!(
+                limit_order().brokerref(brokerref).build(),
+                Err(ValidationError::TooLong { field: "brokerref", max: 20 })
+            );
+        }
+        assert_eq!(
+            limit_order().brokerref("a&b").build(),
+            Err(ValidationError::InvalidCharacters { field: "brokerref", value: "a&b".into() })
+        );
+    }
+
+    fn time(hour: u8, minute: u8, second: u8) -> Timestamp {
+        Timestamp { year: 2024, month: 6, day: 3, hour, minute, second, millisecond: 0 }
+    }
+
+    #[test]
+    fn check_time_ranges() {
+        assert_eq!(check_time("t", &time(0, 0, 0)), Ok(()));
+        assert_eq!(check_time("t", &time(23, 59, 59)), Ok(()));
+        let last_day = Timestamp { month: 12, day: 31, ..time(10, 0, 0) };
+        assert_eq!(check_time("t", &last_day), Ok(()));
+
+        for invalid in [
+            Timestamp { month: 0, ..time(10, 0, 0) },
+            Timestamp { month: 13, ..time(10, 0, 0) },
+            Timestamp { day: 0, ..time(10, 0, 0) },
+            Timestamp { day: 32, ..time(10, 0, 0) },
+            time(24, 0, 0),
+            time(10, 60, 0),
+            time(10, 0, 60),
+            // the connector doesn't accept milliseconds
+            Timestamp { millisecond: 1, ..time(10, 0, 0) },
+        ] {
+            assert_eq!(
+                check_time("t", &invalid),
+                Err(ValidationError::InvalidValue { field: "t", value: invalid.to_string() })
+            );
+        }
+    }
+
+    fn cond_order() -> CondOrderCommand {
+        CondOrderCommand::sell("TQBR", "SBER")
+            .client("C1")
+            .quantity(10)
+            .price(Decimal::new(26000, 2))
+            .condition(Condition::LastUp(Decimal::new(25990, 2)))
+    }
+
+    #[test]
+    fn place_cond_order() {
+        assert_command(
+            cond_order().validbefore(ValidBefore::TillCanceled).build().unwrap(),
+            "<command id=\"newcondorder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><price>260.00</price><quantity>10</quantity>\
+             <buysell>S</buysell><cond_type>LastUp</cond_type><cond_value>259.90</cond_value>\
+             <validafter>0</validafter><validbefore>till_canceled</validbefore></command>",
+        );
+
+        let cmd = CondOrderCommand::buy("FUT", "SiZ4")
+            .union("U1")
+            .quantity(2)
+            .bymarket(true)
+            .brokerref("cond")
+            .condition(Condition::Time(time(10, 0, 0)))
+            .validafter(ValidAfter::At(time(9, 59, 0)))
+            .validbefore(ValidBefore::At(time(18, 45, 0)))
+            .usecredit(true)
+            .nosplit(true)
+            .within_pos(true)
+            .expdate(Timestamp { month: 12, day: 19, ..time(0, 0, 0) });
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"newcondorder\"><security><board>FUT</board><seccode>SiZ4</seccode>\
+             </security><union>U1</union><quantity>2</quantity><buysell>B</buysell><bymarket/>\
+             <brokerref>cond</brokerref><cond_type>Time</cond_type>\
+             <cond_value>03.06.2024 10:00:00</cond_value>\
+             <validafter>03.06.2024 09:59:00</validafter>\
+             <validbefore>03.06.2024 18:45:00</validbefore><usecredit/><nosplit/><within_pos/>\
+             <expdate>19.12.2024 00:00:00</expdate></command>",
+        );
+    }
+
+    #[test]
+    fn cond_order_conditions() {
+        let value = Decimal::new(25990, 2);
+        for (condition, cond_type) in [
+            (Condition::Bid(value), "Bid"),
+            (Condition::BidOrLast(value), "BidOrLast"),
+            (Condition::Ask(value), "Ask"),
+            (Condition::AskOrLast(value), "AskOrLast"),
+            (Condition::CovDown(value), "CovDown"),
+            (Condition::CovUp(value), "CovUp"),
+            (Condition::LastUp(value), "LastUp"),
+            (Condition::LastDown(value), "LastDown"),
+        ] {
+            assert_eq!(condition.as_str(), cond_type);
+            let cmd = cond_order().condition(condition).build().unwrap();
+            let expected = format!(
+                "<cond_type>{cond_type}</cond_type><cond_value>259.90</cond_value><validafter>"
+            );
+            assert!(String::from_utf8(cmd).unwrap().contains(&expected), "{cond_type}");
+        }
+        assert_eq!(Condition::Time(time(10, 0, 0)).as_str(), "Time");
+    }
+
+    #[test]
+    fn cond_order_rejects_invalid_condition() {
+        assert_eq!(
+            CondOrderCommand::sell("TQBR", "SBER")
+                .client("C1")
+                .quantity(10)
+                .price(Decimal::ONE)
+                .build(),
+            Err(ValidationError::MissingField("cond_type"))
+        );
+
+        for value in [Decimal::ZERO, Decimal::NEGATIVE_ONE] {
+            for condition in [
+                Condition::Bid(value),
+                Condition::BidOrLast(value),
+                Condition::Ask(value),
+                Condition::AskOrLast(value),
+                Condition::LastUp(value),
+                Condition::LastDown(value),
+            ] {
+                assert_eq!(
+                    cond_order().condition(condition).build(),
+                    Err(ValidationError::InvalidValue {
+                        field: "cond_value",
+                        value: value.to_string()
+                    }),
+                    "{condition:?}"
+                );
+            }
+            // coverage is compared as is
+            assert!(cond_order().condition(Condition::CovDown(value)).build().is_ok());
+            assert!(cond_order().condition(Condition::CovUp(value)).build().is_ok());
+        }
+
+        let with_ms = Timestamp { millisecond: 500, ..time(10, 0, 0) };
+        assert_eq!(
+            cond_order().condition(Condition::Time(with_ms)).build(),
+            Err(ValidationError::InvalidValue { field: "cond_value", value: with_ms.to_string() })
+        );
+    }
+
+    #[test]
+    fn cond_order_rejects_invalid_period() {
+        let invalid = time(24, 0, 0);
+        let invalid_value =
+            |field| Err(ValidationError::InvalidValue { field, value: invalid.to_string() });
+        assert_eq!(
+            cond_order().validafter(ValidAfter::At(invalid)).build(),
+            invalid_value("validafter")
+        );
+        assert_eq!(
+            cond_order().validbefore(ValidBefore::At(invalid)).build(),
+            invalid_value("validbefore")
+        );
+        assert_eq!(cond_order().expdate(invalid).build(), invalid_value("expdate"));
+
+        // `validbefore` must be later than `validafter`
+        let after = ValidAfter::At(time(12, 0, 0));
+        for before in [time(12, 0, 0), time(11, 59, 59)] {
+            assert_eq!(
+                cond_order().validafter(after).validbefore(ValidBefore::At(before)).build(),
+                Err(ValidationError::InvalidValue {
+                    field: "validbefore",
+                    value: before.to_string()
+                })
+            );
+        }
+        let before = ValidBefore::At(time(12, 0, 1));
+        assert!(cond_order().validafter(after).validbefore(before).build().is_ok());
+        assert!(cond_order().validbefore(before).build().is_ok());
+        let till_canceled = cond_order().validafter(after).validbefore(ValidBefore::TillCanceled);
+        assert!(till_canceled.build().is_ok());
+    }
+
+    #[test]
+    fn cond_order_checks_order_params() {
+        assert_eq!(
+            cond_order().quantity(0).build(),
+            Err(ValidationError::MissingField("quantity"))
+        );
+        assert_eq!(
+            cond_order().bymarket(true).build(),
+            Err(ValidationError::ConflictingFields("bymarket", "price"))
+        );
+        assert_eq!(cond_order().client("").build(), Err(ValidationError::MissingField("client")));
+        assert_eq!(
+            cond_order().brokerref("123456789012345678901").build(),
+            Err(ValidationError::TooLong { field: "brokerref", max: 20 })
+        );
+    }
+
+    fn stop_order() -> StopOrderCommand {
+        StopOrderCommand::sell("TQBR", "SBER").client("C1")
+    }
+
+    fn stoploss() -> StopLossParams {
+        StopLossParams::new(Decimal::new(24500, 2)).bymarket(true).quantity(10)
+    }
+
+    fn takeprofit() -> TakeProfitParams {
+        TakeProfitParams::new(Decimal::new(27000, 2)).quantity(10)
+    }
+
+    #[test]
+    fn place_stop_order() {
+        let cmd = stop_order()
+            .stoploss(stoploss())
+            .takeprofit(takeprofit())
+            .validfor(ValidBefore::TillCanceled);
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"newstoporder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><buysell>S</buysell>\
+             <validfor>till_canceled</validfor><stoploss><activationprice>245.00\
+             </activationprice><bymarket/><quantity>10</quantity></stoploss><takeprofit>\
+             <activationprice>270.00</activationprice><quantity>10</quantity></takeprofit>\
+             </command>",
+        );
+
+        assert_command(
+            stop_order().stoploss(stoploss()).build().unwrap(),
+            "<command id=\"newstoporder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><buysell>S</buysell><validfor>0</validfor>\
+             <stoploss><activationprice>245.00</activationprice><bymarket/>\
+             <quantity>10</quantity></stoploss></command>",
+        );
+        assert_command(
+            stop_order().takeprofit(takeprofit()).build().unwrap(),
+            "<command id=\"newstoporder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><buysell>S</buysell><validfor>0</validfor>\
+             <takeprofit><activationprice>270.00</activationprice><quantity>10</quantity>\
+             </takeprofit></command>",
+        );
+    }
+
+    #[test]
+    fn stop_order_with_all_parameters() {
+        let stoploss = StopLossParams::new(Decimal::new(24500, 2))
+            .orderprice(Decimal::new(24400, 2))
+            .quantity(5)
+            .usecredit(true)
+            .brokerref("sl");
+        let takeprofit = TakeProfitParams::new(Decimal::new(27000, 2))
+            .quantity(5)
+            .correction(Decimal::new(50, 2))
+            .spread(Decimal::new(10, 2))
+            .usecredit(true)
+            .brokerref("tp");
+        let cmd = StopOrderCommand::buy("FUT", "SiZ4")
+            .union("U1")
+            .linkedorderno(123456789)
+            .validfor(ValidBefore::At(time(18, 45, 0)))
+            .expdate(Timestamp { month: 12, day: 19, ..time(0, 0, 0) })
+            .stoploss(stoploss)
+            .takeprofit(takeprofit);
+        assert_command(
+            cmd.build().unwrap(),
+            "<command id=\"newstoporder\"><security><board>FUT</board><seccode>SiZ4</seccode>\
+             </security><union>U1</union><buysell>B</buysell>\
+             <linkedorderno>123456789</linkedorderno>\
+             <validfor>03.06.2024 18:45:00</validfor><expdate>19.12.2024 00:00:00</expdate>\
+             <stoploss><activationprice>245.00</activationprice>\
+             <orderprice>244.00</orderprice><quantity>5</quantity><usecredit/>\
+             <brokerref>sl</brokerref></stoploss><takeprofit>\
+             <activationprice>270.00</activationprice><quantity>5</quantity><usecredit/>\
+             <brokerref>tp</brokerref><correction>0.50</correction><spread>0.10</spread>\
+             </takeprofit></command>",
+        );
+
+        let takeprofit = TakeProfitParams::new(Decimal::new(27000, 2)).quantity(5).bymarket(true);
+        assert_command(
+            StopOrderCommand::sell("TQBR", "SBER")
+                .client("C1")
+                .takeprofit(takeprofit)
+                .build()
+                .unwrap(),
+            "<command id=\"newstoporder\"><security><board>TQBR</board><seccode>SBER</seccode>\
+             </security><client>C1</client><buysell>S</buysell><validfor>0</validfor>\
+             <takeprofit><activationprice>270.00</activationprice><quantity>5</quantity>\
+             <bymarket/></takeprofit></command>",
+        );
+    }
+
+    #[test]
+    fn stop_order_rejects_invalid_fields() {
+        assert_eq!(stop_order().build(), Err(ValidationError::MissingField("stoploss")));
+        assert_eq!(
+            stop_order().stoploss(stoploss()).linkedorderno(0).build(),
+            Err(ValidationError::InvalidValue { field: "linkedorderno", value: "0".into() })
+        );
+
+        let invalid = Timestamp { millisecond: 1, ..time(18, 45, 0) };
+        let invalid_value =
+            |field| Err(ValidationError::InvalidValue { field, value: invalid.to_string() });
+        let cmd = stop_order().stoploss(stoploss());
+        assert_eq!(
+            cmd.clone().validfor(ValidBefore::At(invalid)).build(),
+            invalid_value("validfor")
+        );
+        assert_eq!(cmd.clone().expdate(invalid).build(), invalid_value("expdate"));
+
+        assert_eq!(cmd.clone().client("").build(), Err(ValidationError::MissingField("client")));
+        assert_eq!(
+            cmd.clone().union("U1").build(),
+            Err(ValidationError::ConflictingFields("client", "union"))
+        );
+        assert_eq!(
+            cmd.seccode("SB&ER").build(),
+            Err(ValidationError::InvalidCharacters { field: "seccode", value: "SB&ER".into() })
+        );
+        let cmd = StopOrderCommand::new().board("TQBR").seccode("SBER").client("C1");
+        assert_eq!(cmd.stoploss(stoploss()).build(), Err(ValidationError::MissingField("buysell")));
+    }
+
+    #[test]
+    fn stoploss_rejects_invalid_params() {
+        let build = |stoploss| stop_order().stoploss(stoploss).build();
+        for price in [Decimal::ZERO, Decimal::NEGATIVE_ONE] {
+            let invalid =
+                |field| Err(ValidationError::InvalidValue { field, value: price.to_string() });
+            assert_eq!(
+                build(StopLossParams::new(price).bymarket(true).quantity(1)),
+                invalid("activationprice")
+            );
+            assert_eq!(build(stoploss().bymarket(false).orderprice(price)), invalid("orderprice"));
+        }
+        assert_eq!(
+            build(stoploss().orderprice(Decimal::ONE)),
+            Err(ValidationError::ConflictingFields("bymarket", "orderprice"))
+        );
+        assert_eq!(build(stoploss().bymarket(false)), Err(ValidationError::MissingPrice));
+        assert_eq!(build(stoploss().quantity(0)), Err(ValidationError::MissingField("quantity")));
+        assert_eq!(
+            build(stoploss().brokerref("123456789012345678901")),
+            Err(ValidationError::TooLong { field: "brokerref", max: 20 })
+        );
+        assert_eq!(
+            build(stoploss().brokerref("<sl>")),
+            Err(ValidationError::InvalidCharacters { field: "brokerref", value: "<sl>".into() })
+        );
+        // the stop-loss is checked along with the valid take-profit
+        let cmd = stop_order().stoploss(stoploss().quantity(0)).takeprofit(takeprofit());
+        assert_eq!(cmd.build(), Err(ValidationError::MissingField("quantity")));
+    }
+
+    #[test]
+    fn takeprofit_rejects_invalid_params() {
+        let build = |takeprofit| stop_order().takeprofit(takeprofit).build();
+        for price in [Decimal::ZERO, Decimal::NEGATIVE_ONE] {
+            assert_eq!(
+                build(TakeProfitParams::new(price).quantity(1)),
+                Err(ValidationError::InvalidValue {
+                    field: "activationprice",
+                    value: price.to_string()
+                })
+            );
+        }
+        assert_eq!(build(takeprofit().quantity(0)), Err(ValidationError::MissingField("quantity")));
+        assert_eq!(
+            build(takeprofit().bymarket(true).spread(Decimal::ONE)),
+            Err(ValidationError::ConflictingFields("bymarket", "spread"))
+        );
+        let negative = Decimal::new(-1, 2);
+        assert_eq!(
+            build(takeprofit().correction(negative)),
+            Err(ValidationError::InvalidValue { field: "correction", value: "-0.01".into() })
+        );
+        assert_eq!(
+            build(takeprofit().spread(negative)),
+            Err(ValidationError::InvalidValue { field: "spread", value: "-0.01".into() })
+        );
+        // zero correction and spread are allowed
+        assert!(build(takeprofit().correction(Decimal::ZERO).spread(Decimal::ZERO)).is_ok());
+        assert_eq!(
+            build(takeprofit().brokerref("123456789012345678901")),
+            Err(ValidationError::TooLong { field: "brokerref", max: 20 })
+        );
+        let cmd = stop_order().stoploss(stoploss()).takeprofit(takeprofit().quantity(0));
+        assert_eq!(cmd.build(), Err(ValidationError::MissingField("quantity")));
+    }
+}
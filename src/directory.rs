@@ -0,0 +1,349 @@
+use super::{
+    Board, BoardsResponse, CandleKind, CandleKindsResponse, ClientInfo, Decimal, MarketsResponse,
+    Message, SecuritiesResponse, Security, UnionInfo,
+};
+use std::collections::{hash_map::Entry, HashMap};
+
+/// Справочник инструментов, собранный из сообщений `<securities>`
+///
+/// После подключения коннектор передаёт полный список инструментов, а в дальнейшем - только
+/// изменившиеся инструменты, часть параметров которых может отсутствовать. Справочник объединяет
+/// такие изменения с сохранёнными значениями, см. [`Security::merge`], и позволяет искать
+/// инструменты как по паре `(board, seccode)`, так и по `secid`.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{SecuritiesResponse, SecurityDirectory, Stream};
+/// use std::sync::{Arc, Mutex};
+///
+/// let mut txc = /*..*/;
+/// let directory = Arc::new(Mutex::new(SecurityDirectory::new()));
+/// let shared = Arc::clone(&directory);
+/// txc.input_stream()
+///     .filter_map(|buf| SecuritiesResponse::try_from(&buf).ok())
+///     .subscribe(move |update| shared.lock().unwrap().apply(update))?;
+/// // ..
+/// let minstep = directory.lock().unwrap().minstep_of("TQBR", "SBER");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SecurityDirectory {
+    securities: HashMap<u32, Security>,
+    // board -> seccode -> secid
+    codes: HashMap<String, HashMap<String, u32>>,
+}
+
+impl SecurityDirectory {
+    /// Создаёт пустой справочник
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляет инструменты сообщения `<securities>` или объединяет их с сохранёнными
+    pub fn apply(&mut self, update: SecuritiesResponse) {
+        update.securities.into_iter().for_each(|security| self.update(security));
+    }
+
+    /// Добавляет инструмент или объединяет его с сохранённым инструментом с тем же `secid`
+    pub fn update(&mut self, security: Security) {
+        let codes = &mut self.codes;
+        let known = match self.securities.entry(security.secid) {
+            Entry::Occupied(entry) => {
+                let known = entry.into_mut();
+                // the update may move the instrument to another board or code
+                remove_code(codes, &known.board, &known.seccode);
+                known.merge(security);
+                known
+            }
+            Entry::Vacant(entry) => entry.insert(security),
+        };
+        codes.entry(known.board.clone()).or_default().insert(known.seccode.clone(), known.secid);
+    }
+
+    /// Инструмент **seccode** режима торгов **board**
+    pub fn get(&self, board: &str, seccode: &str) -> Option<&Security> {
+        let secid = self.codes.get(board)?.get(seccode)?;
+        self.securities.get(secid)
+    }
+
+    /// Инструмент с внутренним идентификатором **secid**
+    pub fn by_secid(&self, secid: u32) -> Option<&Security> {
+        self.securities.get(&secid)
+    }
+
+    /// Шаг цены инструмента **seccode** режима торгов **board**
+    pub fn minstep_of(&self, board: &str, seccode: &str) -> Option<Decimal> {
+        self.get(board, seccode)?.minstep
+    }
+
+    /// Размер лота инструмента **seccode** режима торгов **board**
+    pub fn lotsize_of(&self, board: &str, seccode: &str) -> Option<u32> {
+        self.get(board, seccode)?.lotsize
+    }
+
+    /// Количество инструментов
+    pub fn len(&self) -> usize {
+        self.securities.len()
+    }
+
+    /// Справочник не содержит инструментов
+    pub fn is_empty(&self) -> bool {
+        self.securities.is_empty()
+    }
+
+    /// Инструменты в произвольном порядке
+    pub fn iter(&self) -> impl Iterator<Item = &Security> {
+        self.securities.values()
+    }
+}
+
+fn remove_code(codes: &mut HashMap<String, HashMap<String, u32>>, board: &str, seccode: &str) {
+    if let Some(board_codes) = codes.get_mut(board) {
+        board_codes.remove(seccode);
+        if board_codes.is_empty() {
+            codes.remove(board);
+        }
+    }
+}
+
+/// Справочные данные сессии: рынки, режимы торгов, периоды свечей, клиентские счета
+///
+/// Коннектор передаёт справочники `<markets>`, `<boards>`, `<candlekinds>`, `<client>` и
+/// `<union>` после подключения; повторно полученный справочник заменяет сохранённый, счета
+/// добавляются и удаляются по одному.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{ReferenceData, Stream};
+/// use std::sync::{Arc, Mutex};
+///
+/// let mut txc = /*..*/;
+/// let reference = Arc::new(Mutex::new(ReferenceData::new()));
+/// let shared = Arc::clone(&reference);
+/// txc.input_stream().messages().subscribe(move |msg| shared.lock().unwrap().apply(msg))?;
+/// // ..
+/// let hourly = reference.lock().unwrap().candlekind_for(3600).map(|kind| kind.id);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceData {
+    markets: HashMap<u32, String>,
+    boards: HashMap<String, Board>,
+    candlekinds: Vec<CandleKind>,
+    clients: HashMap<String, ClientInfo>,
+    unions: HashMap<String, UnionInfo>,
+}
+
+impl ReferenceData {
+    /// Создаёт пустой справочник
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Применяет сообщение `<markets>`, `<boards>`, `<candlekinds>`, `<client>` или `<union>`,
+    /// остальные сообщения не учитываются
+    pub fn apply(&mut self, message: Message) {
+        match message {
+            Message::Markets(markets) => self.apply_markets(markets),
+            Message::Boards(boards) => self.apply_boards(boards),
+            Message::CandleKinds(kinds) => self.apply_candlekinds(kinds),
+            Message::Client(client) => self.apply_client(client),
+            Message::Union(union) => self.apply_union(union),
+            _ => {}
+        }
+    }
+
+    /// Заменяет список рынков
+    pub fn apply_markets(&mut self, markets: MarketsResponse) {
+        self.markets = markets.markets.into_iter().map(|m| (m.id, m.name)).collect();
+    }
+
+    /// Заменяет список режимов торгов
+    pub fn apply_boards(&mut self, boards: BoardsResponse) {
+        self.boards = boards.boards.into_iter().map(|b| (b.id.clone(), b)).collect();
+    }
+
+    /// Заменяет список периодов свечей
+    pub fn apply_candlekinds(&mut self, kinds: CandleKindsResponse) {
+        self.candlekinds = kinds.kinds;
+    }
+
+    /// Добавляет, обновляет или, для `remove="true"`, удаляет клиентский счёт
+    pub fn apply_client(&mut self, client: ClientInfo) {
+        if client.remove {
+            self.clients.remove(&client.id);
+        } else {
+            self.clients.insert(client.id.clone(), client);
+        }
+    }
+
+    /// Добавляет или, для `remove="true"`, удаляет единый денежный счёт
+    pub fn apply_union(&mut self, union: UnionInfo) {
+        if union.remove {
+            self.unions.remove(&union.id);
+        } else {
+            self.unions.insert(union.id.clone(), union);
+        }
+    }
+
+    /// Наименование рынка **id**
+    pub fn market_name(&self, id: u32) -> Option<&str> {
+        self.markets.get(&id).map(String::as_str)
+    }
+
+    /// Режим торгов с кодом **code**
+    pub fn board(&self, code: &str) -> Option<&Board> {
+        self.boards.get(code)
+    }
+
+    /// Наименование режима торгов с кодом **code**
+    pub fn board_name(&self, code: &str) -> Option<&str> {
+        self.board(code).map(|b| b.name.as_str())
+    }
+
+    /// Период свечей с идентификатором **id**
+    pub fn candlekind(&self, id: u32) -> Option<&CandleKind> {
+        self.candlekinds.iter().find(|k| k.id == id)
+    }
+
+    /// Период свечей длительностью **period_secs** секунд
+    pub fn candlekind_for(&self, period_secs: u32) -> Option<&CandleKind> {
+        self.candlekinds.iter().find(|k| k.period == period_secs)
+    }
+
+    /// Периоды свечей в порядке получения
+    pub fn candlekinds(&self) -> &[CandleKind] {
+        &self.candlekinds
+    }
+
+    /// Клиентский счёт **id**
+    pub fn client(&self, id: &str) -> Option<&ClientInfo> {
+        self.clients.get(id)
+    }
+
+    /// Клиентские счета в произвольном порядке
+    pub fn clients(&self) -> impl Iterator<Item = &ClientInfo> {
+        self.clients.values()
+    }
+
+    /// Единые денежные счета в произвольном порядке
+    pub fn unions(&self) -> impl Iterator<Item = &UnionInfo> {
+        self.unions.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "<securities>\
+        <security secid=\"3\" active=\"true\"><seccode>SBER</seccode><board>TQBR</board>\
+        <market>1</market><shortname>Сбербанк</shortname><decimals>2</decimals>\
+        <minstep>0.01</minstep><lotsize>10</lotsize><point_cost>1</point_cost>\
+        <sectype>SHARE</sectype></security>\
+        <security secid=\"4\" active=\"true\"><seccode>GAZP</seccode><board>TQBR</board>\
+        <market>1</market><shortname>Газпром</shortname><decimals>2</decimals>\
+        <minstep>0.01</minstep><lotsize>10</lotsize><sectype>SHARE</sectype></security>\
+        <security secid=\"66\" active=\"true\"><seccode>SiZ6</seccode><board>FUT</board>\
+        <market>4</market><shortname>Si-12.26</shortname><decimals>0</decimals>\
+        <minstep>1</minstep><lotsize>1</lotsize><point_cost>1</point_cost>\
+        <sectype>FUT</sectype></security>\
+        </securities>";
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn apply(directory: &mut SecurityDirectory, msg: &str) {
+        directory.apply(SecuritiesResponse::parse(msg.as_bytes()).unwrap());
+    }
+
+    fn directory() -> SecurityDirectory {
+        let mut directory = SecurityDirectory::new();
+        apply(&mut directory, DUMP);
+        directory
+    }
+
+    #[test]
+    fn initial_dump() {
+        let directory = directory();
+        assert_eq!(directory.len(), 3);
+        assert!(!directory.is_empty());
+
+        let sber = directory.get("TQBR", "SBER").unwrap();
+        assert_eq!(sber.secid, 3);
+        assert_eq!(sber.shortname, "Сбербанк");
+        assert_eq!(directory.by_secid(66).unwrap().seccode, "SiZ6");
+        assert_eq!(directory.minstep_of("TQBR", "SBER"), Some(dec("0.01")));
+        assert_eq!(directory.lotsize_of("FUT", "SiZ6"), Some(1));
+        assert_eq!(directory.get("FUT", "SBER"), None);
+        assert_eq!(directory.by_secid(5), None);
+
+        let mut secids = directory.iter().map(|s| s.secid).collect::<Vec<_>>();
+        secids.sort_unstable();
+        assert_eq!(secids, [3, 4, 66]);
+    }
+
+    #[test]
+    fn deltas_are_merged() {
+        let mut directory = directory();
+        let before = directory.get("TQBR", "SBER").unwrap().clone();
+
+        // only the changed parameters are sent
+        apply(
+            &mut directory,
+            "<securities><security secid=\"3\" active=\"true\"><minstep>0.1</minstep>\
+             </security></securities>",
+        );
+        apply(
+            &mut directory,
+            "<securities><security secid=\"3\" active=\"false\"><lotsize>1</lotsize>\
+             </security><security secid=\"4\" active=\"true\"><point_cost>0.5</point_cost>\
+             </security></securities>",
+        );
+
+        let sber = directory.get("TQBR", "SBER").unwrap();
+        assert_eq!(
+            *sber,
+            Security { active: false, minstep: Some(dec("0.1")), lotsize: Some(1), ..before }
+        );
+        let gazp = directory.get("TQBR", "GAZP").unwrap();
+        assert_eq!((gazp.point_cost, gazp.minstep), (Some(dec("0.5")), Some(dec("0.01"))));
+        assert_eq!(gazp.shortname, "Газпром");
+        assert_eq!(directory.len(), 3);
+    }
+
+    #[test]
+    fn delta_moves_security() {
+        let mut directory = directory();
+        apply(
+            &mut directory,
+            "<securities><security secid=\"66\" active=\"true\"><seccode>SiH7</seccode>\
+             <shortname>Si-3.27</shortname></security></securities>",
+        );
+        assert_eq!(directory.get("FUT", "SiZ6"), None);
+        let si = directory.get("FUT", "SiH7").unwrap();
+        assert_eq!((si.secid, si.minstep), (66, Some(dec("1"))));
+
+        apply(
+            &mut directory,
+            "<securities><security secid=\"3\" active=\"true\"><board>SMAL</board>\
+             </security></securities>",
+        );
+        assert_eq!(directory.get("TQBR", "SBER"), None);
+        assert_eq!(directory.minstep_of("SMAL", "SBER"), Some(dec("0.01")));
+        assert_eq!(directory.get("TQBR", "GAZP").unwrap().secid, 4);
+        assert_eq!(directory.len(), 3);
+    }
+
+    #[test]
+    fn delta_adds_security() {
+        let mut directory = directory();
+        apply(
+            &mut directory,
+            "<securities><security secid=\"7\" active=\"true\"><seccode>LKOH</seccode>\
+             <board>TQBR</board><lotsize>1</lotsize></security></securities>",
+        );
+        assert_eq!(directory.len(), 4);
+        assert_eq!(directory.lotsize_of("TQBR", "LKOH"), Some(1));
+        assert_eq!(directory.minstep_of("TQBR", "LKOH"), None);
+    }
+}
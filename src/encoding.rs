@@ -0,0 +1,77 @@
+use std::borrow::Cow;
+
+// windows-1251 code points of the bytes 0x80..=0xBF, the bytes 0xC0..=0xFF map to 'А'..='я'
+// and the unassigned 0x98 maps to U+0098, as in the WHATWG encoding standard
+#[rustfmt::skip]
+const CP1251_HIGH: [char; 64] = [
+    'Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', '€', '‰', 'Љ', '‹', 'Њ', 'Ќ', 'Ћ', 'Џ',
+    'ђ', '‘', '’', '“', '”', '•', '–', '—', '\u{98}', '™', 'љ', '›', 'њ', 'ќ', 'ћ', 'џ',
+    '\u{a0}', 'Ў', 'ў', 'Ј', '¤', 'Ґ', '¦', '§', 'Ё', '©', 'Є', '«', '¬', '\u{ad}', '®', 'Ї',
+    '°', '±', 'І', 'і', 'ґ', 'µ', '¶', '·', 'ё', '№', 'є', '»', 'ј', 'Ѕ', 'ѕ', 'ї',
+];
+
+#[inline]
+fn cp1251_char(b: u8) -> char {
+    match b {
+        0x00..=0x7F => b as char,
+        0x80..=0xBF => CP1251_HIGH[(b - 0x80) as usize],
+        // 'А' is U+0410
+        _ => char::from_u32(0x0410 + (b - 0xC0) as u32).unwrap_or(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+// decodes windows-1251 bytes, ASCII content is borrowed as is
+pub(crate) fn decode_cp1251(bytes: &[u8]) -> Cow<'_, str> {
+    if bytes.is_ascii() {
+        // ASCII is valid UTF-8
+        return Cow::Borrowed(unsafe { std::str::from_utf8_unchecked(bytes) });
+    }
+    Cow::Owned(bytes.iter().copied().map(cp1251_char).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_is_borrowed() {
+        let ascii = b"<news_body id=\"1\">text 123</news_body>";
+        match decode_cp1251(ascii) {
+            Cow::Borrowed(s) => assert_eq!(s.as_bytes(), ascii),
+            Cow::Owned(s) => panic!("{s}"),
+        }
+        let all: Vec<u8> = (0x00..=0x7F).collect();
+        assert_eq!(decode_cp1251(&all).as_bytes(), &all[..]);
+    }
+
+    #[test]
+    fn cyrillic_letters() {
+        let upper: Vec<u8> = (0xC0..=0xDF).collect();
+        assert_eq!(decode_cp1251(&upper), "АБВГДЕЖЗИЙКЛМНОПРСТУФХЦЧШЩЪЫЬЭЮЯ");
+        let lower: Vec<u8> = (0xE0..=0xFF).collect();
+        assert_eq!(decode_cp1251(&lower), "абвгдежзийклмнопрстуфхцчшщъыьэюя");
+        // "Сбербанк ао"
+        let word = [0xD1, 0xE1, 0xE5, 0xF0, 0xE1, 0xE0, 0xED, 0xEA, 0x20, 0xE0, 0xEE];
+        assert!(matches!(decode_cp1251(&word), Cow::Owned(s) if s == "Сбербанк ао"));
+    }
+
+    #[test]
+    fn high_range() {
+        assert_eq!(decode_cp1251(&[0xA8, 0xB8]), "Ёё");
+        assert_eq!(decode_cp1251(&[0x80, 0x90, 0xBF]), "Ђђї");
+        assert_eq!(decode_cp1251(&[0x88, 0xB9, 0xAB, 0xBB]), "€№«»");
+        assert_eq!(decode_cp1251(&[0x98]), "\u{98}");
+        assert_eq!(decode_cp1251(&[0xA0]), "\u{a0}");
+        // every byte maps to a single char
+        let all: Vec<u8> = (0x00..=0xFF).collect();
+        let decoded = decode_cp1251(&all);
+        assert_eq!(decoded.chars().count(), 256);
+        assert!(!decoded.contains(char::REPLACEMENT_CHARACTER));
+    }
+
+    #[test]
+    fn mixed_content() {
+        let msg = b"<text>\xcd\xee\xe2\xee\xf1\xf2\xe8 & news</text>";
+        assert_eq!(decode_cp1251(msg), "<text>Новости & news</text>");
+    }
+}
@@ -0,0 +1,225 @@
+use super::{
+    buffers::OwnedMessage,
+    stream::{Stream, Subscription},
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// the recorded frames are flushed to the writer at least this often
+pub(crate) const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Формат разделения сообщений в записи входящих сообщений
+///
+/// См. [`Stream::record_to_writer`](crate::Stream::record_to_writer) и [`FeedReader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Framing {
+    /// Длина сообщения `u32` little-endian, за которой следует сообщение
+    LengthPrefixed,
+    /// Сообщение, за которым следует нулевой байт `\0`
+    ///
+    /// Сообщения коннектора не содержат нулевых байт, поэтому разделитель однозначен.
+    NulDelimited,
+}
+
+// Writing half of the recorder, see `Stream::record_to_writer`
+pub(crate) struct FeedWriter<W: Write> {
+    writer: BufWriter<W>,
+    framing: Framing,
+    last_flush: Instant,
+}
+
+impl<W: Write> FeedWriter<W> {
+    pub fn new(writer: W, framing: Framing) -> Self {
+        Self { writer: BufWriter::new(writer), framing, last_flush: Instant::now() }
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        match self.framing {
+            Framing::LengthPrefixed => {
+                let len = u32::try_from(frame.len())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                self.writer.write_all(&len.to_le_bytes())?;
+                self.writer.write_all(frame)?;
+            }
+            Framing::NulDelimited => {
+                self.writer.write_all(frame)?;
+                self.writer.write_all(b"\0")?;
+            }
+        }
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.writer.flush()
+    }
+}
+
+/// Чтение записи входящих сообщений
+///
+/// Итератор по сообщениям, записанным [`Stream::record_to_writer`](crate::Stream::record_to_writer).
+/// Неполное последнее сообщение, например в записи прерванной сессии, возвращается как ошибка
+/// [`io::ErrorKind::UnexpectedEof`].
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{FeedReader, Framing};
+///
+/// for frame in FeedReader::open("session.feed", Framing::LengthPrefixed)? {
+///     println!("{}", String::from_utf8_lossy(&frame?));
+/// }
+/// ```
+pub struct FeedReader<R> {
+    reader: BufReader<R>,
+    framing: Framing,
+}
+
+impl<R: Read> FeedReader<R> {
+    /// Создаёт итератор по сообщениям из **reader** в формате **framing**
+    pub fn new(reader: R, framing: Framing) -> Self {
+        Self { reader: BufReader::new(reader), framing }
+    }
+
+    fn read_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match self.framing {
+            Framing::LengthPrefixed => {
+                let mut len = [0; 4];
+                if self.reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+                self.reader.read_exact(&mut len)?;
+                let mut frame = vec![0; u32::from_le_bytes(len) as usize];
+                self.reader.read_exact(&mut frame)?;
+                Ok(Some(frame))
+            }
+            Framing::NulDelimited => {
+                let mut frame = Vec::new();
+                match self.reader.read_until(b'\0', &mut frame)? {
+                    0 => Ok(None),
+                    _ if frame.pop() == Some(b'\0') => Ok(Some(frame)),
+                    _ => Err(io::ErrorKind::UnexpectedEof.into()),
+                }
+            }
+        }
+    }
+}
+
+impl FeedReader<File> {
+    /// Открывает файл записи **path** в формате **framing**
+    ///
+    /// # Errors
+    /// Ошибка открытия файла
+    pub fn open<P: AsRef<Path>>(path: P, framing: Framing) -> io::Result<Self> {
+        File::open(path).map(|file| Self::new(file, framing))
+    }
+}
+
+impl<R: Read> Iterator for FeedReader<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+impl<R> fmt::Debug for FeedReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FeedReader").field("framing", &self.framing).finish()
+    }
+}
+
+/// Источник сообщений из записи входящих сообщений
+///
+/// Реализует [`Stream`] с сообщениями [`OwnedMessage`], что позволяет запускать конвейер
+/// обработки без библиотеки коннектора, например для отладки разбора сообщений по записи
+/// [`Stream::record_to_writer`].
+///
+/// Сообщения передаются в обработчик в отдельном потоке, в порядке записи. По окончании
+/// воспроизведения обработчик освобождается, что, например, закрывает канал, отправитель
+/// которого принадлежит обработчику. Освобождение подписки прекращает воспроизведение.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{Framing, ReplayStream, Stream};
+/// use std::sync::mpsc;
+///
+/// let (tx, rx) = mpsc::channel();
+/// ReplayStream::from_file("session.feed", Framing::LengthPrefixed)?
+///     .filter(|msg| msg.xml_tag_name() == Some(b"server_status"))
+///     .subscribe(move |msg| tx.send(msg).unwrap())?;
+///
+/// // завершается по окончании записи
+/// for msg in rx {
+///     println!("{msg}");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplayStream {
+    frames: Vec<Vec<u8>>,
+    interval: Duration,
+}
+
+impl ReplayStream {
+    /// Создаёт источник сообщений **frames**
+    pub fn from_frames(frames: Vec<Vec<u8>>) -> Self {
+        Self { frames, interval: Duration::ZERO }
+    }
+
+    /// Читает запись **path** в формате **framing**
+    ///
+    /// # Errors
+    /// Ошибки чтения файла или неполное последнее сообщение, см. [`FeedReader`]
+    pub fn from_file<P: AsRef<Path>>(path: P, framing: Framing) -> io::Result<Self> {
+        FeedReader::open(path, framing)?.collect::<io::Result<_>>().map(Self::from_frames)
+    }
+
+    /// Задаёт интервал между сообщениями
+    ///
+    /// Запись не содержит моментов поступления сообщений, поэтому по-умолчанию сообщения
+    /// передаются без задержек.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Stream for ReplayStream {
+    type Output = OwnedMessage;
+
+    fn subscribe_guarded<F: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: F,
+    ) -> crate::Result<Subscription> {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&stopped);
+        let Self { frames, interval } = self;
+        std::thread::Builder::new()
+            .name("txc-replay".into())
+            .spawn(move || {
+                for (i, frame) in frames.into_iter().enumerate() {
+                    if i > 0 && !interval.is_zero() {
+                        std::thread::sleep(interval);
+                    }
+                    if stopped.load(Ordering::Acquire) {
+                        break;
+                    }
+                    f(OwnedMessage::from(frame));
+                }
+            })
+            .map_err(|e| crate::Error::Internal(e.to_string().into()))?;
+        Ok(Subscription::new(move || stop.store(true, Ordering::Release)))
+    }
+}
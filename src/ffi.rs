@@ -1,3 +1,4 @@
+use super::lang;
 use std::{
     ffi::{c_int, c_void, CStr, CString, OsStr},
     io, mem,
@@ -5,7 +6,7 @@ use std::{
     path::PathBuf,
 };
 
-use windows_sys::Win32::Foundation::{GetLastError, HMODULE};
+use windows_sys::Win32::Foundation::{GetLastError, FARPROC, HMODULE};
 use windows_sys::Win32::System::Diagnostics::Debug as dbg;
 use windows_sys::Win32::System::LibraryLoader as ll;
 
@@ -16,6 +17,19 @@ pub type FreeMemory = unsafe extern "C" fn(*const u8) -> bool;
 pub type UnInitialize = unsafe extern "C" fn() -> *const u8;
 pub type SetCallbackEx = unsafe extern "C" fn(CallbackEx, *const c_void) -> bool;
 pub type CallbackEx = extern "C" fn(*const u8, *mut c_void) -> bool;
+pub type GetServiceInfo = unsafe extern "C" fn(*const u8, *mut *mut u8) -> i32;
+
+// Known exports of the connector library, required ones and the ones missing in some builds
+const KNOWN_SYMBOLS: [&str; 8] = [
+    "Initialize\0",
+    "UnInitialize\0",
+    "SetLogLevel\0",
+    "SendCommand\0",
+    "SetCallback\0",
+    "SetCallbackEx\0",
+    "FreeMemory\0",
+    "GetServiceInfo\0",
+];
 
 pub struct Module {
     handle: HMODULE,
@@ -25,6 +39,8 @@ pub struct Module {
     pub set_callback_ex: SetCallbackEx,
     pub free_memory: FreeMemory,
     pub uninitialize: UnInitialize,
+    // optional, might be missing in the older library versions
+    pub get_service_info: Option<GetServiceInfo>,
 }
 
 // `TransaqXMLConnector` ensures thread-safety for it's state and methods internally
@@ -59,11 +75,8 @@ unsafe fn load(wide_filename: Vec<u16>) -> Result<HMODULE, io::Error> {
     dbg::SetThreadErrorMode(dbg::SEM_FAILCRITICALERRORS, &mut prev_mode);
 
     let handle = ll::LoadLibraryExW(wide_filename.as_ptr(), NULL as _, NULL);
-    let ret = if handle != NULL as _ {
-        Ok(handle)
-    } else {
-        Err(last_error_or!("Не удалось загрузить библиотеку по неизвестной причине"))
-    };
+    let ret =
+        if handle != NULL as _ { Ok(handle) } else { Err(last_error_or!(lang::LOAD_UNKNOWN)) };
 
     dbg::SetThreadErrorMode(prev_mode, std::ptr::null_mut());
 
@@ -76,10 +89,7 @@ impl Module {
             let wide_filename: Vec<u16> =
                 path.as_ref().encode_wide().chain(Some(NULL as _)).collect();
             if ll::GetModuleHandleExW(0, wide_filename.as_ptr(), &mut 0) != NULL as _ {
-                return Err(io::Error::new(
-                    io::ErrorKind::AlreadyExists,
-                    "Библиотека уже загружена в пространство процесса",
-                ));
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, lang::ALREADY_LOADED));
             }
 
             load(wide_filename)
@@ -89,10 +99,7 @@ impl Module {
                 ($p:expr) => {{
                     let addr = ll::GetProcAddress(handle, $p.as_ptr().cast());
                     if addr.is_none() {
-                        return Err(last_error_or!(format!(
-                            "Не удалось получить адресс функции {}",
-                            $p
-                        )));
+                        return Err(last_error_or!(format!("{} {}", lang::NO_PROC_ADDRESS, $p)));
                     }
                     mem::transmute(addr)
                 }};
@@ -105,26 +112,67 @@ impl Module {
                 set_callback_ex: proc_addr!("SetCallbackEx\0"),
                 free_memory: proc_addr!("FreeMemory\0"),
                 uninitialize: proc_addr!("UnInitialize\0"),
+                get_service_info: mem::transmute::<FARPROC, Option<GetServiceInfo>>(
+                    ll::GetProcAddress(handle, "GetServiceInfo\0".as_ptr()),
+                ),
             })
         })
     }
 
     pub fn initialize(&self, log_dir: PathBuf, logging_level: c_int) -> Result<(), String> {
         let work_dir = CString::new(log_dir.to_string_lossy().to_string()).unwrap();
+        unsafe { self.null_or_error((self.initialize)(work_dir.as_ptr() as _, logging_level)) }
+    }
+
+    pub fn set_log_level(&self, logging_level: c_int) -> Result<(), String> {
+        unsafe { self.null_or_error((self.set_log_level)(logging_level)) }
+    }
+
+    // `Initialize`, `SetLogLevel` return null on success, or the error message otherwise
+    unsafe fn null_or_error(&self, p: *const u8) -> Result<(), String> {
+        if p.is_null() {
+            Ok(())
+        } else {
+            let msg = CStr::from_ptr(p as _).to_string_lossy().to_string();
+            (self.free_memory)(p as _);
+            Err(msg)
+        }
+    }
+
+    pub fn set_callback_ex(&self, callback: CallbackEx, payload: *const c_void) -> bool {
+        unsafe { (self.set_callback_ex)(callback, payload) }
+    }
+
+    pub fn get_service_info(&self, request: *const u8) -> Result<*mut u8, String> {
+        let get_service_info =
+            self.get_service_info.ok_or_else(|| lang::NO_SERVICE_INFO.to_string())?;
+        let mut response = std::ptr::null_mut();
         unsafe {
-            match (self.initialize)(work_dir.as_ptr() as _, logging_level) {
-                p if p.is_null() => Ok(()),
-                p => {
-                    let msg = CStr::from_ptr(p as _).to_string_lossy().to_string();
-                    (self.free_memory)(p as _);
+            match get_service_info(request, &mut response) {
+                0 => Ok(response),
+                code => {
+                    let mut msg = format!("{} {code}", lang::SERVICE_INFO_CODE);
+                    if !response.is_null() {
+                        msg.push_str(&format!(
+                            ": {}",
+                            CStr::from_ptr(response as _).to_string_lossy()
+                        ));
+                        (self.free_memory)(response);
+                    }
                     Err(msg)
                 }
             }
         }
     }
 
-    pub fn set_callback_ex(&self, callback: CallbackEx, payload: *const c_void) -> bool {
-        unsafe { (self.set_callback_ex)(callback, payload) }
+    // Known symbols exported by the loaded library
+    pub fn probe_symbols(&self) -> Vec<&'static str> {
+        KNOWN_SYMBOLS
+            .iter()
+            .copied()
+            .filter(|name| unsafe { ll::GetProcAddress(self.handle, name.as_ptr()).is_some() })
+            .map(|name| name.trim_end_matches('\0'))
+            .collect()
     }
 
     #[inline]
@@ -133,3 +181,216 @@ impl Module {
         unsafe { (self.send_command)(p) }
     }
 }
+
+// Imitation of the connector library for the unit tests.
+// The exports are plain functions sharing the global state, so the tests using the library are
+// serialized by the `Library` guard returned from `load`.
+#[cfg(test)]
+pub mod fake {
+    use super::{CallbackEx, Module};
+    use crate::buffers::{alloc_buffer, free_buffer};
+    use std::{
+        cell::RefCell,
+        ffi::{c_int, c_void, CStr},
+        ptr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex, MutexGuard,
+        },
+        thread::{self, JoinHandle},
+    };
+
+    const DEFAULT_RESPONSE: &str = "<result success=\"true\"/>";
+
+    type Replier = Box<dyn FnMut(&str) -> Option<String> + Send>;
+
+    // the connector internal mutex, orders the callback registration, command processing and
+    // callback execution
+    static LIBRARY: Mutex<()> = Mutex::new(());
+    static TESTS: Mutex<()> = Mutex::new(());
+    static STATE: Mutex<State> = Mutex::new(State::new());
+    static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        // `LIBRARY` lock held by `deliver` for the duration of the callback, see `unlock`
+        static HELD: RefCell<Option<MutexGuard<'static, ()>>> = const { RefCell::new(None) };
+    }
+
+    struct State {
+        // callback and its payload address
+        callback: Option<(CallbackEx, usize)>,
+        commands: Vec<String>,
+        replier: Option<Replier>,
+        replies: Vec<JoinHandle<()>>,
+        // response of `send_command`, `DEFAULT_RESPONSE` if missing
+        responder: Option<Replier>,
+        // library calls and the test checkpoints, in the order of execution
+        events: Vec<&'static str>,
+    }
+
+    impl State {
+        const fn new() -> Self {
+            Self {
+                callback: None,
+                commands: Vec::new(),
+                replier: None,
+                replies: Vec::new(),
+                responder: None,
+                events: Vec::new(),
+            }
+        }
+    }
+
+    // Exclusive use of the library by the test, the asynchronous replies are awaited on drop
+    pub struct Library {
+        _test: MutexGuard<'static, ()>,
+    }
+
+    impl Drop for Library {
+        fn drop(&mut self) {
+            let replies = std::mem::take(&mut lock(&STATE).replies);
+            replies.into_iter().for_each(|reply| reply.join().unwrap());
+        }
+    }
+
+    fn lock<T>(mutex: &'static Mutex<T>) -> MutexGuard<'static, T> {
+        mutex.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Fresh library instance, see `Library`
+    pub fn load() -> (Library, Module) {
+        let test = lock(&TESTS);
+        *lock(&STATE) = State::new();
+        ALLOCATED.store(0, Ordering::SeqCst);
+        FREED.store(0, Ordering::SeqCst);
+
+        let module = Module {
+            handle: 0,
+            initialize,
+            set_log_level,
+            send_command,
+            set_callback_ex,
+            free_memory,
+            uninitialize,
+            get_service_info: None,
+        };
+        (Library { _test: test }, module)
+    }
+
+    // Message delivered to the callback from the connector thread after the command response
+    pub fn reply_with<F: FnMut(&str) -> Option<String> + Send + 'static>(f: F) {
+        lock(&STATE).replier = Some(Box::new(f));
+    }
+
+    // Response of the `send_command`, `DEFAULT_RESPONSE` if `f` returns `None`
+    pub fn respond_with<F: FnMut(&str) -> Option<String> + Send + 'static>(f: F) {
+        lock(&STATE).responder = Some(Box::new(f));
+    }
+
+    // Delivers the message to the installed callback on the current thread, returns the callback
+    // result. The rejected buffers are released by the library.
+    pub fn deliver(msg: &str) -> bool {
+        let library = lock(&LIBRARY);
+        let callback = lock(&STATE).callback;
+        HELD.with(|held| *held.borrow_mut() = Some(library));
+        let consumed = invoke(callback, msg);
+        HELD.with(|held| held.borrow_mut().take());
+        consumed
+    }
+
+    // Delivers the message as `deliver`, but the callback is called without the library lock, as
+    // if the connector did not order the callback execution with its replacement: the replaced
+    // callback may be called after `set_callback_ex` returns.
+    pub fn deliver_unordered(msg: &str) -> bool {
+        let callback = {
+            let _library = lock(&LIBRARY);
+            lock(&STATE).callback
+        };
+        // gives the replacement a chance to complete before the call
+        thread::yield_now();
+        invoke(callback, msg)
+    }
+
+    fn invoke(callback: Option<(CallbackEx, usize)>, msg: &str) -> bool {
+        let buf = alloc(msg);
+        let consumed = match callback {
+            Some((callback, payload)) => callback(buf, payload as *mut c_void),
+            None => false,
+        };
+        if !consumed {
+            unsafe { free_memory(buf) };
+        }
+        consumed
+    }
+
+    // Releases the library lock while the callback is still running, as if the connector did not
+    // wait for the callback completion. Called from within the callback.
+    pub fn unlock() {
+        HELD.with(|held| held.borrow_mut().take());
+    }
+
+    pub fn commands() -> Vec<String> {
+        lock(&STATE).commands.clone()
+    }
+
+    // Records the test checkpoint among the library calls, see `events`
+    pub fn event(event: &'static str) {
+        lock(&STATE).events.push(event);
+    }
+
+    pub fn events() -> Vec<&'static str> {
+        lock(&STATE).events.clone()
+    }
+
+    // Buffers allocated by the library and not yet released with `FreeMemory`
+    pub fn live_buffers() -> usize {
+        ALLOCATED.load(Ordering::SeqCst) - FREED.load(Ordering::SeqCst)
+    }
+
+    fn alloc(msg: &str) -> *const u8 {
+        ALLOCATED.fetch_add(1, Ordering::SeqCst);
+        alloc_buffer(msg.as_bytes()).as_ptr()
+    }
+
+    unsafe extern "C" fn initialize(_: *const u8, _: c_int) -> *const u8 {
+        ptr::null()
+    }
+
+    unsafe extern "C" fn set_log_level(_: c_int) -> *const u8 {
+        ptr::null()
+    }
+
+    unsafe extern "C" fn send_command(cmd: *const u8) -> *const u8 {
+        let _library = lock(&LIBRARY);
+        let cmd = CStr::from_ptr(cmd as _).to_string_lossy().into_owned();
+        let mut state = lock(&STATE);
+        if let Some(reply) = state.replier.as_mut().and_then(|replier| replier(&cmd)) {
+            state.replies.push(thread::spawn(move || {
+                deliver(&reply);
+            }));
+        }
+        let response = state.responder.as_mut().and_then(|responder| responder(&cmd));
+        state.commands.push(cmd);
+        alloc(response.as_deref().unwrap_or(DEFAULT_RESPONSE))
+    }
+
+    unsafe extern "C" fn set_callback_ex(callback: CallbackEx, payload: *const c_void) -> bool {
+        let _library = lock(&LIBRARY);
+        lock(&STATE).callback = Some((callback, payload as usize));
+        true
+    }
+
+    unsafe extern "C" fn free_memory(buf: *const u8) -> bool {
+        FREED.fetch_add(1, Ordering::SeqCst);
+        free_buffer(buf)
+    }
+
+    unsafe extern "C" fn uninitialize() -> *const u8 {
+        let _library = lock(&LIBRARY);
+        let mut state = lock(&STATE);
+        state.callback = None;
+        state.events.push("uninitialize");
+        ptr::null()
+    }
+}
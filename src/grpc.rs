@@ -0,0 +1,238 @@
+use super::{Error, Sender, Stream as _, TransaqConnector};
+use proto::{
+    transaq_connector_server::{self, TransaqConnectorServer},
+    CommandRequest, CommandResponse, Empty, MessageResponse,
+};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use tokio::sync::mpsc::UnboundedSender;
+use tonic::{Request, Response, Status};
+
+/// Типы сообщений и клиент/сервер, сгенерированные из `proto/txc.proto`
+#[allow(missing_docs, clippy::all)]
+pub mod proto {
+    tonic::include_proto!("libtxc");
+}
+
+type Subscriber = UnboundedSender<std::result::Result<MessageResponse, Status>>;
+
+/// gRPC сервис `libtxc.TransaqConnector`, см. `proto/txc.proto`
+///
+/// `Send` отправляет команду через [`Sender::send_async`] и возвращает ответ коннектора,
+/// `Subscribe` передаёт копии входящих сообщений, поступивших после подписки, каждому
+/// подписчику.
+///
+/// Требует опции **grpc**.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::GrpcService;
+///
+/// let mut txc = /*..*/;
+/// let service = GrpcService::new(&mut txc)?;
+/// tonic::transport::Server::builder()
+///     .add_service(service.into_server())
+///     .serve("127.0.0.1:50051".parse()?)
+///     .await?;
+/// ```
+#[derive(Clone)]
+pub struct GrpcService {
+    // `Sender` isn't `Sync`, the lock is held only to start the sending
+    sender: Arc<Mutex<Sender>>,
+    // owned by the input handler, the subscriptions end with it
+    subscribers: Weak<Mutex<Vec<Subscriber>>>,
+}
+
+impl GrpcService {
+    /// Создаёт сервис для коннектора **txc**
+    ///
+    /// Как и [`TransaqConnector::async_incoming_stream`], заменяет текущий обработчик входящих
+    /// сообщений; подписки `Subscribe` завершаются при замене обработчика или освобождении
+    /// коннектора.
+    ///
+    /// # Errors
+    /// - [`Error::Callback`] - не удалось установить обработчик, см. [`Stream::subscribe`]
+    ///
+    /// [`Stream::subscribe`]: crate::Stream::subscribe
+    pub fn new(txc: &mut TransaqConnector) -> crate::Result<Self> {
+        let subscribers = Arc::new(Mutex::new(Vec::<Subscriber>::new()));
+        let weak = Arc::downgrade(&subscribers);
+        txc.input_stream().subscribe(move |buf| {
+            let message = buf.to_string_lossy().into_owned();
+            // closed subscriptions are dropped with the first message that fails to reach them
+            lock(&subscribers)
+                .retain(|tx| tx.send(Ok(MessageResponse { message: message.clone() })).is_ok());
+        })?;
+        Ok(Self { sender: Arc::new(Mutex::new(txc.sender())), subscribers: weak })
+    }
+
+    /// Сервер для [`tonic::transport::Server::add_service`]
+    pub fn into_server(self) -> TransaqConnectorServer<Self> {
+        TransaqConnectorServer::new(self)
+    }
+}
+
+impl std::fmt::Debug for GrpcService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let subscribers = self.subscribers.upgrade().map(|subscribers| lock(&subscribers).len());
+        f.debug_struct("GrpcService").field("subscribers", &subscribers).finish()
+    }
+}
+
+#[tonic::async_trait]
+impl transaq_connector_server::TransaqConnector for GrpcService {
+    async fn send(
+        &self,
+        request: Request<CommandRequest>,
+    ) -> std::result::Result<Response<CommandResponse>, Status> {
+        let response = lock(&self.sender).send_async(request.into_inner().command);
+        match response.await {
+            Ok(response) => Ok(Response::new(CommandResponse { response })),
+            Err(e) => Err(status(e)),
+        }
+    }
+
+    type SubscribeStream = crate::IncomingStream<std::result::Result<MessageResponse, Status>>;
+
+    async fn subscribe(
+        &self,
+        _: Request<Empty>,
+    ) -> std::result::Result<Response<Self::SubscribeStream>, Status> {
+        let (tx, rx) = crate::incoming::unbounded();
+        // the stream ends immediately if the handler is already replaced
+        if let Some(subscribers) = self.subscribers.upgrade() {
+            lock(&subscribers).push(tx);
+        }
+        Ok(Response::new(rx))
+    }
+}
+
+fn status(e: Error) -> Status {
+    let message = e.to_string();
+    match e {
+        Error::InvalidCommand(_) => Status::invalid_argument(message),
+        Error::Timeout => Status::deadline_exceeded(message),
+        Error::RateLimitExceeded => Status::resource_exhausted(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::fake;
+    use proto::transaq_connector_client::TransaqConnectorClient;
+    use std::{
+        net::SocketAddr,
+        pin::Pin,
+        task::{Context, Poll},
+        thread,
+    };
+    use tokio::{net::TcpListener, runtime::Runtime, sync::oneshot};
+    use tonic::transport::{Channel, Server};
+
+    struct Incoming(TcpListener);
+
+    impl futures_core::Stream for Incoming {
+        type Item = std::io::Result<tokio::net::TcpStream>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.0.poll_accept(cx).map(|accepted| Some(accepted.map(|(stream, _)| stream)))
+        }
+    }
+
+    fn runtime() -> Runtime {
+        tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap()
+    }
+
+    // Serves `service` in the background thread until the returned sender is dropped
+    fn serve(service: GrpcService) -> (SocketAddr, oneshot::Sender<()>, thread::JoinHandle<()>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown, stop) = oneshot::channel::<()>();
+        let server = thread::spawn(move || {
+            runtime().block_on(async move {
+                let incoming = Incoming(TcpListener::from_std(listener).unwrap());
+                Server::builder()
+                    .add_service(service.into_server())
+                    .serve_with_incoming_shutdown(incoming, async {
+                        let _ = stop.await;
+                    })
+                    .await
+                    .unwrap();
+            })
+        });
+        (addr, shutdown, server)
+    }
+
+    async fn connect(addr: SocketAddr) -> TransaqConnectorClient<Channel> {
+        TransaqConnectorClient::connect(format!("http://{addr}")).await.unwrap()
+    }
+
+    #[test]
+    fn send() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        fake::respond_with(|cmd| {
+            cmd.contains("bad")
+                .then(|| "<result success=\"false\"><message>bad</message></result>".into())
+        });
+        let (addr, shutdown, server) = serve(GrpcService::new(&mut txc).unwrap());
+
+        runtime().block_on(async {
+            let mut client = connect(addr).await;
+            let command = "<command id=\"get_connector_version\"/>".to_string();
+            let response = client.send(CommandRequest { command }).await.unwrap();
+            assert_eq!(response.into_inner().response, "<result success=\"true\"/>");
+
+            let command = "<command id=\"bad\"/>".to_string();
+            let status = client.send(CommandRequest { command }).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+            assert!(status.message().contains("bad"));
+        });
+        assert_eq!(
+            fake::commands(),
+            ["<command id=\"get_connector_version\"/>", "<command id=\"bad\"/>"]
+        );
+
+        drop(shutdown);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn subscribe() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let (addr, shutdown, server) = serve(GrpcService::new(&mut txc).unwrap());
+
+        runtime().block_on(async {
+            let mut client = connect(addr).await;
+            // not delivered to anyone
+            assert!(fake::deliver("<a/>"));
+
+            let mut first = client.subscribe(Empty {}).await.unwrap().into_inner();
+            let mut second = client.subscribe(Empty {}).await.unwrap().into_inner();
+            assert!(fake::deliver("<server_status connected=\"true\"/>"));
+            for messages in [&mut first, &mut second] {
+                let message = messages.message().await.unwrap().unwrap().message;
+                assert_eq!(message, "<server_status connected=\"true\"/>");
+            }
+
+            // the closed subscription doesn't stop the delivery to the others
+            drop(first);
+            assert!(fake::deliver("<b/>"));
+            assert_eq!(second.message().await.unwrap().unwrap().message, "<b/>");
+
+            // the subscriptions end once the handler is replaced
+            txc.input_stream().subscribe(|_| {}).unwrap();
+            assert!(second.message().await.unwrap().is_none());
+        });
+
+        drop(shutdown);
+        server.join().unwrap();
+    }
+}
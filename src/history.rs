@@ -0,0 +1,312 @@
+use super::{
+    lang, Candle, Candles, CandlesStatus, Error, HistoryDataCommand, Result, Stream,
+    TransaqConnector,
+};
+use std::{
+    convert::TryFrom,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+const DEFAULT_PAGE_SIZE: u32 = 1000;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Загрузка исторических данных
+///
+/// Отправляет команды `gethistorydata` порциями по **page_size** свечей: первую с `reset=true`,
+/// последующие с `reset=false`, пока не будет получено заказанное количество свечей или
+/// коннектор не сообщит об исчерпании истории, см. [`CandlesStatus`]. Свечи соседних порций,
+/// совпадающие по времени, объединяются.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::HistoryFetcher;
+/// use std::time::Duration;
+///
+/// let mut txc = /*..*/;
+/// let candles = HistoryFetcher::new("TQBR", "SBER", 1)
+///     .page_size(500)
+///     .timeout(Duration::from_secs(30))
+///     .fetch(&mut txc, 5000)?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HistoryFetcher {
+    board: String,
+    seccode: String,
+    period: u32,
+    page_size: u32,
+    timeout: Duration,
+}
+
+impl HistoryFetcher {
+    /// Создаёт загрузчик свечей периода **period** инструмента **seccode** режима торгов
+    /// **board**, порциями по 1000 свечей с ожиданием каждого сообщения не более 10 сек.
+    pub fn new<B: Into<String>, S: Into<String>>(board: B, seccode: S, period: u32) -> Self {
+        Self {
+            board: board.into(),
+            seccode: seccode.into(),
+            period,
+            page_size: DEFAULT_PAGE_SIZE,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Количество свечей, запрашиваемых одной командой
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Максимальное время ожидания очередного сообщения `<candles>`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Загружает не более **count** последних свечей
+    ///
+    /// Свечи возвращаются в хронологическом порядке, без повторов. Установленный обработчик
+    /// входящих сообщений продолжает получать все сообщения, в том числе `<candles>`; если
+    /// обработчик не установлен, устанавливается пустой.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - недопустимые параметры запроса или отказ коннектора
+    /// - [`Error::Timeout`] - очередное сообщение `<candles>` не поступило в течение **timeout**
+    /// - [`Error::Internal`] - коннектор сообщил о недоступности данных
+    pub fn fetch(&self, txc: &mut TransaqConnector, count: usize) -> Result<Vec<Candle>> {
+        if !txc.has_subscriber() {
+            txc.input_stream().subscribe(|_| {})?;
+        }
+
+        let (board, seccode, period) = (self.board.clone(), self.seccode.clone(), self.period);
+        let (tx, rx) = mpsc::channel();
+        let tap = txc.0.taps.insert(Box::new(move |buf| {
+            if buf.xml_tag_name() == Some(b"candles") {
+                if let Ok(candles) = Candles::try_from(buf) {
+                    if candles.board == board
+                        && candles.seccode == seccode
+                        && candles.period == period
+                    {
+                        let _ = tx.send(candles);
+                    }
+                }
+            }
+            false
+        }));
+
+        let sender = txc.sender();
+        let ret = self.fetch_pages(
+            count,
+            |cmd| unsafe { sender.send(cmd).map(drop) },
+            |timeout| {
+                rx.recv_timeout(timeout).map_err(|e| match e {
+                    RecvTimeoutError::Timeout => Error::Timeout,
+                    RecvTimeoutError::Disconnected => Error::Internal(e.to_string().into()),
+                })
+            },
+        );
+        txc.0.taps.remove(tap);
+        ret
+    }
+
+    // Requests the pages through `send` and collects the replies delivered by `recv`
+    fn fetch_pages<S, R>(&self, count: usize, mut send: S, mut recv: R) -> Result<Vec<Candle>>
+    where
+        S: FnMut(Vec<u8>) -> Result,
+        R: FnMut(Duration) -> Result<Candles>,
+    {
+        let mut candles: Vec<Candle> = Vec::new();
+        let mut reset = true;
+        while candles.len() < count {
+            let page_size = (count - candles.len()).min(self.page_size as usize) as u32;
+            let cmd = HistoryDataCommand::new(&*self.board, &*self.seccode, self.period, page_size)
+                .reset(reset);
+            send(cmd.build()?)?;
+            reset = false;
+
+            // the reply to one command may be split into several messages
+            let mut page = Vec::new();
+            let status = loop {
+                let reply = recv(self.timeout)?;
+                page.extend(reply.candles);
+                if reply.status != CandlesStatus::Partial {
+                    break reply.status;
+                }
+            };
+
+            // the pages go back in time, the seam candle may be repeated by the earlier page
+            if let Some(first) = candles.first() {
+                page.retain(|c| c.date < first.date);
+            }
+            let received = page.len();
+            page.append(&mut candles);
+            candles = page;
+
+            match status {
+                CandlesStatus::Unavailable => {
+                    return Err(Error::Internal(lang::HISTORY_UNAVAILABLE.into()))
+                }
+                CandlesStatus::Delivered if received > 0 => continue,
+                _ => break,
+            }
+        }
+
+        candles.sort_by_key(|c| c.date);
+        candles.dedup_by_key(|c| c.date);
+        if candles.len() > count {
+            candles.drain(..candles.len() - count);
+        }
+        Ok(candles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi::fake, Timestamp};
+    use std::sync::{Arc, Mutex};
+
+    fn element<'a>(cmd: &'a str, tag: &str) -> &'a str {
+        let start = cmd.find(&format!("<{tag}>")).unwrap() + tag.len() + 2;
+        &cmd[start..start + cmd[start..].find('<').unwrap()]
+    }
+
+    // The candle `n` of the minute history
+    fn candle(n: usize) -> String {
+        format!(
+            "<candle date=\"16.10.2026 {:02}:{:02}:00\" open=\"{n}.5\" high=\"{n}.9\" \
+             low=\"{n}.1\" close=\"{n}.7\" volume=\"{}\"/>",
+            10 + n / 60,
+            n % 60,
+            n * 10
+        )
+    }
+
+    fn date(n: usize) -> Timestamp {
+        format!("16.10.2026 {:02}:{:02}:00", 10 + n / 60, n % 60).parse().unwrap()
+    }
+
+    // Serves the pages of `total` candles back in time, the earliest candle of the previous page
+    // is repeated at the end of the next one
+    fn serve(total: usize) {
+        let served = Arc::new(Mutex::new(0));
+        fake::reply_with(move |cmd| {
+            if !cmd.contains("gethistorydata") {
+                return None;
+            }
+            let mut served = served.lock().unwrap();
+            if element(cmd, "reset") == "true" {
+                *served = 0;
+            }
+            let count = element(cmd, "count").parse::<usize>().unwrap();
+            let end = total - *served;
+            let start = end.saturating_sub(count);
+            let seam = if *served > 0 { end + 1 } else { end };
+            *served += end - start;
+
+            let status = if start == 0 { 0 } else { 1 };
+            let candles = (start..seam).map(candle).collect::<String>();
+            Some(format!(
+                "<candles secid=\"3\" board=\"TQBR\" seccode=\"SBER\" period=\"1\" \
+                 status=\"{status}\">{candles}</candles>"
+            ))
+        });
+    }
+
+    fn fetcher() -> HistoryFetcher {
+        HistoryFetcher::new("TQBR", "SBER", 1).timeout(Duration::from_secs(5))
+    }
+
+    #[test]
+    fn fetch_pages() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        serve(250);
+
+        let candles = fetcher().page_size(100).fetch(&mut txc, 230).unwrap();
+        assert_eq!(
+            candles.iter().map(|c| c.date).collect::<Vec<_>>(),
+            (20..250).map(date).collect::<Vec<_>>()
+        );
+        assert_eq!(candles[0].open, "20.5".parse().unwrap());
+        assert_eq!(candles[229].volume, 2490);
+
+        let commands = fake::commands();
+        assert_eq!(commands.len(), 3);
+        let pages = commands.iter().map(|cmd| (element(cmd, "count"), element(cmd, "reset")));
+        assert_eq!(pages.collect::<Vec<_>>(), [("100", "true"), ("100", "false"), ("30", "false")]);
+    }
+
+    #[test]
+    fn fetch_until_exhausted() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        serve(150);
+
+        let candles = fetcher().page_size(100).fetch(&mut txc, 1000).unwrap();
+        assert_eq!(
+            candles.iter().map(|c| c.date).collect::<Vec<_>>(),
+            (0..150).map(date).collect::<Vec<_>>()
+        );
+        assert_eq!(fake::commands().len(), 2);
+    }
+
+    #[test]
+    fn fetch_unavailable() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        fake::reply_with(|_| {
+            Some("<candles board=\"TQBR\" seccode=\"SBER\" period=\"1\" status=\"3\"/>".into())
+        });
+
+        match fetcher().fetch(&mut txc, 10) {
+            Err(Error::Internal(msg)) => assert_eq!(msg.message(), lang::HISTORY_UNAVAILABLE),
+            ret => panic!("{ret:?}"),
+        }
+    }
+
+    #[test]
+    fn fetch_ignores_other_instruments() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        fake::reply_with(|_| {
+            Some(format!(
+                "<candles board=\"TQBR\" seccode=\"GAZP\" period=\"1\" status=\"0\">{}</candles>",
+                candle(0)
+            ))
+        });
+
+        let ret = fetcher().timeout(Duration::from_millis(50)).fetch(&mut txc, 10);
+        assert!(matches!(ret, Err(Error::Timeout)), "{ret:?}");
+        // the temporary handler is installed and kept
+        assert!(txc.has_subscriber());
+    }
+
+    #[test]
+    fn partial_replies_are_joined() {
+        let mut replies = vec![(2, 5..10), (2, 0..3), (0, 3..5)].into_iter();
+        let ret = fetcher().fetch_pages(
+            100,
+            |_| Ok(()),
+            |_| {
+                let (status, candles) = replies.next().unwrap();
+                let msg = format!(
+                    "<candles board=\"TQBR\" seccode=\"SBER\" period=\"1\" status=\"{status}\">\
+                     {}</candles>",
+                    candles.map(candle).collect::<String>()
+                );
+                Ok(Candles::parse(msg.as_bytes()).unwrap())
+            },
+        );
+        assert_eq!(
+            ret.unwrap().iter().map(|c| c.date).collect::<Vec<_>>(),
+            (0..10).map(date).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn send_error_is_returned() {
+        let ret = fetcher().fetch_pages(10, |_| Err(Error::Timeout), |_| unreachable!());
+        assert!(matches!(ret, Err(Error::Timeout)));
+    }
+}
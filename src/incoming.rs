@@ -0,0 +1,136 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+pub(crate) fn unbounded<T>() -> (UnboundedSender<T>, IncomingStream<T>) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    (tx, IncomingStream(rx))
+}
+
+/// Асинхронный поток входящих сообщений, см. [`TransaqConnector::async_incoming_stream`]
+///
+/// Реализует [`futures_core::Stream`], поток завершается при замене обработчика входящих
+/// сообщений или освобождении коннектора.
+///
+/// [`TransaqConnector::async_incoming_stream`]: crate::TransaqConnector::async_incoming_stream
+#[derive(Debug)]
+pub struct IncomingStream<T>(UnboundedReceiver<T>);
+
+impl<T> IncomingStream<T> {
+    /// Ожидает следующее сообщение
+    ///
+    /// Возвращает `None`, если поток завершён и все сообщения получены.
+    pub async fn recv(&mut self) -> Option<T> {
+        self.0.recv().await
+    }
+
+    /// Закрывает поток, не принимая новых сообщений
+    ///
+    /// Сообщения, уже находящиеся в очереди, могут быть получены и после закрытия.
+    pub fn close(&mut self) {
+        self.0.close()
+    }
+}
+
+impl<T> futures_core::Stream for IncomingStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi::fake, Stream as _, TransaqConnector};
+    use futures_core::Stream;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Wake, Waker},
+    };
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<T>(stream: &mut IncomingStream<T>, waker: &Waker) -> Poll<Option<T>> {
+        Pin::new(stream).poll_next(&mut Context::from_waker(waker))
+    }
+
+    #[test]
+    fn poll_next() {
+        let (tx, mut rx) = unbounded();
+        let wakes = Arc::new(CountingWaker::default());
+        let waker = Waker::from(wakes.clone());
+
+        assert_eq!(poll(&mut rx, &waker), Poll::Pending);
+        tx.send(1).unwrap();
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        tx.send(2).unwrap();
+        assert_eq!(poll(&mut rx, &waker), Poll::Ready(Some(1)));
+        assert_eq!(poll(&mut rx, &waker), Poll::Ready(Some(2)));
+        assert_eq!(poll(&mut rx, &waker), Poll::Pending);
+
+        // the queued messages are received before the end of stream
+        tx.send(3).unwrap();
+        drop(tx);
+        assert_eq!(poll(&mut rx, &waker), Poll::Ready(Some(3)));
+        assert_eq!(poll(&mut rx, &waker), Poll::Ready(None));
+    }
+
+    #[test]
+    fn close() {
+        let (tx, mut rx) = unbounded();
+        let waker = Waker::from(Arc::new(CountingWaker::default()));
+        tx.send(1).unwrap();
+        rx.close();
+        assert!(tx.send(2).is_err());
+        assert_eq!(poll(&mut rx, &waker), Poll::Ready(Some(1)));
+        assert_eq!(poll(&mut rx, &waker), Poll::Ready(None));
+    }
+
+    #[test]
+    fn async_incoming_stream() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let mut incoming = txc.async_incoming_stream().unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+        assert!(fake::deliver("<server_status connected=\"true\"/>"));
+        assert!(fake::deliver("<overnight status=\"false\"/>"));
+        let first = rt.block_on(incoming.recv()).unwrap();
+        assert_eq!(first.to_string(), "<server_status connected=\"true\"/>");
+        let second = rt.block_on(incoming.recv()).unwrap();
+        assert_eq!(second.to_string(), "<overnight status=\"false\"/>");
+
+        // the stream ends once the handler is replaced
+        assert!(fake::deliver("<a/>"));
+        txc.input_stream().subscribe(|_| {}).unwrap();
+        assert_eq!(rt.block_on(incoming.recv()).unwrap().to_string(), "<a/>");
+        assert!(rt.block_on(incoming.recv()).is_none());
+    }
+
+    #[test]
+    fn async_incoming_stream_ends_with_connector() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let mut incoming = txc.async_incoming_stream().unwrap();
+        let waker = Waker::from(Arc::new(CountingWaker::default()));
+
+        assert!(poll(&mut incoming, &waker).is_pending());
+        drop(txc);
+        assert!(matches!(poll(&mut incoming, &waker), Poll::Ready(None)));
+        assert_eq!(fake::live_buffers(), 0);
+    }
+}
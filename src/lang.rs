@@ -0,0 +1,181 @@
+// Messages of errors and diagnostics, in Russian by default or in English with the `lang_en`
+// feature. Messages followed by details are completed at the use site, e.g. "{MSG}: {details}".
+// Some of the messages are used only with the particular features enabled.
+
+macro_rules! messages {
+    ($($name:ident => $ru:literal, $en:literal;)+) => {
+        $(
+            #[cfg(not(feature = "lang_en"))]
+            #[allow(dead_code)]
+            pub const $name: &str = $ru;
+            #[cfg(feature = "lang_en")]
+            #[allow(dead_code)]
+            pub const $name: &str = $en;
+        )+
+    };
+}
+
+messages! {
+    // Error::fmt
+    ERR_LOADING =>
+        "Не удалось загрузить библиотеку",
+        "Failed to load the library";
+    ERR_INITIALIZATION =>
+        "Инициализация библиотеки трагически провалилась",
+        "Library initialization has failed";
+    ERR_INVALID_COMMAND =>
+        "Команда не прошла проверку и не была отправлена",
+        "The command was rejected and has not been sent";
+    ERR_INTERNAL =>
+        "Внутренняя ошибка/exception коннектора - скорее всего это - исключительная ситуация, \
+        которая не должна происходить в принципе.\n\
+        Проверьте целостность файлов, актуальность версий и параметры окружения.\n\
+        Если ситуация повторяется и надежда иссякла, вы могли бы создать issue на github.",
+        "Connector internal error/exception - most likely it's an exceptional situation, \
+        which should not happen at all.\n\
+        Check the integrity of the files, versions and environment parameters.\n\
+        If the situation persists, consider creating an issue on github.";
+    ERR_TIMEOUT =>
+        "Истекло время ожидания",
+        "Timed out";
+    ERR_CALLBACK =>
+        "Обработчик входящих сообщений не установлен",
+        "The input handler has not been installed";
+    ERR_RATE_LIMIT =>
+        "Превышено ограничение частоты отправки команд, команда не отправлена",
+        "Command rate limit exceeded, the command has not been sent";
+
+    // connector responses
+    NULL_POINTER =>
+        "Коннектор вернул нулевой указатель",
+        "The connector has returned a null pointer";
+    UNEXPECTED_MESSAGE =>
+        "Коннектор вернул неожиданное сообщение",
+        "The connector has returned an unexpected message";
+    UNEXPECTED_VERSION =>
+        "Неожиданный формат версии коннектора",
+        "Unexpected connector version format";
+    OUTDATED_VERSION =>
+        "Версия коннектора ниже минимальной",
+        "The connector version is below the minimum";
+    INVALID_VERSION =>
+        "Недопустимый формат версии",
+        "Invalid version format";
+    HISTORY_UNAVAILABLE =>
+        "Исторические данные недоступны",
+        "Historical data is unavailable";
+    NO_SUBSCRIBER =>
+        "Обработчик входящих сообщений не установлен",
+        "No incoming message handler is installed";
+    FREE_MEMORY_FAILED =>
+        "Операция очистки txc буфера FreeMemory(*) завершилась неудачно, \
+        это - недокументированная ситуация и возможно всякое. \
+        Cоздайте issue на github если вам удалось добиться воспроизводимости.",
+        "txc buffer release FreeMemory(*) has failed, \
+        this situation is undocumented and anything is possible. \
+        Please create an issue on github if you manage to reproduce it.";
+    SET_CALLBACK_FAILED =>
+        "`set_callback_ex` - Не удалось установить функцию обратного вызова. \
+        В документации к коннектору нет описания этой ситуации, как и способов её \
+        исправления. Если вам удалось добиться воспроизводимости этой ошибки создайте \
+        issue на github",
+        "`set_callback_ex` - Failed to install the callback. \
+        The connector documentation describes neither this situation nor the ways to fix it. \
+        Please create an issue on github if you manage to reproduce it";
+    SEND_THREAD_FAILED =>
+        "Поток отправки команды завершился без ответа",
+        "The command sending thread has exited without a response";
+
+    // library loading
+    LOAD_UNKNOWN =>
+        "Не удалось загрузить библиотеку по неизвестной причине",
+        "Failed to load the library for an unknown reason";
+    ALREADY_LOADED =>
+        "Библиотека уже загружена в пространство процесса",
+        "The library is already loaded into the process";
+    NO_PROC_ADDRESS =>
+        "Не удалось получить адресс функции",
+        "Failed to get the address of the function";
+    NO_SERVICE_INFO =>
+        "Библиотека не экспортирует функцию GetServiceInfo",
+        "The library doesn't export the GetServiceInfo function";
+    SERVICE_INFO_CODE =>
+        "GetServiceInfo вернула код ошибки",
+        "GetServiceInfo has returned the error code";
+    MISSING_PARAMETER =>
+        "не указан обязательный параметр",
+        "missing required parameter";
+    INVALID_CHARACTERS =>
+        "специальные символы XML в значении параметра",
+        "XML special characters in the value of the parameter";
+    MISSING_PRICE =>
+        "не указана цена лимитной заявки",
+        "missing price of the limit order";
+    ZERO_TRANSACTION_ID =>
+        "нулевой идентификатор транзакции",
+        "zero transaction id";
+    CONFLICTING_PARAMETERS =>
+        "несовместимые параметры",
+        "conflicting parameters";
+    PARAMETER_TOO_LONG =>
+        "превышена длина параметра",
+        "parameter is too long";
+    INVALID_PARAMETER =>
+        "недопустимое значение параметра",
+        "invalid value of the parameter";
+    UNEXPECTED_TAG =>
+        "неожиданный тэг сообщения, ожидался",
+        "unexpected message tag, expected";
+    MISSING_ATTRIBUTE =>
+        "отсутствует атрибут",
+        "missing attribute";
+    MISSING_CONTENT =>
+        "отсутствует содержимое сообщения",
+        "missing message content";
+    INVALID_ATTRIBUTE =>
+        "недопустимое значение атрибута",
+        "invalid value of the attribute";
+    XML_MALFORMED =>
+        "некорректный XML документ",
+        "malformed XML document";
+    XML_TRUNCATED =>
+        "неожиданный конец документа",
+        "unexpected end of the document";
+    XML_NO_ROOT =>
+        "отсутствует корневой элемент",
+        "missing root element";
+    INVALID_LOG_LEVEL =>
+        "недопустимый уровень логирования",
+        "invalid log level";
+
+    // callback panics
+    PANIC_UNKNOWN =>
+        "Неизвестная причина",
+        "Unknown reason";
+    PANIC =>
+        "Паника в ffi коде",
+        "Panic in ffi code";
+    HANDLER_DISABLED =>
+        "обработчик отключен",
+        "the handler is disabled";
+
+    // assertions
+    NULL_PTR =>
+        "нулевой указатель",
+        "null pointer";
+    EMPTY_BUFFER =>
+        "пустой буфер",
+        "empty buffer";
+    NO_NUL =>
+        "отсутствует нулевой байт",
+        "missing nul byte";
+    INVALID_UTF8 =>
+        "буфер содержит не валидные UTF-8 символы",
+        "the buffer contains invalid UTF-8";
+    ZERO_RATE =>
+        "нулевое количество команд",
+        "zero command count";
+    ZERO_PERIOD =>
+        "нулевой период",
+        "zero period";
+}
@@ -21,7 +21,8 @@
 //! Возникновение паники(*panic*) по умолчанию запускает разматывание стека(*stack unwinding*), и на
 //! данный момент это приводит к `undefined behaviour`, если паника произошла в окружении другого языка.
 //! *catch_unwind* включает проверку паники в callback-коде; в случае её возникновения выводится
-//! сообщение и процесс аварийно завершается.
+//! сообщение и процесс аварийно завершается. Для перехвата паники в отдельных этапах конвейера
+//! обработки см. [`Stream::catching`].
 //!
 //! **safe_buffers** *включено по умолчанию*
 //!
@@ -35,6 +36,45 @@
 //! быть использованы для сбора онлайн-метрик, профилирования пользовательского кода обратного вызова
 //! или отладки. Включение опции *tracing* добавляет зависимость `tokio-rs/tracing` и код инструментации.
 //!
+//! **lang_en**
+//!
+//! Сообщения об ошибках и диагностические сообщения на английском языке вместо русского.
+//!
+//! **tokio**
+//!
+//! Асинхронная отправка команд [`Sender::send_async`], [`CommandSink`] и получение входящих
+//! сообщений [`TransaqConnector::async_incoming_stream`] для среды исполнения
+//! [`tokio`](https://docs.rs/tokio/latest/tokio/).
+//!
+//! **serde**
+//!
+//! Реализации `serde::Serialize` и `serde::Deserialize` для разобранных сообщений коннектора,
+//! например [`ServerStatusResponse`].
+//!
+//! **encoding**
+//!
+//! Чтение сообщений в кодировке windows-1251 [`TCStr::decode_cp1251`] и
+//! [`Stream::decode_cp1251`], без дополнительных зависимостей. Текст новостей [`NewsHeader`],
+//! [`NewsBody`] в этой кодировке декодируется при разборе.
+//!
+//! **xml-events**
+//!
+//! Разбор входящих сообщений в последовательности событий XML документа
+//! [`Stream::xml_events`] с помощью [`quick-xml`](https://docs.rs/quick-xml/latest/quick_xml/),
+//! а так же разбор сообщений с проверкой корректности документа [`parse_message`].
+//!
+//! **grpc**
+//!
+//! gRPC сервис [`GrpcService`] на основе [`tonic`](https://docs.rs/tonic/latest/tonic/): отправка
+//! команд и подписка на входящие сообщения, описание сервиса в `proto/txc.proto`. Пример
+//! сервера - `examples/grpc_proxy.rs`.
+//!
+//! **testing**
+//!
+//! Имитация коннектора [`MockConnector`] для тестирования конвейеров обработки сообщений и
+//! отправки команд без библиотеки коннектора, воспроизведение записанных сессий
+//! [`ReplayConnector`].
+//!
 //! ## License
 //! <sup>
 //! Licensed under either of <a href="https://github.com/2dav/libtxc/blob/master/LICENSE-APACHE">Apache License, Version
@@ -53,20 +93,105 @@ compile_error!(
     "TXC library is a 'Windows DLL', and so this doesn't work on anything but 'MS Windows', sorry"
 );
 
-use std::{cell::Cell, fmt, io, path::PathBuf, sync::Arc};
+use std::{
+    borrow::Cow,
+    ffi::CString,
+    fmt, io,
+    path::PathBuf,
+    sync::{
+        atomic::{self, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, TryLockError,
+    },
+    time::Duration,
+};
 #[cfg(feature = "tracing")]
 use tracing::instrument;
 
 mod buffers;
 mod callback;
+mod channel;
+mod commands;
+mod directory;
+#[cfg(feature = "encoding")]
+mod encoding;
+mod feed;
 mod ffi;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod history;
+#[cfg(feature = "tokio")]
+mod incoming;
+mod lang;
+#[cfg(feature = "testing")]
+mod mock;
+mod news;
+mod portfolio;
+mod rate_limit;
+mod reconnect;
+mod recording;
+mod responses;
+mod router;
+#[cfg(feature = "tokio")]
+mod sink;
+mod spsc;
+mod stats;
 mod stream;
+#[cfg(feature = "xml-events")]
+mod xml;
 
 use buffers::{as_nonnull_txc_buf, parse_send_response};
-use callback::{BoxT, InputStream};
+use callback::{Callback, Gates, InputStream, Taps};
 
-pub use buffers::TCStr;
-pub use stream::Stream;
+pub use callback::{CallbackAck, CallbackPanic, PanicPolicy};
+pub use channel::{IntoIter, OverflowPolicy, Receiver};
+pub use commands::{
+    BuySell, CancelOrderCommand, CondOrderCommand, Condition, ConnectCommand, HistoryDataCommand,
+    MoveFlag, MoveOrderCommand, PlaceOrderCommand, StopLossParams, StopOrderCommand,
+    SubscribeCommand, SubscribeTicksCommand, TakeProfitParams, Unfilled, UnsubscribeCommand,
+    ValidAfter, ValidBefore, ValidationError,
+};
+pub use directory::{ReferenceData, SecurityDirectory};
+pub use feed::{FeedReader, Framing, ReplayStream};
+#[cfg(feature = "grpc")]
+pub use grpc::{proto, GrpcService};
+pub use history::HistoryFetcher;
+#[cfg(feature = "tokio")]
+pub use incoming::IncomingStream;
+#[cfg(feature = "testing")]
+pub use mock::{MockConnector, MockSender};
+pub use news::NewsClient;
+pub use portfolio::PortfolioState;
+pub use rate_limit::RateLimitedSender;
+pub use reconnect::AutoReconnectConnector;
+#[cfg(feature = "testing")]
+pub use recording::ReplayConnector;
+pub use recording::{Record, RecordKind, RecordReader, RecordingConnector, RecordingSender};
+pub use responses::{
+    AllTradesResponse, Board, BoardsResponse, BrokerMessage, Candle, CandleKind,
+    CandleKindsResponse, Candles, CandlesStatus, ClientInfo, ClientTrade, ConnectionStatus,
+    ConnectorVersionResponse, ErrorResponse, FortsMoney, FortsPosition, Market, MarketTrade,
+    MarketsResponse, Message, MessagesResponse, MoneyPosition, NewsBody, NewsHeader, OrderStatus,
+    OrderUpdate, OrdersResponse, ParseError, PositionsResponse, QuoteEntry, QuoteVolume,
+    QuotesResponse, SecPosition, SecuritiesResponse, Security, SendAck, ServerStatusResponse,
+    StopLoss, StopOrderUpdate, TakeProfit, TicksResponse, Timestamp, TradesResponse, UnionInfo,
+    UnitedLimits,
+};
+pub use rust_decimal::Decimal;
+#[cfg(feature = "tokio")]
+pub use sink::CommandSink;
+pub use stats::SessionStats;
+
+pub use buffers::{OwnedMessage, TCStr};
+pub use router::Router;
+pub use stream::{
+    from_fn, select_first, BoxStream, CompletionHandle, FlowControl, GroupReceiver, HandlerHandle,
+    ItemError, LatestHandle, ParallelMode, Stream, StreamMetrics, Subscription, TraceLevel,
+    WindowView,
+};
+#[cfg(feature = "xml-events")]
+pub use xml::{
+    parse_message, parse_message_bytes, visit_events, OwnedEvent, ParsedMessage, XmlError,
+};
 
 /// Перечисление возможных ошибок и исключительных ситуаций
 #[derive(Debug)]
@@ -74,23 +199,65 @@ pub enum Error {
     /// Ошибка, возникшая во время загрузки библиотеки
     Loading(io::Error),
     /// Ошибка инициализации TransaqXMLConnector
-    Initialization(String),
+    Initialization(ConnectorError),
     /// Ошибка обработки команды
-    InvalidCommand(String),
+    InvalidCommand(ConnectorError),
     /// Внутренняя ошибка/исключение коннектора
-    Internal(String),
+    Internal(ConnectorError),
+    /// Истекло время ожидания
+    Timeout,
+    /// Не удалось установить обработчик входящих сообщений, предыдущий обработчик остаётся
+    /// установленным
+    Callback(ConnectorError),
+    /// Превышено ограничение частоты отправки команд, см. [`RateLimitedSender`]
+    RateLimitExceeded,
 }
 
 #[allow(missing_docs)]
 pub type Result<T = ()> = std::result::Result<T, Error>;
 
 struct Inner {
-    callback: Cell<Option<BoxT>>,
+    callback: Mutex<CallbackSlot>,
+    taps: Arc<Taps>,
+    panic_policy: Arc<Mutex<PanicPolicy>>,
+    stats: Arc<SessionStats>,
+    // held for the duration of `send_command`, lets `try_send` detect the command in progress
+    sending: Mutex<()>,
     module: ffi::Module,
+    // callback payloads, referenced by the connector until it's released along with the `module`
+    gates: Arc<Gates>,
+}
+
+// Installed callback, `generation` identifies the subscription it belongs to
+#[derive(Default)]
+struct CallbackSlot {
+    generation: u64,
+    callback: Option<Callback>,
+}
+
+impl Inner {
+    #[inline]
+    fn callback_slot(&self) -> MutexGuard<'_, CallbackSlot> {
+        self.callback.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Replaces the callback of the subscription `generation`, if it's still installed, with the
+    // one releasing incoming buffers.
+    fn release_callback(&self, generation: u64) {
+        let mut slot = self.callback_slot();
+        if slot.generation != generation || slot.callback.is_none() {
+            return;
+        }
+        let free_memory = self.module.free_memory as *const std::ffi::c_void;
+        // `set_callback_ex` waits for the running callback to complete, see `input_stream`
+        if self.module.set_callback_ex(callback::release_trampoline, free_memory) {
+            atomic::fence(Ordering::SeqCst);
+            if let Some(callback) = slot.callback.take() {
+                callback.retire();
+            }
+        }
+    }
 }
-// 'TransaqConnector' is non-`Copy`, and it is the only one who might mutate `Inner`,
-// see `TransaqConnector::input_stream` for soundness of this.
-unsafe impl Sync for Inner {}
 
 /// Экземпляр загруженной библиотеки
 ///
@@ -130,9 +297,93 @@ impl TransaqConnector {
 
         let module = unsafe { ffi::Module::load(library_path).map_err(Error::Loading)? };
 
-        module.initialize(log_dir, logging_level as _).map_err(Error::Initialization)?;
+        module
+            .initialize(log_dir, logging_level as _)
+            .map_err(|e| Error::Initialization(e.into()))?;
+
+        Ok(Self::from_module(module))
+    }
+
+    fn from_module(module: ffi::Module) -> Self {
+        Self(Arc::new(Inner {
+            module,
+            callback: Default::default(),
+            taps: Default::default(),
+            panic_policy: Default::default(),
+            stats: Default::default(),
+            sending: Default::default(),
+            gates: Default::default(),
+        }))
+    }
+
+    /// Создаёт [`TransaqConnectorBuilder`] для настройки параметров загрузки библиотеки
+    pub fn builder() -> TransaqConnectorBuilder {
+        TransaqConnectorBuilder::default()
+    }
+
+    /// Устанавливает реакцию на панику в функции обратного вызова, см. [`PanicPolicy`]
+    ///
+    /// Изменение применяется ко всем обработчикам, включая уже установленный.
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        *self.0.panic_policy.lock().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    /// Изменяет уровень логирования коннектора
+    ///
+    /// Вызывает функцию коннектора `BYTE* SetLogLevel(int)`, позволяя, например, уменьшить
+    /// детализацию логов после подключения или увеличить её на время отладки.
+    ///
+    /// # Errors
+    /// - [`Error::Internal`] - коннектор вернул сообщение об ошибке
+    pub fn set_log_level(&self, level: LogLevel) -> Result<()> {
+        self.0.module.set_log_level(level as _).map_err(|e| Error::Internal(e.into()))
+    }
+
+    /// Запрашивает служебную информацию коннектора
+    ///
+    /// Передаёт запрос **request_xml** в функцию коннектора
+    /// `int GetServiceInfo(const BYTE* request, BYTE** response)`, которая возвращает размеры
+    /// внутренних очередей и статистику работы коннектора.
+    ///
+    /// Функция отсутствует в старых версиях библиотеки, её наличие проверяется во время загрузки.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - запрос содержит нулевой байт
+    /// - [`Error::Internal`] - библиотека не экспортирует `GetServiceInfo`, функция вернула код
+    ///   ошибки или нулевой указатель
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let txc = /*..*/;
+    /// let info = txc.service_info("<request><value>queue_size</value></request>")?;
+    /// println!("{info}");
+    /// ```
+    pub fn service_info(&self, request_xml: &str) -> Result<TCStr<'_>> {
+        let request =
+            CString::new(request_xml).map_err(|e| Error::InvalidCommand(e.to_string().into()))?;
+        let response = self
+            .0
+            .module
+            .get_service_info(request.as_ptr() as _)
+            .map_err(|e| Error::Internal(e.into()))?;
+
+        as_nonnull_txc_buf(response).map(|ptr| TCStr::new(ptr, self.0.module.free_memory))
+    }
 
-        Ok(Self(Arc::new(Inner { module, callback: Cell::new(None) })))
+    /// Экспортируемые функции библиотеки из числа известных
+    ///
+    /// Разные сборки библиотеки могут не экспортировать необязательные функции, например
+    /// `GetServiceInfo` в старых версиях. Отсутствующие функции в список не включаются.
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let txc = /*..*/;
+    /// if !txc.exported_symbols().contains(&"GetServiceInfo") {
+    ///     println!("service_info недоступен");
+    /// }
+    /// ```
+    pub fn exported_symbols(&self) -> Vec<&'static str> {
+        self.0.module.probe_symbols()
     }
 
     /// Создаёт обьект-отправитель сообщений
@@ -170,7 +421,7 @@ impl TransaqConnector {
     /// use libtxc::TCStr;
     ///
     /// let mut txc = /*..*/;
-    /// txc.input_stream().subscribe(|buf:TCStr| println!("{buf}"));
+    /// txc.input_stream().subscribe(|buf:TCStr| println!("{buf}"))?;
     /// ```
     ///
     /// При поступлении новых сообщений функция обратного вызова запускается в отдельном потоке,
@@ -182,7 +433,7 @@ impl TransaqConnector {
     /// let v = vec![];
     /// txc.input_stream().subscribe(move |buf|{
     ///     v.extend(buf.to_bytes());
-    /// });
+    /// })?;
     /// println!("{:?}", v);
     /// // Ошибка компиляции! `Vec` требует явной синхронизации для многопоточного использования
     /// ```
@@ -202,7 +453,7 @@ impl TransaqConnector {
     ///     .map(|buf| /*parse(buf)*/)
     ///     .subscribe(move |msg| {
     ///         tx.send(msg);
-    ///     });
+    ///     })?;
     ///
     /// // ...
     ///
@@ -215,29 +466,461 @@ impl TransaqConnector {
     /// различных примеров использования.  
     #[inline(always)]
     pub fn input_stream(&mut self) -> impl stream::Stream<Output = TCStr<'_>> + '_ {
-        let subscribe_fn = |trampoline: ffi::CallbackEx, payload: BoxT| {
+        let inner = &self.0;
+        let weak = Arc::downgrade(inner);
+        let subscribe_fn = move |trampoline: ffi::CallbackEx, payload: Callback| {
             // `set_callback_ex` and callback execution routine are both internally ordered by the same
             // 'mutex' and this prevents 'race condition' in this section.
-            // However further we are mutating internal field without any synchronization, and this
-            // is if the compiler/CPU decides to reorder instructions, may cause the current `callback`
-            // state to be dropped while it is executing on another thread.
-            // To prevent this we need to fix instruction order
-            if self.0.module.set_callback_ex(trampoline, payload.as_raw_ptr()) {
+            // The slot lock is held over `set_callback_ex` for the concurrent release of the
+            // previous subscription not to override the new callback, see `Inner::release_callback`.
+            // However the previous `callback` state must not be dropped before `set_callback_ex`
+            // returns, and if the compiler/CPU decides to reorder instructions, it may be dropped
+            // while it is executing on another thread.
+            // To prevent this the instruction order is fixed with the `SeqCst` fence, and on top
+            // of that the previous state is only dropped once it's observed not executing,
+            // see `Callback::retire`
+            let mut slot = inner.callback_slot();
+            if inner.module.set_callback_ex(trampoline, payload.as_raw_ptr()) {
                 // fix instruction order, see comment above
-                unsafe { std::arch::asm!("mfence", options(nostack, preserves_flags)) };
-                self.0.callback.set(Some(payload));
+                atomic::fence(Ordering::SeqCst);
+                slot.generation += 1;
+                let generation = slot.generation;
+                let previous = slot.callback.replace(payload);
+                drop(slot);
+                if let Some(previous) = previous {
+                    previous.retire();
+                }
+
+                let weak = weak.clone();
+                Ok(Subscription::new(move || {
+                    if let Some(inner) = weak.upgrade() {
+                        inner.release_callback(generation)
+                    }
+                }))
             } else {
-                eprintln!("`set_callback_ex` - Не удалось установить функцию обратного вызова. \
-                В документации к коннектору нет описания этой ситуации, как и способов её исправления.\
-                Если вам удалось добиться воспроизводимости этой ошибки создайте issue на github");
+                Err(Error::Callback(lang::SET_CALLBACK_FAILED.into()))
             }
         };
 
         let free_mem = self.0.module.free_memory;
-        InputStream(subscribe_fn).map(move |ptr| TCStr::new(ptr, free_mem))
+        let taps = Arc::clone(&self.0.taps);
+        let panic_policy = Arc::clone(&self.0.panic_policy);
+        let stats = Arc::clone(&self.0.stats);
+        let gates = Arc::clone(&self.0.gates);
+        InputStream { subscribe_fn, free_memory: free_mem, panic_policy, stats, gates }
+            .map(move |ptr| TCStr::new(ptr, free_mem))
+            .inspect(move |buf| taps.dispatch(buf))
+    }
+
+    /// Устанавливает обработчик входящих сообщений, сообщающий коннектору результат обработки
+    ///
+    /// Результат [`CallbackAck`] обработчика **f** передаётся коннектору как значение,
+    /// возвращаемое функцией обратного вызова: `true` для [`CallbackAck::Consumed`] и `false`
+    /// для [`CallbackAck::Rejected`]. Отклонённый буфер **libtxc** не освобождает вызовом
+    /// `FreeMemory`. Документация коннектора не описывает, как коннектор обрабатывает значение
+    /// `false`: освобождает ли он буфер и доставляет ли сообщение повторно, поэтому отклонённые
+    /// буферы могут оставаться не освобождёнными, а обработчик не должен полагаться на повторную
+    /// доставку. Используйте [`CallbackAck::Rejected`] только если это поведение проверено
+    /// для используемой версии коннектора.
+    ///
+    /// Обработчик получает буфер по ссылке, т.к. освобождение буфера определяется результатом
+    /// обработки. Как и [`Stream::subscribe_guarded`], заменяет текущий обработчик.
+    ///
+    /// # Пример
+    /// ```no_run
+    /// use libtxc::CallbackAck;
+    ///
+    /// let mut txc = /*..*/;
+    /// let subscription = txc.subscribe_with_ack(move |buf| match tx.try_send(buf.to_owned_message()) {
+    ///     Ok(_) => CallbackAck::Consumed,
+    ///     Err(_) => CallbackAck::Rejected,
+    /// })?;
+    /// ```
+    ///
+    /// # Errors
+    /// - [`Error::Callback`] - не удалось установить обработчик, см. [`Stream::subscribe`]
+    pub fn subscribe_with_ack<F>(&mut self, mut f: F) -> Result<Subscription>
+    where
+        F: FnMut(&TCStr) -> CallbackAck + Send + Sync + 'static,
+    {
+        self.input_stream().subscribe_guarded(move |buf| match f(&buf) {
+            CallbackAck::Consumed => drop(buf),
+            CallbackAck::Rejected => {
+                std::mem::forget(buf);
+                callback::reject_buffer();
+            }
+        })
+    }
+
+    /// Заменяет обработчик входящих сообщений заранее подготовленной функцией обратного вызова
+    ///
+    /// В отличие от повторного вызова [`TransaqConnector::input_stream()`], обработчик может быть
+    /// создан заранее, без заимствования `TransaqConnector`, например, в другом потоке или
+    /// компоненте приложения.
+    ///
+    /// Замена выполняется без промежутка, в котором обработчик отсутствует: новый обработчик
+    /// регистрируется в коннекторе `set_callback_ex` до освобождения предыдущего. Регистрация и
+    /// исполнение обработчика упорядочены внутренним мьютексом коннектора, поэтому к моменту
+    /// освобождения предыдущий обработчик не исполняется.
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let (tx, rx) = std::sync::mpsc::sync_channel(1 << 10);
+    /// let subscriber = move |buf: TCStr| {
+    ///     let _ = tx.try_send(buf.to_owned_message());
+    /// };
+    ///
+    /// txc.replace_subscriber(subscriber)?;
+    /// ```
+    ///
+    /// # Errors
+    /// - [`Error::Callback`] - не удалось установить обработчик, см. [`Stream::subscribe`]
+    pub fn replace_subscriber<F>(&mut self, subscriber: F) -> Result
+    where
+        F: FnMut(TCStr) + Send + Sync + 'static,
+    {
+        self.input_stream().subscribe(subscriber)
+    }
+
+    /// Устанавливает обработчик входящих сообщений, передающий их в ограниченную очередь
+    ///
+    /// Реализует рекомендуемую архитектуру "передать сообщение в очередь и обработать в другом
+    /// потоке": обработчик копирует буфер коннектора в [`OwnedMessage`] и помещает его в очередь
+    /// размером **capacity**; поведение при переполнении очереди определяется **overflow**.
+    ///
+    /// Как и [`Stream::subscribe`], заменяет текущий обработчик; очередь закрывается при замене
+    /// обработчика или освобождении коннектора.
+    ///
+    /// Накладные расходы по сравнению с [`Stream::subscribe`] - аллокация и копирование
+    /// сообщения, а так же захват мьютекса очереди. Синтетический замер при активном получателе
+    /// в другом потоке: ~0.5 мкс на сообщение размером 64 байта, ~0.9 мкс для 512 байт,
+    /// ~2.7 мкс для 4 КиБ.
+    ///
+    /// # Пример
+    /// ```no_run
+    /// use libtxc::OverflowPolicy;
+    ///
+    /// let mut txc = /*..*/;
+    /// let rx = txc.input_channel(1 << 10, OverflowPolicy::Block)?;
+    ///
+    /// std::thread::spawn(move || {
+    ///     for msg in rx {
+    ///         println!("{msg}");
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    /// - [`Error::Callback`] - не удалось установить обработчик, см. [`Stream::subscribe`]
+    pub fn input_channel(
+        &mut self,
+        capacity: usize,
+        overflow: OverflowPolicy,
+    ) -> Result<Receiver<OwnedMessage>> {
+        let (tx, rx) = channel::bounded(capacity, overflow);
+        self.input_stream().subscribe(move |buf| tx.send(buf.to_owned_message()))?;
+        Ok(rx)
+    }
+
+    /// Устанавливает обработчик, передающий копии входящих сообщений в асинхронный поток
+    ///
+    /// Как и [`TransaqConnector::input_channel`], заменяет текущий обработчик; поток завершается
+    /// при замене обработчика или освобождении коннектора. Очередь сообщений не ограничена.
+    ///
+    /// Требует опции **tokio**.
+    ///
+    /// # Пример
+    /// ```no_run
+    /// use futures::StreamExt;
+    ///
+    /// let mut txc = /*..*/;
+    /// let mut incoming = txc.async_incoming_stream()?;
+    /// while let Some(msg) = incoming.next().await {
+    ///     println!("{msg}");
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// - [`Error::Callback`] - не удалось установить обработчик, см. [`Stream::subscribe`]
+    #[cfg(feature = "tokio")]
+    pub fn async_incoming_stream(&mut self) -> Result<IncomingStream<OwnedMessage>> {
+        let (tx, rx) = incoming::unbounded();
+        self.input_stream().subscribe(move |buf| {
+            let _ = tx.send(buf.to_owned_message());
+        })?;
+        Ok(rx)
+    }
+
+    /// Отправляет команду и ожидает первое входящее сообщение, удовлетворяющее условию
+    ///
+    /// Перед отправкой команды устанавливает временный обработчик входящих сообщений, который
+    /// выполняется перед обработчиком, установленным через [`TransaqConnector::input_stream()`],
+    /// и не влияет на него - все сообщения продолжают поступать в пользовательский обработчик.
+    /// Если обработчик не был установлен, на время ожидания устанавливается пустой обработчик,
+    /// который освобождается перед возвратом.
+    ///
+    /// Завершающий нулевой байт добавляется к команде при необходимости.
+    ///
+    /// # Errors
+    /// - [`Error::Timeout`] - подходящее сообщение не поступило в течение **timeout**
+    /// - [`Error::Callback`] - не удалось установить пустой обработчик
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    ///
+    /// # Пример
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let mut txc = /*..*/;
+    /// let version = txc.send_expect(
+    ///     "<command id=\"get_connector_version\"/>",
+    ///     |buf| buf.xml_tag_name() == Some(b"connector_version"),
+    ///     Duration::from_secs(1),
+    /// )?;
+    /// println!("{version}");
+    /// ```
+    pub fn send_expect<P>(
+        &mut self,
+        cmd: &str,
+        predicate: P,
+        timeout: Duration,
+    ) -> Result<OwnedMessage>
+    where
+        P: Fn(&TCStr) -> bool + Send + Sync + 'static,
+    {
+        let subscription = if self.has_subscriber() {
+            None
+        } else {
+            Some(self.input_stream().subscribe_guarded(|_| {})?)
+        };
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let tap = self.0.taps.insert(Box::new(move |buf| {
+            predicate(buf) && {
+                let _ = tx.try_send(buf.to_owned_message());
+                true
+            }
+        }));
+
+        let sent = if cmd.ends_with('\0') {
+            unsafe { self.sender().send(cmd).map(drop) }
+        } else {
+            unsafe { self.sender().send(format!("{cmd}\0")).map(drop) }
+        };
+
+        let ret = sent.and_then(|_| rx.recv_timeout(timeout).map_err(|_| Error::Timeout));
+        self.0.taps.remove(tap);
+        drop(subscription);
+        ret
+    }
+
+    /// Запрашивает версию коннектора
+    ///
+    /// Отправляет команду `get_connector_version` и ожидает ответное сообщение
+    /// `<connector_version>*.*.*.*</connector_version>`, не более 5 сек. Установленный обработчик
+    /// входящих сообщений продолжает получать все сообщения, см. [`TransaqConnector::send_expect`].
+    ///
+    /// # Errors
+    /// - [`Error::Timeout`] - ответ не поступил в течение 5 сек.
+    /// - [`Error::Internal`] - ответ не содержит версии
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    pub fn connector_version(&mut self) -> Result<String> {
+        const TIMEOUT: Duration = Duration::from_secs(5);
+
+        let msg = self.send_expect(
+            "<command id=\"get_connector_version\"/>\0",
+            |buf| buf.xml_tag_name() == Some(b"connector_version"),
+            TIMEOUT,
+        )?;
+        ConnectorVersionResponse::try_from(&msg)
+            .map(ConnectorVersionResponse::into_string)
+            .map_err(|_| Error::Internal(format!("{} \"{msg}\"", lang::UNEXPECTED_VERSION).into()))
+    }
+
+    /// Количество установленных обработчиков входящих сообщений: `0` или `1`
+    ///
+    /// Коннектор поддерживает единственный обработчик, повторная подписка заменяет текущий.
+    /// Значение уменьшается до `0` при освобождении [`Subscription`] текущей подписки;
+    /// освобождение подписки, которую уже заменила более поздняя, значение не изменяет.
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// assert_eq!(txc.subscriber_count(), 0);
+    /// let subscription = txc.input_stream().subscribe_guarded(|_| {})?;
+    /// assert_eq!(txc.subscriber_count(), 1);
+    /// drop(subscription);
+    /// assert_eq!(txc.subscriber_count(), 0);
+    /// ```
+    pub fn subscriber_count(&self) -> usize {
+        self.0.callback_slot().callback.is_some() as usize
+    }
+
+    /// Статистика сессии: количество входящих сообщений, команд, ошибок и длительность обработки
+    /// сообщений, см. [`SessionStats`]
+    ///
+    /// Счётчики общие для всех подписок и отправителей [`Sender`] коннектора и обновляются с
+    /// момента его загрузки.
+    pub fn stats(&self) -> Arc<SessionStats> {
+        Arc::clone(&self.0.stats)
+    }
+
+    fn has_subscriber(&self) -> bool {
+        self.subscriber_count() > 0
+    }
+}
+
+/// Конструктор [`TransaqConnector`]
+///
+/// Позволяет задать необязательные параметры загрузки библиотеки, не изменяя сигнатуру
+/// [`TransaqConnector::new`].
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{LogLevel, TransaqConnector};
+/// use std::time::Duration;
+///
+/// let txc = TransaqConnector::builder()
+///     .library_path("txmlconnector64.dll".into())
+///     .log_dir("logs".into())
+///     .log_level(LogLevel::Minimum)
+///     .load_timeout(Duration::from_secs(5))
+///     .build()?;
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct TransaqConnectorBuilder {
+    library_path: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    log_level: LogLevel,
+    load_timeout: Option<Duration>,
+    panic_policy: PanicPolicy,
+    min_version: Option<String>,
+}
+
+impl TransaqConnectorBuilder {
+    /// Путь к библиотеке коннектора, *обязательный параметр*
+    pub fn library_path(mut self, library_path: PathBuf) -> Self {
+        self.library_path = Some(library_path);
+        self
+    }
+
+    /// Директория для логов коннектора, *обязательный параметр*
+    pub fn log_dir(mut self, log_dir: PathBuf) -> Self {
+        self.log_dir = Some(log_dir);
+        self
+    }
+
+    /// Уровень логирования, [`LogLevel::Default`] по-умолчанию
+    pub fn log_level(mut self, log_level: LogLevel) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    /// Максимальное время загрузки и инициализации библиотеки
+    ///
+    /// Загрузка выполняется в отдельном потоке; по истечении времени ожидания возвращается
+    /// [`Error::Timeout`], а библиотека будет выгружена по завершении загрузки.
+    pub fn load_timeout(mut self, load_timeout: Duration) -> Self {
+        self.load_timeout = Some(load_timeout);
+        self
+    }
+
+    /// Реакция на панику в функции обратного вызова, [`PanicPolicy::Abort`] по-умолчанию
+    pub fn panic_policy(mut self, panic_policy: PanicPolicy) -> Self {
+        self.panic_policy = panic_policy;
+        self
+    }
+
+    /// Минимальная версия коннектора в формате `"X.Y.Z"`, без ограничения по-умолчанию
+    ///
+    /// После инициализации запрашивается версия коннектора, см.
+    /// [`TransaqConnector::connector_version`], и библиотека более ранней версии выгружается.
+    /// Версии сравниваются покомпонентно, недостающие компоненты считаются нулевыми, например
+    /// `"6.19"` соответствует `"6.19.0.0"`.
+    pub fn min_version<S: Into<String>>(mut self, min_version: S) -> Self {
+        self.min_version = Some(min_version.into());
+        self
+    }
+
+    /// Загружает и подготавливает библиотеку к использованию, см. [`TransaqConnector::new`]
+    ///
+    /// # Errors
+    /// - [`Error::Loading`] - не указан один из обязательных параметров, а так же ошибки
+    ///   [`TransaqConnector::new`]
+    /// - [`Error::Timeout`] - загрузка не завершилась за время **load_timeout**
+    /// - [`Error::Initialization`] - см. [`TransaqConnector::new`], а так же версия коннектора
+    ///   ниже **min_version** или недопустимый формат версии
+    /// - ошибки запроса версии, см. [`TransaqConnector::connector_version`]
+    pub fn build(self) -> Result<TransaqConnector> {
+        let missing = |param| {
+            let msg = format!("{} '{param}'", lang::MISSING_PARAMETER);
+            Error::Loading(io::Error::new(io::ErrorKind::InvalidInput, msg))
+        };
+        let library_path = self.library_path.ok_or_else(|| missing("library_path"))?;
+        let log_dir = self.log_dir.ok_or_else(|| missing("log_dir"))?;
+        let log_level = self.log_level;
+        // the invalid version is rejected before the library is loaded
+        if let Some(min_version) = &self.min_version {
+            parse_min_version(min_version)?;
+        }
+
+        let mut txc = match self.load_timeout {
+            None => TransaqConnector::new(library_path, log_dir, log_level),
+            Some(timeout) => {
+                let (tx, rx) = mpsc::sync_channel(1);
+                std::thread::spawn(move || {
+                    let _ = tx.send(TransaqConnector::new(library_path, log_dir, log_level));
+                });
+                rx.recv_timeout(timeout).map_err(|_| Error::Timeout)?
+            }
+        }?;
+        txc.set_panic_policy(self.panic_policy);
+
+        if let Some(min_version) = &self.min_version {
+            txc.require_version(min_version)?;
+        }
+        Ok(txc)
+    }
+}
+
+impl TransaqConnector {
+    // Queries the connector version and compares it with `min_version`, see
+    // `TransaqConnectorBuilder::min_version`
+    fn require_version(&mut self, min_version: &str) -> Result<String> {
+        let min_parsed = parse_min_version(min_version)?;
+        let version = self.connector_version()?;
+        let parsed = parse_version(&version).ok_or_else(|| {
+            Error::Initialization(format!("{} \"{version}\"", lang::UNEXPECTED_VERSION).into())
+        })?;
+        if compare_versions(&parsed, &min_parsed).is_lt() {
+            let msg = format!("{}: {version} < {min_version}", lang::OUTDATED_VERSION);
+            return Err(Error::Initialization(msg.into()));
+        }
+        Ok(version)
     }
 }
 
+// Numeric components of the dot separated version "X.Y.Z"
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.trim().split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn parse_min_version(min_version: &str) -> Result<Vec<u64>> {
+    parse_version(min_version).ok_or_else(|| {
+        Error::Initialization(format!("{} \"{min_version}\"", lang::INVALID_VERSION).into())
+    })
+}
+
+// Compares the versions component-wise, the missing components are zeros
+fn compare_versions(a: &[u64], b: &[u64]) -> std::cmp::Ordering {
+    let len = a.len().max(b.len());
+    let component = |v: &[u64], i| v.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| component(a, i).cmp(&component(b, i)))
+        .find(|o| o.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
 /// Обьект-отправитель сообщений.
 ///
 /// Использование методов [`Sender::send`] и [`Sender::send_ptr`] компилируется в прямые вызовы функции  
@@ -253,7 +936,7 @@ impl TransaqConnector {
 /// use libtxc::{TransaqConnector, Sender};
 ///
 /// let txc = /*..*/;
-/// txc.subscribe(|buf| println!("rx: {}", buf.to_string_lossy()));
+/// txc.input_stream().subscribe(|buf| println!("rx: {}", buf.to_string_lossy()))?;
 ///
 /// let sender: Sender = txc.sender();
 /// let sender_2: Sender = sender.clone();
@@ -332,18 +1015,247 @@ impl Sender {
     #[inline]
     pub unsafe fn send<B: AsRef<[u8]>>(&self, buf: B) -> Result<TCStr<'_>> {
         #[cfg(debug_assertions)]
-        {
-            let non_empty = !buf.as_ref().is_empty();
-            let contains_term = buf.as_ref().iter().any(|b| b'\0'.eq(b));
-            let valid_utf8 = std::str::from_utf8(buf.as_ref()).is_ok();
-            assert!(non_empty, "пустой буфер");
-            assert!(contains_term, "отсутствует нулевой байт");
-            assert!(valid_utf8, "буфер содержит не валидные UTF-8 символы");
-        }
+        debug_check_command(buf.as_ref());
 
         self.send_ptr(buf.as_ref().as_ptr())
     }
 
+    /// Последовательно передаёт команды коннектору
+    ///
+    /// Возвращает ответные сообщения в порядке следования команд, отправка прекращается на
+    /// первой ошибке. Коннектор обрабатывает команды последовательно, поэтому порядок ответов
+    /// соответствует порядку команд.
+    ///
+    /// В `debug` сборке все команды проверяются до отправки первой из них, что исключает
+    /// частичное выполнение последовательности из-за ошибки формирования одной из команд.
+    ///
+    /// # Safety
+    /// Требования к каждой из команд, см. [`Sender::send`]
+    ///
+    /// # Errors
+    /// Первая ошибка отправки команды, см. [`Sender::send`]
+    ///
+    /// # Examples
+    /// ```no_run
+    /// let sender = /**/;
+    /// let cmds: [&[u8]; 2] = [
+    ///     b"<command id=\"get_securities\"/>\0",
+    ///     b"<command id=\"get_portfolio\" client=\"..\"/>\0",
+    /// ];
+    /// let results = unsafe { sender.send_sequence(&cmds)? };
+    /// assert_eq!(results.len(), 2);
+    /// ```
+    ///
+    /// # Panics
+    /// В `debug` сборке - если одна из команд не соответствует условиям
+    pub unsafe fn send_sequence(&self, cmds: &[&[u8]]) -> Result<Vec<TCStr<'_>>> {
+        #[cfg(debug_assertions)]
+        cmds.iter().for_each(|cmd| debug_check_command(cmd));
+
+        cmds.iter().map(|cmd| self.send_ptr(cmd.as_ptr())).collect()
+    }
+
+    /// Отправляет команду подписки на биржевую информацию, см. [`SubscribeCommand`]
+    ///
+    /// Возвращает ответ коннектора `<result success="true"/>`, данные передаются сообщениями
+    /// `<alltrades>`, `<quotations>` и `<quotes>`.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - недопустимые параметры команды, см.
+    ///   [`SubscribeCommand::build`], или отказ коннектора
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    pub fn subscribe(&self, cmd: &SubscribeCommand) -> Result<TCStr<'_>> {
+        let cmd = cmd.build()?;
+        unsafe { self.send(cmd) }
+    }
+
+    /// Отправляет команду отмены подписки на биржевую информацию, см. [`UnsubscribeCommand`]
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - недопустимые параметры команды, см.
+    ///   [`UnsubscribeCommand::build`], или отказ коннектора
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    pub fn unsubscribe(&self, cmd: &UnsubscribeCommand) -> Result<TCStr<'_>> {
+        let cmd = cmd.build()?;
+        unsafe { self.send(cmd) }
+    }
+
+    /// Выставляет заявку, см. [`PlaceOrderCommand`]
+    ///
+    /// Возвращает идентификатор транзакции из ответа коннектора, состояние заявки передаётся
+    /// сообщениями `<orders>`, см. [`Router`].
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - недопустимые параметры команды, см.
+    ///   [`PlaceOrderCommand::build`], или отказ коннектора
+    /// - [`Error::Internal`] - ответ не содержит идентификатора транзакции
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    pub fn place(&self, cmd: &PlaceOrderCommand) -> Result<SendAck> {
+        self.send_order(cmd.build()?)
+    }
+
+    /// Выставляет условную заявку, см. [`CondOrderCommand`] и [`Sender::place`]
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - недопустимые параметры команды, см.
+    ///   [`CondOrderCommand::build`], или отказ коннектора
+    /// - [`Error::Internal`] - ответ не содержит идентификатора транзакции
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    pub fn place_cond(&self, cmd: &CondOrderCommand) -> Result<SendAck> {
+        self.send_order(cmd.build()?)
+    }
+
+    /// Выставляет стоп-заявку, см. [`StopOrderCommand`] и [`Sender::place`]
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - недопустимые параметры команды, см.
+    ///   [`StopOrderCommand::build`], или отказ коннектора
+    /// - [`Error::Internal`] - ответ не содержит идентификатора транзакции
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    pub fn place_stop(&self, cmd: &StopOrderCommand) -> Result<SendAck> {
+        self.send_order(cmd.build()?)
+    }
+
+    fn send_order(&self, cmd: Vec<u8>) -> Result<SendAck> {
+        let result = unsafe { self.send(cmd)? };
+        SendAck::try_from(&result)
+            .map_err(|e| Error::Internal(format!("{e} \"{}\"", result.to_string_lossy()).into()))
+    }
+
+    /// Передаёт данные коннектору, если он не занят
+    ///
+    /// Коннектор обрабатывает команды и входящие сообщения последовательно, и [`Sender::send`]
+    /// ожидает завершения выполняющегося обработчика входящих сообщений или отправки другой
+    /// команды. `try_send` возвращает `Ok(None)` не ожидая, если в момент вызова коннектор
+    /// занят одним из них, что позволяет отложить команду, не блокируя поток(например,
+    /// цикл обработки событий).
+    ///
+    /// Состояние коннектора определяется на стороне **libtxc** и не учитывает его внутренние
+    /// задачи, поэтому отсутствие блокировки гарантируется лишь в отношении описанных выше
+    /// случаев. Завершающий нулевой байт добавляется к команде при необходимости.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - команда содержит не валидные UTF-8 символы
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let sender = /**/;
+    /// match sender.try_send(r#"<command id="server_status"/>"#)? {
+    ///     Some(result) => println!("{result}"),
+    ///     None => /* повторить позже */,
+    /// }
+    /// ```
+    pub fn try_send<B: AsRef<[u8]>>(&self, cmd: B) -> Result<Option<TCStr<'_>>> {
+        let cmd = command_buffer(cmd.as_ref())?;
+
+        // held across `send_command`, so no command may start between the check and the call
+        let _sending = match self.0.sending.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Ok(None),
+        };
+        let busy = match self.0.callback.try_lock() {
+            Ok(slot) => slot.callback.as_ref().map_or(false, Callback::is_in_flight),
+            Err(TryLockError::Poisoned(e)) => {
+                e.into_inner().callback.as_ref().map_or(false, Callback::is_in_flight)
+            }
+            // the handler is being replaced
+            Err(TryLockError::WouldBlock) => true,
+        };
+        if busy {
+            return Ok(None);
+        }
+
+        unsafe { self.send_unguarded(cmd.as_ptr()).map(Some) }
+    }
+
+    /// Передаёт данные коннектору, ожидая ответ не дольше **timeout**
+    ///
+    /// Команда копируется и передаётся коннектору из фонового потока, завершающий нулевой байт
+    /// добавляется к команде при необходимости. По истечении **timeout** возвращается
+    /// [`Error::Timeout`], при этом фоновый поток продолжает ожидать ответ коннектора, после
+    /// чего ответ освобождается. Фоновый поток удерживает коннектор от освобождения до своего
+    /// завершения.
+    ///
+    /// # Errors
+    /// - [`Error::Timeout`] - коннектор не ответил в течение **timeout**
+    /// - [`Error::InvalidCommand`] - команда содержит не валидные UTF-8 символы
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    ///
+    /// # Пример
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let sender = /**/;
+    /// match sender.send_timeout(r#"<command id="server_status"/>"#, Duration::from_secs(1)) {
+    ///     Err(libtxc::Error::Timeout) => eprintln!("коннектор не отвечает"),
+    ///     result => println!("{:?}", result),
+    /// }
+    /// ```
+    pub fn send_timeout<B: AsRef<[u8]>>(&self, cmd: B, timeout: Duration) -> Result<TCStr<'_>> {
+        // the response is owned by the background thread until it's received
+        struct Response(TCStr<'static>);
+        unsafe impl Send for Response {}
+
+        let cmd = command_buffer(cmd.as_ref())?.into_owned();
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let sender = self.clone();
+        std::thread::Builder::new()
+            .name("txc-send-timeout".into())
+            .spawn(move || {
+                let result = unsafe { sender.send_ptr(cmd.as_ptr()) };
+                // response is released here if the receiver has given up
+                let _ = tx.send(result.map(|buf| Response(buf.rebind())));
+            })
+            .map_err(|e| Error::Internal(e.to_string().into()))?;
+
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result.map(|Response(buf)| buf.rebind()),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(Error::Timeout),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(Error::Internal(lang::SEND_THREAD_FAILED.into()))
+            }
+        }
+    }
+
+    /// Передаёт данные коннектору, не блокируя поток асинхронной среды исполнения `tokio`
+    ///
+    /// Команда копируется и передаётся коннектору в потоке [`tokio::task::spawn_blocking`],
+    /// завершающий нулевой байт добавляется к команде при необходимости. Ответ коннектора
+    /// преобразуется в [`String`], поэтому возвращаемый `Future` не заимствует `Sender`.
+    ///
+    /// Требует опции **tokio**, `Future` должен исполняться в контексте среды исполнения `tokio`.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`] - команда содержит не валидные UTF-8 символы
+    /// - [`Error::Internal`] - поток отправки команды завершился паникой
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    ///
+    /// # Пример
+    /// ```no_run
+    /// let sender = txc.sender();
+    /// let response = sender.send_async(r#"<command id="server_status"/>"#).await?;
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn send_async<B: AsRef<[u8]>>(
+        &self,
+        cmd: B,
+    ) -> impl std::future::Future<Output = Result<String>> + Send + 'static {
+        let cmd = command_buffer(cmd.as_ref()).map(Cow::into_owned);
+        let sender = self.clone();
+        async move {
+            let cmd = cmd?;
+            tokio::task::spawn_blocking(move || {
+                let response = unsafe { sender.send_ptr(cmd.as_ptr()) };
+                response.map(|buf| buf.to_string_lossy().into_owned())
+            })
+            .await
+            .map_err(|e| Error::Internal(e.to_string().into()))?
+        }
+    }
+
     /// Передаёт данные коннектору
     ///
     /// Передаёт указатель на данные в функцию коннектора `BYTE* send_command(BYTE*)` и возвращает
@@ -370,15 +1282,132 @@ impl Sender {
     ///
     /// # Panics
     /// В `debug` сборке - если передан нулевой указатель
-    #[cfg_attr(feature = "tracing", instrument(level = "debug", skip_all))]
     #[inline]
     pub unsafe fn send_ptr(&self, ptr: *const u8) -> Result<TCStr<'_>> {
-        debug_assert!(!ptr.is_null(), "нулевой указатель");
+        debug_assert!(!ptr.is_null(), "{}", lang::NULL_PTR);
+
+        let _sending = self.0.sending.lock().unwrap_or_else(|e| e.into_inner());
+        self.send_unguarded(ptr)
+    }
 
-        as_nonnull_txc_buf(self.0.module.send_command(ptr) as _)
+    // `send_ptr` with the `sending` lock held by the caller
+    #[cfg_attr(
+        feature = "tracing",
+        instrument(
+            name = "send_ptr",
+            level = "debug",
+            skip_all,
+            fields(commands = tracing::field::Empty, errors = tracing::field::Empty)
+        )
+    )]
+    #[inline]
+    unsafe fn send_unguarded(&self, ptr: *const u8) -> Result<TCStr<'_>> {
+        let response = self.0.module.send_command(ptr);
+
+        let ret = as_nonnull_txc_buf(response as _)
             .map(|ptr| TCStr::new(ptr, self.0.module.free_memory))
-            .and_then(parse_send_response)
+            .and_then(parse_send_response);
+        self.0.stats.record_command(ret.is_ok());
+        ret
+    }
+}
+
+// Entry point of the `parse_response` fuzz target, the input is truncated at the first nul byte
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub fn fuzz_parse_send_response(data: &[u8]) -> Result<Vec<u8>> {
+    let data = data.iter().position(|b| *b == b'\0').map_or(data, |end| &data[..end]);
+    let buf = TCStr::new(buffers::alloc_buffer(data), buffers::free_buffer);
+    parse_send_response(buf).map(|response| response.to_bytes().to_vec())
+}
+
+#[cfg(debug_assertions)]
+fn debug_check_command(buf: &[u8]) {
+    let non_empty = !buf.is_empty();
+    let contains_term = buf.iter().any(|b| b'\0'.eq(b));
+    let valid_utf8 = std::str::from_utf8(buf).is_ok();
+    assert!(non_empty, "{}", lang::EMPTY_BUFFER);
+    assert!(contains_term, "{}", lang::NO_NUL);
+    assert!(valid_utf8, "{}", lang::INVALID_UTF8);
+}
+
+// Validates the command and appends the terminating nul byte if it's missing
+fn command_buffer(cmd: &[u8]) -> Result<Cow<'_, [u8]>> {
+    if let Err(e) = std::str::from_utf8(cmd) {
+        return Err(Error::InvalidCommand(e.to_string().into()));
+    }
+    Ok(if cmd.contains(&b'\0') {
+        Cow::Borrowed(cmd)
+    } else {
+        let mut buf = Vec::with_capacity(cmd.len() + 1);
+        buf.extend_from_slice(cmd);
+        buf.push(b'\0');
+        Cow::Owned(buf)
+    })
+}
+
+/// Команда из строкового литерала, с завершающим нулевым байтом
+///
+/// Возвращает `&'static [u8]`, добавляя нулевой байт, если он отсутствует. Команда проверяется
+/// во время компиляции: пустой литерал или литерал, содержащий нулевой байт не в конце, приводит
+/// к ошибке компиляции. Литерал является строкой и, следовательно, корректной UTF-8
+/// последовательностью.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::cmd;
+///
+/// const GET_VERSION: &[u8] = cmd!("<command id=\"get_connector_version\"/>");
+/// assert_eq!(GET_VERSION, b"<command id=\"get_connector_version\"/>\0");
+///
+/// let response = unsafe { sender.send(GET_VERSION)? };
+/// ```
+/// ```compile_fail
+/// let cmd = libtxc::cmd!("<command id=\"server_status\"/>\0<command/>");
+/// ```
+#[macro_export]
+macro_rules! cmd {
+    ($cmd:literal) => {{
+        const CMD: &str = $cmd;
+        const LEN: usize = $crate::__command_len(CMD.as_bytes());
+        const BUF: [u8; LEN] = $crate::__command_buf(CMD.as_bytes());
+        &BUF as &'static [u8]
+    }};
+}
+
+// length of the `cmd!` literal with the terminating nul byte, fails the compilation on invalid
+// literals
+#[doc(hidden)]
+pub const fn __command_len(cmd: &[u8]) -> usize {
+    let mut i = 0;
+    while i < cmd.len() {
+        if cmd[i] == b'\0' && i + 1 != cmd.len() {
+            if cfg!(feature = "lang_en") {
+                panic!("command literal contains an interior nul byte")
+            } else {
+                panic!("литерал команды содержит нулевой байт не в конце")
+            }
+        }
+        i += 1;
+    }
+    match cmd {
+        [] | [b'\0'] if cfg!(feature = "lang_en") => panic!("empty command literal"),
+        [] | [b'\0'] => panic!("пустой литерал команды"),
+        [.., b'\0'] => cmd.len(),
+        _ => cmd.len() + 1,
+    }
+}
+
+#[doc(hidden)]
+pub const fn __command_buf<const N: usize>(cmd: &[u8]) -> [u8; N] {
+    // the terminating nul byte is left in place
+    let mut buf = [0; N];
+    let mut i = 0;
+    while i < cmd.len() && cmd[i] != b'\0' {
+        buf[i] = cmd[i];
+        i += 1;
     }
+    buf
 }
 
 /// Глубина логирования в соответствии с детализацией и размером лог-файла
@@ -409,42 +1438,149 @@ impl From<i32> for LogLevel {
         }
     }
 }
+impl TryFrom<u8> for LogLevel {
+    type Error = String;
+
+    /// Уровень логирования по номеру 1-3, без ограничения значения
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            1 => Ok(LogLevel::Minimum),
+            2 => Ok(LogLevel::Default),
+            3 => Ok(LogLevel::Maximum),
+            _ => Err(format!("{} '{value}'", lang::INVALID_LOG_LEVEL)),
+        }
+    }
+}
+impl TryFrom<&str> for LogLevel {
+    type Error = String;
+
+    /// Уровень логирования по номеру 1-3 или названию `minimum`, `default`, `maximum` в любом
+    /// регистре, например из переменной окружения
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        let level = value.trim();
+        if level.eq_ignore_ascii_case("minimum") || level == "1" {
+            Ok(LogLevel::Minimum)
+        } else if level.eq_ignore_ascii_case("default") || level == "2" {
+            Ok(LogLevel::Default)
+        } else if level.eq_ignore_ascii_case("maximum") || level == "3" {
+            Ok(LogLevel::Maximum)
+        } else {
+            Err(format!("{} '{value}'", lang::INVALID_LOG_LEVEL))
+        }
+    }
+}
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        LogLevel::try_from(s)
+    }
+}
+
+impl Error {
+    /// Имеет ли смысл повторить операцию, завершившуюся ошибкой
+    ///
+    /// - [`Error::Loading`], [`Error::Initialization`] - `false`, ошибки окружения, повтор без
+    ///   исправления параметров загрузки не поможет
+    /// - [`Error::InvalidCommand`] - `false`, команда сформирована неверно или не может быть
+    ///   выполнена в текущем состоянии коннектора(например, без подключения к серверу)
+    /// - [`Error::Internal`] - `true`, исключение внутри коннектора может быть временным
+    /// - [`Error::Timeout`] - `true`, коннектор мог быть временно занят
+    /// - [`Error::Callback`] - `true`, причины ошибки не документированы, предыдущий обработчик
+    ///   остаётся установленным
+    /// - [`Error::RateLimitExceeded`] - `true`, после восстановления лимита отправки
+    ///
+    /// Повторять операцию следует ограниченное количество раз и с задержкой.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            Error::Loading(_) | Error::Initialization(_) | Error::InvalidCommand(_) => false,
+            Error::Internal(_) | Error::Timeout | Error::Callback(_) | Error::RateLimitExceeded => {
+                true
+            }
+        }
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::Loading(inner) => {
-                write!(f, "Не удалось загрузить библиотеку: {inner} ")
-            }
-            Error::Initialization(msg) => {
-                write!(f, "Инициализация библиотеки трагически провалилась: {msg} ")
-            }
-            Error::InvalidCommand(msg) => {
-                write!(f, "Команда не прошла проверку и не была отправлена: {msg} ")
-            }
-            Error::Internal(msg) => {
-                write!(
-                    f,
-                    "Внутренняя ошибка/exception коннектора - скорее всего это - исключительная ситуация, \
-                       которая не должна происходить в принципе.\n\
-                       Проверьте целостность файлов, актуальность версий и параметры окружения.\n\
-                       Если ситуация повторяется и надежда иссякла, вы могли бы создать issue на\
-                       github.\n{msg}"
-                )
-            }
+            Error::Loading(inner) => write!(f, "{}: {inner} ", lang::ERR_LOADING),
+            Error::Initialization(msg) => write!(f, "{}: {msg} ", lang::ERR_INITIALIZATION),
+            Error::InvalidCommand(msg) => write!(f, "{}: {msg} ", lang::ERR_INVALID_COMMAND),
+            Error::Internal(msg) => write!(f, "{}\n{msg}", lang::ERR_INTERNAL),
+            Error::Timeout => f.write_str(lang::ERR_TIMEOUT),
+            Error::Callback(msg) => write!(f, "{}: {msg}", lang::ERR_CALLBACK),
+            Error::RateLimitExceeded => f.write_str(lang::ERR_RATE_LIMIT),
         }
     }
 }
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        if let Self::Loading(src) = self {
-            Some(src)
-        } else {
-            None
+        match self {
+            Error::Loading(src) => Some(src),
+            Error::Initialization(msg)
+            | Error::InvalidCommand(msg)
+            | Error::Internal(msg)
+            | Error::Callback(msg) => Some(msg),
+            Error::Timeout | Error::RateLimitExceeded => None,
         }
     }
 }
 
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Self {
+        Error::InvalidCommand(err.to_string().into())
+    }
+}
+
+/// Исходное сообщение об ошибке, см. [`std::error::Error::source`] для [`Error`]
+///
+/// Преобразование [`Error`] в `Box<dyn std::error::Error + Send + Sync>` доступно через
+/// стандартную реализацию `From`, что позволяет использовать оператор `?`.
+///
+/// # Пример
+/// ```no_run
+/// use std::error::Error as _;
+///
+/// let err: libtxc::Error = /*..*/;
+/// let mut source = err.source();
+/// while let Some(err) = source {
+///     println!("{err}");
+///     source = err.source();
+/// }
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ConnectorError(String);
+
+impl ConnectorError {
+    /// Текст сообщения
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.0
+    }
+}
+impl fmt::Debug for ConnectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ConnectorError").field(&self.0).finish()
+    }
+}
+impl fmt::Display for ConnectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl std::error::Error for ConnectorError {}
+impl From<String> for ConnectorError {
+    fn from(msg: String) -> Self {
+        Self(msg)
+    }
+}
+impl From<&str> for ConnectorError {
+    fn from(msg: &str) -> Self {
+        Self(msg.to_string())
+    }
+}
+
 unsafe impl Send for Error {}
 unsafe impl Sync for Error {}
 
@@ -454,6 +1590,16 @@ impl fmt::Debug for TransaqConnector {
     }
 }
 
+impl fmt::Debug for PanicPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PanicPolicy::Abort => f.write_str("Abort"),
+            PanicPolicy::LogAndContinue => f.write_str("LogAndContinue"),
+            PanicPolicy::Custom(_) => f.debug_tuple("Custom").finish(),
+        }
+    }
+}
+
 impl fmt::Debug for Sender {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_tuple("Sender").finish()
@@ -494,3 +1640,566 @@ fn unlikely(b: bool) -> bool {
     }
     b
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi::fake;
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+
+    const VERSION: &str = "<connector_version>6.19.2.21</connector_version>";
+
+    fn reply_version() {
+        fake::reply_with(|cmd| cmd.contains("get_connector_version").then(|| VERSION.into()));
+    }
+
+    // Replaces the handlers while the previous ones are executing, the library lock is released
+    // from within the callback as if the connector did not wait for the callback completion
+    fn resubscribe_while_delivering(deliver: fn(&str) -> bool) {
+        const ROUNDS: usize = if cfg!(miri) { 8 } else { 200 };
+
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let handled = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let delivery = {
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    deliver("<quotes/>");
+                }
+            })
+        };
+
+        for round in 0..ROUNDS {
+            let seen = handled.load(Ordering::SeqCst);
+            let counter = Arc::clone(&handled);
+            // handler state, its use after the release is caught by the sanitizers or the
+            // assertion below
+            let state = Box::new(round);
+            txc.input_stream()
+                .subscribe(move |_| {
+                    fake::unlock();
+                    std::thread::yield_now();
+                    assert_eq!(*state, round);
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+                .unwrap();
+            while handled.load(Ordering::SeqCst) == seen {
+                std::thread::yield_now();
+            }
+        }
+
+        done.store(true, Ordering::SeqCst);
+        delivery.join().unwrap();
+        drop(txc);
+        assert!(handled.load(Ordering::SeqCst) >= ROUNDS);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+
+    #[test]
+    fn resubscribe_while_delivering_unlocked() {
+        resubscribe_while_delivering(fake::deliver);
+    }
+
+    // the replaced callback is called after `set_callback_ex` returns
+    #[test]
+    fn resubscribe_while_delivering_unordered() {
+        resubscribe_while_delivering(fake::deliver_unordered);
+    }
+
+    #[test]
+    fn error_is_retriable() {
+        let loading = io::Error::new(io::ErrorKind::NotFound, "txcn64.dll");
+        assert!(!Error::Loading(loading).is_retriable());
+        assert!(!Error::Initialization("log dir".into()).is_retriable());
+        assert!(!Error::InvalidCommand("no connection".into()).is_retriable());
+
+        assert!(Error::Internal("exception".into()).is_retriable());
+        assert!(Error::Timeout.is_retriable());
+        assert!(Error::Callback("rejected".into()).is_retriable());
+        assert!(Error::RateLimitExceeded.is_retriable());
+    }
+
+    const STATUS: &str = "<command id=\"server_status\"/>";
+
+    #[test]
+    fn try_send_skips_while_handler_runs() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let sender = txc.sender();
+        let (entered_tx, entered) = mpsc::channel();
+        let (release, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+
+        txc.input_stream()
+            .subscribe(move |_| {
+                entered_tx.send(()).unwrap();
+                release_rx.lock().unwrap().recv().unwrap();
+            })
+            .unwrap();
+        let delivery = std::thread::spawn(|| fake::deliver("<quotes/>"));
+        entered.recv().unwrap();
+
+        assert!(sender.try_send(STATUS).unwrap().is_none());
+        assert!(fake::commands().is_empty());
+
+        release.send(()).unwrap();
+        assert!(delivery.join().unwrap());
+        assert!(sender.try_send(STATUS).unwrap().is_some());
+        assert_eq!(fake::commands(), [STATUS]);
+    }
+
+    #[test]
+    fn try_send_skips_while_command_in_progress() {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+        let (entered_tx, entered) = mpsc::channel();
+        let (release, release_rx) = mpsc::channel::<()>();
+        // the first command is processed until released
+        let mut pending = Some((entered_tx, release_rx));
+        fake::reply_with(move |_| {
+            if let Some((entered, release)) = pending.take() {
+                entered.send(()).unwrap();
+                release.recv().unwrap();
+            }
+            None
+        });
+
+        let sender = txc.sender();
+        let sending = std::thread::spawn(move || unsafe {
+            sender.send("<command id=\"server_status\"/>\0").is_ok()
+        });
+        entered.recv().unwrap();
+
+        assert!(txc.sender().try_send(STATUS).unwrap().is_none());
+
+        release.send(()).unwrap();
+        assert!(sending.join().unwrap());
+        assert!(txc.sender().try_send(STATUS).unwrap().is_some());
+        assert_eq!(fake::commands(), [STATUS, STATUS]);
+    }
+
+    #[test]
+    fn send_expect_releases_temporary_subscriber() {
+        let (_library, module) = fake::load();
+        reply_version();
+        let mut txc = TransaqConnector::from_module(module);
+
+        assert_eq!(txc.connector_version().unwrap(), "6.19.2.21");
+        assert_eq!(txc.subscriber_count(), 0);
+        assert!(fake::deliver("<server_status connected=\"true\"/>"));
+
+        let subscription = txc.input_stream().subscribe_guarded(|_| {}).unwrap();
+        assert_eq!(txc.connector_version().unwrap(), "6.19.2.21");
+        assert_eq!(txc.subscriber_count(), 1);
+        drop(subscription);
+        assert_eq!(txc.subscriber_count(), 0);
+
+        drop(txc);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+
+    #[test]
+    fn send_expect_timeout_releases_temporary_subscriber() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+
+        let ret = txc.send_expect("<command id=\"server_status\"/>", |_| true, Duration::ZERO);
+        assert!(matches!(ret, Err(Error::Timeout)));
+        assert_eq!(txc.subscriber_count(), 0);
+        assert_eq!(fake::commands(), ["<command id=\"server_status\"/>"]);
+    }
+
+    #[test]
+    fn error_source_chain() {
+        use std::error::Error as _;
+
+        let source = |err: &Error| err.source().map(ToString::to_string);
+        let loading = Error::Loading(io::Error::new(io::ErrorKind::NotFound, "txcn64.dll"));
+        assert_eq!(source(&loading).as_deref(), Some("txcn64.dll"));
+        assert!(loading.source().unwrap().downcast_ref::<io::Error>().is_some());
+
+        for err in [
+            Error::Initialization("message".into()),
+            Error::InvalidCommand("message".into()),
+            Error::Internal("message".into()),
+            Error::Callback("message".into()),
+        ] {
+            let connector = err.source().unwrap().downcast_ref::<ConnectorError>().unwrap();
+            assert_eq!(connector.message(), "message");
+            assert_eq!(connector.to_string(), "message");
+            assert!(connector.source().is_none());
+        }
+
+        assert!(Error::Timeout.source().is_none());
+        assert!(Error::RateLimitExceeded.source().is_none());
+    }
+
+    #[test]
+    fn error_into_boxed_error() {
+        fn send() -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Err(Error::InvalidCommand("no connection".into()))?
+        }
+
+        let err = send().unwrap_err();
+        assert!(err.source().is_some());
+        let err = err.downcast::<Error>().unwrap();
+        assert!(matches!(*err, Error::InvalidCommand(ref msg) if msg.message() == "no connection"));
+    }
+
+    #[test]
+    fn log_level_from_u8() {
+        assert_eq!(LogLevel::try_from(1u8), Ok(LogLevel::Minimum));
+        assert_eq!(LogLevel::try_from(2u8), Ok(LogLevel::Default));
+        assert_eq!(LogLevel::try_from(3u8), Ok(LogLevel::Maximum));
+        for level in [0, 4, u8::MAX] {
+            let err = LogLevel::try_from(level).unwrap_err();
+            assert!(err.starts_with(lang::INVALID_LOG_LEVEL));
+            assert!(err.ends_with(&format!("'{level}'")));
+        }
+    }
+
+    #[test]
+    fn log_level_from_str() {
+        assert_eq!(LogLevel::try_from("minimum"), Ok(LogLevel::Minimum));
+        assert_eq!(LogLevel::try_from("Default"), Ok(LogLevel::Default));
+        assert_eq!(LogLevel::try_from(" MAXIMUM\n"), Ok(LogLevel::Maximum));
+        assert_eq!("1".parse(), Ok(LogLevel::Minimum));
+        assert_eq!(" 2 ".parse(), Ok(LogLevel::Default));
+        assert_eq!("3".parse(), Ok(LogLevel::Maximum));
+
+        for level in ["", "0", "4", "max", "default level", "+1", "02"] {
+            let err = level.parse::<LogLevel>().unwrap_err();
+            assert!(err.starts_with(lang::INVALID_LOG_LEVEL));
+            assert!(err.ends_with(&format!("'{level}'")));
+        }
+    }
+
+    #[test]
+    fn on_drop_runs_before_connector_is_released() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let items = Arc::new(AtomicUsize::new(0));
+        let handled = Arc::clone(&items);
+        txc.input_stream()
+            .on_drop(|| fake::event("on_drop"))
+            .subscribe(move |_| {
+                handled.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        assert!(fake::deliver("<quotes/>"));
+
+        drop(txc);
+        assert_eq!(items.load(Ordering::SeqCst), 1);
+        assert_eq!(fake::events(), ["on_drop", "uninitialize"]);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+
+    #[test]
+    fn cmd_literal_is_nul_terminated() {
+        assert_eq!(cmd!("<command id=\"server_status\"/>"), b"<command id=\"server_status\"/>\0");
+        assert_eq!(cmd!("<command id=\"server_status\"/>\0"), b"<command id=\"server_status\"/>\0");
+        assert_eq!(cmd!("<command id=\"disconnect\"/>").len(), 27);
+
+        // the buffer is evaluated at compile time, repeated uses share the same memory
+        let ptrs =
+            (0..2).map(|_| cmd!("<command id=\"disconnect\"/>").as_ptr()).collect::<Vec<_>>();
+        assert_eq!(ptrs[0], ptrs[1]);
+    }
+
+    #[test]
+    fn cmd_literal_passes_send_checks() {
+        let cmd = cmd!("<command id=\"get_connector_version\"/>");
+        #[cfg(debug_assertions)]
+        debug_check_command(cmd);
+        assert!(matches!(command_buffer(cmd), Ok(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn command_len() {
+        assert_eq!(__command_len(b"<command/>"), 11);
+        assert_eq!(__command_len(b"<command/>\0"), 11);
+        assert_eq!(__command_buf::<11>(b"<command/>"), *b"<command/>\0");
+        assert_eq!(__command_buf::<11>(b"<command/>\0"), *b"<command/>\0");
+    }
+
+    #[test]
+    #[should_panic]
+    fn command_len_rejects_interior_nul() {
+        __command_len(b"<command/>\0<command/>");
+    }
+
+    #[test]
+    #[should_panic]
+    fn command_len_rejects_empty() {
+        __command_len(b"");
+    }
+
+    #[test]
+    #[should_panic]
+    fn command_len_rejects_nul_only() {
+        __command_len(b"\0");
+    }
+
+    #[test]
+    fn stats_count_every_command_and_message() {
+        const THREADS: usize = 4;
+        const COMMANDS: usize = 250;
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let mut subscription = txc.input_stream().subscribe_guarded(|_| {}).unwrap();
+
+        let senders = (0..THREADS)
+            .map(|_| {
+                let sender = txc.sender();
+                std::thread::spawn(move || {
+                    for _ in 0..COMMANDS {
+                        unsafe { sender.send("<command id=\"server_status\"/>\0").unwrap() };
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        // the messages are delivered while the commands are sent and the handler is replaced
+        for i in 0..COMMANDS {
+            assert!(fake::deliver("<server_status connected=\"true\"/>"));
+            if i == COMMANDS / 2 {
+                subscription = txc.input_stream().subscribe_guarded(|_| {}).unwrap();
+            }
+        }
+        senders.into_iter().for_each(|sender| sender.join().unwrap());
+        drop(subscription);
+
+        let stats = txc.stats();
+        assert_eq!(stats.commands(), (THREADS * COMMANDS) as u64);
+        assert_eq!(stats.errors(), 0);
+        assert_eq!(stats.messages(), COMMANDS as u64);
+        drop(txc);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+
+    #[test]
+    fn place_returns_transaction_id() {
+        let (_library, module) = fake::load();
+        fake::respond_with(|cmd| {
+            cmd.starts_with("<command id=\"new")
+                .then(|| "<result success=\"true\" transactionid=\"4242\"/>".into())
+        });
+        let txc = TransaqConnector::from_module(module);
+        let sender = txc.sender();
+        let (price, quantity) = (Decimal::new(25050, 2), 10);
+
+        let order = PlaceOrderCommand::buy("TQBR", "SBER").client("C1").quantity(quantity);
+        let ack = sender.place(&order.clone().price(price)).unwrap();
+        assert_eq!(ack, SendAck { transactionid: 4242 });
+
+        let cond = CondOrderCommand::sell("TQBR", "SBER")
+            .client("C1")
+            .quantity(quantity)
+            .price(price)
+            .condition(Condition::LastUp(price));
+        assert_eq!(sender.place_cond(&cond).unwrap().transactionid, 4242);
+
+        let stoploss = StopLossParams::new(price).bymarket(true).quantity(quantity);
+        let stop = StopOrderCommand::sell("TQBR", "SBER").client("C1").stoploss(stoploss);
+        assert_eq!(sender.place_stop(&stop).unwrap().transactionid, 4242);
+
+        let commands = fake::commands();
+        assert_eq!(commands.len(), 3);
+        assert!(commands[0].starts_with("<command id=\"neworder\">"));
+        assert!(commands[1].starts_with("<command id=\"newcondorder\">"));
+        assert!(commands[2].starts_with("<command id=\"newstoporder\">"));
+
+        // invalid commands are not sent
+        let ret = sender.place(&order);
+        assert!(matches!(ret, Err(Error::InvalidCommand(_))), "{ret:?}");
+        assert_eq!(fake::commands().len(), 3);
+        drop(txc);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+
+    #[test]
+    fn place_rejected_or_without_transaction_id() {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+        let order = PlaceOrderCommand::buy("TQBR", "SBER").client("C1").quantity(1).bymarket(true);
+
+        // the default response lacks the transaction id
+        let ret = txc.sender().place(&order);
+        assert!(matches!(ret, Err(Error::Internal(_))), "{ret:?}");
+
+        fake::respond_with(|_| {
+            Some("<result success=\"false\"><message>rejected</message></result>".into())
+        });
+        match txc.sender().place(&order) {
+            Err(Error::InvalidCommand(msg)) => assert!(msg.message().contains("rejected"), "{msg}"),
+            ret => panic!("{ret:?}"),
+        }
+        drop(txc);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+
+    #[track_caller]
+    fn assert_initialization_error<T: fmt::Debug>(result: Result<T>, expected: &str) {
+        match result {
+            Err(Error::Initialization(msg)) => {
+                assert!(msg.message().starts_with(expected), "{msg}")
+            }
+            other => panic!("expected initialization error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn version_parse() {
+        assert_eq!(parse_version("6.19.2.21"), Some(vec![6, 19, 2, 21]));
+        assert_eq!(parse_version(" 6.19 "), Some(vec![6, 19]));
+        for invalid in ["", "6.x", "6..19", "a.b", "6.19.", "-6.19"] {
+            assert_eq!(parse_version(invalid), None, "{invalid:?}");
+            assert_initialization_error(parse_min_version(invalid), lang::INVALID_VERSION);
+        }
+    }
+
+    #[test]
+    fn version_compare() {
+        use std::cmp::Ordering;
+        let cmp = |a, b| compare_versions(&parse_version(a).unwrap(), &parse_version(b).unwrap());
+        assert_eq!(cmp("6.19", "6.19.0.0"), Ordering::Equal);
+        assert_eq!(cmp("6.19.0.0", "6.19"), Ordering::Equal);
+        assert_eq!(cmp("6.19.2.21", "6.19.2.21"), Ordering::Equal);
+        assert_eq!(cmp("6.19.2.21", "6.19.2.22"), Ordering::Less);
+        assert_eq!(cmp("6.19.2.21", "6.20"), Ordering::Less);
+        assert_eq!(cmp("6.19.2.21", "6.19"), Ordering::Greater);
+        assert_eq!(cmp("6.9", "6.19"), Ordering::Less);
+        assert_eq!(cmp("7", "6.99.99"), Ordering::Greater);
+    }
+
+    #[test]
+    fn require_version_compares_with_minimum() {
+        let (_library, module) = fake::load();
+        reply_version();
+        let mut txc = TransaqConnector::from_module(module);
+
+        for min_version in ["6.19", "6.19.0.0", "6.19.2.21", "5"] {
+            assert_eq!(txc.require_version(min_version).unwrap(), "6.19.2.21", "{min_version}");
+        }
+        for min_version in ["6.19.2.22", "6.20", "7"] {
+            let result = txc.require_version(min_version);
+            assert_initialization_error(result, lang::OUTDATED_VERSION);
+        }
+        // the version query doesn't leave a subscriber behind
+        assert_eq!(txc.subscriber_count(), 0);
+        drop(txc);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+
+    #[test]
+    fn require_version_rejects_invalid_versions() {
+        let (_library, module) = fake::load();
+        fake::reply_with(|cmd| {
+            cmd.contains("get_connector_version")
+                .then(|| "<connector_version>unknown</connector_version>".into())
+        });
+        let mut txc = TransaqConnector::from_module(module);
+
+        // rejected before the query
+        assert_initialization_error(txc.require_version("6.x"), lang::INVALID_VERSION);
+        assert!(fake::commands().is_empty());
+
+        assert_initialization_error(txc.require_version("6.19"), lang::UNEXPECTED_VERSION);
+        assert_eq!(fake::commands(), ["<command id=\"get_connector_version\"/>"]);
+    }
+
+    fn received(rx: &Receiver<OwnedMessage>) -> Vec<String> {
+        rx.try_iter().map(|msg| msg.to_string()).collect()
+    }
+
+    #[test]
+    fn input_channel_drop_newest() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let rx = txc.input_channel(2, OverflowPolicy::DropNewest).unwrap();
+
+        ["<a/>", "<b/>", "<c/>", "<d/>"].into_iter().for_each(|msg| assert!(fake::deliver(msg)));
+        assert_eq!(received(&rx), ["<a/>", "<b/>"]);
+        assert_eq!(rx.dropped(), 2);
+
+        assert!(fake::deliver("<e/>"));
+        assert_eq!(received(&rx), ["<e/>"]);
+    }
+
+    #[test]
+    fn input_channel_drop_oldest() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let rx = txc.input_channel(2, OverflowPolicy::DropOldest).unwrap();
+
+        ["<a/>", "<b/>", "<c/>", "<d/>"].into_iter().for_each(|msg| assert!(fake::deliver(msg)));
+        assert_eq!(received(&rx), ["<c/>", "<d/>"]);
+        assert_eq!(rx.dropped(), 2);
+    }
+
+    #[test]
+    fn input_channel_block() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let rx = txc.input_channel(2, OverflowPolicy::Block).unwrap();
+
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let delivery = {
+            let delivered = Arc::clone(&delivered);
+            std::thread::spawn(move || {
+                for msg in ["<a/>", "<b/>", "<c/>", "<d/>"] {
+                    fake::deliver(msg);
+                    delivered.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        // the connector thread waits for the receiver on the third message
+        while rx.len() < 2 {
+            std::thread::yield_now();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(delivered.load(Ordering::SeqCst), 2);
+
+        let all = rx.iter().take(4).map(|msg| msg.to_string()).collect::<Vec<_>>();
+        delivery.join().unwrap();
+        assert_eq!(all, ["<a/>", "<b/>", "<c/>", "<d/>"]);
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[test]
+    fn input_channel_closed_on_replacement() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let rx = txc.input_channel(4, OverflowPolicy::Block).unwrap();
+        assert!(fake::deliver("<a/>"));
+
+        txc.input_stream().subscribe(|_| {}).unwrap();
+        assert_eq!(rx.iter().map(|msg| msg.to_string()).collect::<Vec<_>>(), ["<a/>"]);
+        assert!(matches!(rx.try_recv(), Err(mpsc::TryRecvError::Disconnected)));
+    }
+
+    #[test]
+    fn send_reports_error_text() {
+        use std::error::Error as _;
+
+        let (_library, module) = fake::load();
+        fake::respond_with(|_| Some("<error>Соединение не установлено</error>".into()));
+        let txc = TransaqConnector::from_module(module);
+
+        match unsafe { txc.sender().send("<command id=\"server_status\"/>\0") } {
+            Err(err @ Error::Internal(_)) => {
+                assert_eq!(
+                    err.to_string(),
+                    format!("{}\nСоединение не установлено", lang::ERR_INTERNAL)
+                );
+                assert_eq!(err.source().unwrap().to_string(), "Соединение не установлено");
+            }
+            ret => panic!("{ret:?}"),
+        }
+        drop(txc);
+        assert_eq!(fake::live_buffers(), 0);
+    }
+}
@@ -0,0 +1,236 @@
+use super::{
+    buffers::{alloc_buffer, free_buffer, parse_send_response},
+    stream::{Stream, Subscription},
+    Result, TCStr,
+};
+use std::{
+    fmt,
+    ptr::NonNull,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+const DEFAULT_RESPONSE: &str = "<result success=\"true\"/>";
+
+type Subscriber = Box<dyn FnMut(NonNull<u8>) + Send>;
+type Responder = Box<dyn FnMut(&str) -> String + Send>;
+
+/// Имитация коннектора для тестирования без библиотеки Transaq XML Connector
+///
+/// Повторяет интерфейс [`TransaqConnector::input_stream`](crate::TransaqConnector::input_stream)
+/// и [`TransaqConnector::sender`](crate::TransaqConnector::sender): входящие сообщения
+/// передаются в обработчик вызовом [`MockConnector::push_message`] в текущем потоке, а
+/// отправленные команды сохраняются и доступны через [`MockConnector::sent_commands`].
+///
+/// Требует опции **testing**.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{MockConnector, Stream};
+/// use std::sync::mpsc;
+///
+/// let mut txc = MockConnector::new();
+/// let (tx, rx) = mpsc::channel();
+/// txc.input_stream()
+///     .filter(|buf| buf.xml_tag_name() == Some(b"server_status"))
+///     .subscribe(move |buf| tx.send(buf.to_owned_message()).unwrap())?;
+///
+/// unsafe { txc.sender().send("<command id=\"server_status\"/>\0")? };
+/// txc.push_message("<server_status connected=\"true\"/>");
+///
+/// assert_eq!(txc.sent_commands(), ["<command id=\"server_status\"/>"]);
+/// assert!(rx.try_recv().is_ok());
+/// ```
+#[derive(Clone, Default)]
+pub struct MockConnector(Arc<MockState>);
+
+#[derive(Default)]
+struct MockState {
+    subscriber: Mutex<SubscriberSlot>,
+    sent: Mutex<Vec<String>>,
+    responder: Mutex<Option<Responder>>,
+}
+
+#[derive(Default)]
+struct SubscriberSlot {
+    generation: u64,
+    subscriber: Option<Subscriber>,
+}
+
+impl MockConnector {
+    /// Создаёт коннектор, отвечающий `<result success="true"/>` на все команды
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Создаёт [`Stream`] для компоновки конвейера обработки входящих сообщений
+    ///
+    /// Как и в [`TransaqConnector`](crate::TransaqConnector), повторная подписка заменяет
+    /// текущий обработчик.
+    pub fn input_stream(&mut self) -> impl Stream<Output = TCStr<'_>> + '_ {
+        MockInput(Arc::clone(&self.0)).map(|ptr| TCStr::new(ptr, free_buffer))
+    }
+
+    /// Создаёт канал для отправки команд
+    pub fn sender(&self) -> MockSender {
+        MockSender(Arc::clone(&self.0))
+    }
+
+    /// Передаёт сообщение **msg** в обработчик входящих сообщений
+    ///
+    /// Обработчик вызывается в текущем потоке; если обработчик не установлен, сообщение
+    /// отбрасывается.
+    ///
+    /// # Panics
+    /// Если сообщение содержит нулевой байт
+    pub fn push_message(&self, msg: &str) {
+        let ptr = alloc_buffer(msg.as_bytes());
+        match self.0.subscriber().subscriber.as_mut() {
+            Some(subscriber) => subscriber(ptr),
+            None => drop(TCStr::new(ptr, free_buffer)),
+        }
+    }
+
+    /// Отправленные команды в порядке отправки, без завершающего нулевого байта
+    pub fn sent_commands(&self) -> Vec<String> {
+        lock(&self.0.sent).clone()
+    }
+
+    /// Устанавливает функцию, формирующую ответ коннектора на команду
+    ///
+    /// Ответ разбирается так же, как ответ коннектора, см. [`Sender::send`](crate::Sender::send):
+    /// например `<result success="false">..</result>` приводит к ошибке
+    /// [`Error::InvalidCommand`](crate::Error::InvalidCommand). Без опции **safe_buffers** ответ
+    /// не проверяется и, как и ответ коннектора, должен быть не короче `<result success="true"/>`.
+    pub fn respond_with<F>(&self, f: F)
+    where
+        F: FnMut(&str) -> String + Send + 'static,
+    {
+        *lock(&self.0.responder) = Some(Box::new(f));
+    }
+}
+
+impl MockState {
+    fn subscriber(&self) -> MutexGuard<'_, SubscriberSlot> {
+        lock(&self.subscriber)
+    }
+}
+
+/// Канал отправки команд [`MockConnector`], повторяет интерфейс [`Sender`](crate::Sender)
+#[derive(Clone)]
+pub struct MockSender(Arc<MockState>);
+
+impl MockSender {
+    /// Сохраняет команду и возвращает ответ, см. [`MockConnector::respond_with`]
+    ///
+    /// # Safety
+    /// Не требует дополнительных условий, `unsafe` сохранён для совместимости с
+    /// [`Sender::send`](crate::Sender::send)
+    ///
+    /// # Errors
+    /// Ошибки разбора ответа, см. [`Sender::send`](crate::Sender::send)
+    pub unsafe fn send<B: AsRef<[u8]>>(&self, buf: B) -> Result<TCStr<'_>> {
+        let buf = buf.as_ref();
+        let cmd = buf.iter().position(|b| *b == b'\0').map_or(buf, |end| &buf[..end]);
+        let cmd = String::from_utf8_lossy(cmd).into_owned();
+
+        let response = match lock(&self.0.responder).as_mut() {
+            Some(responder) => responder(&cmd),
+            None => DEFAULT_RESPONSE.to_string(),
+        };
+        lock(&self.0.sent).push(cmd);
+
+        parse_send_response(TCStr::new(alloc_buffer(response.as_bytes()), free_buffer))
+    }
+}
+
+struct MockInput(Arc<MockState>);
+
+impl Stream for MockInput {
+    type Output = NonNull<u8>;
+
+    fn subscribe_guarded<F: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: F,
+    ) -> Result<Subscription> {
+        let mut slot = self.0.subscriber();
+        slot.generation += 1;
+        let generation = slot.generation;
+        let previous = slot.subscriber.replace(Box::new(f));
+        drop(slot);
+        drop(previous);
+
+        let weak = Arc::downgrade(&self.0);
+        Ok(Subscription::new(move || {
+            if let Some(state) = weak.upgrade() {
+                let mut slot = state.subscriber();
+                let released =
+                    if slot.generation == generation { slot.subscriber.take() } else { None };
+                drop(slot);
+                drop(released);
+            }
+        }))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl fmt::Debug for MockConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockConnector")
+            .field("subscribed", &self.0.subscriber().subscriber.is_some())
+            .field("sent", &lock(&self.0.sent).len())
+            .finish()
+    }
+}
+
+impl fmt::Debug for MockSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockSender").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn on_drop_counter() -> (Arc<AtomicUsize>, impl FnOnce() + Send + 'static) {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&dropped);
+        (dropped, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    #[test]
+    fn on_drop_runs_when_connector_is_dropped() {
+        let mut txc = MockConnector::new();
+        let (dropped, on_drop) = on_drop_counter();
+        txc.input_stream().on_drop(on_drop).subscribe(|_| {}).unwrap();
+
+        txc.push_message("<quotes/>");
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        drop(txc);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn on_drop_runs_once_on_release_or_replacement() {
+        let mut txc = MockConnector::new();
+        let (dropped, on_drop) = on_drop_counter();
+        let subscription = txc.input_stream().on_drop(on_drop).subscribe_guarded(|_| {}).unwrap();
+        drop(subscription);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+
+        let (replaced, on_drop) = on_drop_counter();
+        txc.input_stream().on_drop(on_drop).subscribe(|_| {}).unwrap();
+        txc.input_stream().subscribe(|_| {}).unwrap();
+        assert_eq!(replaced.load(Ordering::SeqCst), 1);
+
+        drop(txc);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        assert_eq!(replaced.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,164 @@
+use super::{lang, Error, NewsBody, NewsHeader, Result, Sender};
+use std::{
+    convert::TryFrom,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Загрузка текста новостей
+///
+/// Отправляет команду `get_news_body` и ожидает сообщение `<news_body>` с тем же
+/// идентификатором новости. Установленный обработчик входящих сообщений продолжает получать все
+/// сообщения, в том числе `<news_body>`.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{Message, NewsClient, Stream};
+/// use std::time::Duration;
+///
+/// let mut txc = /*..*/;
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// txc.input_stream().messages().subscribe(move |msg| {
+///     if let Message::NewsHeader(header) = msg {
+///         let _ = tx.send(header);
+///     }
+/// })?;
+///
+/// let news = NewsClient::new(txc.sender()).timeout(Duration::from_secs(5));
+/// for header in rx {
+///     let body = news.body(&header)?;
+///     println!("{}\n{}", header.title, body.text);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct NewsClient {
+    sender: Sender,
+    timeout: Duration,
+}
+
+impl NewsClient {
+    /// Создаёт загрузчик, отправляющий команды через **sender**, с ожиданием ответа не более
+    /// 10 сек.
+    pub fn new(sender: Sender) -> Self {
+        Self { sender, timeout: DEFAULT_TIMEOUT }
+    }
+
+    /// Максимальное время ожидания сообщения `<news_body>`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Загружает текст новости **header**, см. [`NewsClient::body_by_id`]
+    ///
+    /// # Errors
+    /// См. [`NewsClient::body_by_id`]
+    pub fn body(&self, header: &NewsHeader) -> Result<NewsBody> {
+        self.body_by_id(header.id)
+    }
+
+    /// Загружает текст новости с идентификатором **id**
+    ///
+    /// Сообщения коннектора доставляются только при установленном обработчике входящих
+    /// сообщений, см. [`TransaqConnector::input_stream`](crate::TransaqConnector::input_stream).
+    /// Вызов блокирует поток до получения ответа, поэтому не должен выполняться из обработчика.
+    ///
+    /// # Errors
+    /// - [`Error::Callback`] - обработчик входящих сообщений не установлен
+    /// - [`Error::Timeout`] - ответ не поступил в течение **timeout**
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    pub fn body_by_id(&self, id: u64) -> Result<NewsBody> {
+        let inner = &self.sender.0;
+        if inner.callback_slot().callback.is_none() {
+            return Err(Error::Callback(lang::NO_SUBSCRIBER.into()));
+        }
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let tap = inner.taps.insert(Box::new(move |buf| {
+            buf.xml_tag_name() == Some(b"news_body")
+                && match NewsBody::try_from(buf) {
+                    Ok(body) if body.id == id => {
+                        let _ = tx.try_send(body);
+                        true
+                    }
+                    _ => false,
+                }
+        }));
+
+        let cmd = format!("<command id=\"get_news_body\" news_id=\"{id}\"/>\0");
+        let sent = unsafe { self.sender.send(cmd).map(drop) };
+        let ret = sent.and_then(|_| {
+            rx.recv_timeout(self.timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => Error::Timeout,
+                RecvTimeoutError::Disconnected => Error::Internal(e.to_string().into()),
+            })
+        });
+        inner.taps.remove(tap);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi::fake, Stream, TransaqConnector};
+    use std::sync::{Arc, Mutex};
+
+    fn news_id(cmd: &str) -> &str {
+        let start = cmd.find("news_id=\"").unwrap() + 9;
+        &cmd[start..start + cmd[start..].find('"').unwrap()]
+    }
+
+    #[test]
+    fn body_round_trip() {
+        let (library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        let tags = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&tags);
+        txc.input_stream()
+            .subscribe(move |buf| seen.lock().unwrap().push(buf.xml_tag_name().unwrap().to_vec()))
+            .unwrap();
+        fake::reply_with(|cmd| {
+            Some(format!(
+                "<news_body><id>{}</id><text><![CDATA[<p>Совет директоров</p>]]></text></news_body>",
+                news_id(cmd)
+            ))
+        });
+
+        let news = NewsClient::new(txc.sender()).timeout(Duration::from_secs(5));
+        let header = NewsHeader { id: 42, time: None, source: String::new(), title: String::new() };
+        let body = news.body(&header).unwrap();
+        assert_eq!(body, NewsBody { id: 42, text: "<p>Совет директоров</p>".into() });
+        assert_eq!(fake::commands(), ["<command id=\"get_news_body\" news_id=\"42\"/>"]);
+
+        // the handler receives the body as well, after `body` has returned
+        drop(library);
+        assert_eq!(*tags.lock().unwrap(), [b"news_body".to_vec()]);
+    }
+
+    #[test]
+    fn body_of_other_news_is_skipped() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        txc.input_stream().subscribe(|_| {}).unwrap();
+        fake::reply_with(|_| Some("<news_body><id>7</id><text>other</text></news_body>".into()));
+
+        let news = NewsClient::new(txc.sender()).timeout(Duration::from_millis(50));
+        assert!(matches!(news.body_by_id(8), Err(Error::Timeout)));
+    }
+
+    #[test]
+    fn body_requires_subscriber() {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+
+        let news = NewsClient::new(txc.sender());
+        match news.body_by_id(1) {
+            Err(Error::Callback(msg)) => assert_eq!(msg.message(), lang::NO_SUBSCRIBER),
+            ret => panic!("{ret:?}"),
+        }
+        assert!(fake::commands().is_empty());
+    }
+}
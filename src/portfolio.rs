@@ -0,0 +1,264 @@
+use super::{
+    ClientInfo, Decimal, FortsMoney, FortsPosition, Message, MoneyPosition, PositionsResponse,
+    SecPosition, UnitedLimits,
+};
+use std::collections::HashMap;
+
+// FORTS money is always denominated in rubles
+const FORTS_CURRENCY: &str = "RUB";
+
+/// Состояние портфеля, собранное из сообщений `<positions>`, `<united_limits>` и `<client>`
+///
+/// Коннектор передаёт только изменившиеся позиции, состояние заменяет сохранённую позицию
+/// полученной позицией того же клиента, счёта и инструмента(вида средств).
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{PortfolioState, Stream};
+/// use std::sync::{Arc, Mutex};
+///
+/// let mut txc = /*..*/;
+/// let portfolio = Arc::new(Mutex::new(PortfolioState::new()));
+/// let shared = Arc::clone(&portfolio);
+/// txc.input_stream().messages().subscribe(move |msg| shared.lock().unwrap().apply(msg))?;
+/// // ..
+/// let portfolio = portfolio.lock().unwrap();
+/// let (cash, sber) = (portfolio.cash("RUB"), portfolio.position("SBER"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PortfolioState {
+    // (client, union, register, asset)
+    money: HashMap<(String, String, String, String), MoneyPosition>,
+    // (client, union, register, seccode)
+    securities: HashMap<(String, String, String, String), SecPosition>,
+    // (client, union, seccode)
+    forts: HashMap<(String, String, String), FortsPosition>,
+    // (client, union)
+    forts_money: HashMap<(String, String), FortsMoney>,
+    united_limits: HashMap<String, UnitedLimits>,
+    clients: HashMap<String, ClientInfo>,
+}
+
+impl PortfolioState {
+    /// Создаёт пустое состояние
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Применяет сообщение `<positions>`, `<united_limits>` или `<client>`, остальные сообщения
+    /// не учитываются
+    pub fn apply(&mut self, message: Message) {
+        match message {
+            Message::Positions(positions) => self.apply_positions(positions),
+            Message::UnitedLimits(limits) => self.apply_united_limits(limits),
+            Message::Client(client) => self.apply_client(client),
+            _ => {}
+        }
+    }
+
+    /// Применяет сообщение `<positions>`
+    pub fn apply_positions(&mut self, positions: PositionsResponse) {
+        for p in positions.money {
+            let key = (p.client.clone(), p.union.clone(), p.register.clone(), p.asset.clone());
+            self.money.insert(key, p);
+        }
+        for p in positions.securities {
+            let key = (p.client.clone(), p.union.clone(), p.register.clone(), p.seccode.clone());
+            self.securities.insert(key, p);
+        }
+        for p in positions.forts {
+            self.forts.insert((p.client.clone(), p.union.clone(), p.seccode.clone()), p);
+        }
+        for m in positions.forts_money {
+            self.forts_money.insert((m.client.clone(), m.union.clone()), m);
+        }
+    }
+
+    /// Применяет сообщение `<united_limits>`
+    pub fn apply_united_limits(&mut self, limits: UnitedLimits) {
+        self.united_limits.insert(limits.union.clone(), limits);
+    }
+
+    /// Применяет сообщение `<client>`, удалённый счёт исключается из состояния вместе с его
+    /// позициями
+    pub fn apply_client(&mut self, client: ClientInfo) {
+        if client.remove {
+            let id = &client.id;
+            self.money.retain(|(c, ..), _| c != id);
+            self.securities.retain(|(c, ..), _| c != id);
+            self.forts.retain(|(c, ..), _| c != id);
+            self.forts_money.retain(|(c, _), _| c != id);
+            self.clients.remove(id);
+        } else {
+            self.clients.insert(client.id.clone(), client);
+        }
+    }
+
+    /// Текущий остаток денежных средств вида **currency** по всем клиентам
+    ///
+    /// Учитываются денежные позиции с кодом вида средств **currency** и, для `"RUB"`, текущие
+    /// средства на срочном рынке FORTS. `None`, если таких позиций нет.
+    pub fn cash(&self, currency: &str) -> Option<Decimal> {
+        let money = self.money.values().filter(|p| p.asset == currency).map(|p| p.saldo);
+        let forts =
+            self.forts_money.values().filter(|_| currency == FORTS_CURRENCY).map(|m| m.current);
+        sum(money.chain(forts))
+    }
+
+    /// Текущая позиция по инструменту **seccode** по всем клиентам: остаток бумаг в штуках и
+    /// позиция по контрактам FORTS. `None`, если позиций по инструменту нет.
+    pub fn position(&self, seccode: &str) -> Option<Decimal> {
+        let securities = self.securities.values().filter(|p| p.seccode == seccode).map(|p| p.saldo);
+        let forts = self.forts.values().filter(|p| p.seccode == seccode).map(|p| p.totalnet);
+        sum(securities.chain(forts))
+    }
+
+    /// Текущая оценка стоимости портфеля по всем единым денежным счетам. `None`, если сообщения
+    /// `<united_limits>` не поступали.
+    pub fn equity(&self) -> Option<Decimal> {
+        sum(self.united_limits.values().map(|l| l.equity))
+    }
+
+    /// Денежные позиции в произвольном порядке
+    pub fn money_positions(&self) -> impl Iterator<Item = &MoneyPosition> {
+        self.money.values()
+    }
+
+    /// Позиции по бумагам в произвольном порядке
+    pub fn sec_positions(&self) -> impl Iterator<Item = &SecPosition> {
+        self.securities.values()
+    }
+
+    /// Позиции по контрактам FORTS в произвольном порядке
+    pub fn forts_positions(&self) -> impl Iterator<Item = &FortsPosition> {
+        self.forts.values()
+    }
+
+    /// Деньги на срочном рынке FORTS в произвольном порядке
+    pub fn forts_money(&self) -> impl Iterator<Item = &FortsMoney> {
+        self.forts_money.values()
+    }
+
+    /// Единый денежный счёт **union**
+    pub fn united_limits(&self, union: &str) -> Option<&UnitedLimits> {
+        self.united_limits.get(union)
+    }
+
+    /// Клиентский счёт **id**
+    pub fn client(&self, id: &str) -> Option<&ClientInfo> {
+        self.clients.get(id)
+    }
+}
+
+// `None` for no values, absent values are skipped
+fn sum<I: Iterator<Item = Option<Decimal>>>(values: I) -> Option<Decimal> {
+    values.flatten().fold(None, |acc, v| Some(acc.unwrap_or_default() + v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn apply(portfolio: &mut PortfolioState, msg: &str) {
+        portfolio.apply(Message::parse_bytes(msg.as_bytes()));
+    }
+
+    // stock and FORTS accounts of the same union
+    fn portfolio() -> PortfolioState {
+        let mut portfolio = PortfolioState::new();
+        apply(
+            &mut portfolio,
+            "<client id=\"virt/9975\"><type>mct</type><currency>RUB</currency><market>1</market>\
+             <union>508437</union></client>",
+        );
+        apply(
+            &mut portfolio,
+            "<positions><money_position><asset>RUB</asset><client>virt/9975</client>\
+             <union>508437</union><register>T0</register><saldo>73455.73</saldo></money_position>\
+             <sec_position><seccode>SBER</seccode><register>T0</register><client>virt/9975</client>\
+             <union>508437</union><saldo>100</saldo></sec_position></positions>",
+        );
+        apply(
+            &mut portfolio,
+            "<positions><forts_position><seccode>SiZ6</seccode><client>7600abc</client>\
+             <union>508437</union><totalnet>-3</totalnet></forts_position>\
+             <forts_money><client>7600abc</client><union>508437</union>\
+             <current>50000.00</current></forts_money></positions>",
+        );
+        apply(
+            &mut portfolio,
+            "<united_limits union=\"508437\"><equity>149847.50</equity></united_limits>",
+        );
+        portfolio
+    }
+
+    #[test]
+    fn stock_and_forts_accounts() {
+        let portfolio = portfolio();
+        assert_eq!(portfolio.cash("RUB"), Some(dec("123455.73")));
+        assert_eq!(portfolio.cash("USD"), None);
+        assert_eq!(portfolio.position("SBER"), Some(dec("100")));
+        assert_eq!(portfolio.position("SiZ6"), Some(dec("-3")));
+        assert_eq!(portfolio.position("GAZP"), None);
+        assert_eq!(portfolio.equity(), Some(dec("149847.50")));
+
+        assert_eq!(portfolio.client("virt/9975").unwrap().union, "508437");
+        assert_eq!(portfolio.united_limits("508437").unwrap().equity, Some(dec("149847.50")));
+        assert_eq!(portfolio.money_positions().count(), 1);
+        assert_eq!(portfolio.sec_positions().count(), 1);
+        assert_eq!(portfolio.forts_positions().count(), 1);
+        assert_eq!(portfolio.forts_money().count(), 1);
+    }
+
+    #[test]
+    fn positions_are_replaced() {
+        let mut portfolio = portfolio();
+        apply(
+            &mut portfolio,
+            "<positions><sec_position><seccode>SBER</seccode><register>T0</register>\
+             <client>virt/9975</client><union>508437</union><saldo>40</saldo></sec_position>\
+             <sec_position><seccode>SBER</seccode><register>T2</register>\
+             <client>virt/9975</client><union>508437</union><saldo>10</saldo></sec_position>\
+             </positions>",
+        );
+        // T0 is replaced, T2 is a separate register
+        assert_eq!(portfolio.position("SBER"), Some(dec("50")));
+
+        // the position without the current value is kept but not counted
+        apply(
+            &mut portfolio,
+            "<positions><forts_position><seccode>SiZ6</seccode><client>7600abc</client>\
+             <union>508437</union></forts_position></positions>",
+        );
+        assert_eq!(portfolio.position("SiZ6"), None);
+        assert_eq!(portfolio.forts_positions().count(), 1);
+    }
+
+    #[test]
+    fn removed_client() {
+        let mut portfolio = portfolio();
+        apply(&mut portfolio, "<client id=\"virt/9975\" remove=\"true\"/>");
+
+        assert!(portfolio.client("virt/9975").is_none());
+        assert_eq!(portfolio.position("SBER"), None);
+        assert_eq!(portfolio.cash("RUB"), Some(dec("50000.00")));
+        assert_eq!(portfolio.position("SiZ6"), Some(dec("-3")));
+    }
+
+    #[test]
+    fn other_messages_are_ignored() {
+        let mut portfolio = PortfolioState::new();
+        apply(&mut portfolio, "<server_status connected=\"true\"/>");
+        apply(
+            &mut portfolio,
+            "<positions><money_position><saldo>x</saldo></money_position></positions>",
+        );
+        assert_eq!(portfolio.cash("RUB"), None);
+        assert_eq!(portfolio.equity(), None);
+        assert_eq!(portfolio.money_positions().count(), 0);
+    }
+}
@@ -0,0 +1,197 @@
+use super::{lang, Error, Result, Sender, TCStr};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Отправитель команд с ограничением частоты
+///
+/// Ограничивает отправку команд не более чем **rate** команд за период **per** по алгоритму
+/// *token bucket*: в начале работы доступно **rate** команд, далее возможность отправки
+/// восстанавливается равномерно в течение периода. Клоны `RateLimitedSender` разделяют общее
+/// ограничение.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::RateLimitedSender;
+/// use std::time::Duration;
+///
+/// let txc = /*..*/;
+/// let sender = RateLimitedSender::new(txc.sender(), 5, Duration::from_secs(1));
+/// for cmd in commands {
+///     // не более 5 команд в секунду
+///     let result = unsafe { sender.send(cmd)? };
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RateLimitedSender {
+    sender: Sender,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    // tokens per second
+    refill_rate: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u32, per: Duration, now: Instant) -> Self {
+        let capacity = f64::from(rate);
+        Self {
+            tokens: capacity,
+            capacity,
+            refill_rate: capacity / per.as_secs_f64(),
+            last_refill: now,
+        }
+    }
+
+    // Takes the token if one is available, otherwise returns the time until it is
+    fn acquire(&mut self, now: Instant) -> std::result::Result<(), Duration> {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_rate))
+        }
+    }
+}
+
+impl RateLimitedSender {
+    /// Создаёт отправитель, ограниченный **rate** командами за период **per**
+    ///
+    /// # Panics
+    /// Если **rate** или **per** равны нулю
+    pub fn new(sender: Sender, rate: u32, per: Duration) -> Self {
+        assert!(rate > 0, "{}", lang::ZERO_RATE);
+        assert!(!per.is_zero(), "{}", lang::ZERO_PERIOD);
+
+        let bucket = Bucket::new(rate, per, Instant::now());
+        Self { sender, bucket: Arc::new(Mutex::new(bucket)) }
+    }
+
+    /// Передаёт данные коннектору, ожидая возможности отправки при превышении ограничения
+    ///
+    /// См. [`Sender::send`].
+    ///
+    /// # Safety
+    /// См. [`Sender::send`]
+    pub unsafe fn send<B: AsRef<[u8]>>(&self, buf: B) -> Result<TCStr<'_>> {
+        while let Err(wait) = self.acquire() {
+            thread::sleep(wait);
+        }
+        self.sender.send(buf)
+    }
+
+    /// Передаёт данные коннектору, если ограничение не превышено
+    ///
+    /// См. [`Sender::send`].
+    ///
+    /// # Errors
+    /// - [`Error::RateLimitExceeded`] - ограничение частоты отправки превышено, команда не
+    ///   отправлена
+    /// - ошибки отправки команды, см. [`Sender::send`]
+    ///
+    /// # Safety
+    /// См. [`Sender::send`]
+    pub unsafe fn send_nonblocking<B: AsRef<[u8]>>(&self, buf: B) -> Result<TCStr<'_>> {
+        self.acquire().map_err(|_| Error::RateLimitExceeded)?;
+        self.sender.send(buf)
+    }
+
+    /// Отправитель без ограничения частоты
+    pub fn sender(&self) -> &Sender {
+        &self.sender
+    }
+
+    #[inline]
+    fn acquire(&self) -> std::result::Result<(), Duration> {
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner()).acquire(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi::fake, TransaqConnector};
+
+    // Sends `n` commands through the bucket, advancing the clock by the requested waits
+    fn simulate(bucket: &mut Bucket, start: Instant, n: usize) -> Vec<Duration> {
+        let mut now = start;
+        let mut waits = Vec::with_capacity(n);
+        for _ in 0..n {
+            let before = now;
+            while let Err(wait) = bucket.acquire(now) {
+                now += wait;
+            }
+            waits.push(now - before);
+        }
+        waits
+    }
+
+    fn approx(actual: Duration, expected: Duration) -> bool {
+        let diff = actual.as_secs_f64() - expected.as_secs_f64();
+        diff.abs() < 1e-3
+    }
+
+    #[test]
+    fn bucket_waits() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(5, Duration::from_secs(1), start);
+        let waits = simulate(&mut bucket, start, 20);
+
+        // the first `rate` commands are sent immediately, the rest are spread over the period
+        assert!(waits[..5].iter().all(Duration::is_zero), "{waits:?}");
+        assert!(waits[5..].iter().all(|w| approx(*w, Duration::from_millis(200))), "{waits:?}");
+        assert!(approx(waits.iter().sum(), Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn bucket_refills_up_to_capacity() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(5, Duration::from_secs(1), start);
+        assert!(simulate(&mut bucket, start, 5).iter().all(Duration::is_zero));
+
+        // an idle period longer than `per` doesn't accumulate more than `rate` tokens
+        let later = start + Duration::from_secs(10);
+        let waits = simulate(&mut bucket, later, 6);
+        assert!(waits[..5].iter().all(Duration::is_zero), "{waits:?}");
+        assert!(approx(waits[5], Duration::from_millis(200)), "{waits:?}");
+    }
+
+    #[test]
+    fn send_waits_for_tokens() {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+        let sender = RateLimitedSender::new(txc.sender(), 5, Duration::from_millis(200));
+
+        let start = Instant::now();
+        for _ in 0..20 {
+            unsafe { sender.send("<command id=\"server_status\"/>\0").unwrap() };
+        }
+        assert!(start.elapsed() >= Duration::from_millis(600), "{:?}", start.elapsed());
+        assert_eq!(fake::commands().len(), 20);
+    }
+
+    #[test]
+    fn send_nonblocking_exceeds_limit() {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+        let sender = RateLimitedSender::new(txc.sender(), 3, Duration::from_secs(60));
+
+        for _ in 0..3 {
+            unsafe { sender.send_nonblocking("<command id=\"server_status\"/>\0").unwrap() };
+        }
+        let clone = sender.clone();
+        let result = unsafe { clone.send_nonblocking("<command id=\"server_status\"/>\0") };
+        assert!(matches!(result, Err(Error::RateLimitExceeded)));
+        assert_eq!(fake::commands().len(), 3);
+    }
+}
@@ -0,0 +1,322 @@
+use super::{
+    command_buffer, stream::Stream, ConnectionStatus, Result, Sender, ServerStatusResponse,
+    TransaqConnector,
+};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvError, RecvTimeoutError},
+        Arc,
+    },
+    time::Duration,
+};
+
+// the delay before the first reconnect attempt, doubles with every following attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Коннектор с автоматическим восстановлением подключения
+///
+/// Отслеживает сообщения `<server_status connected="false"/>` и `connected="error"` и повторно
+/// отправляет команду подключения с экспоненциально растущей задержкой: 1 сек., 2 сек., 4 сек. и
+/// т.д., но не более **max_backoff**. Задержка сбрасывается после получения
+/// `<server_status connected="true"/>`.
+///
+/// Отключение командой [`AutoReconnectConnector::disconnect`] не приводит к повторному
+/// подключению до явного вызова [`AutoReconnectConnector::connect`].
+///
+/// Подключение отслеживается в фоновом потоке, независимо от установленного обработчика входящих
+/// сообщений; если обработчик не установлен, устанавливается пустой. Доступ к методам
+/// [`TransaqConnector`] осуществляется через [`Deref`].
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{AutoReconnectConnector, ConnectCommand, Stream};
+/// use std::time::Duration;
+///
+/// let txc = /*..*/;
+/// let connect = ConnectCommand::new().login(login).password(password).host(host).port(port).build();
+///
+/// let mut txc = AutoReconnectConnector::new(txc, connect, Duration::from_secs(60))?;
+/// txc.input_stream().subscribe(|buf| println!("{buf}"))?;
+/// txc.connect()?;
+/// ```
+pub struct AutoReconnectConnector {
+    txc: TransaqConnector,
+    connect_cmd: Arc<[u8]>,
+    state: Arc<ReconnectState>,
+    tap: u64,
+}
+
+struct ReconnectState {
+    intentional: AtomicBool,
+    attempts: AtomicU64,
+}
+
+impl AutoReconnectConnector {
+    /// Создаёт коннектор с командой подключения **connect_cmd** и максимальной задержкой
+    /// повторного подключения **max_backoff**
+    ///
+    /// Завершающий нулевой байт добавляется к команде при необходимости.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidCommand`](crate::Error::InvalidCommand) - не валидная команда
+    /// - [`Error::Callback`](crate::Error::Callback) - не удалось установить пустой обработчик
+    pub fn new<B: Into<Vec<u8>>>(
+        mut txc: TransaqConnector,
+        connect_cmd: B,
+        max_backoff: Duration,
+    ) -> Result<Self> {
+        let connect_cmd: Arc<[u8]> = command_buffer(&connect_cmd.into())?.into_owned().into();
+        if !txc.has_subscriber() {
+            txc.input_stream().subscribe(|_| {})?;
+        }
+
+        let state = Arc::new(ReconnectState {
+            intentional: AtomicBool::new(false),
+            attempts: AtomicU64::new(0),
+        });
+
+        let (tx, rx) = mpsc::channel();
+        let tap = txc.0.taps.insert(Box::new(move |buf| {
+            if buf.xml_tag_name() == Some(b"server_status") {
+                if let Ok(status) = ServerStatusResponse::try_from(buf) {
+                    let _ = tx.send(status.connected == ConnectionStatus::True);
+                }
+            }
+            false
+        }));
+
+        let monitor = Monitor {
+            sender: txc.sender(),
+            connect_cmd: Arc::clone(&connect_cmd),
+            state: Arc::clone(&state),
+            max_backoff,
+        };
+        std::thread::Builder::new()
+            .name("txc-reconnect".into())
+            .spawn(move || monitor.run(rx, |rx, delay| rx.recv_timeout(delay)))
+            .map_err(|e| crate::Error::Internal(e.to_string().into()))?;
+
+        Ok(Self { txc, connect_cmd, state, tap })
+    }
+
+    /// Отправляет команду подключения и возобновляет автоматическое восстановление подключения
+    ///
+    /// # Errors
+    /// Ошибки отправки команды, см. [`Sender::send`]
+    pub fn connect(&self) -> Result {
+        self.state.intentional.store(false, Ordering::Release);
+        unsafe { self.txc.sender().send(&self.connect_cmd).map(drop) }
+    }
+
+    /// Отправляет команду `disconnect`, отключение не приводит к повторному подключению
+    ///
+    /// # Errors
+    /// Ошибки отправки команды, см. [`Sender::send`]
+    pub fn disconnect(&self) -> Result {
+        self.state.intentional.store(true, Ordering::Release);
+        unsafe { self.txc.sender().send("<command id=\"disconnect\"/>\0").map(drop) }
+    }
+
+    /// Количество попыток повторного подключения
+    pub fn reconnect_attempts(&self) -> u64 {
+        self.state.attempts.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for AutoReconnectConnector {
+    fn drop(&mut self) {
+        // drops the status channel and the monitor thread exits
+        self.txc.0.taps.remove(self.tap);
+    }
+}
+
+impl Deref for AutoReconnectConnector {
+    type Target = TransaqConnector;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txc
+    }
+}
+
+impl DerefMut for AutoReconnectConnector {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.txc
+    }
+}
+
+impl fmt::Debug for AutoReconnectConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoReconnectConnector")
+            .field("intentional_disconnect", &self.state.intentional.load(Ordering::Relaxed))
+            .field("reconnect_attempts", &self.reconnect_attempts())
+            .finish()
+    }
+}
+
+struct Monitor {
+    sender: Sender,
+    connect_cmd: Arc<[u8]>,
+    state: Arc<ReconnectState>,
+    max_backoff: Duration,
+}
+
+impl Monitor {
+    // `wait` receives the next status or times out after the delay, the waiting strategy is a
+    // parameter to keep the backoff schedule independent of the clock
+    fn run<W>(&self, rx: Receiver<bool>, mut wait: W)
+    where
+        W: FnMut(&Receiver<bool>, Duration) -> std::result::Result<bool, RecvTimeoutError>,
+    {
+        let mut backoff = Backoff::new(self.max_backoff);
+        let mut status = rx.recv();
+
+        while let Ok(connected) = status {
+            if connected {
+                backoff.reset();
+            }
+            if connected || self.state.intentional.load(Ordering::Acquire) {
+                status = rx.recv();
+                continue;
+            }
+
+            // the status received while waiting supersedes the pending attempt
+            status = match wait(&rx, backoff.next_delay()) {
+                Ok(connected) => Ok(connected),
+                Err(RecvTimeoutError::Disconnected) => Err(RecvError),
+                Err(RecvTimeoutError::Timeout)
+                    if self.state.intentional.load(Ordering::Acquire) =>
+                {
+                    Ok(false)
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.state.attempts.fetch_add(1, Ordering::Relaxed);
+                    match unsafe { self.sender.send(&self.connect_cmd) } {
+                        // the outcome is reported with the next `server_status`
+                        Ok(_) => rx.recv(),
+                        Err(_) => Ok(false),
+                    }
+                }
+            };
+        }
+    }
+}
+
+struct Backoff {
+    next: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    fn new(max: Duration) -> Self {
+        Self { next: INITIAL_BACKOFF.min(max), max }
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = self.next.saturating_mul(2).min(self.max);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.next = INITIAL_BACKOFF.min(self.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::fake;
+
+    const CONNECT: &str = "<command id=\"connect\"/>\0";
+
+    // Runs the monitor over the `statuses`, every wait times out and the delays are collected
+    fn run(statuses: &[bool], intentional: bool, max_backoff: Duration) -> (Vec<Duration>, u64) {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+        let state = Arc::new(ReconnectState {
+            intentional: AtomicBool::new(intentional),
+            attempts: AtomicU64::new(0),
+        });
+        let monitor = Monitor {
+            sender: txc.sender(),
+            connect_cmd: CONNECT.as_bytes().into(),
+            state: Arc::clone(&state),
+            max_backoff,
+        };
+
+        let (tx, rx) = mpsc::channel();
+        statuses.iter().for_each(|&status| tx.send(status).unwrap());
+        drop(tx);
+
+        let mut delays = Vec::new();
+        monitor.run(rx, |_, delay| {
+            delays.push(delay);
+            Err(RecvTimeoutError::Timeout)
+        });
+        (delays, state.attempts.load(Ordering::Relaxed))
+    }
+
+    fn secs(secs: &[u64]) -> Vec<Duration> {
+        secs.iter().copied().map(Duration::from_secs).collect()
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let (delays, attempts) = run(&[false, false, false], false, Duration::from_secs(60));
+        assert_eq!(delays, secs(&[1, 2, 4]));
+        assert_eq!(attempts, 3);
+        assert_eq!(fake::commands(), vec![CONNECT.trim_end_matches('\0'); 3]);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let (delays, _) = run(&[false, false, false, false], false, Duration::from_secs(3));
+        assert_eq!(delays, secs(&[1, 2, 3, 3]));
+    }
+
+    #[test]
+    fn connected_resets_backoff() {
+        let (delays, attempts) = run(&[false, false, true, false], false, Duration::from_secs(60));
+        assert_eq!(delays, secs(&[1, 2, 1]));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn disconnect_suppresses_reconnect() {
+        let (delays, attempts) = run(&[false, false, false], true, Duration::from_secs(60));
+        assert!(delays.is_empty());
+        assert_eq!(attempts, 0);
+        assert!(fake::commands().is_empty());
+    }
+
+    #[test]
+    fn disconnect_while_waiting() {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+        let state = Arc::new(ReconnectState {
+            intentional: AtomicBool::new(false),
+            attempts: AtomicU64::new(0),
+        });
+        let monitor = Monitor {
+            sender: txc.sender(),
+            connect_cmd: CONNECT.as_bytes().into(),
+            state: Arc::clone(&state),
+            max_backoff: Duration::from_secs(60),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        tx.send(false).unwrap();
+        drop(tx);
+
+        // `disconnect` is called before the pending attempt fires
+        monitor.run(rx, |_, _| {
+            state.intentional.store(true, Ordering::Release);
+            Err(RecvTimeoutError::Timeout)
+        });
+        assert_eq!(state.attempts.load(Ordering::Relaxed), 0);
+        assert!(fake::commands().is_empty());
+    }
+}
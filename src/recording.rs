@@ -0,0 +1,583 @@
+use super::{feed::FLUSH_INTERVAL, stream::Stream, Error, Result, Sender, TCStr, TransaqConnector};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::{Deref, DerefMut},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// file header: magic, schema version u16 LE, recording start as unix nanoseconds u64 LE
+const MAGIC: &[u8; 4] = b"TXCR";
+const SCHEMA_VERSION: u16 = 1;
+
+/// Тип записи сессии
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordKind {
+    /// Входящее сообщение
+    Incoming,
+    /// Отправленная команда, без завершающего нулевого байта
+    Command,
+    /// Ответ коннектора на команду
+    Response,
+}
+
+impl RecordKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Incoming => 0,
+            Self::Command => 1,
+            Self::Response => 2,
+        }
+    }
+
+    fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            0 => Some(Self::Incoming),
+            1 => Some(Self::Command),
+            2 => Some(Self::Response),
+            _ => None,
+        }
+    }
+}
+
+/// Запись сессии, см. [`RecordingConnector`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Record {
+    /// Тип записи
+    pub kind: RecordKind,
+    /// Время от начала записи сессии
+    pub elapsed: Duration,
+    /// Содержимое сообщения
+    pub data: Vec<u8>,
+}
+
+/// Коннектор, записывающий входящие сообщения и отправленные команды в файл
+///
+/// Каждая запись содержит тип, время от начала записи с точностью до наносекунды и содержимое
+/// сообщения, формат записи описан в [`RecordReader`]. Запись может быть воспроизведена
+/// [`ReplayConnector`] для отладки или прочитана [`RecordReader`], например для анализа задержек.
+///
+/// Входящие сообщения записываются независимо от установленного обработчика; если обработчик не
+/// установлен, устанавливается пустой. Записываются только команды, отправленные через
+/// [`RecordingConnector::sender`]. Доступ к методам [`TransaqConnector`] осуществляется через
+/// [`Deref`]. После первой ошибки записи запись прекращается.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{RecordingConnector, Stream};
+///
+/// let txc = /*..*/;
+/// let mut txc = RecordingConnector::create(txc, "session.txcr")?;
+/// txc.input_stream().subscribe(|buf| println!("{buf}"))?;
+/// unsafe { txc.sender().send("<command id=\"get_connector_version\"/>\0")? };
+/// ```
+pub struct RecordingConnector {
+    txc: TransaqConnector,
+    recorder: Arc<Recorder>,
+    tap: u64,
+}
+
+impl RecordingConnector {
+    /// Создаёт коннектор, записывающий сессию в файл **path**
+    ///
+    /// # Errors
+    /// - [`Error::Internal`] - не удалось создать файл
+    /// - [`Error::Callback`] - не удалось установить пустой обработчик
+    pub fn create<P: AsRef<Path>>(txc: TransaqConnector, path: P) -> Result<Self> {
+        let file = File::create(path).map_err(|e| Error::Internal(e.to_string().into()))?;
+        Self::new(txc, file)
+    }
+
+    /// Создаёт коннектор, записывающий сессию в **writer**
+    ///
+    /// # Errors
+    /// - [`Error::Internal`] - не удалось записать заголовок
+    /// - [`Error::Callback`] - не удалось установить пустой обработчик
+    pub fn new<W: Write + Send + 'static>(mut txc: TransaqConnector, writer: W) -> Result<Self> {
+        let recorder =
+            Recorder::new(Box::new(writer)).map_err(|e| Error::Internal(e.to_string().into()))?;
+        let recorder = Arc::new(recorder);
+        if !txc.has_subscriber() {
+            txc.input_stream().subscribe(|_| {})?;
+        }
+
+        let tap_recorder = Arc::clone(&recorder);
+        let tap = txc.0.taps.insert(Box::new(move |buf| {
+            tap_recorder.record(RecordKind::Incoming, buf.to_bytes());
+            false
+        }));
+
+        Ok(Self { txc, recorder, tap })
+    }
+
+    /// Создаёт канал для отправки команд, записывающий команды и ответы коннектора
+    pub fn sender(&self) -> RecordingSender {
+        RecordingSender { sender: self.txc.sender(), recorder: Arc::clone(&self.recorder) }
+    }
+}
+
+impl Drop for RecordingConnector {
+    fn drop(&mut self) {
+        self.txc.0.taps.remove(self.tap);
+        self.recorder.flush();
+    }
+}
+
+impl Deref for RecordingConnector {
+    type Target = TransaqConnector;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txc
+    }
+}
+
+impl DerefMut for RecordingConnector {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.txc
+    }
+}
+
+impl fmt::Debug for RecordingConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordingConnector").field("txc", &self.txc).finish()
+    }
+}
+
+/// Канал отправки команд [`RecordingConnector`]
+#[derive(Clone)]
+pub struct RecordingSender {
+    sender: Sender,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingSender {
+    /// Записывает и передаёт данные коннектору, см. [`Sender::send`]
+    ///
+    /// Ответ коннектора записывается, в том числе ответ, приведший к ошибке
+    /// [`Error::InvalidCommand`] или [`Error::Internal`].
+    ///
+    /// # Safety
+    /// Требования к буферу, см. [`Sender::send`]
+    ///
+    /// # Errors
+    /// См. [`Sender::send`]
+    pub unsafe fn send<B: AsRef<[u8]>>(&self, buf: B) -> Result<TCStr<'_>> {
+        let buf = buf.as_ref();
+        let cmd = buf.iter().position(|b| *b == b'\0').map_or(buf, |end| &buf[..end]);
+        self.recorder.record(RecordKind::Command, cmd);
+
+        let result = self.sender.send(buf);
+        match &result {
+            Ok(response) => self.recorder.record(RecordKind::Response, response.to_bytes()),
+            Err(Error::InvalidCommand(response) | Error::Internal(response)) => {
+                self.recorder.record(RecordKind::Response, response.message().as_bytes())
+            }
+            Err(_) => {}
+        }
+        result
+    }
+}
+
+impl fmt::Debug for RecordingSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordingSender").field("sender", &self.sender).finish()
+    }
+}
+
+struct Recorder {
+    start: Instant,
+    out: Mutex<Option<RecorderOutput>>,
+}
+
+struct RecorderOutput {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    last_flush: Instant,
+}
+
+impl Recorder {
+    fn new(writer: Box<dyn Write + Send>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(writer);
+        let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&SCHEMA_VERSION.to_le_bytes())?;
+        writer.write_all(&(start.as_nanos() as u64).to_le_bytes())?;
+        writer.flush()?;
+
+        let out = RecorderOutput { writer, last_flush: Instant::now() };
+        Ok(Self { start: Instant::now(), out: Mutex::new(Some(out)) })
+    }
+
+    fn record(&self, kind: RecordKind, data: &[u8]) {
+        let elapsed = self.start.elapsed().as_nanos() as u64;
+        let mut out = self.lock();
+        if let Some(o) = out.as_mut() {
+            if o.write(kind, elapsed, data).is_err() {
+                *out = None;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(o) = self.lock().as_mut() {
+            let _ = o.writer.flush();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<RecorderOutput>> {
+        self.out.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl RecorderOutput {
+    fn write(&mut self, kind: RecordKind, elapsed: u64, data: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(data.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.writer.write_all(&[kind.to_byte()])?;
+        self.writer.write_all(&elapsed.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(data)?;
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.last_flush = Instant::now();
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Чтение записи сессии [`RecordingConnector`]
+///
+/// Формат файла: заголовок из сигнатуры `TXCR`, версии формата `u16` и момента начала записи -
+/// количества наносекунд от начала эпохи Unix `u64`, за которым следуют записи из типа записи
+/// `u8`, времени от начала записи в наносекундах `u64`, длины сообщения `u32` и сообщения.
+/// Числа записываются в little-endian.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{RecordKind, RecordReader};
+///
+/// let reader = RecordReader::open("session.txcr")?;
+/// for record in reader.filter_map(Result::ok).filter(|r| r.kind == RecordKind::Command) {
+///     println!("+{:?} {}", record.elapsed, String::from_utf8_lossy(&record.data));
+/// }
+/// ```
+pub struct RecordReader<R> {
+    reader: BufReader<R>,
+    started_at: SystemTime,
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Читает заголовок записи из **reader**
+    ///
+    /// # Errors
+    /// Ошибка чтения, [`io::ErrorKind::InvalidData`] - неизвестный формат или версия формата
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut header = [0; 14];
+        reader.read_exact(&mut header)?;
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if &header[..4] != MAGIC || version != SCHEMA_VERSION {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let mut start = [0; 8];
+        start.copy_from_slice(&header[6..]);
+        let started_at = UNIX_EPOCH + Duration::from_nanos(u64::from_le_bytes(start));
+        Ok(Self { reader, started_at })
+    }
+
+    /// Момент начала записи
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<Record>> {
+        let mut kind = [0; 1];
+        if self.reader.read(&mut kind)? == 0 {
+            return Ok(None);
+        }
+        let kind = RecordKind::from_byte(kind[0]).ok_or(io::ErrorKind::InvalidData)?;
+
+        let mut header = [0; 12];
+        self.reader.read_exact(&mut header)?;
+        let mut elapsed = [0; 8];
+        elapsed.copy_from_slice(&header[..8]);
+        let len = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+
+        let mut data = vec![0; len as usize];
+        self.reader.read_exact(&mut data)?;
+        Ok(Some(Record { kind, elapsed: Duration::from_nanos(u64::from_le_bytes(elapsed)), data }))
+    }
+}
+
+impl RecordReader<File> {
+    /// Открывает файл записи **path**
+    ///
+    /// # Errors
+    /// См. [`RecordReader::new`]
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        File::open(path).and_then(Self::new)
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+impl<R> fmt::Debug for RecordReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordReader").field("started_at", &self.started_at).finish()
+    }
+}
+
+/// Воспроизведение записи сессии [`RecordingConnector`]
+///
+/// Повторяет интерфейс [`MockConnector`](crate::MockConnector), доступный через [`Deref`]:
+/// [`ReplayConnector::replay`] передаёт записанные входящие сообщения в обработчик в порядке
+/// записи, а на отправленные команды возвращаются записанные ответы коннектора в порядке их
+/// записи.
+///
+/// Требует опции **testing**.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{ReplayConnector, Stream};
+///
+/// let mut txc = ReplayConnector::from_file("session.txcr")?.with_timing(true);
+/// txc.input_stream().subscribe(|buf| println!("{buf}"))?;
+/// txc.replay();
+/// ```
+#[cfg(feature = "testing")]
+pub struct ReplayConnector {
+    mock: crate::MockConnector,
+    incoming: Vec<Record>,
+    timing: bool,
+}
+
+#[cfg(feature = "testing")]
+impl ReplayConnector {
+    /// Читает запись сессии **path**
+    ///
+    /// # Errors
+    /// См. [`RecordReader::new`]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_records(RecordReader::open(path)?.collect::<io::Result<Vec<_>>>()?)
+    }
+
+    /// Создаёт коннектор из записей **records**
+    ///
+    /// # Errors
+    /// [`io::ErrorKind::InvalidData`] - сообщение содержит нулевой байт или не валидные UTF-8
+    /// символы
+    pub fn from_records(records: Vec<Record>) -> io::Result<Self> {
+        let invalid = |r: &Record| std::str::from_utf8(&r.data).map_or(true, |s| s.contains('\0'));
+        if records.iter().any(invalid) {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let (incoming, rest): (Vec<_>, Vec<_>) =
+            records.into_iter().partition(|r| r.kind == RecordKind::Incoming);
+        let mut responses = rest
+            .into_iter()
+            .filter(|r| r.kind == RecordKind::Response)
+            .map(|r| String::from_utf8(r.data).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        let mock = crate::MockConnector::new();
+        mock.respond_with(move |_| {
+            responses.next().unwrap_or_else(|| "<result success=\"true\"/>".to_string())
+        });
+        Ok(Self { mock, incoming, timing: false })
+    }
+
+    /// Воспроизводить входящие сообщения с записанными интервалами между ними
+    ///
+    /// По-умолчанию сообщения передаются без задержек.
+    pub fn with_timing(mut self, timing: bool) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Передаёт записанные входящие сообщения в обработчик в текущем потоке
+    pub fn replay(&self) {
+        let mut prev: Option<Duration> = None;
+        for record in &self.incoming {
+            if let (true, Some(prev)) = (self.timing, prev) {
+                std::thread::sleep(record.elapsed.saturating_sub(prev));
+            }
+            prev = Some(record.elapsed);
+            // validated in `from_records`
+            self.mock.push_message(std::str::from_utf8(&record.data).unwrap_or_default());
+        }
+    }
+}
+
+#[cfg(feature = "testing")]
+impl Deref for ReplayConnector {
+    type Target = crate::MockConnector;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mock
+    }
+}
+
+#[cfg(feature = "testing")]
+impl DerefMut for ReplayConnector {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.mock
+    }
+}
+
+#[cfg(feature = "testing")]
+impl fmt::Debug for ReplayConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplayConnector")
+            .field("incoming", &self.incoming.len())
+            .field("timing", &self.timing)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::fake;
+
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const STATUS: &str = "<command id=\"server_status\"/>";
+    const REJECTED: &str = "<result success=\"false\"><message>rejected</message></result>";
+
+    // Records a session of two commands, the second one rejected, and three incoming messages
+    fn record() -> Vec<u8> {
+        let (_library, module) = fake::load();
+        let writer = SharedWriter::default();
+        let txc =
+            RecordingConnector::new(TransaqConnector::from_module(module), writer.clone()).unwrap();
+        let sender = txc.sender();
+
+        assert!(fake::deliver("<server_status connected=\"true\"/>"));
+        unsafe { sender.send(format!("{STATUS}\0")).unwrap() };
+        assert!(fake::deliver("<quotes/>"));
+        fake::respond_with(|_| Some(REJECTED.into()));
+        let ret = unsafe { sender.send("<command id=\"disconnect\"/>\0").map(drop) };
+        assert!(matches!(ret, Err(Error::InvalidCommand(_))));
+        assert!(fake::deliver("<server_status connected=\"false\"/>"));
+
+        drop((sender, txc));
+        let buf = writer.0.lock().unwrap();
+        buf.clone()
+    }
+
+    #[test]
+    fn record_and_read() {
+        let before = SystemTime::now();
+        let buf = record();
+        let reader = RecordReader::new(&buf[..]).unwrap();
+        assert!(reader.started_at() >= before - Duration::from_millis(1));
+        assert!(reader.started_at() <= SystemTime::now());
+
+        let records = reader.collect::<io::Result<Vec<_>>>().unwrap();
+        let kinds = records.iter().map(|r| (r.kind, String::from_utf8_lossy(&r.data)));
+        assert_eq!(
+            kinds.collect::<Vec<_>>(),
+            [
+                (RecordKind::Incoming, "<server_status connected=\"true\"/>".into()),
+                (RecordKind::Command, STATUS.into()),
+                (RecordKind::Response, "<result success=\"true\"/>".into()),
+                (RecordKind::Incoming, "<quotes/>".into()),
+                (RecordKind::Command, "<command id=\"disconnect\"/>".into()),
+                (RecordKind::Response, REJECTED.into()),
+                (RecordKind::Incoming, "<server_status connected=\"false\"/>".into()),
+            ]
+        );
+        // nanosecond timestamps of the single clock
+        assert!(records.windows(2).all(|w| w[0].elapsed <= w[1].elapsed));
+        assert!(records.first().unwrap().elapsed < records.last().unwrap().elapsed);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn record_and_replay() {
+        use crate::Stream;
+
+        let buf = record();
+        let records = RecordReader::new(&buf[..]).unwrap().collect::<io::Result<Vec<_>>>();
+        let mut replay = ReplayConnector::from_records(records.unwrap()).unwrap();
+
+        let incoming = Arc::new(Mutex::new(Vec::new()));
+        let out = Arc::clone(&incoming);
+        replay
+            .input_stream()
+            .subscribe(move |buf| out.lock().unwrap().push(buf.to_string()))
+            .unwrap();
+        replay.replay();
+
+        // the commands and responses are not delivered as incoming messages
+        assert_eq!(
+            *incoming.lock().unwrap(),
+            [
+                "<server_status connected=\"true\"/>",
+                "<quotes/>",
+                "<server_status connected=\"false\"/>"
+            ]
+        );
+
+        // the recorded responses are returned in order, regardless of the command
+        let sender = replay.sender();
+        let ok = unsafe { sender.send("<command id=\"get_securities\"/>\0").unwrap() };
+        assert_eq!(ok.to_bytes(), b"<result success=\"true\"/>");
+        match unsafe { sender.send("<command id=\"get_securities\"/>\0") } {
+            Err(Error::InvalidCommand(msg)) => assert_eq!(msg.message(), REJECTED),
+            ret => panic!("{ret:?}"),
+        }
+        assert!(unsafe { sender.send("<command id=\"get_securities\"/>\0") }.is_ok());
+        // the recorded commands aren't replayed as sent
+        assert_eq!(replay.sent_commands().len(), 3);
+    }
+
+    #[test]
+    fn read_rejects_malformed() {
+        let buf = record();
+        let invalid = |buf: &[u8]| RecordReader::new(buf).err().map(|e| e.kind());
+
+        assert_eq!(invalid(b"TXC"), Some(io::ErrorKind::UnexpectedEof));
+        let mut other = buf.clone();
+        other[..4].copy_from_slice(b"TXCF");
+        assert_eq!(invalid(&other), Some(io::ErrorKind::InvalidData));
+        let mut version = buf.clone();
+        version[4..6].copy_from_slice(&2u16.to_le_bytes());
+        assert_eq!(invalid(&version), Some(io::ErrorKind::InvalidData));
+
+        // truncated record
+        let truncated = RecordReader::new(&buf[..buf.len() - 1]).unwrap();
+        let last = truncated.last().unwrap();
+        assert_eq!(last.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // unknown record kind
+        let mut kind = buf.clone();
+        kind[14] = 3;
+        let mut reader = RecordReader::new(&kind[..]).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
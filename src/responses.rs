@@ -0,0 +1,3093 @@
+use super::buffers::{
+    xml_attr, xml_child_text, xml_elements, xml_leaf_elements, xml_tag_name, xml_text,
+    xml_unescape, OwnedMessage, TCStr,
+};
+#[cfg(feature = "encoding")]
+use super::encoding::decode_cp1251;
+use super::{lang, BuySell, Decimal};
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+/// Ошибка разбора сообщения коннектора
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ParseError {
+    /// Корневой тэг сообщения не соответствует ожидаемому
+    UnexpectedTag {
+        /// Ожидаемый тэг
+        expected: &'static str,
+        /// Тэг сообщения
+        found: String,
+    },
+    /// В сообщении отсутствует обязательный атрибут
+    MissingAttribute(&'static str),
+    /// В сообщении отсутствует текстовое содержимое корневого элемента
+    MissingContent,
+    /// Недопустимое значение атрибута
+    InvalidValue {
+        /// Имя атрибута
+        attr: &'static str,
+        /// Значение атрибута
+        value: String,
+    },
+    /// Некорректный XML документ, см. `parse_message`(опция **xml-events**)
+    Malformed {
+        /// Смещение в байтах, на котором обнаружена ошибка
+        position: usize,
+        /// Описание ошибки
+        message: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedTag { expected, found } => {
+                write!(f, "{} '{expected}': '{found}'", lang::UNEXPECTED_TAG)
+            }
+            ParseError::MissingAttribute(attr) => write!(f, "{} '{attr}'", lang::MISSING_ATTRIBUTE),
+            ParseError::MissingContent => f.write_str(lang::MISSING_CONTENT),
+            ParseError::InvalidValue { attr, value } => {
+                write!(f, "{} '{attr}': \"{value}\"", lang::INVALID_ATTRIBUTE)
+            }
+            ParseError::Malformed { position, message } => {
+                write!(f, "{} ({position}): {message}", lang::XML_MALFORMED)
+            }
+        }
+    }
+}
+impl std::error::Error for ParseError {}
+
+// Checks the root tag of the message
+fn expect_tag(bytes: &[u8], expected: &'static str) -> Result<(), ParseError> {
+    match xml_tag_name(bytes) {
+        Some(tag) if tag == expected.as_bytes() => Ok(()),
+        tag => Err(ParseError::UnexpectedTag {
+            expected,
+            found: String::from_utf8_lossy(tag.unwrap_or_default()).into_owned(),
+        }),
+    }
+}
+
+fn attr_string(bytes: &[u8], attr: &[u8]) -> Option<String> {
+    xml_attr(bytes, attr).map(|value| String::from_utf8_lossy(value).into_owned())
+}
+
+fn parse_bool(attr: &'static str, value: &[u8]) -> Result<bool, ParseError> {
+    match value {
+        b"true" => Ok(true),
+        b"false" => Ok(false),
+        value => Err(invalid_value(attr, value)),
+    }
+}
+
+fn invalid_value(attr: &'static str, value: &[u8]) -> ParseError {
+    ParseError::InvalidValue { attr, value: String::from_utf8_lossy(value).into_owned() }
+}
+
+// Values of the child elements `<tag>value</tag>` of the record
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn string(&self, tag: &'static str) -> String {
+        xml_child_text(self.0, tag.as_bytes())
+            .map(|v| xml_unescape(v).into_owned())
+            .unwrap_or_default()
+    }
+
+    fn parse<T: FromStr>(&self, tag: &'static str) -> Result<Option<T>, ParseError> {
+        xml_child_text(self.0, tag.as_bytes()).map(|value| parse_value(tag, value)).transpose()
+    }
+
+    fn text(&self, tag: &'static str) -> String {
+        xml_child_text(self.0, tag.as_bytes()).map(decode_text).unwrap_or_default()
+    }
+
+    fn buysell(&self) -> Result<Option<BuySell>, ParseError> {
+        xml_child_text(self.0, b"buysell").map(parse_buysell).transpose()
+    }
+
+    // `0` and empty values stand for the missing time
+    fn time(&self, tag: &'static str) -> Result<Option<Timestamp>, ParseError> {
+        match xml_child_text(self.0, tag.as_bytes()) {
+            None => Ok(None),
+            Some(value) if matches!(std::str::from_utf8(value).map(str::trim), Ok("" | "0")) => {
+                Ok(None)
+            }
+            Some(value) => parse_value(tag, value).map(Some),
+        }
+    }
+
+    fn status(&self) -> Option<OrderStatus> {
+        xml_child_text(self.0, b"status").map(|v| OrderStatus::from(&*xml_unescape(v)))
+    }
+}
+
+// Text expected in UTF-8, that may come in windows-1251 as well, the latter is decoded with the
+// `encoding` feature and is converted lossy otherwise
+fn decode_text(value: &[u8]) -> String {
+    // the content of `<![CDATA[..]]>` is taken as is
+    let cdata = value.strip_prefix(b"<![CDATA[").and_then(|v| v.strip_suffix(b"]]>"));
+    #[cfg(feature = "encoding")]
+    if std::str::from_utf8(value).is_err() {
+        return match cdata {
+            Some(cdata) => decode_cp1251(cdata).into_owned(),
+            None => xml_unescape(decode_cp1251(value).as_bytes()).into_owned(),
+        };
+    }
+    match cdata {
+        Some(cdata) => String::from_utf8_lossy(cdata).into_owned(),
+        None => xml_unescape(value).into_owned(),
+    }
+}
+
+fn parse_value<T: FromStr>(tag: &'static str, value: &[u8]) -> Result<T, ParseError> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or_else(|| invalid_value(tag, value))
+}
+
+fn parse_buysell(value: &[u8]) -> Result<BuySell, ParseError> {
+    match value {
+        b"B" => Ok(BuySell::Buy),
+        b"S" => Ok(BuySell::Sell),
+        value => Err(invalid_value("buysell", value)),
+    }
+}
+
+fn parse_attr<T: FromStr>(bytes: &[u8], attr: &'static str) -> Result<Option<T>, ParseError> {
+    match xml_attr(bytes, attr.as_bytes()) {
+        None => Ok(None),
+        Some(value) => std::str::from_utf8(value)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Some)
+            .ok_or_else(|| invalid_value(attr, value)),
+    }
+}
+
+/// Состояние подключения к серверу
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ConnectionStatus {
+    /// Подключение установлено
+    True,
+    /// Подключение отсутствует
+    False,
+    /// Ошибка подключения, текст ошибки передаётся в теле сообщения
+    Error,
+}
+
+impl TryFrom<&[u8]> for ConnectionStatus {
+    type Error = ParseError;
+
+    fn try_from(value: &[u8]) -> Result<Self, ParseError> {
+        match value {
+            b"true" => Ok(ConnectionStatus::True),
+            b"false" => Ok(ConnectionStatus::False),
+            b"error" => Ok(ConnectionStatus::Error),
+            value => Err(invalid_value("connected", value)),
+        }
+    }
+}
+
+/// Сообщение о состоянии подключения `<server_status>`
+///
+/// Разбирается из атрибутов корневого тэга, без построения дерева документа и копирования
+/// остального содержимого сообщения.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{ConnectionStatus, ServerStatusResponse, Stream};
+/// use std::convert::TryFrom;
+///
+/// let mut txc = /*..*/;
+/// txc.input_stream()
+///     .filter_tag("server_status")
+///     .filter_map(|buf| ServerStatusResponse::try_from(&buf).ok())
+///     .subscribe(|status| {
+///         if status.connected == ConnectionStatus::True {
+///             println!("connected to {}, tz: {}", status.server, status.timezone);
+///         }
+///     })?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServerStatusResponse {
+    /// Состояние подключения, атрибут `connected`
+    pub connected: ConnectionStatus,
+    /// Идёт восстановление подключения, атрибут `recover`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub recover: bool,
+    /// Идентификатор сервера, атрибут `id`
+    #[cfg_attr(feature = "serde", serde(rename = "id", default))]
+    pub server: String,
+    /// Часовой пояс сервера, атрибут `server_tz`
+    #[cfg_attr(feature = "serde", serde(rename = "server_tz", default))]
+    pub timezone: String,
+    /// Текст ошибки подключения в теле сообщения, при `connected="error"`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub text: Option<String>,
+}
+
+impl ServerStatusResponse {
+    /// Разбирает сообщение `<server_status>`
+    ///
+    /// Атрибуты `recover`, `id` и `server_tz` передаются коннектором только при установленном
+    /// подключении, при их отсутствии используются значения по-умолчанию. Порядок атрибутов
+    /// не имеет значения.
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<server_status>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует атрибут `connected`
+    /// - [`ParseError::InvalidValue`] - недопустимое значение `connected` или `recover`
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "server_status")?;
+        let connected = xml_attr(bytes, b"connected")
+            .ok_or(ParseError::MissingAttribute("connected"))
+            .and_then(ConnectionStatus::try_from)?;
+        let recover = xml_attr(bytes, b"recover").map(|v| parse_bool("recover", v)).transpose()?;
+
+        Ok(Self {
+            connected,
+            recover: recover.unwrap_or_default(),
+            server: attr_string(bytes, b"id").unwrap_or_default(),
+            timezone: attr_string(bytes, b"server_tz").unwrap_or_default(),
+            text: xml_text(bytes)
+                .map(|text| xml_unescape(text).trim().to_string())
+                .filter(|text| !text.is_empty()),
+        })
+    }
+
+    /// Подключение установлено
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.connected == ConnectionStatus::True
+    }
+}
+
+impl TryFrom<&TCStr<'_>> for ServerStatusResponse {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(buf: &TCStr<'_>) -> Result<Self, Self::Error> {
+        Self::parse(buf.to_bytes())
+    }
+}
+
+impl TryFrom<TCStr<'_>> for ServerStatusResponse {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(buf: TCStr<'_>) -> Result<Self, Self::Error> {
+        Self::parse(buf.to_bytes())
+    }
+}
+
+impl TryFrom<&OwnedMessage> for ServerStatusResponse {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(msg: &OwnedMessage) -> Result<Self, Self::Error> {
+        Self::parse(msg.as_ref())
+    }
+}
+
+/// Версия коннектора `<connector_version>`
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::ConnectorVersionResponse;
+///
+/// let version = ConnectorVersionResponse::parse(b"<connector_version>7.15.3.5.1</connector_version>")?;
+/// assert_eq!(version.as_str(), "7.15.3.5.1");
+/// assert_eq!(version.parts(), [7, 15, 3, 5, 1]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConnectorVersionResponse(String);
+
+impl ConnectorVersionResponse {
+    /// Разбирает сообщение `<connector_version>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<connector_version>`
+    /// - [`ParseError::MissingContent`] - сообщение не содержит версии
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "connector_version")?;
+        xml_text(bytes)
+            .map(|version| String::from_utf8_lossy(version).trim().to_string())
+            .filter(|version| !version.is_empty())
+            .map(Self)
+            .ok_or(ParseError::MissingContent)
+    }
+
+    /// Версия в исходном виде
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Числовые компоненты версии
+    ///
+    /// Недостающие компоненты равны нулю; если версия содержит более пяти компонент или
+    /// нечисловые значения, возвращается `[0; 5]`.
+    pub fn parts(&self) -> [u8; 5] {
+        let mut parts = [0; 5];
+        let mut components = self.0.split('.');
+        for part in parts.iter_mut() {
+            match components.next().map(str::parse) {
+                Some(Ok(value)) => *part = value,
+                Some(Err(_)) => return [0; 5],
+                None => break,
+            }
+        }
+        if components.next().is_some() {
+            return [0; 5];
+        }
+        parts
+    }
+
+    /// Возвращает версию в исходном виде
+    #[inline]
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for ConnectorVersionResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<&TCStr<'_>> for ConnectorVersionResponse {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(buf: &TCStr<'_>) -> Result<Self, Self::Error> {
+        Self::parse(buf.to_bytes())
+    }
+}
+
+impl TryFrom<TCStr<'_>> for ConnectorVersionResponse {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(buf: TCStr<'_>) -> Result<Self, Self::Error> {
+        Self::parse(buf.to_bytes())
+    }
+}
+
+impl TryFrom<&OwnedMessage> for ConnectorVersionResponse {
+    type Error = ParseError;
+
+    #[inline]
+    fn try_from(msg: &OwnedMessage) -> Result<Self, Self::Error> {
+        Self::parse(msg.as_ref())
+    }
+}
+
+macro_rules! impl_try_from_buffers {
+    ($($ty:ty),+) => {
+        $(
+            impl TryFrom<&TCStr<'_>> for $ty {
+                type Error = ParseError;
+
+                #[inline]
+                fn try_from(buf: &TCStr<'_>) -> Result<Self, Self::Error> {
+                    Self::parse(buf.to_bytes())
+                }
+            }
+
+            impl TryFrom<&OwnedMessage> for $ty {
+                type Error = ParseError;
+
+                #[inline]
+                fn try_from(msg: &OwnedMessage) -> Result<Self, Self::Error> {
+                    Self::parse(msg.as_ref())
+                }
+            }
+        )+
+    };
+}
+
+/// Инструмент, элемент `<security>` сообщения `<securities>`
+///
+/// Значения, отсутствующие в сообщении, остаются незаполненными(`None` или пустая строка).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Security {
+    /// Внутренний идентификатор инструмента, атрибут `secid`
+    pub secid: u32,
+    /// Инструмент доступен для торговли, атрибут `active`
+    pub active: bool,
+    /// Код инструмента
+    pub seccode: String,
+    /// Режим торгов по-умолчанию
+    pub board: String,
+    /// Идентификатор рынка
+    pub market: Option<u32>,
+    /// Наименование бумаги
+    pub shortname: String,
+    /// Количество десятичных знаков в цене
+    pub decimals: Option<u32>,
+    /// Шаг цены
+    pub minstep: Option<Decimal>,
+    /// Размер лота
+    pub lotsize: Option<u32>,
+    /// Стоимость пункта цены
+    pub point_cost: Option<Decimal>,
+    /// Тип бумаги
+    pub sectype: String,
+}
+
+impl Security {
+    pub(crate) fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            secid: parse_attr(record, "secid")?.ok_or(ParseError::MissingAttribute("secid"))?,
+            active: xml_attr(record, b"active")
+                .map(|v| parse_bool("active", v))
+                .transpose()?
+                .unwrap_or_default(),
+            seccode: fields.string("seccode"),
+            board: fields.string("board"),
+            market: fields.parse("market")?,
+            shortname: fields.string("shortname"),
+            decimals: fields.parse("decimals")?,
+            minstep: fields.parse("minstep")?,
+            lotsize: fields.parse("lotsize")?,
+            point_cost: fields.parse("point_cost")?,
+            sectype: fields.string("sectype"),
+        })
+    }
+
+    /// Объединяет инструмент с изменениями **update**, например из последующего сообщения
+    /// `<securities>`
+    ///
+    /// Параметры, отсутствующие в **update**(`None` или пустая строка), сохраняют прежние
+    /// значения. Атрибут `active` передаётся коннектором всегда и заменяется.
+    pub fn merge(&mut self, update: Security) {
+        fn keep<T>(value: &mut Option<T>, update: Option<T>) {
+            if update.is_some() {
+                *value = update;
+            }
+        }
+        fn keep_string(value: &mut String, update: String) {
+            if !update.is_empty() {
+                *value = update;
+            }
+        }
+
+        self.secid = update.secid;
+        self.active = update.active;
+        keep_string(&mut self.seccode, update.seccode);
+        keep_string(&mut self.board, update.board);
+        keep(&mut self.market, update.market);
+        keep_string(&mut self.shortname, update.shortname);
+        keep(&mut self.decimals, update.decimals);
+        keep(&mut self.minstep, update.minstep);
+        keep(&mut self.lotsize, update.lotsize);
+        keep(&mut self.point_cost, update.point_cost);
+        keep_string(&mut self.sectype, update.sectype);
+    }
+}
+
+/// Справочник инструментов `<securities>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecuritiesResponse {
+    /// Инструменты
+    pub securities: Vec<Security>,
+}
+
+impl SecuritiesResponse {
+    /// Разбирает сообщение `<securities>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<securities>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует идентификатор инструмента
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "securities")?;
+        let securities =
+            xml_elements(bytes, b"security").map(Security::parse).collect::<Result<_, _>>()?;
+        Ok(Self { securities })
+    }
+}
+
+/// Статус заявки, элемент `<status>` записей `<order>` и `<stoporder>`
+///
+/// Статусы, отсутствующие в документации коннектора, передаются как [`OrderStatus::Other`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OrderStatus {
+    /// `active` - активная
+    Active,
+    /// `cancelled` - снята трейдером, заявка уже попала на рынок и была отменена
+    Cancelled,
+    /// `denied` - отклонена брокером
+    Denied,
+    /// `disabled` - прекращена трейдером, условная заявка, которую сняли до наступления условия
+    Disabled,
+    /// `expired` - время действия истекло
+    Expired,
+    /// `failed` - не удалось выставить на биржу
+    Failed,
+    /// `forwarding` - выставляется на биржу
+    Forwarding,
+    /// `inactive` - статус не известен из-за проблем со связью с биржей
+    Inactive,
+    /// `matched` - исполнена
+    Matched,
+    /// `refused` - отклонена контрагентом
+    Refused,
+    /// `rejected` - отклонена биржей
+    Rejected,
+    /// `removed` - аннулирована биржей
+    Removed,
+    /// `wait` - не наступило время активации
+    Wait,
+    /// `watching` - ожидает наступления условия
+    Watching,
+    /// `linkwait` - ожидает исполнения связанной заявки
+    LinkWait,
+    /// `sl_executed` - исполнен стоп-лосс
+    #[cfg_attr(feature = "serde", serde(rename = "sl_executed"))]
+    SlExecuted,
+    /// `sl_guardtime` - стоп-лосс ожидает защитного времени
+    #[cfg_attr(feature = "serde", serde(rename = "sl_guardtime"))]
+    SlGuardTime,
+    /// `sl_forwarding` - стоп-лосс выставляется на биржу
+    #[cfg_attr(feature = "serde", serde(rename = "sl_forwarding"))]
+    SlForwarding,
+    /// `tp_executed` - исполнен тейк-профит
+    #[cfg_attr(feature = "serde", serde(rename = "tp_executed"))]
+    TpExecuted,
+    /// `tp_guardtime` - тейк-профит ожидает защитного времени
+    #[cfg_attr(feature = "serde", serde(rename = "tp_guardtime"))]
+    TpGuardTime,
+    /// `tp_correction` - тейк-профит в режиме коррекции
+    #[cfg_attr(feature = "serde", serde(rename = "tp_correction"))]
+    TpCorrection,
+    /// `tp_correction_guardtime` - тейк-профит ожидает защитного времени в режиме коррекции
+    #[cfg_attr(feature = "serde", serde(rename = "tp_correction_guardtime"))]
+    TpCorrectionGuardTime,
+    /// `tp_forwarding` - тейк-профит выставляется на биржу
+    #[cfg_attr(feature = "serde", serde(rename = "tp_forwarding"))]
+    TpForwarding,
+    /// Статус, отсутствующий в документации коннектора
+    Other(String),
+}
+
+impl OrderStatus {
+    /// Значение элемента `<status>`
+    pub fn as_str(&self) -> &str {
+        match self {
+            OrderStatus::Active => "active",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Denied => "denied",
+            OrderStatus::Disabled => "disabled",
+            OrderStatus::Expired => "expired",
+            OrderStatus::Failed => "failed",
+            OrderStatus::Forwarding => "forwarding",
+            OrderStatus::Inactive => "inactive",
+            OrderStatus::Matched => "matched",
+            OrderStatus::Refused => "refused",
+            OrderStatus::Rejected => "rejected",
+            OrderStatus::Removed => "removed",
+            OrderStatus::Wait => "wait",
+            OrderStatus::Watching => "watching",
+            OrderStatus::LinkWait => "linkwait",
+            OrderStatus::SlExecuted => "sl_executed",
+            OrderStatus::SlGuardTime => "sl_guardtime",
+            OrderStatus::SlForwarding => "sl_forwarding",
+            OrderStatus::TpExecuted => "tp_executed",
+            OrderStatus::TpGuardTime => "tp_guardtime",
+            OrderStatus::TpCorrection => "tp_correction",
+            OrderStatus::TpCorrectionGuardTime => "tp_correction_guardtime",
+            OrderStatus::TpForwarding => "tp_forwarding",
+            OrderStatus::Other(status) => status,
+        }
+    }
+
+    /// Заявка больше не может быть исполнена: исполнена, снята, отклонена или истекла
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Cancelled
+                | OrderStatus::Denied
+                | OrderStatus::Disabled
+                | OrderStatus::Expired
+                | OrderStatus::Failed
+                | OrderStatus::Matched
+                | OrderStatus::Refused
+                | OrderStatus::Rejected
+                | OrderStatus::Removed
+                | OrderStatus::SlExecuted
+                | OrderStatus::TpExecuted
+        )
+    }
+}
+
+impl From<&str> for OrderStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "active" => OrderStatus::Active,
+            "cancelled" => OrderStatus::Cancelled,
+            "denied" => OrderStatus::Denied,
+            "disabled" => OrderStatus::Disabled,
+            "expired" => OrderStatus::Expired,
+            "failed" => OrderStatus::Failed,
+            "forwarding" => OrderStatus::Forwarding,
+            "inactive" => OrderStatus::Inactive,
+            "matched" => OrderStatus::Matched,
+            "refused" => OrderStatus::Refused,
+            "rejected" => OrderStatus::Rejected,
+            "removed" => OrderStatus::Removed,
+            "wait" => OrderStatus::Wait,
+            "watching" => OrderStatus::Watching,
+            "linkwait" => OrderStatus::LinkWait,
+            "sl_executed" => OrderStatus::SlExecuted,
+            "sl_guardtime" => OrderStatus::SlGuardTime,
+            "sl_forwarding" => OrderStatus::SlForwarding,
+            "tp_executed" => OrderStatus::TpExecuted,
+            "tp_guardtime" => OrderStatus::TpGuardTime,
+            "tp_correction" => OrderStatus::TpCorrection,
+            "tp_correction_guardtime" => OrderStatus::TpCorrectionGuardTime,
+            "tp_forwarding" => OrderStatus::TpForwarding,
+            status => OrderStatus::Other(status.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Заявка, элемент `<order>` сообщения `<orders>`
+///
+/// Коннектор передаёт заявку целиком при каждом изменении её состояния.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderUpdate {
+    /// Идентификатор транзакции, атрибут `transactionid`
+    pub transactionid: u64,
+    /// Биржевой номер заявки, отсутствует до регистрации заявки биржей
+    pub orderno: Option<u64>,
+    /// Внутренний идентификатор инструмента
+    pub secid: Option<u32>,
+    /// Единый денежный счёт
+    pub union: String,
+    /// Режим торгов
+    pub board: String,
+    /// Код инструмента
+    pub seccode: String,
+    /// Клиент
+    pub client: String,
+    /// Статус заявки, `None` если статус не передан
+    pub status: Option<OrderStatus>,
+    /// Направление заявки
+    pub buysell: Option<BuySell>,
+    /// Время регистрации заявки биржей
+    pub time: Option<Timestamp>,
+    /// Дата экспирации, только для фьючерсов
+    pub expdate: Option<Timestamp>,
+    /// Биржевой номер родительской заявки
+    pub origin_orderno: Option<u64>,
+    /// Время регистрации заявки сервером Transaq, только для условных заявок
+    pub accepttime: Option<Timestamp>,
+    /// Примечание
+    pub brokerref: String,
+    /// Объём заявки в денежном выражении
+    pub value: Option<Decimal>,
+    /// НКД, только для облигаций
+    pub accruedint: Option<Decimal>,
+    /// Код поставки
+    pub settlecode: String,
+    /// Неисполненный остаток, лотов
+    pub balance: Option<u32>,
+    /// Цена
+    pub price: Option<Decimal>,
+    /// Количество, лотов
+    pub quantity: Option<u32>,
+    /// Скрытое количество, лотов
+    pub hidden: Option<u32>,
+    /// Доходность, только для облигаций
+    pub yield_: Option<Decimal>,
+    /// Время снятия заявки
+    pub withdrawtime: Option<Timestamp>,
+    /// Условие, только для условных заявок
+    pub condition: String,
+    /// Цена для условия
+    pub conditionvalue: Option<Decimal>,
+    /// Условная заявка действительна с
+    pub validafter: String,
+    /// Условная заявка действительна до
+    pub validbefore: String,
+    /// Максимальная комиссия
+    pub maxcomission: Option<Decimal>,
+    /// Сообщение биржи в случае отказа выставить заявку
+    pub result: String,
+}
+
+impl OrderUpdate {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            transactionid: parse_attr(record, "transactionid")?
+                .ok_or(ParseError::MissingAttribute("transactionid"))?,
+            orderno: fields.parse("orderno")?,
+            secid: fields.parse("secid")?,
+            union: fields.string("union"),
+            board: fields.string("board"),
+            seccode: fields.string("seccode"),
+            client: fields.string("client"),
+            status: fields.status(),
+            buysell: fields.buysell()?,
+            time: fields.time("time")?,
+            expdate: fields.time("expdate")?,
+            origin_orderno: fields.parse("origin_orderno")?,
+            accepttime: fields.time("accepttime")?,
+            brokerref: fields.string("brokerref"),
+            value: fields.parse("value")?,
+            accruedint: fields.parse("accruedint")?,
+            settlecode: fields.string("settlecode"),
+            balance: fields.parse("balance")?,
+            price: fields.parse("price")?,
+            quantity: fields.parse("quantity")?,
+            hidden: fields.parse("hidden")?,
+            yield_: fields.parse("yield")?,
+            withdrawtime: fields.time("withdrawtime")?,
+            condition: fields.string("condition"),
+            conditionvalue: fields.parse("conditionvalue")?,
+            validafter: fields.string("validafter"),
+            validbefore: fields.string("validbefore"),
+            maxcomission: fields.parse("maxcomission")?,
+            result: fields.string("result"),
+        })
+    }
+}
+
+/// Стоп-лосс условной заявки, элемент `<stoploss>` записи `<stoporder>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StopLoss {
+    /// Цена активации
+    pub activationprice: Option<Decimal>,
+    /// Цена исполнения, `None` для исполнения по рынку
+    pub orderprice: Option<Decimal>,
+    /// Количество, лотов или процент от позиции(`10%`)
+    pub quantity: String,
+    /// Защитное время
+    pub guardtime: String,
+    /// Примечание
+    pub brokerref: String,
+}
+
+/// Тейк-профит условной заявки, элемент `<takeprofit>` записи `<stoporder>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TakeProfit {
+    /// Цена активации
+    pub activationprice: Option<Decimal>,
+    /// Количество, лотов или процент от позиции(`10%`)
+    pub quantity: String,
+    /// Коррекция
+    pub correction: String,
+    /// Защитный спред
+    pub guardspread: String,
+    /// Защитное время
+    pub guardtime: String,
+    /// Примечание
+    pub brokerref: String,
+}
+
+/// Условная заявка, элемент `<stoporder>` сообщения `<orders>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StopOrderUpdate {
+    /// Идентификатор транзакции, атрибут `transactionid`
+    pub transactionid: u64,
+    /// Биржевой номер заявки, выставленной при срабатывании условия
+    pub activeorderno: Option<u64>,
+    /// Внутренний идентификатор инструмента
+    pub secid: Option<u32>,
+    /// Режим торгов
+    pub board: String,
+    /// Код инструмента
+    pub seccode: String,
+    /// Клиент
+    pub client: String,
+    /// Направление заявки
+    pub buysell: Option<BuySell>,
+    /// Снявший заявку
+    pub canceller: String,
+    /// Номер сделки, приведшей к срабатыванию условия
+    pub alltradeno: Option<u64>,
+    /// Заявка действительна до
+    pub validbefore: String,
+    /// Автор заявки
+    pub author: String,
+    /// Время регистрации заявки сервером Transaq
+    pub accepttime: Option<Timestamp>,
+    /// Биржевой номер связанной заявки
+    pub linkedorderno: Option<u64>,
+    /// Дата экспирации, только для фьючерсов
+    pub expdate: Option<Timestamp>,
+    /// Статус заявки
+    pub status: Option<OrderStatus>,
+    /// Стоп-лосс
+    pub stoploss: Option<StopLoss>,
+    /// Тейк-профит
+    pub takeprofit: Option<TakeProfit>,
+    /// Сообщение сервера в случае отказа выставить заявку
+    pub result: String,
+}
+
+impl StopOrderUpdate {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        // the names of the top level fields don't clash with the ones of the nested legs
+        let fields = Fields(record);
+        Ok(Self {
+            transactionid: parse_attr(record, "transactionid")?
+                .ok_or(ParseError::MissingAttribute("transactionid"))?,
+            activeorderno: fields.parse("activeorderno")?,
+            secid: fields.parse("secid")?,
+            board: fields.string("board"),
+            seccode: fields.string("seccode"),
+            client: fields.string("client"),
+            buysell: fields.buysell()?,
+            canceller: fields.string("canceller"),
+            alltradeno: fields.parse("alltradeno")?,
+            validbefore: fields.string("validbefore"),
+            author: fields.string("author"),
+            accepttime: fields.time("accepttime")?,
+            linkedorderno: fields.parse("linkedorderno")?,
+            expdate: fields.time("expdate")?,
+            status: fields.status(),
+            stoploss: xml_elements(record, b"stoploss").next().map(StopLoss::parse).transpose()?,
+            takeprofit: xml_elements(record, b"takeprofit")
+                .next()
+                .map(TakeProfit::parse)
+                .transpose()?,
+            result: fields.string("result"),
+        })
+    }
+}
+
+impl StopLoss {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            activationprice: fields.parse("activationprice")?,
+            orderprice: fields.parse("orderprice")?,
+            quantity: fields.string("quantity"),
+            guardtime: fields.string("guardtime"),
+            brokerref: fields.string("brokerref"),
+        })
+    }
+}
+
+impl TakeProfit {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            activationprice: fields.parse("activationprice")?,
+            quantity: fields.string("quantity"),
+            correction: fields.string("correction"),
+            guardspread: fields.string("guardspread"),
+            guardtime: fields.string("guardtime"),
+            brokerref: fields.string("brokerref"),
+        })
+    }
+}
+
+/// Состояние заявок `<orders>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrdersResponse {
+    /// Заявки
+    pub orders: Vec<OrderUpdate>,
+    /// Условные заявки
+    pub stoporders: Vec<StopOrderUpdate>,
+}
+
+impl OrdersResponse {
+    /// Разбирает сообщение `<orders>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<orders>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует идентификатор транзакции заявки
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "orders")?;
+        let orders =
+            xml_elements(bytes, b"order").map(OrderUpdate::parse).collect::<Result<_, _>>()?;
+        let stoporders = xml_elements(bytes, b"stoporder")
+            .map(StopOrderUpdate::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { orders, stoporders })
+    }
+}
+
+/// Сделка клиента, элемент `<trade>` сообщения `<trades>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientTrade {
+    /// Биржевой номер сделки
+    pub tradeno: Option<u64>,
+    /// Биржевой номер заявки
+    pub orderno: Option<u64>,
+    /// Внутренний идентификатор инструмента
+    pub secid: Option<u32>,
+    /// Режим торгов
+    pub board: String,
+    /// Код инструмента
+    pub seccode: String,
+    /// Клиент
+    pub client: String,
+    /// Единый денежный счёт
+    pub union: String,
+    /// Направление сделки
+    pub buysell: Option<BuySell>,
+    /// Время сделки
+    pub time: Option<Timestamp>,
+    /// Примечание
+    pub brokerref: String,
+    /// Объём сделки в денежном выражении
+    pub value: Option<Decimal>,
+    /// Комиссия
+    pub comission: Option<Decimal>,
+    /// Цена
+    pub price: Option<Decimal>,
+    /// Количество, штук
+    pub items: Option<u64>,
+    /// Количество, лотов
+    pub quantity: Option<u32>,
+    /// Доходность, только для облигаций
+    pub yield_: Option<Decimal>,
+    /// Текущая позиция по инструменту
+    pub currentpos: Option<i64>,
+    /// НКД, только для облигаций
+    pub accruedint: Option<Decimal>,
+    /// Тип сделки(`T` - обычная, `N` - РПС, `R` - РЕПО, `P` - размещение)
+    pub tradetype: String,
+    /// Код поставки
+    pub settlecode: String,
+}
+
+impl ClientTrade {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            tradeno: fields.parse("tradeno")?,
+            orderno: fields.parse("orderno")?,
+            secid: fields.parse("secid")?,
+            board: fields.string("board"),
+            seccode: fields.string("seccode"),
+            client: fields.string("client"),
+            union: fields.string("union"),
+            buysell: fields.buysell()?,
+            time: fields.time("time")?,
+            brokerref: fields.string("brokerref"),
+            value: fields.parse("value")?,
+            comission: fields.parse("comission")?,
+            price: fields.parse("price")?,
+            items: fields.parse("items")?,
+            quantity: fields.parse("quantity")?,
+            yield_: fields.parse("yield")?,
+            currentpos: fields.parse("currentpos")?,
+            accruedint: fields.parse("accruedint")?,
+            tradetype: fields.string("tradetype"),
+            settlecode: fields.string("settlecode"),
+        })
+    }
+}
+
+/// Сделки клиента `<trades>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TradesResponse {
+    /// Сделки
+    pub trades: Vec<ClientTrade>,
+}
+
+impl TradesResponse {
+    /// Разбирает сообщение `<trades>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<trades>`
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "trades")?;
+        let trades =
+            xml_elements(bytes, b"trade").map(ClientTrade::parse).collect::<Result<_, _>>()?;
+        Ok(Self { trades })
+    }
+}
+
+/// Время в формате коннектора `дд.мм.гггг чч:мм:сс[.ммм]`
+///
+/// Упорядочивание соответствует хронологическому, миллисекунды равны нулю, если коннектор
+/// подключен без параметра `milliseconds`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timestamp {
+    /// Год
+    pub year: u16,
+    /// Месяц, 1..=12
+    pub month: u8,
+    /// День месяца, 1..=31
+    pub day: u8,
+    /// Час, 0..=23
+    pub hour: u8,
+    /// Минута, 0..=59
+    pub minute: u8,
+    /// Секунда, 0..=59
+    pub second: u8,
+    /// Миллисекунда, 0..=999
+    pub millisecond: u16,
+}
+
+impl FromStr for Timestamp {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || invalid_value("time", s.as_bytes());
+        // every component is a fixed width run of ASCII digits
+        let number = |part: &str, width: usize| {
+            Some(part)
+                .filter(|p| p.len() == width && p.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|p| p.parse::<u16>().ok())
+                .ok_or_else(invalid)
+        };
+
+        let (date, time) = s.trim().split_once(' ').ok_or_else(invalid)?;
+        let (time, millisecond) = match time.split_once('.') {
+            Some((time, ms)) => (time, number(ms, 3)?),
+            None => (time, 0),
+        };
+        let mut date = date.split('.');
+        let mut time = time.split(':');
+        let next = |parts: &mut std::str::Split<'_, char>, width| {
+            parts.next().ok_or_else(invalid).and_then(|part| number(part, width))
+        };
+
+        let ts = Self {
+            day: next(&mut date, 2)? as u8,
+            month: next(&mut date, 2)? as u8,
+            year: next(&mut date, 4)?,
+            hour: next(&mut time, 2)? as u8,
+            minute: next(&mut time, 2)? as u8,
+            second: next(&mut time, 2)? as u8,
+            millisecond,
+        };
+        let valid = date.next().is_none()
+            && time.next().is_none()
+            && (1..=12).contains(&ts.month)
+            && (1..=31).contains(&ts.day)
+            && ts.hour < 24
+            && ts.minute < 60
+            && ts.second < 60;
+        if valid {
+            Ok(ts)
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}.{:02}.{:04} {:02}:{:02}:{:02}",
+            self.day, self.month, self.year, self.hour, self.minute, self.second
+        )?;
+        if self.millisecond != 0 {
+            write!(f, ".{:03}", self.millisecond)?;
+        }
+        Ok(())
+    }
+}
+
+/// Признак полноты ответа на запрос исторических данных, атрибут `status` сообщения
+/// `<candles>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CandlesStatus {
+    /// `0` - данных больше нет, история исчерпана
+    Exhausted,
+    /// `1` - заказанное количество свечей выдано, более ранние свечи можно запросить
+    /// повторно с `reset=false`
+    Delivered,
+    /// `2` - продолжение следует, ответ будет передан ещё одним сообщением
+    Partial,
+    /// `3` - данные недоступны, имеет смысл повторить запрос позже
+    Unavailable,
+}
+
+/// Свеча, элемент `<candle>` сообщения `<candles>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Candle {
+    /// Время начала периода свечи, атрибут `date`
+    pub date: Timestamp,
+    /// Цена открытия
+    pub open: Decimal,
+    /// Максимальная цена
+    pub high: Decimal,
+    /// Минимальная цена
+    pub low: Decimal,
+    /// Цена закрытия
+    pub close: Decimal,
+    /// Объём, лотов
+    pub volume: u64,
+    /// Открытый интерес, только для фьючерсов и опционов
+    pub oi: Option<u64>,
+}
+
+impl Candle {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let required = |attr: &'static str| {
+            parse_attr(record, attr)?.ok_or(ParseError::MissingAttribute(attr))
+        };
+        Ok(Self {
+            date: parse_attr(record, "date")?.ok_or(ParseError::MissingAttribute("date"))?,
+            open: required("open")?,
+            high: required("high")?,
+            low: required("low")?,
+            close: required("close")?,
+            volume: parse_attr(record, "volume")?.unwrap_or_default(),
+            oi: parse_attr(record, "oi")?,
+        })
+    }
+}
+
+/// Исторические данные `<candles>`, ответ на команду `gethistorydata`
+///
+/// Ответ на один запрос может быть передан несколькими сообщениями, см. [`CandlesStatus`] и
+/// [`HistoryFetcher`](crate::HistoryFetcher).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Candles {
+    /// Внутренний идентификатор инструмента
+    pub secid: Option<u32>,
+    /// Режим торгов
+    pub board: String,
+    /// Код инструмента
+    pub seccode: String,
+    /// Идентификатор периода, см. сообщение `<candlekinds>`
+    pub period: u32,
+    /// Признак полноты ответа
+    pub status: CandlesStatus,
+    /// Свечи в хронологическом порядке
+    pub candles: Vec<Candle>,
+}
+
+impl Candles {
+    /// Разбирает сообщение `<candles>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<candles>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует период, признак полноты или параметр
+    ///   свечи
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "candles")?;
+        let status = match xml_attr(bytes, b"status") {
+            Some(b"0") => CandlesStatus::Exhausted,
+            Some(b"1") => CandlesStatus::Delivered,
+            Some(b"2") => CandlesStatus::Partial,
+            Some(b"3") => CandlesStatus::Unavailable,
+            Some(value) => return Err(invalid_value("status", value)),
+            None => return Err(ParseError::MissingAttribute("status")),
+        };
+        let candles =
+            xml_elements(bytes, b"candle").map(Candle::parse).collect::<Result<_, _>>()?;
+        Ok(Self {
+            secid: parse_attr(bytes, "secid")?,
+            board: attr_string(bytes, b"board").unwrap_or_default(),
+            seccode: attr_string(bytes, b"seccode").unwrap_or_default(),
+            period: parse_attr(bytes, "period")?.ok_or(ParseError::MissingAttribute("period"))?,
+            status,
+            candles,
+        })
+    }
+}
+
+/// Изменение объёма уровня стакана, элементы `<buy>`/`<sell>` записи `<quote>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuoteVolume {
+    /// Новый объём заявок на уровне, лотов
+    Volume(u64),
+    /// Уровень удалён из стакана, значение `-1`
+    Removed,
+}
+
+/// Изменение уровня стакана, элемент `<quote>` сообщения `<quotes>`
+///
+/// Коннектор передаёт только изменения стакана: отсутствие `buy` или `sell` означает, что
+/// объём на этой стороне уровня не изменился, [`QuoteVolume::Removed`] - что заявок на этой
+/// стороне уровня больше нет.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuoteEntry {
+    /// Внутренний идентификатор инструмента, атрибут `secid`
+    pub secid: Option<u32>,
+    /// Режим торгов
+    pub board: String,
+    /// Код инструмента
+    pub seccode: String,
+    /// Цена уровня
+    pub price: Decimal,
+    /// Доходность, только для облигаций
+    pub yield_: Option<Decimal>,
+    /// Объём заявок на покупку
+    pub buy: Option<QuoteVolume>,
+    /// Объём заявок на продажу
+    pub sell: Option<QuoteVolume>,
+    /// Источник котировки, маркетмейкер
+    pub source: String,
+}
+
+impl QuoteEntry {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        let volume = |tag| {
+            fields.parse::<i64>(tag)?.map_or(Ok(None), |v| match v {
+                -1 => Ok(Some(QuoteVolume::Removed)),
+                v => u64::try_from(v)
+                    .map(|v| Some(QuoteVolume::Volume(v)))
+                    .map_err(|_| invalid_value(tag, v.to_string().as_bytes())),
+            })
+        };
+        Ok(Self {
+            secid: parse_attr(record, "secid")?,
+            board: fields.string("board"),
+            seccode: fields.string("seccode"),
+            price: fields.parse("price")?.ok_or(ParseError::MissingAttribute("price"))?,
+            yield_: fields.parse("yield")?,
+            buy: volume("buy")?,
+            sell: volume("sell")?,
+            source: fields.string("source"),
+        })
+    }
+
+    /// Изменение удаляет уровень из стакана: одна из сторон уровня удалена, а другая не
+    /// получила нового объёма
+    pub fn is_removal(&self) -> bool {
+        match (self.buy, self.sell) {
+            (Some(QuoteVolume::Removed), other) | (other, Some(QuoteVolume::Removed)) => {
+                !matches!(other, Some(QuoteVolume::Volume(v)) if v > 0)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Изменения стакана `<quotes>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuotesResponse {
+    /// Изменённые уровни стакана в порядке следования
+    pub quotes: Vec<QuoteEntry>,
+}
+
+impl QuotesResponse {
+    /// Разбирает сообщение `<quotes>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<quotes>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует цена уровня
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "quotes")?;
+        let quotes =
+            xml_elements(bytes, b"quote").map(QuoteEntry::parse).collect::<Result<_, _>>()?;
+        Ok(Self { quotes })
+    }
+}
+
+/// Сделка на рынке, элемент `<trade>` сообщения `<alltrades>` или `<tick>` сообщения `<ticks>`
+///
+/// Время сделки содержит миллисекунды, если подключение выполнено с параметром
+/// `milliseconds=true`, см. [`Timestamp`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MarketTrade {
+    /// Внутренний идентификатор инструмента
+    pub secid: Option<u32>,
+    /// Режим торгов
+    pub board: String,
+    /// Код инструмента
+    pub seccode: String,
+    /// Биржевой номер сделки
+    pub tradeno: Option<u64>,
+    /// Время сделки
+    pub time: Timestamp,
+    /// Цена
+    pub price: Decimal,
+    /// Количество, лотов
+    pub quantity: u64,
+    /// Направление сделки, по заявке инициатора
+    pub buysell: Option<BuySell>,
+    /// Открытый интерес, только для фьючерсов и опционов
+    pub openinterest: Option<i64>,
+    /// Период торгов(`O` - открытие, `N` - торги, `C` - закрытие, etc.)
+    pub period: String,
+}
+
+impl MarketTrade {
+    // `time_tag` is `time` for `<alltrades>` and `tradetime` for `<ticks>`
+    fn parse(record: &[u8], time_tag: &'static str) -> Result<Self, ParseError> {
+        let string = |value| xml_unescape(value).into_owned();
+        let (mut time, mut price) = (None, None);
+        let mut trade = Self {
+            secid: parse_attr(record, "secid")?,
+            board: String::new(),
+            seccode: String::new(),
+            tradeno: None,
+            time: Timestamp::default(),
+            price: Decimal::ZERO,
+            quantity: 0,
+            buysell: None,
+            openinterest: None,
+            period: String::new(),
+        };
+        // the streams are high volume, the fields are collected in a single pass over the record
+        for (tag, value) in xml_leaf_elements(record) {
+            match tag {
+                b"secid" => trade.secid = Some(parse_value("secid", value)?),
+                b"board" => trade.board = string(value),
+                b"seccode" => trade.seccode = string(value),
+                b"tradeno" => trade.tradeno = Some(parse_value("tradeno", value)?),
+                b"price" => price = Some(parse_value("price", value)?),
+                b"quantity" => trade.quantity = parse_value("quantity", value)?,
+                b"buysell" => trade.buysell = Some(parse_buysell(value)?),
+                b"openinterest" => trade.openinterest = Some(parse_value("openinterest", value)?),
+                b"period" => trade.period = string(value),
+                tag if tag == time_tag.as_bytes() => time = Some(parse_value(time_tag, value)?),
+                _ => {}
+            }
+        }
+        trade.time = time.ok_or(ParseError::MissingAttribute(time_tag))?;
+        trade.price = price.ok_or(ParseError::MissingAttribute("price"))?;
+        Ok(trade)
+    }
+}
+
+/// Сделки на рынке `<alltrades>`, см. [`SubscribeTicksCommand`](crate::SubscribeTicksCommand)
+/// и команду `subscribe`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllTradesResponse {
+    /// Сделки
+    pub trades: Vec<MarketTrade>,
+}
+
+impl AllTradesResponse {
+    /// Разбирает сообщение `<alltrades>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<alltrades>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует время или цена сделки
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "alltrades")?;
+        let trades = xml_elements(bytes, b"trade")
+            .map(|record| MarketTrade::parse(record, "time"))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { trades })
+    }
+}
+
+/// Тиковые данные `<ticks>`, ответ на команду `subscribe_ticks`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TicksResponse {
+    /// Сделки
+    pub ticks: Vec<MarketTrade>,
+}
+
+impl TicksResponse {
+    /// Разбирает сообщение `<ticks>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<ticks>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует время или цена сделки
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "ticks")?;
+        let ticks = xml_elements(bytes, b"tick")
+            .map(|record| MarketTrade::parse(record, "tradetime"))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { ticks })
+    }
+}
+
+/// Денежная позиция, элемент `<money_position>` сообщения `<positions>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoneyPosition {
+    /// Клиент
+    pub client: String,
+    /// Единый денежный счёт
+    pub union: String,
+    /// Код вида средств
+    pub asset: String,
+    /// Регистр учёта
+    pub register: String,
+    /// Наименование вида средств
+    pub shortname: String,
+    /// Входящий остаток
+    pub saldoin: Option<Decimal>,
+    /// Затрачено на покупки
+    pub bought: Option<Decimal>,
+    /// Выручено от продаж
+    pub sold: Option<Decimal>,
+    /// Текущий остаток
+    pub saldo: Option<Decimal>,
+    /// В заявках на покупку и комиссии
+    pub ordbuy: Option<Decimal>,
+    /// В условных заявках на покупку и комиссии
+    pub ordbuycond: Option<Decimal>,
+    /// Сумма списанной комиссии
+    pub comission: Option<Decimal>,
+}
+
+impl MoneyPosition {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            client: fields.string("client"),
+            union: fields.string("union"),
+            asset: fields.string("asset"),
+            register: fields.string("register"),
+            shortname: fields.string("shortname"),
+            saldoin: fields.parse("saldoin")?,
+            bought: fields.parse("bought")?,
+            sold: fields.parse("sold")?,
+            saldo: fields.parse("saldo")?,
+            ordbuy: fields.parse("ordbuy")?,
+            ordbuycond: fields.parse("ordbuycond")?,
+            comission: fields.parse("comission")?,
+        })
+    }
+}
+
+/// Позиция по бумаге, элемент `<sec_position>` сообщения `<positions>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecPosition {
+    /// Внутренний идентификатор инструмента
+    pub secid: Option<u32>,
+    /// Идентификатор рынка
+    pub market: Option<u32>,
+    /// Код инструмента
+    pub seccode: String,
+    /// Регистр учёта
+    pub register: String,
+    /// Клиент
+    pub client: String,
+    /// Единый денежный счёт
+    pub union: String,
+    /// Наименование бумаги
+    pub shortname: String,
+    /// Входящий остаток, штук
+    pub saldoin: Option<Decimal>,
+    /// Минимальный остаток, штук
+    pub saldomin: Option<Decimal>,
+    /// Куплено, штук
+    pub bought: Option<Decimal>,
+    /// Продано, штук
+    pub sold: Option<Decimal>,
+    /// Текущий остаток, штук
+    pub saldo: Option<Decimal>,
+    /// В заявках на покупку, штук
+    pub ordbuy: Option<Decimal>,
+    /// В заявках на продажу, штук
+    pub ordsell: Option<Decimal>,
+    /// Сумма позиции по цене последней сделки
+    pub amount: Option<Decimal>,
+    /// Оценка позиции
+    pub equity: Option<Decimal>,
+}
+
+impl SecPosition {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            secid: fields.parse("secid")?,
+            market: fields.parse("market")?,
+            seccode: fields.string("seccode"),
+            register: fields.string("register"),
+            client: fields.string("client"),
+            union: fields.string("union"),
+            shortname: fields.string("shortname"),
+            saldoin: fields.parse("saldoin")?,
+            saldomin: fields.parse("saldomin")?,
+            bought: fields.parse("bought")?,
+            sold: fields.parse("sold")?,
+            saldo: fields.parse("saldo")?,
+            ordbuy: fields.parse("ordbuy")?,
+            ordsell: fields.parse("ordsell")?,
+            amount: fields.parse("amount")?,
+            equity: fields.parse("equity")?,
+        })
+    }
+}
+
+/// Позиция по контракту FORTS, элемент `<forts_position>` сообщения `<positions>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FortsPosition {
+    /// Внутренний идентификатор инструмента
+    pub secid: Option<u32>,
+    /// Код инструмента
+    pub seccode: String,
+    /// Клиент
+    pub client: String,
+    /// Единый денежный счёт
+    pub union: String,
+    /// Входящая позиция, контрактов
+    pub startnet: Option<Decimal>,
+    /// В заявках на покупку, контрактов
+    pub openbuys: Option<Decimal>,
+    /// В заявках на продажу, контрактов
+    pub opensells: Option<Decimal>,
+    /// Текущая позиция, контрактов
+    pub totalnet: Option<Decimal>,
+    /// Куплено, контрактов
+    pub todaybuy: Option<Decimal>,
+    /// Продано, контрактов
+    pub todaysell: Option<Decimal>,
+    /// Маржа для маржируемых опционов
+    pub optmargin: Option<Decimal>,
+    /// Вариационная маржа
+    pub varmargin: Option<Decimal>,
+    /// Опционов в заявках на исполнение
+    pub expirationpos: Option<Decimal>,
+    /// Объём использованного спот-лимита на продажу
+    pub usedsellspotlimit: Option<Decimal>,
+    /// Текущий спот-лимит на продажу
+    pub sellspotlimit: Option<Decimal>,
+    /// Нетто-позиция по всем инструментам данного спота
+    pub netto: Option<Decimal>,
+    /// Коэффициент ГО для спота
+    pub kgo: Option<Decimal>,
+}
+
+impl FortsPosition {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            secid: fields.parse("secid")?,
+            seccode: fields.string("seccode"),
+            client: fields.string("client"),
+            union: fields.string("union"),
+            startnet: fields.parse("startnet")?,
+            openbuys: fields.parse("openbuys")?,
+            opensells: fields.parse("opensells")?,
+            totalnet: fields.parse("totalnet")?,
+            todaybuy: fields.parse("todaybuy")?,
+            todaysell: fields.parse("todaysell")?,
+            optmargin: fields.parse("optmargin")?,
+            varmargin: fields.parse("varmargin")?,
+            expirationpos: fields.parse("expirationpos")?,
+            usedsellspotlimit: fields.parse("usedsellspotlimit")?,
+            sellspotlimit: fields.parse("sellspotlimit")?,
+            netto: fields.parse("netto")?,
+            kgo: fields.parse("kgo")?,
+        })
+    }
+}
+
+/// Деньги на срочном рынке FORTS, элемент `<forts_money>` сообщения `<positions>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FortsMoney {
+    /// Клиент
+    pub client: String,
+    /// Единый денежный счёт
+    pub union: String,
+    /// Наименование вида средств
+    pub shortname: String,
+    /// Текущие средства
+    pub current: Option<Decimal>,
+    /// Заблокировано в заявках и позициях
+    pub blocked: Option<Decimal>,
+    /// Свободные средства
+    pub free: Option<Decimal>,
+    /// Вариационная маржа
+    pub varmargin: Option<Decimal>,
+}
+
+impl FortsMoney {
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        Ok(Self {
+            client: fields.string("client"),
+            union: fields.string("union"),
+            shortname: fields.string("shortname"),
+            current: fields.parse("current")?,
+            blocked: fields.parse("blocked")?,
+            free: fields.parse("free")?,
+            varmargin: fields.parse("varmargin")?,
+        })
+    }
+}
+
+/// Позиции клиента `<positions>`
+///
+/// Коннектор передаёт только изменившиеся позиции, см. [`PortfolioState`](crate::PortfolioState).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionsResponse {
+    /// Денежные позиции
+    pub money: Vec<MoneyPosition>,
+    /// Позиции по бумагам
+    pub securities: Vec<SecPosition>,
+    /// Позиции по контрактам FORTS
+    pub forts: Vec<FortsPosition>,
+    /// Деньги на срочном рынке FORTS
+    pub forts_money: Vec<FortsMoney>,
+}
+
+impl PositionsResponse {
+    /// Разбирает сообщение `<positions>`
+    ///
+    /// Элементы, отличные от перечисленных в [`PositionsResponse`], например `<spot_limit>`, не
+    /// учитываются.
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<positions>`
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "positions")?;
+        Ok(Self {
+            money: xml_elements(bytes, b"money_position")
+                .map(MoneyPosition::parse)
+                .collect::<Result<_, _>>()?,
+            securities: xml_elements(bytes, b"sec_position")
+                .map(SecPosition::parse)
+                .collect::<Result<_, _>>()?,
+            forts: xml_elements(bytes, b"forts_position")
+                .map(FortsPosition::parse)
+                .collect::<Result<_, _>>()?,
+            forts_money: xml_elements(bytes, b"forts_money")
+                .map(FortsMoney::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Единый денежный счёт `<united_limits>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnitedLimits {
+    /// Код единого денежного счёта, атрибут `union`
+    pub union: String,
+    /// Входящая оценка стоимости портфеля
+    pub open_equity: Option<Decimal>,
+    /// Текущая оценка стоимости портфеля
+    pub equity: Option<Decimal>,
+    /// Начальные требования
+    pub requirements: Option<Decimal>,
+    /// Свободные средства
+    pub free: Option<Decimal>,
+    /// Вариационная маржа
+    pub vm: Option<Decimal>,
+    /// Финансовый результат последнего клиринга
+    pub finres: Option<Decimal>,
+    /// Размер требуемого гарантийного обеспечения
+    pub go: Option<Decimal>,
+}
+
+impl UnitedLimits {
+    /// Разбирает сообщение `<united_limits>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<united_limits>`
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "united_limits")?;
+        let fields = Fields(bytes);
+        Ok(Self {
+            union: attr_string(bytes, b"union").unwrap_or_default(),
+            open_equity: fields.parse("open_equity")?,
+            equity: fields.parse("equity")?,
+            requirements: fields.parse("requirements")?,
+            free: fields.parse("free")?,
+            vm: fields.parse("vm")?,
+            finres: fields.parse("finres")?,
+            go: fields.parse("go")?,
+        })
+    }
+}
+
+/// Клиентский счёт `<client>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientInfo {
+    /// Идентификатор клиента, атрибут `id`
+    pub id: String,
+    /// Счёт удалён, атрибут `remove`
+    pub remove: bool,
+    /// Тип клиента(`spot`, `leverage`, `mct`)
+    pub type_: String,
+    /// Валюта фондового портфеля клиента
+    pub currency: String,
+    /// Идентификатор рынка
+    pub market: Option<u32>,
+    /// Единый денежный счёт
+    pub union: String,
+    /// Счёт FORTS
+    pub forts_acc: String,
+}
+
+impl ClientInfo {
+    /// Разбирает сообщение `<client>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<client>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует идентификатор клиента
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "client")?;
+        let fields = Fields(bytes);
+        Ok(Self {
+            id: attr_string(bytes, b"id").ok_or(ParseError::MissingAttribute("id"))?,
+            remove: xml_attr(bytes, b"remove")
+                .map(|v| parse_bool("remove", v))
+                .transpose()?
+                .unwrap_or_default(),
+            type_: fields.string("type"),
+            currency: fields.string("currency"),
+            market: fields.parse("market")?,
+            union: fields.string("union"),
+            forts_acc: fields.string("forts_acc"),
+        })
+    }
+}
+
+/// Рынок, элемент `<market>` сообщения `<markets>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Market {
+    /// Идентификатор рынка, атрибут `id`
+    pub id: u32,
+    /// Наименование рынка
+    pub name: String,
+}
+
+/// Список рынков `<markets>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MarketsResponse {
+    /// Рынки
+    pub markets: Vec<Market>,
+}
+
+impl MarketsResponse {
+    /// Разбирает сообщение `<markets>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<markets>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует идентификатор рынка
+    /// - [`ParseError::InvalidValue`] - недопустимый идентификатор рынка
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "markets")?;
+        let markets = xml_elements(bytes, b"market")
+            .map(|record| {
+                Ok(Market {
+                    id: parse_attr(record, "id")?.ok_or(ParseError::MissingAttribute("id"))?,
+                    name: xml_text(record)
+                        .map(|v| xml_unescape(v).into_owned())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect::<Result<_, ParseError>>()?;
+        Ok(Self { markets })
+    }
+}
+
+/// Режим торгов, элемент `<board>` сообщения `<boards>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Board {
+    /// Код режима торгов, атрибут `id`
+    pub id: String,
+    /// Наименование режима торгов
+    pub name: String,
+    /// Идентификатор рынка
+    pub market: Option<u32>,
+    /// Тип режима торгов: `0` - FORTS, `1` - T+0, `2` - T+2
+    pub type_: Option<u32>,
+}
+
+/// Список режимов торгов `<boards>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoardsResponse {
+    /// Режимы торгов
+    pub boards: Vec<Board>,
+}
+
+impl BoardsResponse {
+    /// Разбирает сообщение `<boards>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<boards>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует код режима торгов
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "boards")?;
+        let boards = xml_elements(bytes, b"board")
+            .map(|record| {
+                let fields = Fields(record);
+                Ok(Board {
+                    id: attr_string(record, b"id").ok_or(ParseError::MissingAttribute("id"))?,
+                    name: fields.string("name"),
+                    market: fields.parse("market")?,
+                    type_: fields.parse("type")?,
+                })
+            })
+            .collect::<Result<_, ParseError>>()?;
+        Ok(Self { boards })
+    }
+}
+
+/// Период свечей, элемент `<kind>` сообщения `<candlekinds>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CandleKind {
+    /// Идентификатор периода, используется в команде `gethistorydata`
+    pub id: u32,
+    /// Длительность периода, сек.
+    pub period: u32,
+    /// Наименование периода
+    pub name: String,
+}
+
+/// Список доступных периодов свечей `<candlekinds>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CandleKindsResponse {
+    /// Периоды свечей
+    pub kinds: Vec<CandleKind>,
+}
+
+impl CandleKindsResponse {
+    /// Разбирает сообщение `<candlekinds>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<candlekinds>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует идентификатор или длительность периода
+    /// - [`ParseError::InvalidValue`] - недопустимое значение одного из параметров
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "candlekinds")?;
+        let kinds = xml_elements(bytes, b"kind")
+            .map(|record| {
+                let fields = Fields(record);
+                Ok(CandleKind {
+                    id: fields.parse("id")?.ok_or(ParseError::MissingAttribute("id"))?,
+                    period: fields
+                        .parse("period")?
+                        .ok_or(ParseError::MissingAttribute("period"))?,
+                    name: fields.string("name"),
+                })
+            })
+            .collect::<Result<_, ParseError>>()?;
+        Ok(Self { kinds })
+    }
+}
+
+/// Единый денежный счёт клиента `<union>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnionInfo {
+    /// Код единого денежного счёта, атрибут `id`
+    pub id: String,
+    /// Счёт удалён, атрибут `remove`
+    pub remove: bool,
+}
+
+impl UnionInfo {
+    /// Разбирает сообщение `<union>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<union>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует код счёта
+    /// - [`ParseError::InvalidValue`] - недопустимое значение `remove`
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "union")?;
+        Ok(Self {
+            id: attr_string(bytes, b"id").ok_or(ParseError::MissingAttribute("id"))?,
+            remove: xml_attr(bytes, b"remove")
+                .map(|v| parse_bool("remove", v))
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Заголовок новости `<news_header>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewsHeader {
+    /// Идентификатор новости, используется для запроса текста новости
+    pub id: u64,
+    /// Время публикации
+    pub time: Option<Timestamp>,
+    /// Источник новости
+    pub source: String,
+    /// Заголовок
+    pub title: String,
+}
+
+impl NewsHeader {
+    /// Разбирает сообщение `<news_header>`
+    ///
+    /// Текст в кодировке windows-1251 декодируется при включенной опции **encoding**, см.
+    /// [`crate`], без неё некорректные символы заменяются на `U+FFFD`.
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<news_header>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует идентификатор новости
+    /// - [`ParseError::InvalidValue`] - недопустимый идентификатор или время публикации
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "news_header")?;
+        let fields = Fields(bytes);
+        Ok(Self {
+            id: fields.parse("id")?.ok_or(ParseError::MissingAttribute("id"))?,
+            time: fields.time("timestamp")?,
+            source: fields.text("source"),
+            title: fields.text("title"),
+        })
+    }
+}
+
+/// Текст новости `<news_body>`, ответ на команду `get_news_body`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NewsBody {
+    /// Идентификатор новости
+    pub id: u64,
+    /// Текст новости
+    pub text: String,
+}
+
+impl NewsBody {
+    /// Разбирает сообщение `<news_body>`
+    ///
+    /// Текст в кодировке windows-1251 декодируется при включенной опции **encoding**, см.
+    /// [`NewsHeader::parse`].
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<news_body>`
+    /// - [`ParseError::MissingAttribute`] - отсутствует идентификатор новости
+    /// - [`ParseError::InvalidValue`] - недопустимый идентификатор новости
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "news_body")?;
+        let fields = Fields(bytes);
+        Ok(Self {
+            id: fields.parse("id")?.ok_or(ParseError::MissingAttribute("id"))?,
+            text: fields.text("text"),
+        })
+    }
+}
+
+/// Сообщение брокера, элемент `<message>` сообщения `<messages>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BrokerMessage {
+    /// Время сообщения
+    pub date: Option<Timestamp>,
+    /// Срочное сообщение, например требование о пополнении счёта
+    pub urgent: bool,
+    /// Отправитель
+    pub from: String,
+    /// Текст сообщения
+    pub text: String,
+}
+
+impl BrokerMessage {
+    // the values are expected in the child elements, the attributes are accepted as well
+    fn parse(record: &[u8]) -> Result<Self, ParseError> {
+        let fields = Fields(record);
+        let value = |name: &'static str| {
+            xml_attr(record, name.as_bytes()).or_else(|| xml_child_text(record, name.as_bytes()))
+        };
+        let date = match value("date") {
+            Some(date) if !date.is_empty() => Some(parse_value("date", date)?),
+            _ => None,
+        };
+        let urgent = match value("urgent") {
+            None => false,
+            Some(b"Y" | b"true") => true,
+            Some(b"N" | b"false") => false,
+            Some(urgent) => return Err(invalid_value("urgent", urgent)),
+        };
+        let text = match xml_child_text(record, b"text") {
+            Some(text) => decode_text(text),
+            None => xml_text(record).map(decode_text).unwrap_or_default(),
+        };
+        Ok(Self { date, urgent, from: fields.text("from"), text })
+    }
+}
+
+/// Сообщения брокера `<messages>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessagesResponse {
+    /// Сообщения
+    pub messages: Vec<BrokerMessage>,
+}
+
+impl MessagesResponse {
+    /// Разбирает сообщение `<messages>`
+    ///
+    /// Признак срочности `urgent` принимает значения `Y`/`N`(или `true`/`false`), другие значения
+    /// считаются ошибкой.
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<messages>`
+    /// - [`ParseError::InvalidValue`] - недопустимое время или признак срочности сообщения
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "messages")?;
+        let messages =
+            xml_elements(bytes, b"message").map(BrokerMessage::parse).collect::<Result<_, _>>()?;
+        Ok(Self { messages })
+    }
+}
+
+/// Сообщение о внутренней ошибке коннектора `<error>`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorResponse {
+    /// Текст ошибки
+    pub text: String,
+}
+
+impl ErrorResponse {
+    /// Разбирает сообщение `<error>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<error>`
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "error")?;
+        Ok(Self { text: xml_text(bytes).map(decode_text).unwrap_or_default() })
+    }
+}
+
+/// Ответ коннектора на команду выставления заявки `<result success="true" transactionid=".."/>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SendAck {
+    /// Идентификатор транзакции, атрибут `transactionid`
+    pub transactionid: u64,
+}
+
+impl SendAck {
+    /// Разбирает ответ `<result>`
+    ///
+    /// # Errors
+    /// - [`ParseError::UnexpectedTag`] - сообщение не является `<result>`
+    /// - [`ParseError::MissingAttribute`] - ответ не содержит `transactionid`
+    /// - [`ParseError::InvalidValue`] - недопустимое значение `transactionid`
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        expect_tag(bytes, "result")?;
+        Ok(Self {
+            transactionid: parse_attr(bytes, "transactionid")?
+                .ok_or(ParseError::MissingAttribute("transactionid"))?,
+        })
+    }
+}
+
+impl_try_from_buffers!(
+    SecuritiesResponse,
+    OrdersResponse,
+    TradesResponse,
+    Candles,
+    QuotesResponse,
+    AllTradesResponse,
+    TicksResponse,
+    PositionsResponse,
+    UnitedLimits,
+    ClientInfo,
+    MarketsResponse,
+    BoardsResponse,
+    CandleKindsResponse,
+    UnionInfo,
+    NewsHeader,
+    NewsBody,
+    MessagesResponse,
+    ErrorResponse,
+    SendAck
+);
+
+/// Разобранное сообщение коннектора
+///
+/// Разбор устойчив к неизвестным атрибутам и элементам, новые типы сообщений могут быть
+/// добавлены в следующих версиях. См. так же [`Stream::messages`](crate::Stream::messages).
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{Message, Stream};
+///
+/// let mut txc = /*..*/;
+/// txc.input_stream().map(|buf| Message::parse(&buf)).subscribe(|msg| match msg {
+///     Message::ServerStatus(status) => println!("connected: {:?}", status.connected),
+///     Message::Orders(orders) => /*..*/,
+///     _ => {}
+/// })?;
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Message {
+    /// `<server_status>`
+    ServerStatus(ServerStatusResponse),
+    /// `<connector_version>`
+    ConnectorVersion(ConnectorVersionResponse),
+    /// `<result>`, ответ на команду
+    Result {
+        /// Команда выполнена успешно, атрибут `success`
+        success: bool,
+        /// Текст сообщения `<message>` об ошибке выполнения команды
+        body: String,
+    },
+    /// `<error>`, текст ошибки
+    Error(String),
+    /// `<securities>`
+    Securities(SecuritiesResponse),
+    /// `<orders>`
+    Orders(OrdersResponse),
+    /// `<trades>`
+    Trades(TradesResponse),
+    /// `<candles>`
+    Candles(Candles),
+    /// `<quotes>`
+    Quotes(QuotesResponse),
+    /// `<alltrades>`
+    AllTrades(AllTradesResponse),
+    /// `<ticks>`
+    Ticks(TicksResponse),
+    /// `<positions>`
+    Positions(PositionsResponse),
+    /// `<united_limits>`
+    UnitedLimits(UnitedLimits),
+    /// `<client>`
+    Client(ClientInfo),
+    /// `<markets>`
+    Markets(MarketsResponse),
+    /// `<boards>`
+    Boards(BoardsResponse),
+    /// `<candlekinds>`
+    CandleKinds(CandleKindsResponse),
+    /// `<union>`
+    Union(UnionInfo),
+    /// `<news_header>`
+    NewsHeader(NewsHeader),
+    /// `<news_body>`
+    NewsBody(NewsBody),
+    /// `<messages>`
+    Messages(MessagesResponse),
+    /// Сообщение неизвестного типа или сообщение, которое не удалось разобрать
+    Unknown {
+        /// Имя корневого тэга, пустое если сообщение не содержит тэга
+        tag: String,
+        /// Содержимое сообщения
+        raw: Vec<u8>,
+    },
+}
+
+impl Message {
+    /// Разбирает сообщение коннектора, см. [`Message::parse_bytes`]
+    #[inline]
+    pub fn parse(buf: &TCStr<'_>) -> Self {
+        Self::parse_bytes(buf.to_bytes())
+    }
+
+    /// Разбирает сообщение по имени корневого тэга
+    ///
+    /// Сообщения неизвестного типа, а так же сообщения, которые не удалось разобрать,
+    /// возвращаются как [`Message::Unknown`].
+    pub fn parse_bytes(bytes: &[u8]) -> Self {
+        Self::decode(bytes).unwrap_or_else(|_| Self::unknown(bytes))
+    }
+
+    // Decodes the message by the root tag, unknown messages are `Message::Unknown`
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, ParseError> {
+        match xml_tag_name(bytes) {
+            Some(b"server_status") => ServerStatusResponse::parse(bytes).map(Message::ServerStatus),
+            Some(b"connector_version") => {
+                ConnectorVersionResponse::parse(bytes).map(Message::ConnectorVersion)
+            }
+            Some(b"result") => xml_attr(bytes, b"success")
+                .ok_or(ParseError::MissingAttribute("success"))
+                .and_then(|v| parse_bool("success", v))
+                .map(|success| Message::Result { success, body: Fields(bytes).string("message") }),
+            Some(b"error") => ErrorResponse::parse(bytes).map(|error| Message::Error(error.text)),
+            Some(b"securities") => SecuritiesResponse::parse(bytes).map(Message::Securities),
+            Some(b"orders") => OrdersResponse::parse(bytes).map(Message::Orders),
+            Some(b"trades") => TradesResponse::parse(bytes).map(Message::Trades),
+            Some(b"candles") => Candles::parse(bytes).map(Message::Candles),
+            Some(b"quotes") => QuotesResponse::parse(bytes).map(Message::Quotes),
+            Some(b"alltrades") => AllTradesResponse::parse(bytes).map(Message::AllTrades),
+            Some(b"ticks") => TicksResponse::parse(bytes).map(Message::Ticks),
+            Some(b"positions") => PositionsResponse::parse(bytes).map(Message::Positions),
+            Some(b"united_limits") => UnitedLimits::parse(bytes).map(Message::UnitedLimits),
+            Some(b"client") => ClientInfo::parse(bytes).map(Message::Client),
+            Some(b"markets") => MarketsResponse::parse(bytes).map(Message::Markets),
+            Some(b"boards") => BoardsResponse::parse(bytes).map(Message::Boards),
+            Some(b"candlekinds") => CandleKindsResponse::parse(bytes).map(Message::CandleKinds),
+            Some(b"union") => UnionInfo::parse(bytes).map(Message::Union),
+            Some(b"news_header") => NewsHeader::parse(bytes).map(Message::NewsHeader),
+            Some(b"news_body") => NewsBody::parse(bytes).map(Message::NewsBody),
+            Some(b"messages") => MessagesResponse::parse(bytes).map(Message::Messages),
+            _ => Ok(Self::unknown(bytes)),
+        }
+    }
+
+    fn unknown(bytes: &[u8]) -> Self {
+        let tag = String::from_utf8_lossy(xml_tag_name(bytes).unwrap_or_default()).into_owned();
+        Message::Unknown { tag, raw: bytes.to_vec() }
+    }
+}
+
+impl From<&TCStr<'_>> for Message {
+    #[inline]
+    fn from(buf: &TCStr<'_>) -> Self {
+        Self::parse(buf)
+    }
+}
+
+impl From<&OwnedMessage> for Message {
+    #[inline]
+    fn from(msg: &OwnedMessage) -> Self {
+        Self::parse_bytes(msg.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffers::{alloc_buffer, free_buffer};
+
+    fn buf(msg: &str) -> TCStr<'static> {
+        TCStr::new(alloc_buffer(msg.as_bytes()), free_buffer)
+    }
+
+    #[test]
+    fn server_status_connected() {
+        let msg = "<server_status id=\"7\" connected=\"true\" recover=\"true\" \
+                   server_tz=\"Russian Standard Time\"/>";
+        let status = ServerStatusResponse::try_from(buf(msg)).unwrap();
+        assert_eq!(
+            status,
+            ServerStatusResponse {
+                connected: ConnectionStatus::True,
+                recover: true,
+                server: "7".into(),
+                timezone: "Russian Standard Time".into(),
+                text: None,
+            }
+        );
+        assert!(status.is_connected());
+        assert_eq!(ServerStatusResponse::try_from(&buf(msg)), Ok(status.clone()));
+        assert_eq!(ServerStatusResponse::try_from(&OwnedMessage::from(msg.as_bytes())), Ok(status));
+    }
+
+    #[test]
+    fn server_status_disconnected() {
+        let status = ServerStatusResponse::parse(b"<server_status connected=\"false\"/>").unwrap();
+        assert_eq!(status.connected, ConnectionStatus::False);
+        assert!(!status.is_connected());
+        assert!(!status.recover);
+        assert!(status.server.is_empty() && status.timezone.is_empty());
+        assert_eq!(status.text, None);
+    }
+
+    #[test]
+    fn server_status_error() {
+        let msg =
+            b"<server_status connected=\"error\"> Wrong login &amp; password </server_status>";
+        let status = ServerStatusResponse::parse(msg).unwrap();
+        assert_eq!(status.connected, ConnectionStatus::Error);
+        assert_eq!(status.text.as_deref(), Some("Wrong login & password"));
+
+        let status =
+            ServerStatusResponse::parse(b"<server_status connected=\"error\"></server_status>");
+        assert_eq!(status.unwrap().text, None);
+    }
+
+    #[test]
+    fn server_status_rejects_malformed() {
+        assert_eq!(
+            ServerStatusResponse::parse(b"<server_statuses connected=\"true\"/>"),
+            Err(ParseError::UnexpectedTag {
+                expected: "server_status",
+                found: "server_statuses".into()
+            })
+        );
+        assert_eq!(
+            ServerStatusResponse::parse(b""),
+            Err(ParseError::UnexpectedTag { expected: "server_status", found: String::new() })
+        );
+        assert_eq!(
+            ServerStatusResponse::parse(b"<server_status recover=\"true\"/>"),
+            Err(ParseError::MissingAttribute("connected"))
+        );
+        assert_eq!(
+            ServerStatusResponse::parse(b"<server_status connected=\"yes\"/>"),
+            Err(ParseError::InvalidValue { attr: "connected", value: "yes".into() })
+        );
+        assert_eq!(
+            ServerStatusResponse::parse(b"<server_status connected=\"true\" recover=\"1\"/>"),
+            Err(ParseError::InvalidValue { attr: "recover", value: "1".into() })
+        );
+    }
+
+    #[test]
+    fn connector_version() {
+        let msg = "<connector_version>6.19.2.21</connector_version>";
+        let version = ConnectorVersionResponse::try_from(buf(msg)).unwrap();
+        assert_eq!(version.as_str(), "6.19.2.21");
+        assert_eq!(version.to_string(), "6.19.2.21");
+        assert_eq!(version.parts(), [6, 19, 2, 21, 0]);
+        assert_eq!(ConnectorVersionResponse::try_from(&buf(msg)), Ok(version.clone()));
+        let owned = OwnedMessage::from(msg.as_bytes());
+        assert_eq!(ConnectorVersionResponse::try_from(&owned), Ok(version.clone()));
+        assert_eq!(version.into_string(), "6.19.2.21");
+
+        let version = ConnectorVersionResponse::parse(
+            b"<connector_version> 7.15.3.5.1\n</connector_version>",
+        );
+        assert_eq!(version.unwrap().parts(), [7, 15, 3, 5, 1]);
+    }
+
+    #[test]
+    fn connector_version_parts_of_unexpected_format() {
+        let parts = |version: &str| ConnectorVersionResponse(version.into()).parts();
+        assert_eq!(parts("6"), [6, 0, 0, 0, 0]);
+        assert_eq!(parts("6.19"), [6, 19, 0, 0, 0]);
+        assert_eq!(parts("1.2.3.4.5.6"), [0; 5]);
+        assert_eq!(parts("6.19.beta"), [0; 5]);
+        assert_eq!(parts("6..19"), [0; 5]);
+        assert_eq!(parts("6.256"), [0; 5]);
+    }
+
+    #[test]
+    fn connector_version_rejects_malformed() {
+        assert_eq!(
+            ConnectorVersionResponse::parse(b"<server_status connected=\"true\"/>"),
+            Err(ParseError::UnexpectedTag {
+                expected: "connector_version",
+                found: "server_status".into()
+            })
+        );
+        for msg in [
+            &b"<connector_version/>"[..],
+            b"<connector_version></connector_version>",
+            b"<connector_version> \n</connector_version>",
+        ] {
+            assert_eq!(ConnectorVersionResponse::parse(msg), Err(ParseError::MissingContent));
+        }
+    }
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn quotes_delta() {
+        let msg = "<quotes>\
+            <quote secid=\"3\"><board>TQBR</board><seccode>SBER</seccode><price>265.31</price>\
+            <source>MICEX</source><buy>14</buy></quote>\
+            <quote secid=\"3\"><board>TQBR</board><seccode>SBER</seccode><price>265.35</price>\
+            <sell>-1</sell></quote>\
+            <quote secid=\"3\"><board>TQBR</board><seccode>SBER</seccode><price>265.40</price>\
+            <buy>-1</buy><sell>120</sell></quote>\
+            <quote secid=\"1781\"><board>TQCB</board><seccode>SU26238RMFS4</seccode>\
+            <price>58.815</price><yield>12.54</yield><buy>0</buy></quote>\
+            </quotes>";
+        let quotes = QuotesResponse::try_from(&buf(msg)).unwrap().quotes;
+        assert_eq!(quotes.len(), 4);
+
+        assert_eq!(
+            quotes[0],
+            QuoteEntry {
+                secid: Some(3),
+                board: "TQBR".into(),
+                seccode: "SBER".into(),
+                price: dec("265.31"),
+                yield_: None,
+                buy: Some(QuoteVolume::Volume(14)),
+                sell: None,
+                source: "MICEX".into(),
+            }
+        );
+        assert!(!quotes[0].is_removal());
+
+        assert_eq!(quotes[1].price, dec("265.35"));
+        assert_eq!((quotes[1].buy, quotes[1].sell), (None, Some(QuoteVolume::Removed)));
+        assert!(quotes[1].source.is_empty());
+        assert!(quotes[1].is_removal());
+
+        // the level moved from the bid to the ask side
+        assert_eq!(
+            (quotes[2].buy, quotes[2].sell),
+            (Some(QuoteVolume::Removed), Some(QuoteVolume::Volume(120)))
+        );
+        assert!(!quotes[2].is_removal());
+
+        assert_eq!(quotes[3].secid, Some(1781));
+        assert_eq!(quotes[3].yield_, Some(dec("12.54")));
+        assert_eq!(quotes[3].buy, Some(QuoteVolume::Volume(0)));
+        assert!(!quotes[3].is_removal());
+    }
+
+    #[test]
+    fn quotes_removal() {
+        let entry = |buy, sell| QuoteEntry {
+            secid: None,
+            board: String::new(),
+            seccode: String::new(),
+            price: Decimal::ONE,
+            yield_: None,
+            buy,
+            sell,
+            source: String::new(),
+        };
+        let (removed, zero, some) = (
+            Some(QuoteVolume::Removed),
+            Some(QuoteVolume::Volume(0)),
+            Some(QuoteVolume::Volume(1)),
+        );
+
+        assert!(entry(removed, None).is_removal());
+        assert!(entry(None, removed).is_removal());
+        assert!(entry(removed, removed).is_removal());
+        assert!(entry(removed, zero).is_removal());
+        assert!(!entry(removed, some).is_removal());
+        assert!(!entry(some, removed).is_removal());
+        assert!(!entry(zero, None).is_removal());
+        assert!(!entry(None, None).is_removal());
+    }
+
+    #[test]
+    fn quotes_rejects_malformed() {
+        assert_eq!(QuotesResponse::parse(b"<quotes/>"), Ok(QuotesResponse::default()));
+        assert_eq!(
+            QuotesResponse::parse(b"<quote><price>1</price></quote>"),
+            Err(ParseError::UnexpectedTag { expected: "quotes", found: "quote".into() })
+        );
+        assert_eq!(
+            QuotesResponse::parse(
+                b"<quotes><quote><seccode>SBER</seccode><buy>1</buy></quote></quotes>"
+            ),
+            Err(ParseError::MissingAttribute("price"))
+        );
+        assert_eq!(
+            QuotesResponse::parse(b"<quotes><quote><price>1</price><buy>-2</buy></quote></quotes>"),
+            Err(ParseError::InvalidValue { attr: "buy", value: "-2".into() })
+        );
+        assert_eq!(
+            QuotesResponse::parse(
+                b"<quotes><quote><price>1</price><sell>x</sell></quote></quotes>"
+            ),
+            Err(ParseError::InvalidValue { attr: "sell", value: "x".into() })
+        );
+        assert_eq!(
+            QuotesResponse::parse(b"<quotes><quote secid=\"a\"><price>1</price></quote></quotes>"),
+            Err(ParseError::InvalidValue { attr: "secid", value: "a".into() })
+        );
+    }
+
+    const STOCK_POSITIONS: &str = "<positions>\
+        <money_position><asset>FOND_MICEX</asset><client>virt/9975</client>\
+        <union>508437</union><shortname>Рубли РФ КЦБ ММВБ</shortname>\
+        <saldoin>100000.00</saldoin><bought>26531.0</bought><sold>0</sold>\
+        <saldo>73455.73</saldo><ordbuy>0</ordbuy><ordbuycond>0</ordbuycond>\
+        <comission>13.27</comission><register>T0</register></money_position>\
+        <sec_position><secid>3</secid><market>1</market><seccode>SBER</seccode>\
+        <register>T0</register><client>virt/9975</client><union>508437</union>\
+        <shortname>Сбербанк</shortname><saldoin>0</saldoin><saldomin>0</saldomin>\
+        <bought>100</bought><sold>0</sold><saldo>100</saldo><ordbuy>0</ordbuy>\
+        <ordsell>0</ordsell><amount>26531.00</amount><equity>26531.00</equity></sec_position>\
+        <spot_limit><client>virt/9975</client></spot_limit>\
+        </positions>";
+
+    const FORTS_POSITIONS: &str = "<positions>\
+        <forts_position><secid>66</secid><seccode>SiZ6</seccode><client>7600abc</client>\
+        <union>508437</union><startnet>0</startnet><openbuys>2</openbuys><opensells>0</opensells>\
+        <totalnet>-3</totalnet><todaybuy>0</todaybuy><todaysell>3</todaysell>\
+        <optmargin>0</optmargin><varmargin>-152.50</varmargin><expirationpos>0</expirationpos>\
+        <usedsellspotlimit>0</usedsellspotlimit><sellspotlimit>0</sellspotlimit>\
+        <netto>-3</netto><kgo>1.5</kgo></forts_position>\
+        <forts_money><client>7600abc</client><union>508437</union><shortname>Деньги FORTS</shortname>\
+        <current>50000.00</current><blocked>21340.55</blocked><free>28659.45</free>\
+        <varmargin>-152.50</varmargin></forts_money>\
+        </positions>";
+
+    #[test]
+    fn positions_stock_account() {
+        let positions = PositionsResponse::try_from(&buf(STOCK_POSITIONS)).unwrap();
+        assert!(positions.forts.is_empty() && positions.forts_money.is_empty());
+        assert_eq!(
+            positions.money,
+            [MoneyPosition {
+                client: "virt/9975".into(),
+                union: "508437".into(),
+                asset: "FOND_MICEX".into(),
+                register: "T0".into(),
+                shortname: "Рубли РФ КЦБ ММВБ".into(),
+                saldoin: Some(dec("100000.00")),
+                bought: Some(dec("26531.0")),
+                sold: Some(Decimal::ZERO),
+                saldo: Some(dec("73455.73")),
+                ordbuy: Some(Decimal::ZERO),
+                ordbuycond: Some(Decimal::ZERO),
+                comission: Some(dec("13.27")),
+            }]
+        );
+        let sec = &positions.securities[..];
+        assert_eq!(sec.len(), 1);
+        assert_eq!((sec[0].secid, sec[0].market), (Some(3), Some(1)));
+        assert_eq!(sec[0].seccode, "SBER");
+        assert_eq!(sec[0].saldo, Some(dec("100")));
+        assert_eq!(sec[0].equity, Some(dec("26531.00")));
+    }
+
+    #[test]
+    fn positions_forts_account() {
+        let positions = PositionsResponse::parse(FORTS_POSITIONS.as_bytes()).unwrap();
+        assert!(positions.money.is_empty() && positions.securities.is_empty());
+
+        let forts = &positions.forts[0];
+        assert_eq!((forts.secid, forts.seccode.as_str()), (Some(66), "SiZ6"));
+        assert_eq!(forts.totalnet, Some(dec("-3")));
+        assert_eq!(forts.varmargin, Some(dec("-152.50")));
+        assert_eq!(forts.kgo, Some(dec("1.5")));
+
+        assert_eq!(
+            positions.forts_money,
+            [FortsMoney {
+                client: "7600abc".into(),
+                union: "508437".into(),
+                shortname: "Деньги FORTS".into(),
+                current: Some(dec("50000.00")),
+                blocked: Some(dec("21340.55")),
+                free: Some(dec("28659.45")),
+                varmargin: Some(dec("-152.50")),
+            }]
+        );
+    }
+
+    #[test]
+    fn positions_absent_fields() {
+        // a delta carries only the changed values
+        let msg = b"<positions><sec_position><seccode>SBER</seccode><client>virt/9975</client>\
+                    <saldo>90</saldo></sec_position><money_position><client>virt/9975</client>\
+                    </money_position><forts_money><client>7600abc</client></forts_money>\
+                    </positions>";
+        let positions = PositionsResponse::parse(msg).unwrap();
+        let sec = &positions.securities[0];
+        assert_eq!(sec.saldo, Some(dec("90")));
+        assert_eq!((sec.secid, sec.market, sec.amount, sec.equity), (None, None, None, None));
+        assert!(sec.union.is_empty() && sec.register.is_empty());
+        assert_eq!(positions.money[0].saldo, None);
+        assert_eq!(positions.forts_money[0].current, None);
+
+        assert_eq!(PositionsResponse::parse(b"<positions/>"), Ok(PositionsResponse::default()));
+    }
+
+    #[test]
+    fn positions_rejects_malformed() {
+        assert_eq!(
+            PositionsResponse::parse(b"<united_limits union=\"1\"/>"),
+            Err(ParseError::UnexpectedTag { expected: "positions", found: "united_limits".into() })
+        );
+        assert_eq!(
+            PositionsResponse::parse(
+                b"<positions><money_position><saldo>1,5</saldo></money_position></positions>"
+            ),
+            Err(ParseError::InvalidValue { attr: "saldo", value: "1,5".into() })
+        );
+        assert_eq!(
+            PositionsResponse::parse(
+                b"<positions><forts_position><secid>-1</secid></forts_position></positions>"
+            ),
+            Err(ParseError::InvalidValue { attr: "secid", value: "-1".into() })
+        );
+    }
+
+    #[test]
+    fn united_limits() {
+        let msg = "<united_limits union=\"508437\"><open_equity>150000.00</open_equity>\
+                   <equity>149847.50</equity><requirements>21340.55</requirements>\
+                   <free>128506.95</free><vm>-152.50</vm><finres>0</finres><go>21340.55</go>\
+                   </united_limits>";
+        assert_eq!(
+            UnitedLimits::try_from(&buf(msg)),
+            Ok(UnitedLimits {
+                union: "508437".into(),
+                open_equity: Some(dec("150000.00")),
+                equity: Some(dec("149847.50")),
+                requirements: Some(dec("21340.55")),
+                free: Some(dec("128506.95")),
+                vm: Some(dec("-152.50")),
+                finres: Some(Decimal::ZERO),
+                go: Some(dec("21340.55")),
+            })
+        );
+
+        let limits = UnitedLimits::parse(b"<united_limits><equity>1</equity></united_limits>");
+        assert_eq!(limits, Ok(UnitedLimits { equity: Some(Decimal::ONE), ..Default::default() }));
+        assert_eq!(
+            UnitedLimits::parse(b"<united_limits union=\"1\"><vm>n/a</vm></united_limits>"),
+            Err(ParseError::InvalidValue { attr: "vm", value: "n/a".into() })
+        );
+    }
+
+    #[test]
+    fn client_info() {
+        let msg = "<client id=\"virt/9975\" remove=\"false\"><type>mct</type>\
+                   <currency>RUB</currency><market>1</market><union>508437</union>\
+                   <forts_acc>7600abc</forts_acc></client>";
+        assert_eq!(
+            ClientInfo::try_from(&buf(msg)),
+            Ok(ClientInfo {
+                id: "virt/9975".into(),
+                remove: false,
+                type_: "mct".into(),
+                currency: "RUB".into(),
+                market: Some(1),
+                union: "508437".into(),
+                forts_acc: "7600abc".into(),
+            })
+        );
+
+        let removed = ClientInfo::parse(b"<client id=\"virt/9975\" remove=\"true\"/>").unwrap();
+        assert_eq!(
+            removed,
+            ClientInfo { id: "virt/9975".into(), remove: true, ..Default::default() }
+        );
+        assert_eq!(
+            ClientInfo::parse(b"<client><type>spot</type></client>"),
+            Err(ParseError::MissingAttribute("id"))
+        );
+        assert_eq!(
+            ClientInfo::parse(b"<client id=\"1\" remove=\"yes\"/>"),
+            Err(ParseError::InvalidValue { attr: "remove", value: "yes".into() })
+        );
+    }
+
+    #[test]
+    fn candles() {
+        let msg = "<candles secid=\"66\" board=\"FUT\" seccode=\"SiZ6\" period=\"2\" status=\"1\">\
+                   <candle date=\"16.10.2026 10:00:00.000\" open=\"96100\" high=\"96250\" \
+                   low=\"96050\" close=\"96200\" volume=\"1520\" oi=\"1830211\"/>\
+                   <candle date=\"16.10.2026 10:05:00.000\" open=\"96200.5\" high=\"96200.5\" \
+                   low=\"96110\" close=\"96115\"/>\
+                   </candles>";
+        let candles = Candles::try_from(&buf(msg)).unwrap();
+        assert_eq!(
+            (candles.secid, candles.board.as_str(), candles.seccode.as_str(), candles.period),
+            (Some(66), "FUT", "SiZ6", 2)
+        );
+        assert_eq!(candles.status, CandlesStatus::Delivered);
+        assert_eq!(
+            candles.candles[0],
+            Candle {
+                date: "16.10.2026 10:00:00".parse().unwrap(),
+                open: dec("96100"),
+                high: dec("96250"),
+                low: dec("96050"),
+                close: dec("96200"),
+                volume: 1520,
+                oi: Some(1830211),
+            }
+        );
+        let second = &candles.candles[1];
+        assert_eq!((second.date.hour, second.date.minute), (10, 5));
+        assert_eq!(second.open, dec("96200.5"));
+        assert_eq!((second.volume, second.oi), (0, None));
+    }
+
+    #[test]
+    fn candles_status() {
+        let status = |value: &str| {
+            let msg = format!("<candles period=\"1\" status=\"{value}\"/>");
+            Candles::parse(msg.as_bytes()).map(|c| c.status)
+        };
+        assert_eq!(status("0"), Ok(CandlesStatus::Exhausted));
+        assert_eq!(status("1"), Ok(CandlesStatus::Delivered));
+        assert_eq!(status("2"), Ok(CandlesStatus::Partial));
+        assert_eq!(status("3"), Ok(CandlesStatus::Unavailable));
+        assert_eq!(
+            status("4"),
+            Err(ParseError::InvalidValue { attr: "status", value: "4".into() })
+        );
+        assert_eq!(
+            Candles::parse(b"<candles period=\"1\"/>"),
+            Err(ParseError::MissingAttribute("status"))
+        );
+    }
+
+    #[test]
+    fn candles_rejects_malformed() {
+        assert_eq!(
+            Candles::parse(b"<candles status=\"0\"/>"),
+            Err(ParseError::MissingAttribute("period"))
+        );
+        assert_eq!(
+            Candles::parse(
+                b"<candles period=\"1\" status=\"0\"><candle date=\"16.10.2026 10:00:00\" \
+                  open=\"1\" high=\"1\" low=\"1\"/></candles>"
+            ),
+            Err(ParseError::MissingAttribute("close"))
+        );
+        assert_eq!(
+            Candles::parse(
+                b"<candles period=\"1\" status=\"0\"><candle open=\"1\" high=\"1\" low=\"1\" \
+                  close=\"1\"/></candles>"
+            ),
+            Err(ParseError::MissingAttribute("date"))
+        );
+        assert_eq!(
+            Candles::parse(
+                b"<candles period=\"1\" status=\"0\"><candle date=\"16.10.2026 10:00:00\" \
+                  open=\"1\" high=\"1\" low=\"1\" close=\"1\" volume=\"-5\"/></candles>"
+            ),
+            Err(ParseError::InvalidValue { attr: "volume", value: "-5".into() })
+        );
+    }
+
+    #[test]
+    fn news_header() {
+        let msg = "<news_header><id>1830211</id><timestamp>16.10.2026 10:15:30</timestamp>\
+                   <source>Интерфакс</source><title>&quot;Сбербанк&quot; &amp; ВТБ</title>\
+                   </news_header>";
+        assert_eq!(
+            NewsHeader::try_from(&buf(msg)),
+            Ok(NewsHeader {
+                id: 1830211,
+                time: Some("16.10.2026 10:15:30".parse().unwrap()),
+                source: "Интерфакс".into(),
+                title: "\"Сбербанк\" & ВТБ".into(),
+            })
+        );
+
+        let header = NewsHeader::parse(
+            b"<news_header><id>1</id><timestamp>0</timestamp><title><![CDATA[a & b]]></title>\
+              </news_header>",
+        );
+        assert_eq!(
+            header,
+            Ok(NewsHeader { id: 1, time: None, source: String::new(), title: "a & b".into() })
+        );
+    }
+
+    #[test]
+    fn news_body() {
+        let msg = "<news_body><id>1830211</id><text><![CDATA[<p>Текст &amp; новости</p>]]></text>\
+                   </news_body>";
+        assert_eq!(
+            NewsBody::try_from(&buf(msg)),
+            Ok(NewsBody { id: 1830211, text: "<p>Текст &amp; новости</p>".into() })
+        );
+        assert_eq!(
+            NewsBody::parse(b"<news_body><id>2</id><text>a &lt; b</text></news_body>"),
+            Ok(NewsBody { id: 2, text: "a < b".into() })
+        );
+        assert_eq!(
+            NewsBody::parse(b"<news_body><id>3</id></news_body>"),
+            Ok(NewsBody { id: 3, text: String::new() })
+        );
+    }
+
+    #[test]
+    fn news_rejects_malformed() {
+        assert_eq!(
+            NewsHeader::parse(b"<news_header><title>t</title></news_header>"),
+            Err(ParseError::MissingAttribute("id"))
+        );
+        assert_eq!(
+            NewsHeader::parse(
+                b"<news_header><id>1</id><timestamp>16.10.26</timestamp></news_header>"
+            ),
+            Err(ParseError::InvalidValue { attr: "timestamp", value: "16.10.26".into() })
+        );
+        assert_eq!(
+            NewsBody::parse(b"<news_body><id>x</id></news_body>"),
+            Err(ParseError::InvalidValue { attr: "id", value: "x".into() })
+        );
+        assert_eq!(
+            NewsBody::parse(b"<news_header><id>1</id></news_header>"),
+            Err(ParseError::UnexpectedTag { expected: "news_body", found: "news_header".into() })
+        );
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn news_windows_1251() {
+        // "Новость" in windows-1251
+        let title = b"\xcd\xee\xe2\xee\xf1\xf2\xfc";
+        let msg =
+            [&b"<news_header><id>1</id><title>"[..], title, b" &amp; 1</title></news_header>"]
+                .concat();
+        assert_eq!(NewsHeader::parse(&msg).unwrap().title, "Новость & 1");
+
+        let msg =
+            [&b"<news_body><id>1</id><text><![CDATA["[..], title, b" &amp;]]></text></news_body>"]
+                .concat();
+        assert_eq!(NewsBody::parse(&msg).unwrap().text, "Новость &amp;");
+    }
+
+    #[test]
+    fn broker_messages() {
+        let msg = "<messages>\
+                   <message><date>16.10.2026 10:15:30</date><urgent>Y</urgent><from>Брокер</from>\
+                   <text><![CDATA[Требование о пополнении счёта]]></text></message>\
+                   <message date=\"16.10.2026 18:45:00\" urgent=\"N\">Плановые работы</message>\
+                   <message><text>a &amp; b</text></message>\
+                   </messages>";
+        let messages = MessagesResponse::try_from(&buf(msg)).unwrap().messages;
+        assert_eq!(
+            messages,
+            [
+                BrokerMessage {
+                    date: Some("16.10.2026 10:15:30".parse().unwrap()),
+                    urgent: true,
+                    from: "Брокер".into(),
+                    text: "Требование о пополнении счёта".into(),
+                },
+                BrokerMessage {
+                    date: Some("16.10.2026 18:45:00".parse().unwrap()),
+                    urgent: false,
+                    from: String::new(),
+                    text: "Плановые работы".into(),
+                },
+                BrokerMessage {
+                    date: None,
+                    urgent: false,
+                    from: String::new(),
+                    text: "a & b".into()
+                },
+            ]
+        );
+        let urgent =
+            MessagesResponse::parse(b"<messages><message urgent=\"true\">x</message></messages>");
+        assert!(urgent.unwrap().messages[0].urgent);
+        assert_eq!(MessagesResponse::parse(b"<messages/>"), Ok(MessagesResponse::default()));
+    }
+
+    #[test]
+    fn broker_messages_rejects_malformed() {
+        assert_eq!(
+            MessagesResponse::parse(b"<messages><message urgent=\"yes\">x</message></messages>"),
+            Err(ParseError::InvalidValue { attr: "urgent", value: "yes".into() })
+        );
+        assert_eq!(
+            MessagesResponse::parse(
+                b"<messages><message><urgent>1</urgent><text>x</text></message></messages>"
+            ),
+            Err(ParseError::InvalidValue { attr: "urgent", value: "1".into() })
+        );
+        assert_eq!(
+            MessagesResponse::parse(
+                b"<messages><message date=\"yesterday\">x</message></messages>"
+            ),
+            Err(ParseError::InvalidValue { attr: "date", value: "yesterday".into() })
+        );
+        assert_eq!(
+            MessagesResponse::parse(b"<message>x</message>"),
+            Err(ParseError::UnexpectedTag { expected: "messages", found: "message".into() })
+        );
+    }
+
+    #[test]
+    fn error_response() {
+        let msg = "<error>Внутренняя ошибка: &quot;timeout&quot;</error>";
+        assert_eq!(
+            ErrorResponse::try_from(&buf(msg)),
+            Ok(ErrorResponse { text: "Внутренняя ошибка: \"timeout\"".into() })
+        );
+        assert_eq!(ErrorResponse::parse(b"<error/>"), Ok(ErrorResponse::default()));
+        assert_eq!(
+            ErrorResponse::parse(b"<result success=\"false\"/>"),
+            Err(ParseError::UnexpectedTag { expected: "error", found: "result".into() })
+        );
+    }
+
+    #[test]
+    fn alltrades() {
+        let msg = "<alltrades>\
+                   <trade secid=\"3\"><board>TQBR</board><seccode>SBER</seccode>\
+                   <tradeno>9876543210</tradeno><time>16.10.2026 10:15:30.125</time>\
+                   <price>265.31</price><quantity>14</quantity><buysell>B</buysell>\
+                   <period>N</period></trade>\
+                   <trade secid=\"66\"><board>FUT</board><seccode>SiZ6</seccode>\
+                   <tradeno>1830211</tradeno><time>16.10.2026 10:15:31.000</time>\
+                   <price>96115</price><quantity>2</quantity><buysell>S</buysell>\
+                   <openinterest>1830211</openinterest><period>N</period></trade>\
+                   </alltrades>";
+        let trades = AllTradesResponse::try_from(&buf(msg)).unwrap().trades;
+        assert_eq!(
+            trades[0],
+            MarketTrade {
+                secid: Some(3),
+                board: "TQBR".into(),
+                seccode: "SBER".into(),
+                tradeno: Some(9876543210),
+                time: "16.10.2026 10:15:30.125".parse().unwrap(),
+                price: dec("265.31"),
+                quantity: 14,
+                buysell: Some(BuySell::Buy),
+                openinterest: None,
+                period: "N".into(),
+            }
+        );
+        assert_eq!(trades[0].time.millisecond, 125);
+        assert_eq!(
+            (trades[1].buysell, trades[1].openinterest),
+            (Some(BuySell::Sell), Some(1830211))
+        );
+        assert_eq!(AllTradesResponse::parse(b"<alltrades/>"), Ok(AllTradesResponse::default()));
+    }
+
+    #[test]
+    fn ticks() {
+        let msg = "<ticks>\
+                   <tick><secid>3</secid><board>TQBR</board><seccode>SBER</seccode>\
+                   <tradeno>9876543210</tradeno><tradetime>16.10.2026 10:15:30</tradetime>\
+                   <price>265.31</price><quantity>14</quantity><period>O</period>\
+                   <buysell>S</buysell></tick>\
+                   <tick><tradetime>16.10.2026 10:15:31</tradetime><price>265.3</price></tick>\
+                   </ticks>";
+        let ticks = TicksResponse::try_from(&buf(msg)).unwrap().ticks;
+        assert_eq!(ticks.len(), 2);
+        assert_eq!((ticks[0].secid, ticks[0].tradeno), (Some(3), Some(9876543210)));
+        assert_eq!((ticks[0].buysell, ticks[0].period.as_str()), (Some(BuySell::Sell), "O"));
+        assert_eq!(ticks[0].time, "16.10.2026 10:15:30".parse().unwrap());
+
+        // absent optional fields
+        let tick = &ticks[1];
+        assert_eq!(
+            (tick.secid, tick.tradeno, tick.buysell, tick.openinterest),
+            (None, None, None, None)
+        );
+        assert!(tick.board.is_empty() && tick.seccode.is_empty() && tick.period.is_empty());
+        assert_eq!((tick.price, tick.quantity), (dec("265.3"), 0));
+    }
+
+    #[test]
+    fn market_trades_reject_malformed() {
+        // the time element differs between the documents
+        assert_eq!(
+            AllTradesResponse::parse(
+                b"<alltrades><trade><tradetime>16.10.2026 10:15:30</tradetime><price>1</price>\
+                  </trade></alltrades>"
+            ),
+            Err(ParseError::MissingAttribute("time"))
+        );
+        assert_eq!(
+            TicksResponse::parse(
+                b"<ticks><tick><time>16.10.2026 10:15:30</time><price>1</price></tick></ticks>"
+            ),
+            Err(ParseError::MissingAttribute("tradetime"))
+        );
+        assert_eq!(
+            TicksResponse::parse(
+                b"<ticks><tick><tradetime>16.10.2026 10:15:30</tradetime></tick></ticks>"
+            ),
+            Err(ParseError::MissingAttribute("price"))
+        );
+        assert_eq!(
+            AllTradesResponse::parse(
+                b"<alltrades><trade><time>16.10.2026 10:15:30</time><price>1</price>\
+                  <buysell>X</buysell></trade></alltrades>"
+            ),
+            Err(ParseError::InvalidValue { attr: "buysell", value: "X".into() })
+        );
+        assert_eq!(
+            AllTradesResponse::parse(
+                b"<alltrades><trade><time>16.10.2026 10:15:30</time><price>1</price>\
+                  <quantity>-1</quantity></trade></alltrades>"
+            ),
+            Err(ParseError::InvalidValue { attr: "quantity", value: "-1".into() })
+        );
+        assert_eq!(
+            TicksResponse::parse(b"<alltrades/>"),
+            Err(ParseError::UnexpectedTag { expected: "ticks", found: "alltrades".into() })
+        );
+    }
+
+    #[test]
+    fn message_variants() {
+        type Check = fn(&Message) -> bool;
+        let table: &[(&str, Check)] = &[
+            ("<server_status connected=\"true\"/>", |m| matches!(m, Message::ServerStatus(_))),
+            (
+                "<connector_version>6.19.2.21</connector_version>",
+                |m| matches!(m, Message::ConnectorVersion(v) if v.as_str() == "6.19.2.21"),
+            ),
+            (
+                "<result success=\"false\"><message>rejected</message></result>",
+                |m| matches!(m, Message::Result { success: false, body } if body == "rejected"),
+            ),
+            ("<result success=\"true\"/>", |m| matches!(m, Message::Result { success: true, .. })),
+            ("<error>text</error>", |m| matches!(m, Message::Error(text) if text == "text")),
+            ("<securities/>", |m| matches!(m, Message::Securities(_))),
+            ("<orders/>", |m| matches!(m, Message::Orders(_))),
+            ("<trades/>", |m| matches!(m, Message::Trades(_))),
+            ("<candles period=\"1\" status=\"0\"/>", |m| matches!(m, Message::Candles(_))),
+            ("<quotes/>", |m| matches!(m, Message::Quotes(_))),
+            ("<alltrades/>", |m| matches!(m, Message::AllTrades(_))),
+            ("<ticks/>", |m| matches!(m, Message::Ticks(_))),
+            ("<positions/>", |m| matches!(m, Message::Positions(_))),
+            ("<united_limits union=\"1\"/>", |m| matches!(m, Message::UnitedLimits(_))),
+            ("<client id=\"C1\"/>", |m| matches!(m, Message::Client(_))),
+            ("<markets/>", |m| matches!(m, Message::Markets(_))),
+            ("<boards/>", |m| matches!(m, Message::Boards(_))),
+            ("<candlekinds/>", |m| matches!(m, Message::CandleKinds(_))),
+            ("<union id=\"1\"/>", |m| matches!(m, Message::Union(_))),
+            ("<news_header><id>1</id></news_header>", |m| matches!(m, Message::NewsHeader(_))),
+            ("<news_body><id>1</id></news_body>", |m| matches!(m, Message::NewsBody(_))),
+            ("<messages/>", |m| matches!(m, Message::Messages(_))),
+        ];
+        for (msg, check) in table {
+            let parsed = Message::parse(&buf(msg));
+            assert!(check(&parsed), "{msg}: {parsed:?}");
+            assert_eq!(Message::from(&OwnedMessage::from(msg.as_bytes())), parsed);
+        }
+    }
+
+    #[test]
+    fn message_unknown() {
+        let unknown = |msg: &str| match Message::parse_bytes(msg.as_bytes()) {
+            Message::Unknown { tag, raw } => {
+                assert_eq!(raw, msg.as_bytes());
+                tag
+            }
+            parsed => panic!("{msg}: {parsed:?}"),
+        };
+        // unknown tags
+        assert_eq!(unknown("<sec_info secid=\"3\"/>"), "sec_info");
+        assert_eq!(unknown("<overnight status=\"true\"/>"), "overnight");
+        assert_eq!(unknown(""), "");
+        assert_eq!(unknown("not a document"), "");
+        // known tags failing to parse
+        assert_eq!(unknown("<server_status/>"), "server_status");
+        assert_eq!(unknown("<result/>"), "result");
+        assert_eq!(unknown("<candles status=\"0\"/>"), "candles");
+        assert_eq!(unknown("<client/>"), "client");
+    }
+}
@@ -0,0 +1,331 @@
+use super::buffers::{xml_attr, xml_tag_name, OwnedMessage, TCStr};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+
+// message types carrying `transactionid` of the client order
+const ROUTED_TAGS: [&[u8]; 3] = [b"result", b"orders", b"stoporders"];
+// the number of ids kept in the backlog at the same time
+const BACKLOG_IDS: usize = 1 << 10;
+
+/// Маршрутизатор сообщений по идентификатору транзакции
+///
+/// Связывает ответ на команду `<result success="true" transactionid="N"/>` с последующими
+/// сообщениями о состоянии заявки `<orders><order transactionid="N" ..>`, поступающими в
+/// обработчик входящих сообщений.
+///
+/// Интерес к транзакции регистрируется вызовом [`Router::watch`], возвращающим канал, в который
+/// будут поступать все сообщения, содержащие `transactionid` транзакции. Сообщения, поступившие
+/// до вызова [`Router::watch`], сохраняются в ограниченной очереди(**backlog** сообщений на
+/// транзакцию) и передаются в канал при регистрации. Сообщения без `transactionid`, а так же
+/// сообщения вытесненные из очереди, передаются в канал по-умолчанию.
+///
+/// `transactionid` извлекается из сообщений `result`, `orders` и `stoporders`; сообщение,
+/// содержащее несколько транзакций, передаётся каждому заинтересованному получателю.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{Router, Stream};
+///
+/// let mut txc = /*..*/;
+/// let (router, unmatched) = Router::new(16);
+/// txc.input_stream().subscribe(router.handler())?;
+///
+/// let ack = unsafe { txc.sender().send(/*<command id="neworder">..*/)? };
+/// let id = ack.xml_attr(b"transactionid").unwrap();
+/// let updates = router.watch(std::str::from_utf8(id)?.parse()?);
+///
+/// for msg in updates {
+///     println!("{msg}");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Router(Arc<Mutex<State>>);
+
+struct State {
+    backlog_size: usize,
+    watchers: HashMap<u64, Sender<OwnedMessage>>,
+    backlog: HashMap<u64, VecDeque<OwnedMessage>>,
+    backlog_order: VecDeque<u64>,
+    unmatched: Sender<OwnedMessage>,
+}
+
+impl Router {
+    /// Создаёт маршрутизатор и канал для сообщений без получателя
+    ///
+    /// **backlog** - количество сообщений, сохраняемых для каждой транзакции до вызова
+    /// [`Router::watch`].
+    pub fn new(backlog: usize) -> (Self, Receiver<OwnedMessage>) {
+        let (unmatched, rx) = mpsc::channel();
+        let state = State {
+            backlog_size: backlog,
+            watchers: HashMap::new(),
+            backlog: HashMap::new(),
+            backlog_order: VecDeque::new(),
+            unmatched,
+        };
+        (Self(Arc::new(Mutex::new(state))), rx)
+    }
+
+    /// Регистрирует интерес к транзакции **transaction_id**
+    ///
+    /// Возвращает канал, в который передаются сохранённые и все последующие сообщения транзакции.
+    /// Повторный вызов для той же транзакции заменяет предыдущий канал.
+    pub fn watch(&self, transaction_id: u64) -> Receiver<OwnedMessage> {
+        let (tx, rx) = mpsc::channel();
+        let mut state = self.lock();
+        if let Some(backlog) = state.backlog.remove(&transaction_id) {
+            state.backlog_order.retain(|id| *id != transaction_id);
+            backlog.into_iter().for_each(|msg| {
+                let _ = tx.send(msg);
+            });
+        }
+        state.watchers.insert(transaction_id, tx);
+        rx
+    }
+
+    /// Отменяет регистрацию интереса к транзакции **transaction_id**
+    pub fn unwatch(&self, transaction_id: u64) {
+        self.lock().watchers.remove(&transaction_id);
+    }
+
+    /// Обрабатывает входящее сообщение
+    pub fn dispatch(&self, buf: &[u8]) {
+        let mut state = self.lock();
+        let mut routed = false;
+
+        if xml_tag_name(buf).map_or(false, |tag| ROUTED_TAGS.contains(&tag)) {
+            let mut owned = None;
+            for id in transaction_ids(buf) {
+                let msg = owned.get_or_insert_with(|| OwnedMessage::from(buf)).clone();
+                state.route(id, msg);
+                routed = true;
+            }
+        }
+
+        if !routed {
+            let _ = state.unmatched.send(OwnedMessage::from(buf));
+        }
+    }
+
+    /// Функция обратного вызова для [`Stream::subscribe`](crate::Stream::subscribe)
+    pub fn handler(&self) -> impl FnMut(TCStr) + Send + Sync + 'static {
+        let router = self.clone();
+        move |buf| router.dispatch(buf.to_bytes())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn route(&mut self, id: u64, msg: OwnedMessage) {
+        if let Some(tx) = self.watchers.get(&id) {
+            match tx.send(msg) {
+                Ok(_) => return,
+                // receiver is gone, the message is treated as unwatched
+                Err(mpsc::SendError(msg)) => {
+                    self.watchers.remove(&id);
+                    let _ = self.unmatched.send(msg);
+                    return;
+                }
+            }
+        }
+
+        if !self.backlog.contains_key(&id) {
+            if self.backlog_order.len() == BACKLOG_IDS {
+                if let Some(evicted) = self.backlog_order.pop_front() {
+                    self.evict(evicted);
+                }
+            }
+            self.backlog_order.push_back(id);
+        }
+        let backlog = self.backlog.entry(id).or_default();
+        backlog.push_back(msg);
+        if backlog.len() > self.backlog_size {
+            if let Some(msg) = backlog.pop_front() {
+                let _ = self.unmatched.send(msg);
+            }
+        }
+    }
+
+    fn evict(&mut self, id: u64) {
+        if let Some(backlog) = self.backlog.remove(&id) {
+            backlog.into_iter().for_each(|msg| {
+                let _ = self.unmatched.send(msg);
+            });
+        }
+    }
+}
+
+// `transactionid` attributes of the root element and its children
+fn transaction_ids(buf: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    buf.iter()
+        .enumerate()
+        .filter(|(_, b)| b'<'.eq(b))
+        .filter_map(move |(i, _)| xml_attr(&buf[i..], b"transactionid"))
+        .filter_map(|id| std::str::from_utf8(id).ok()?.parse().ok())
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("Router")
+            .field("watchers", &state.watchers.len())
+            .field("backlog", &state.backlog.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Barrier, thread, time::Duration};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn order(id: u64, status: &str) -> String {
+        format!("<orders><order transactionid=\"{id}\"><status>{status}</status></order></orders>")
+    }
+
+    fn drain(rx: &Receiver<OwnedMessage>) -> Vec<String> {
+        rx.try_iter().map(|msg| String::from_utf8(msg.as_bytes().to_vec()).unwrap()).collect()
+    }
+
+    #[test]
+    fn routes_by_transaction_id() {
+        let (router, unmatched) = Router::new(16);
+        let first = router.watch(1);
+        let second = router.watch(2);
+
+        router.dispatch(b"<result success=\"true\" transactionid=\"1\"/>");
+        router.dispatch(order(2, "active").as_bytes());
+        router.dispatch(order(1, "matched").as_bytes());
+        router.dispatch(b"<stoporders><stoporder transactionid=\"2\"/></stoporders>");
+
+        assert_eq!(
+            drain(&first),
+            ["<result success=\"true\" transactionid=\"1\"/>".to_string(), order(1, "matched")]
+        );
+        assert_eq!(
+            drain(&second),
+            [
+                order(2, "active"),
+                "<stoporders><stoporder transactionid=\"2\"/></stoporders>".into()
+            ]
+        );
+        assert!(drain(&unmatched).is_empty());
+    }
+
+    #[test]
+    fn multiple_transactions() {
+        let (router, unmatched) = Router::new(16);
+        let (first, second) = (router.watch(1), router.watch(2));
+
+        let msg = "<orders><order transactionid=\"1\"/><order transactionid=\"2\"/></orders>";
+        router.dispatch(msg.as_bytes());
+        assert_eq!(drain(&first), [msg]);
+        assert_eq!(drain(&second), [msg]);
+        assert!(drain(&unmatched).is_empty());
+    }
+
+    #[test]
+    fn missing_transaction_id() {
+        let (router, unmatched) = Router::new(16);
+        let watched = router.watch(1);
+
+        router.dispatch(b"<result success=\"true\"/>");
+        router.dispatch(b"<orders><order><status>active</status></order></orders>");
+        router.dispatch(b"<result success=\"true\" transactionid=\"abc\"/>");
+        // not routed message types
+        router.dispatch(b"<trades><trade><orderno>1</orderno></trade></trades>");
+        router.dispatch(b"<error transactionid=\"1\">text</error>");
+
+        assert!(drain(&watched).is_empty());
+        assert_eq!(drain(&unmatched).len(), 5);
+    }
+
+    #[test]
+    fn unknown_transaction_id_is_kept() {
+        let (router, unmatched) = Router::new(2);
+        router.dispatch(order(7, "forwarding").as_bytes());
+        router.dispatch(order(7, "active").as_bytes());
+        router.dispatch(order(7, "matched").as_bytes());
+        router.dispatch(order(8, "active").as_bytes());
+
+        // the backlog overflow goes to the unmatched channel
+        assert_eq!(drain(&unmatched), [order(7, "forwarding")]);
+        assert_eq!(drain(&router.watch(7)), [order(7, "active"), order(7, "matched")]);
+        assert_eq!(drain(&router.watch(8)), [order(8, "active")]);
+        assert!(drain(&router.watch(9)).is_empty());
+    }
+
+    #[test]
+    fn unwatched_transaction() {
+        let (router, unmatched) = Router::new(16);
+        let watched = router.watch(1);
+        router.unwatch(1);
+        router.dispatch(order(1, "active").as_bytes());
+        assert!(drain(&watched).is_empty());
+
+        // the dropped receiver releases the transaction
+        let watched = router.watch(2);
+        drop(watched);
+        router.dispatch(order(2, "active").as_bytes());
+        assert_eq!(drain(&unmatched), [order(2, "active")]);
+    }
+
+    #[test]
+    fn concurrent_watchers() {
+        const WATCHERS: u64 = 8;
+        const MESSAGES: usize = 100;
+
+        let (router, unmatched) = Router::new(MESSAGES);
+        let barrier = Arc::new(Barrier::new(WATCHERS as usize + 1));
+
+        let watchers = (0..WATCHERS)
+            .map(|id| {
+                let router = router.clone();
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    let rx = router.watch(id);
+                    barrier.wait();
+                    (0..MESSAGES)
+                        .map(|_| rx.recv_timeout(TIMEOUT).unwrap())
+                        .map(|msg| String::from_utf8(msg.as_bytes().to_vec()).unwrap())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        barrier.wait();
+        let dispatchers = (0..2)
+            .map(|n| {
+                let router = router.clone();
+                thread::spawn(move || {
+                    for i in (n..MESSAGES).step_by(2) {
+                        (0..WATCHERS)
+                            .for_each(|id| router.dispatch(order(id, &i.to_string()).as_bytes()));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        dispatchers.into_iter().for_each(|h| h.join().unwrap());
+
+        for (id, watcher) in watchers.into_iter().enumerate() {
+            let mut received = watcher.join().unwrap();
+            received.sort();
+            let mut expected =
+                (0..MESSAGES).map(|i| order(id as u64, &i.to_string())).collect::<Vec<_>>();
+            expected.sort();
+            assert_eq!(received, expected);
+        }
+        assert!(drain(&unmatched).is_empty());
+    }
+}
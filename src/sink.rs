@@ -0,0 +1,219 @@
+use super::{incoming, Error, IncomingStream, Result, Sender};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+type PendingSend = Pin<Box<dyn Future<Output = Result<String>> + Send>>;
+
+/// Асинхронный получатель команд, реализующий [`futures_sink::Sink`]
+///
+/// Команды отправляются последовательно через [`Sender::send_async`]: следующая команда
+/// принимается после получения ответа на предыдущую. Ответы коннектора передаются в поток,
+/// возвращаемый [`CommandSink::with_responses`], иначе отбрасываются; ошибка отправки без
+/// потока ответов возвращается из `Sink`.
+///
+/// Требует опции **tokio**.
+///
+/// # Пример
+/// ```no_run
+/// use futures::{stream, SinkExt, StreamExt};
+/// use libtxc::CommandSink;
+///
+/// let txc = /*..*/;
+/// let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(16);
+/// let mut commands = stream::poll_fn(move |cx| rx.poll_recv(cx)).map(Ok);
+///
+/// let (mut sink, mut responses) = CommandSink::with_responses(txc.sender());
+/// sink.send_all(&mut commands).await?;
+/// while let Some(response) = responses.next().await {
+///     println!("{response:?}");
+/// }
+/// ```
+pub struct CommandSink {
+    sender: Sender,
+    pending: Option<PendingSend>,
+    responses: Option<UnboundedSender<Result<String>>>,
+}
+
+impl CommandSink {
+    /// Создаёт получатель команд, ответы коннектора отбрасываются
+    pub fn new(sender: Sender) -> Self {
+        Self { sender, pending: None, responses: None }
+    }
+
+    /// Создаёт получатель команд и поток ответов коннектора
+    ///
+    /// Поток ответов завершается после удаления получателя команд.
+    pub fn with_responses(sender: Sender) -> (Self, IncomingStream<Result<String>>) {
+        let (tx, rx) = incoming::unbounded();
+        (Self { sender, pending: None, responses: Some(tx) }, rx)
+    }
+
+    // Drives the command in flight to completion
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result> {
+        let result = match self.pending.as_mut() {
+            Some(pending) => match pending.as_mut().poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            },
+            None => return Poll::Ready(Ok(())),
+        };
+        self.pending = None;
+
+        match &self.responses {
+            Some(responses) => {
+                let _ = responses.send(result);
+                Poll::Ready(Ok(()))
+            }
+            None => Poll::Ready(result.map(drop)),
+        }
+    }
+}
+
+impl futures_sink::Sink<String> for CommandSink {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: String) -> Result {
+        let this = self.get_mut();
+        this.pending = Some(Box::pin(this.sender.send_async(item)));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result> {
+        let this = self.get_mut();
+        let result = futures_core::ready!(this.poll_pending(cx));
+        this.responses = None;
+        Poll::Ready(result)
+    }
+}
+
+impl std::fmt::Debug for CommandSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandSink")
+            .field("pending", &self.pending.is_some())
+            .field("responses", &self.responses.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi::fake, TransaqConnector};
+    use futures_sink::Sink;
+    use std::future::poll_fn;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread().build().unwrap()
+    }
+
+    async fn send(sink: &mut CommandSink, cmd: &str) -> Result {
+        poll_fn(|cx| Pin::new(&mut *sink).poll_ready(cx)).await?;
+        Pin::new(&mut *sink).start_send(cmd.into())
+    }
+
+    async fn flush(sink: &mut CommandSink) -> Result {
+        poll_fn(|cx| Pin::new(&mut *sink).poll_flush(cx)).await
+    }
+
+    async fn close(sink: &mut CommandSink) -> Result {
+        poll_fn(|cx| Pin::new(&mut *sink).poll_close(cx)).await
+    }
+
+    #[test]
+    fn commands_are_sent_in_order() {
+        let (_library, module) = fake::load();
+        fake::respond_with(|cmd| {
+            let id = if cmd.contains("\"bb\"") { "bb" } else { "a" };
+            Some(format!("<result success=\"true\" id=\"{id}\"/>"))
+        });
+        let txc = TransaqConnector::from_module(module);
+        let (mut sink, mut responses) = CommandSink::with_responses(txc.sender());
+
+        runtime().block_on(async {
+            // the next command is accepted once the previous one is answered
+            send(&mut sink, "<command id=\"a\"/>").await.unwrap();
+            assert!(sink.pending.is_some());
+            send(&mut sink, "<command id=\"bb\"/>").await.unwrap();
+            flush(&mut sink).await.unwrap();
+            assert!(sink.pending.is_none());
+            // flushing an idle sink is a no-op
+            flush(&mut sink).await.unwrap();
+
+            assert_eq!(
+                responses.recv().await.unwrap().unwrap(),
+                "<result success=\"true\" id=\"a\"/>"
+            );
+            assert_eq!(
+                responses.recv().await.unwrap().unwrap(),
+                "<result success=\"true\" id=\"bb\"/>"
+            );
+        });
+        assert_eq!(fake::commands(), ["<command id=\"a\"/>", "<command id=\"bb\"/>"]);
+    }
+
+    #[test]
+    fn errors_go_to_responses() {
+        let (_library, module) = fake::load();
+        fake::respond_with(|_| Some("<error>Соединение не установлено</error>".into()));
+        let txc = TransaqConnector::from_module(module);
+        let (mut sink, mut responses) = CommandSink::with_responses(txc.sender());
+
+        runtime().block_on(async {
+            send(&mut sink, "<command id=\"server_status\"/>").await.unwrap();
+            flush(&mut sink).await.unwrap();
+            match responses.recv().await.unwrap() {
+                Err(Error::Internal(msg)) => {
+                    assert_eq!(msg.message(), "Соединение не установлено")
+                }
+                ret => panic!("{ret:?}"),
+            }
+        });
+    }
+
+    #[test]
+    fn errors_without_responses() {
+        let (_library, module) = fake::load();
+        fake::respond_with(|_| Some("<error>Соединение не установлено</error>".into()));
+        let txc = TransaqConnector::from_module(module);
+        let mut sink = CommandSink::new(txc.sender());
+
+        runtime().block_on(async {
+            send(&mut sink, "<command id=\"server_status\"/>").await.unwrap();
+            assert!(matches!(flush(&mut sink).await, Err(Error::Internal(_))));
+            // the error is reported once
+            flush(&mut sink).await.unwrap();
+
+            send(&mut sink, "<command id=\"server_status\"/>").await.unwrap();
+            assert!(matches!(close(&mut sink).await, Err(Error::Internal(_))));
+        });
+    }
+
+    #[test]
+    fn close_ends_responses() {
+        let (_library, module) = fake::load();
+        let txc = TransaqConnector::from_module(module);
+        let (mut sink, mut responses) = CommandSink::with_responses(txc.sender());
+
+        runtime().block_on(async {
+            send(&mut sink, "<command id=\"server_status\"/>").await.unwrap();
+            // the command in flight completes before the sink is closed
+            close(&mut sink).await.unwrap();
+            assert!(sink.pending.is_none());
+            assert_eq!(responses.recv().await.unwrap().unwrap(), "<result success=\"true\"/>");
+            assert!(responses.recv().await.is_none());
+        });
+        assert_eq!(fake::commands(), ["<command id=\"server_status\"/>"]);
+    }
+}
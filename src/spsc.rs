@@ -0,0 +1,247 @@
+// Lock-free single-producer single-consumer queue, see `Stream::subscribe_spsc`.
+//
+// `head` and `tail` are the monotonic read and write positions, the slot is the position masked
+// by the power of two capacity. The producer publishes the written slot with the `Release` store
+// of `tail`, the consumer returns the read slot with the `Release` store of `head`; each side
+// loads the position of the other one with `Acquire`.
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, Thread},
+};
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    mask: usize,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    sender_alive: AtomicBool,
+    receiver_alive: AtomicBool,
+}
+
+// slots are accessed by one side at a time, as handed over by `head` and `tail`
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.get_mut(), *self.tail.get_mut());
+        for pos in head..tail {
+            unsafe { ptr::drop_in_place((*self.slots[pos & self.mask].get()).as_mut_ptr()) };
+        }
+    }
+}
+
+pub(crate) fn spsc_channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let capacity = capacity.max(1).next_power_of_two();
+    let ring = Arc::new(Ring {
+        slots: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+        mask: capacity - 1,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        sender_alive: AtomicBool::new(true),
+        receiver_alive: AtomicBool::new(true),
+    });
+    (Sender { ring: Arc::clone(&ring), consumer: None }, Receiver { ring })
+}
+
+pub(crate) struct Sender<T> {
+    ring: Arc<Ring<T>>,
+    // the consumer thread parked on the empty queue
+    consumer: Option<Thread>,
+}
+
+impl<T> Sender<T> {
+    // Sets the thread woken up on every push
+    pub fn wake(&mut self, consumer: Thread) {
+        self.consumer = Some(consumer);
+    }
+
+    // Waits for a free slot while the queue is full, the item is dropped if the receiver is gone
+    pub fn push(&self, item: T) {
+        let ring = &*self.ring;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        while tail - ring.head.load(Ordering::Acquire) > ring.mask {
+            if !ring.receiver_alive.load(Ordering::Acquire) {
+                return;
+            }
+            thread::yield_now();
+        }
+        unsafe { (*ring.slots[tail & ring.mask].get()).write(item) };
+        ring.tail.store(tail + 1, Ordering::Release);
+
+        if let Some(consumer) = &self.consumer {
+            consumer.unpark();
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.ring.sender_alive.store(false, Ordering::Release);
+        if let Some(consumer) = &self.consumer {
+            consumer.unpark();
+        }
+    }
+}
+
+pub(crate) struct Receiver<T> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T> Receiver<T> {
+    pub fn try_pop(&self) -> Option<T> {
+        let ring = &*self.ring;
+        let head = ring.head.load(Ordering::Relaxed);
+        if head == ring.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let item = unsafe { ptr::read((*ring.slots[head & ring.mask].get()).as_ptr()) };
+        ring.head.store(head + 1, Ordering::Release);
+        Some(item)
+    }
+
+    // Parks the current thread until the next item, `None` once the sender is gone and the queue
+    // is drained. Must be called from the thread set by `Sender::wake`.
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            if let Some(item) = self.try_pop() {
+                return Some(item);
+            }
+            if !self.ring.sender_alive.load(Ordering::Acquire) {
+                // the items pushed before the sender was dropped
+                return self.try_pop();
+            }
+            thread::park();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.ring.receiver_alive.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Counts the dropped items
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn capacity_is_power_of_two() {
+        let (tx, rx) = spsc_channel(5);
+        (0..8).for_each(|i| tx.push(i));
+        assert_eq!(
+            std::iter::from_fn(|| rx.try_pop()).collect::<Vec<_>>(),
+            (0..8).collect::<Vec<_>>()
+        );
+        assert_eq!(spsc_channel::<u8>(0).1.ring.mask, 0);
+    }
+
+    #[test]
+    fn fifo_across_wraparound() {
+        let (tx, rx) = spsc_channel(4);
+        let mut next = 0;
+        for round in 0..10 {
+            (0..3).for_each(|i| tx.push(round * 3 + i));
+            for _ in 0..3 {
+                assert_eq!(rx.try_pop(), Some(next));
+                next += 1;
+            }
+            assert_eq!(rx.try_pop(), None);
+        }
+    }
+
+    #[test]
+    fn fifo_between_threads() {
+        const N: usize = 100_000;
+        let (mut tx, rx) = spsc_channel(16);
+
+        let consumer = thread::spawn(move || std::iter::from_fn(|| rx.pop()).collect::<Vec<_>>());
+        tx.wake(consumer.thread().clone());
+        (0..N).for_each(|i| tx.push(i));
+        drop(tx);
+
+        assert_eq!(consumer.join().unwrap(), (0..N).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn full_queue_blocks_producer() {
+        let (tx, rx) = spsc_channel(2);
+        tx.push(0);
+        tx.push(1);
+
+        let pushed = Arc::new(AtomicBool::new(false));
+        let producer = {
+            let pushed = Arc::clone(&pushed);
+            thread::spawn(move || {
+                tx.push(2);
+                pushed.store(true, Ordering::SeqCst);
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert!(!pushed.load(Ordering::SeqCst));
+
+        assert_eq!(rx.try_pop(), Some(0));
+        producer.join().unwrap();
+        assert!(pushed.load(Ordering::SeqCst));
+        assert_eq!(rx.pop(), Some(1));
+        assert_eq!(rx.pop(), Some(2));
+        assert_eq!(rx.pop(), None);
+    }
+
+    #[test]
+    fn drains_after_sender_dropped() {
+        let (tx, rx) = spsc_channel(4);
+        (0..3).for_each(|i| tx.push(i));
+        drop(tx);
+
+        assert_eq!(std::iter::from_fn(|| rx.pop()).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(rx.try_pop(), None);
+    }
+
+    #[test]
+    fn dropped_receiver_unblocks_producer() {
+        let (tx, rx) = spsc_channel(1);
+        tx.push(0);
+
+        let producer = thread::spawn(move || (1..10).for_each(|i| tx.push(i)));
+        thread::sleep(Duration::from_millis(10));
+        drop(rx);
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn undelivered_items_are_dropped() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = spsc_channel(4);
+        (0..4).for_each(|_| tx.push(Counted(Arc::clone(&dropped))));
+
+        drop(rx.try_pop());
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+
+        // wraps around with the items left in the queue
+        (0..1).for_each(|_| tx.push(Counted(Arc::clone(&dropped))));
+        drop(rx);
+        // the queue is full and the receiver is gone, the item is dropped by `push`
+        tx.push(Counted(Arc::clone(&dropped)));
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+
+        drop(tx);
+        assert_eq!(dropped.load(Ordering::SeqCst), 6);
+    }
+}
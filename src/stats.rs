@@ -0,0 +1,206 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+// one bucket per microsecond, the last one collects the longer invocations
+const LATENCY_BUCKETS: usize = 256;
+
+/// Статистика сессии коннектора, см. [`TransaqConnector::stats`](crate::TransaqConnector::stats)
+///
+/// Счётчики обновляются библиотекой: входящие сообщения и длительность их обработки - при каждом
+/// вызове обработчика, команды и ошибки - при каждой отправке команды через [`Sender`](crate::Sender).
+/// Ошибками считаются команды, завершившиеся ошибкой, и паника в обработчике входящих сообщений.
+///
+/// Длительность обработки учитывается гистограммой с шагом в 1 мкс до 255 мкс, более длительные
+/// вызовы попадают в последний интервал.
+///
+/// С опцией **tracing** значения так же записываются в поля `messages` и `latency_us` span-а
+/// `trampoline` и поля `commands` и `errors` span-а `send_ptr`.
+///
+/// # Пример
+/// ```no_run
+/// let mut txc = /*..*/;
+/// let stats = txc.stats();
+/// // ..
+/// println!(
+///     "messages: {}, commands: {}, errors: {}, p99: {:?}",
+///     stats.messages(),
+///     stats.commands(),
+///     stats.errors(),
+///     stats.latency_percentile(99.0)
+/// );
+/// ```
+pub struct SessionStats {
+    messages: AtomicU64,
+    commands: AtomicU64,
+    errors: AtomicU64,
+    latency: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            messages: Default::default(),
+            commands: Default::default(),
+            errors: Default::default(),
+            latency: [(); LATENCY_BUCKETS].map(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl SessionStats {
+    #[inline]
+    pub(crate) fn record_message(&self, latency: Duration) {
+        let bucket = (latency.as_micros() as usize).min(LATENCY_BUCKETS - 1);
+        self.latency[bucket].fetch_add(1, Ordering::Relaxed);
+        let _messages = self.messages.fetch_add(1, Ordering::Relaxed) + 1;
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current()
+            .record("messages", _messages)
+            .record("latency_us", latency.as_micros() as u64);
+    }
+
+    #[inline]
+    pub(crate) fn record_command(&self, ok: bool) {
+        let _commands = self.commands.fetch_add(1, Ordering::Relaxed) + 1;
+        let _errors = if ok {
+            self.errors.load(Ordering::Relaxed)
+        } else {
+            self.errors.fetch_add(1, Ordering::Relaxed) + 1
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("commands", _commands).record("errors", _errors);
+    }
+
+    #[cfg(feature = "catch_unwind")]
+    #[inline]
+    pub(crate) fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Количество входящих сообщений, переданных обработчику
+    pub fn messages(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// Количество отправленных команд
+    pub fn commands(&self) -> u64 {
+        self.commands.load(Ordering::Relaxed)
+    }
+
+    /// Количество ошибок: команд, завершившихся ошибкой, и паник в обработчике
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Длительность обработки входящего сообщения, не превышенная в **percentile** процентах
+    /// вызовов обработчика, например `50.0`, `95.0`, `99.0`
+    ///
+    /// Точность - 1 мкс, значения от 255 мкс и выше возвращаются как 255 мкс. `None`, если
+    /// сообщения не поступали.
+    pub fn latency_percentile(&self, percentile: f64) -> Option<Duration> {
+        let counts: Vec<u64> = self.latency.iter().map(|c| c.load(Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        // rank of the sample, counted from one
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0 * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bucket = counts
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(LATENCY_BUCKETS - 1);
+        Some(Duration::from_micros(bucket as u64))
+    }
+}
+
+impl fmt::Debug for SessionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionStats")
+            .field("messages", &self.messages())
+            .field("commands", &self.commands())
+            .field("errors", &self.errors())
+            .field("p50", &self.latency_percentile(50.0))
+            .field("p95", &self.latency_percentile(95.0))
+            .field("p99", &self.latency_percentile(99.0))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi::fake, Stream, TransaqConnector};
+
+    #[test]
+    fn commands_messages_and_errors() {
+        let (_library, module) = fake::load();
+        fake::respond_with(|cmd| {
+            cmd.contains("\"fail\"").then(|| "<error>Команда не поддерживается</error>".into())
+        });
+        let mut txc = TransaqConnector::from_module(module);
+        let stats = txc.stats();
+        assert_eq!((stats.messages(), stats.commands(), stats.errors()), (0, 0, 0));
+        assert_eq!(stats.latency_percentile(50.0), None);
+
+        let sender = txc.sender();
+        for _ in 0..3 {
+            unsafe { sender.send("<command id=\"server_status\"/>\0") }.unwrap();
+        }
+        for _ in 0..2 {
+            assert!(unsafe { sender.send("<command id=\"fail\"/>\0") }.is_err());
+        }
+        assert_eq!((stats.messages(), stats.commands(), stats.errors()), (0, 5, 2));
+
+        // the messages without a handler are not delivered
+        assert!(!fake::deliver("<a/>"));
+        let _subscription = txc.input_stream().subscribe_guarded(|_| {}).unwrap();
+        for _ in 0..4 {
+            assert!(fake::deliver("<server_status connected=\"true\"/>"));
+        }
+        assert_eq!((stats.messages(), stats.commands(), stats.errors()), (4, 5, 2));
+        assert!(stats.latency_percentile(99.0).is_some());
+        assert_eq!(txc.stats().commands(), 5);
+    }
+
+    #[cfg(feature = "catch_unwind")]
+    #[test]
+    fn handler_panic_is_an_error() {
+        let (_library, module) = fake::load();
+        let mut txc = TransaqConnector::from_module(module);
+        txc.set_panic_policy(crate::PanicPolicy::Custom(std::sync::Arc::new(|_| {})));
+        let stats = txc.stats();
+
+        let _subscription = txc.input_stream().subscribe_guarded(|_| panic!("boom")).unwrap();
+        assert!(fake::deliver("<a/>"));
+        assert!(fake::deliver("<b/>"));
+        assert_eq!(stats.errors(), 1);
+        assert_eq!(stats.messages(), 2);
+        assert_eq!(stats.commands(), 0);
+    }
+
+    #[test]
+    fn latency_percentiles() {
+        let stats = SessionStats::default();
+        for us in 1..=100 {
+            stats.record_message(Duration::from_micros(us));
+        }
+        assert_eq!(stats.latency_percentile(50.0), Some(Duration::from_micros(50)));
+        assert_eq!(stats.latency_percentile(99.0), Some(Duration::from_micros(99)));
+        assert_eq!(stats.latency_percentile(100.0), Some(Duration::from_micros(100)));
+        assert_eq!(stats.latency_percentile(0.0), Some(Duration::from_micros(1)));
+
+        // the longer invocations are collected by the last bucket
+        stats.record_message(Duration::from_secs(1));
+        assert_eq!(stats.latency_percentile(100.0), Some(Duration::from_micros(255)));
+        assert_eq!(stats.messages(), 101);
+    }
+}
@@ -1,6 +1,27 @@
 #![allow(missing_docs)]
 
-use std::fmt::Debug;
+use super::buffers::{xml_root_tag_name, OwnedMessage};
+use super::channel::{self, OverflowPolicy, Receiver};
+#[cfg(feature = "encoding")]
+use super::encoding::decode_cp1251;
+use super::feed::{FeedWriter, Framing};
+use super::spsc;
+#[cfg(feature = "xml-events")]
+use super::xml::{ParsedMessage, XmlError};
+use super::{BrokerMessage, Message, MessagesResponse, OrdersResponse, ServerStatusResponse};
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{Debug, Display},
+    io::Write,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, Condvar, Mutex, MutexGuard, Weak,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
 /// Аналог [`std::iter::Iterator`] для многопоточного использования.
 ///
@@ -16,7 +37,144 @@ use std::fmt::Debug;
 pub trait Stream: Sized + Send {
     type Output;
 
-    fn subscribe<F: FnMut(Self::Output) + Sync + Send + 'static>(self, f: F);
+    /// Устанавливает обработчик и возвращает [`Subscription`], удаление которой отключает
+    /// обработчик
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let subscription = txc.input_stream().subscribe_guarded(|buf| println!("{buf}"))?;
+    /// // ..
+    /// drop(subscription);
+    /// // обработчик больше не вызывается
+    /// ```
+    fn subscribe_guarded<F: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: F,
+    ) -> crate::Result<Subscription>;
+
+    /// Устанавливает обработчик на всё время жизни источника, см. [`Subscription::detach`]
+    ///
+    /// # Errors
+    /// - [`Error::Callback`](crate::Error::Callback) - источник не смог установить обработчик,
+    ///   при этом остаётся установленным предыдущий обработчик
+    #[inline(always)]
+    fn subscribe<F: FnMut(Self::Output) + Sync + Send + 'static>(self, f: F) -> crate::Result {
+        self.subscribe_guarded(f).map(Subscription::detach)
+    }
+
+    /// Описание конвейера, имя типа `Self` без путей модулей, например
+    /// `Map<FilterMap<InputStream<_>, _>, _>`
+    ///
+    /// Типы замыканий заменяются на `_`. Имя типа, как и [`std::any::type_name`], не является
+    /// стабильным и предназначено только для отладки.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let stream = txc.input_stream().filter(|buf| buf.len() > 32).map(|buf| buf.to_owned_message());
+    /// println!("{}", stream.pipeline_description());
+    /// ```
+    fn pipeline_description(&self) -> String {
+        short_type_name(std::any::type_name::<Self>())
+    }
+
+    /// Устанавливает обработчик, выполняемый в отдельном потоке, на всё время жизни источника
+    ///
+    /// Элементы передаются в поток обработчика через очередь без блокировок ёмкостью
+    /// **capacity**, что освобождает поток источника от выполнения обработчика. При заполнении
+    /// очереди поток источника ожидает освобождения места. Поток обработчика завершается после
+    /// обработки оставшихся элементов при замене обработчика или освобождении источника.
+    ///
+    /// Элементы должны быть `'static`, например [`OwnedMessage`](crate::OwnedMessage).
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .map(|buf| buf.to_owned_message())
+    ///     .subscribe_spsc(|msg| /*..*/, 1 << 12)?;
+    /// ```
+    ///
+    /// # Errors
+    /// Ошибки создания потока или установки обработчика, см. [`Stream::subscribe`]
+    fn subscribe_spsc<F>(self, mut f: F, capacity: usize) -> crate::Result
+    where
+        F: FnMut(Self::Output) + Send + 'static,
+        Self::Output: Send + 'static,
+    {
+        let (mut tx, rx) = spsc::spsc_channel(capacity);
+        let consumer = std::thread::Builder::new()
+            .name("txc-spsc".into())
+            .spawn(move || {
+                while let Some(x) = rx.pop() {
+                    f(x)
+                }
+            })
+            .map_err(|e| crate::Error::Internal(e.to_string().into()))?;
+        tx.wake(consumer.thread().clone());
+        self.subscribe(move |x| tx.push(x))
+    }
+
+    /// Преобразует сообщения в кодировке windows-1251 в строки
+    ///
+    /// См. [`TCStr::decode_cp1251`](crate::TCStr::decode_cp1251). Требует опции **encoding**.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter(|buf| buf.xml_tag_name() == Some(b"news_body"))
+    ///     .decode_cp1251()
+    ///     .subscribe(|news: String| println!("{news}"))?;
+    /// ```
+    #[cfg(feature = "encoding")]
+    #[inline(always)]
+    fn decode_cp1251(self) -> DecodeCp1251<Self>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        DecodeCp1251 { inner: self }
+    }
+
+    /// Разбирает сообщения в последовательности событий XML документа
+    ///
+    /// Некорректные или неполные сообщения передаются в обработчик как ошибка [`XmlError`], без
+    /// прерывания обработки последующих сообщений. См. [`ParsedMessage`]. Требует опции
+    /// **xml-events**.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .xml_events()
+    ///     .subscribe(|msg| match msg {
+    ///         Ok(msg) if msg.tag == "quotes" => println!("{:?}", msg.events),
+    ///         Ok(_) => {}
+    ///         Err(e) => eprintln!("{e}"),
+    ///     })?;
+    /// ```
+    #[cfg(feature = "xml-events")]
+    #[inline(always)]
+    fn xml_events(self) -> XmlEvents<Self>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        XmlEvents { inner: self }
+    }
+
+    /// Разбирает сообщения коннектора, см. [`Message`](crate::Message)
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream().messages().subscribe(|msg| match msg {
+    ///     Message::ServerStatus(status) => println!("connected: {:?}", status.connected),
+    ///     Message::Unknown { tag, .. } => println!("<{tag}>"),
+    ///     _ => {}
+    /// })?;
+    /// ```
+    #[inline(always)]
+    fn messages(self) -> Messages<Self>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        Messages { inner: self }
+    }
 
     #[inline(always)]
     fn map<F, R>(self, f: F) -> Map<Self, F>
@@ -49,107 +207,5329 @@ pub trait Stream: Sized + Send {
     {
         Inspect { inner: self, f }
     }
-}
 
-pub struct Map<S, F> {
-    inner: S,
-    f: F,
-}
-impl<S: Stream + Debug, F> Debug for Map<S, F> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Map").field("inner", &self.inner).finish()
+    /// Пропускает элементы, равные предыдущему
+    ///
+    /// Сравниваются только последовательные элементы: `a, a, b, a` передаётся как `a, b, a`.
+    /// Копия последнего переданного элемента хранится в состоянии комбинатора.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .map(OwnedMessage::from)
+    ///     .filter(|msg| msg.xml_tag_name() == Some(b"server_status"))
+    ///     .dedup()
+    ///     .subscribe(|msg| println!("{msg}"))?;
+    /// ```
+    #[inline(always)]
+    fn dedup(self) -> Dedup<Self>
+    where
+        Self::Output: PartialEq + Clone + Send + Sync,
+    {
+        Dedup { inner: self }
     }
-}
-impl<S, F, R> Stream for Map<S, F>
-where
-    S: Stream,
-    F: FnMut(S::Output) -> R + Sync + Send + 'static,
-{
-    type Output = R;
 
+    /// Пропускает элементы, ключ которых равен ключу предыдущего элемента
+    ///
+    /// Как и [`Stream::dedup`], сравниваются только последовательные элементы, в состоянии
+    /// комбинатора хранится ключ последнего переданного элемента.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .dedup_by_key(|buf| buf.to_bytes().to_vec())
+    ///     .subscribe(|buf| println!("{buf}"))?;
+    /// ```
     #[inline(always)]
-    fn subscribe<FSub: FnMut(Self::Output) + Sync + Send + 'static>(self, mut f: FSub) {
-        let mut mapf = self.f;
-        self.inner.subscribe(move |x| f((mapf)(x)));
+    fn dedup_by_key<F, K>(self, f: F) -> DedupByKey<Self, F>
+    where
+        F: FnMut(&Self::Output) -> K + Sync + Send,
+        K: PartialEq + Send + Sync,
+    {
+        DedupByKey { inner: self, f }
     }
-}
 
-pub struct Filter<S, F> {
-    inner: S,
-    f: F,
-}
-impl<S: Stream + Debug, F> Debug for Filter<S, F> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Filter").field("inner", &self.inner).finish()
+    /// Пропускает сообщения с корневым XML тэгом **tag**
+    ///
+    /// Имя тэга сравнивается побайтово, без преобразования буфера в строку и аллокаций;
+    /// XML декларация `<?xml ..?>` в начале сообщения пропускается.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter_tag("server_status")
+    ///     .subscribe(|buf| println!("{buf}"))?;
+    /// ```
+    #[inline(always)]
+    fn filter_tag<T: AsRef<[u8]>>(self, tag: T) -> FilterTag<Self>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        self.filter_tags([tag])
     }
-}
-impl<S, F> Stream for Filter<S, F>
-where
-    S: Stream,
-    F: FnMut(&S::Output) -> bool + Sync + Send + 'static,
-{
-    type Output = S::Output;
 
+    /// Пропускает сообщения, корневой XML тэг которых входит в **tags**, см. [`Stream::filter_tag`]
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter_tags(["quotes", "alltrades"])
+    ///     .subscribe(|buf| println!("{buf}"))?;
+    /// ```
     #[inline(always)]
-    fn subscribe<FSub: FnMut(Self::Output) + Sync + Send + 'static>(self, mut f: FSub) {
-        let mut filterf = self.f;
-        self.inner.subscribe(move |x| {
-            if (filterf)(&x) {
-                f(x)
-            }
+    fn filter_tags<I>(self, tags: I) -> FilterTag<Self>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+        Self::Output: AsRef<[u8]>,
+    {
+        let tags = tags.into_iter().map(|tag| tag.as_ref().into()).collect();
+        FilterTag { inner: self, tags }
+    }
+
+    /// Пропускает сообщения `<server_status>`, разобранные в [`ServerStatusResponse`]
+    ///
+    /// Сообщения, которые не удалось разобрать, пропускаются.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .server_status()
+    ///     .subscribe(|status| match status.connected {
+    ///         ConnectionStatus::Error => eprintln!("{}", status.text.unwrap_or_default()),
+    ///         connected => println!("{connected:?}"),
+    ///     })?;
+    /// ```
+    #[inline(always)]
+    fn server_status(self) -> ServerStatus<Self>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        ServerStatus { inner: self }
+    }
+
+    /// Пропускает сообщения `<orders>`, разобранные в [`OrdersResponse`]
+    ///
+    /// Сообщения, которые не удалось разобрать, пропускаются.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .order_updates()
+    ///     .subscribe(|update| {
+    ///         for order in update.orders {
+    ///             println!("{}: {:?}, {:?}", order.transactionid, order.status, order.balance);
+    ///         }
+    ///     })?;
+    /// ```
+    #[inline(always)]
+    fn order_updates(self) -> OrderUpdates<Self>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        OrderUpdates { inner: self }
+    }
+
+    /// Пропускает сообщения брокера из сообщений `<messages>`, см. [`BrokerMessage`]
+    ///
+    /// Каждое сообщение брокера передаётся отдельно, сообщения `<messages>`, которые не удалось
+    /// разобрать, пропускаются.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let (alerts, rx) = std::sync::mpsc::channel();
+    /// txc.input_stream()
+    ///     .broker_messages()
+    ///     .filter(|msg| msg.urgent)
+    ///     .subscribe(move |msg| {
+    ///         let _ = alerts.send(msg.text);
+    ///     })?;
+    /// ```
+    #[inline(always)]
+    fn broker_messages(self) -> BrokerMessages<Self>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        BrokerMessages { inner: self }
+    }
+
+    /// Нумерует элементы, начиная с 0
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let mut prev = None;
+    /// txc.input_stream()
+    ///     .timestamped()
+    ///     .enumerate()
+    ///     .subscribe(move |(seq, (at, buf))| {
+    ///         let latency = prev.replace(at).map(|prev| at - prev).unwrap_or_default();
+    ///         let tag = buf.xml_tag_name().map(String::from_utf8_lossy);
+    ///         println!("#{seq} +{latency:?} {tag:?}");
+    ///     })?;
+    /// ```
+    #[inline(always)]
+    fn enumerate(self) -> Enumerate<Self> {
+        Enumerate { inner: self }
+    }
+
+    /// Добавляет к элементам момент их поступления, [`Instant`]
+    ///
+    /// Момент фиксируется до вызова последующих комбинаторов и обработчика, поэтому соответствует
+    /// поступлению элемента в этот этап конвейера, а не времени его обработки; для измерения
+    /// задержек `timestamped` следует размещать в начале конвейера. [`Instant`] монотонен, и
+    /// моменты последовательных элементов не убывают, что позволяет вычислять интервалы между
+    /// ними, см. [`Stream::enumerate`].
+    #[inline(always)]
+    fn timestamped(self) -> Timestamped<Self> {
+        Timestamped { inner: self }
+    }
+
+    /// Добавляет к элементам системное время их поступления, [`SystemTime`], например для
+    /// логирования
+    #[inline(always)]
+    fn timestamped_system(self) -> TimestampedSystem<Self> {
+        TimestampedSystem { inner: self }
+    }
+
+    /// Подсчитывает количество и объём прошедших сообщений
+    ///
+    /// Возвращает [`StreamMetrics`] - счётчики, которые можно опрашивать из другого потока,
+    /// например для оценки пропускной способности конвейера. Счётчики обновляются в потоке
+    /// коннектора до передачи элемента в обработчик.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let (stream, metrics) = txc.input_stream().metered();
+    /// stream.subscribe(|buf| /*..*/)?;
+    ///
+    /// loop {
+    ///     std::thread::sleep(Duration::from_secs(1));
+    ///     println!("{} msgs, {} bytes", metrics.messages(), metrics.bytes());
+    /// }
+    /// ```
+    #[inline(always)]
+    fn metered(self) -> (Metered<Self>, StreamMetrics)
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        let metrics = StreamMetrics(Arc::new(MetricsState {
+            messages: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            last_arrival: AtomicU64::new(0),
+            epoch: Instant::now(),
+        }));
+        (Metered { inner: self, metrics: metrics.clone() }, metrics)
+    }
+
+    /// Измеряет частоту поступления элементов в скользящем окне **window**
+    ///
+    /// Частота в элементах в секунду вычисляется по количеству элементов, поступивших за последние
+    /// **window**, и передаётся в **observer**, если она изменилась больше чем на **threshold**
+    /// с момента последнего вызова, а так же при поступлении первого элемента. Элементы
+    /// передаются дальше без изменений.
+    ///
+    /// **observer** вызывается в потоке источника перед обработчиком и должен выполняться
+    /// быстро. Моменты поступления элементов окна хранятся в состоянии комбинатора.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .rate_per_second(Duration::from_secs(1), 10.0, |rate| println!("{rate:.0} msg/s"))
+    ///     .subscribe(|buf| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn rate_per_second<F>(
+        self,
+        window: Duration,
+        threshold: f64,
+        observer: F,
+    ) -> RatePerSecond<Self, F>
+    where
+        F: FnMut(f64) + Sync + Send,
+    {
+        RatePerSecond { inner: self, window, threshold, observer, now: Instant::now }
+    }
+
+    /// Записывает каждый элемент в журнал `tracing` с уровнем **level**, передавая элементы
+    /// дальше без изменений
+    ///
+    /// Событие с целью `libtxc::stream` содержит поле `target` со значением **target** и
+    /// элемент в качестве сообщения, что заменяет `inspect(|buf| tracing::debug!("{buf}"))`.
+    /// Без опции **tracing** комбинатор передаёт обработчик источнику без изменений.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter(|buf| buf.xml_tag_name() == Some(b"server_status"))
+    ///     .log_each(TraceLevel::INFO, "server_status")
+    ///     .subscribe(|buf| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn log_each(self, level: TraceLevel, target: &'static str) -> LogEach<Self>
+    where
+        Self::Output: Display,
+    {
+        LogEach { inner: self, level, target }
+    }
+
+    /// Преобразует элементы функцией **f** в пуле из **workers** потоков
+    ///
+    /// Элементы передаются в пул через ограниченную очередь; при заполнении очереди поток
+    /// источника ожидает освобождения места. Обработчик вызывается в потоках пула, но не
+    /// одновременно. В режиме [`ParallelMode::Ordered`] результаты передаются в обработчик в
+    /// порядке поступления элементов, в режиме [`ParallelMode::Unordered`] - по готовности.
+    ///
+    /// Элементы должны быть `'static`, например [`OwnedMessage`](crate::OwnedMessage). Элемент,
+    /// при обработке которого **f** запаниковала, пропускается. Освобождение подписки
+    /// дожидается обработки элементов очереди и завершения потоков пула.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter(|buf| buf.xml_tag_name() == Some(b"securities"))
+    ///     .map(|buf| buf.to_owned_message())
+    ///     .map_parallel(4, ParallelMode::Ordered, |msg| SecuritiesResponse::try_from(&msg))
+    ///     .subscribe(|securities| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn map_parallel<F, R>(self, workers: usize, mode: ParallelMode, f: F) -> MapParallel<Self, F>
+    where
+        Self::Output: Send + 'static,
+        F: Fn(Self::Output) -> R + Sync + Send + 'static,
+        R: Send + 'static,
+    {
+        MapParallel { inner: self, workers: workers.max(1), mode, f }
+    }
+
+    /// Вызывает **f** однократно после установки обработчика
+    ///
+    /// **f** вызывается в потоке, устанавливающем обработчик, когда обработчик уже получает
+    /// сообщения, например для отправки команды подписки без риска пропустить ответ. Сообщения,
+    /// поступившие до вызова **f**, обрабатываются как обычно. Если обработчик не удалось
+    /// установить, **f** не вызывается.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let sender = txc.sender();
+    /// txc.input_stream()
+    ///     .on_subscribe(move || unsafe { sender.send(subscribe_cmd).map(drop).unwrap() })
+    ///     .filter(|buf| buf.xml_tag_name() == Some(b"quotes"))
+    ///     .subscribe(|buf| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn on_subscribe<F>(self, f: F) -> OnSubscribe<Self, F>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        OnSubscribe { inner: self, f }
+    }
+
+    /// Вызывает **f** при освобождении обработчика
+    ///
+    /// Обработчик освобождается при замене обработчика, освобождении подписки или источника,
+    /// например при освобождении [`TransaqConnector`](crate::TransaqConnector) - до завершения
+    /// работы коннектора и выгрузки библиотеки. **f** вызывается в потоке, освобождающем
+    /// обработчик, в том числе если обработчик не удалось установить.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let sender = txc.sender();
+    /// txc.input_stream()
+    ///     .on_drop(move || unsafe { sender.send(unsubscribe_cmd).map(drop).unwrap() })
+    ///     .subscribe(|buf| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn on_drop<F>(self, f: F) -> OnDrop<Self, F>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        OnDrop { inner: self, f }
+    }
+
+    /// Записывает сообщения в **writer**, передавая их дальше без изменений
+    ///
+    /// Сообщения записываются в формате **framing** и могут быть прочитаны
+    /// [`FeedReader`](crate::FeedReader), например для отладки разбора сообщений без подключения
+    /// к серверу. Запись буферизуется и сбрасывается в **writer** не реже раза в секунду, а так же
+    /// при освобождении подписки. После первой ошибки записи запись прекращается, сообщения
+    /// продолжают передаваться в обработчик.
+    ///
+    /// ```no_run
+    /// use libtxc::Framing;
+    ///
+    /// let mut txc = /*..*/;
+    /// let file = std::fs::File::create("session.feed")?;
+    /// txc.input_stream()
+    ///     .record_to_writer(file, Framing::LengthPrefixed)
+    ///     .subscribe(|buf| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn record_to_writer<W>(self, writer: W, framing: Framing) -> RecordToWriter<Self, W>
+    where
+        Self::Output: AsRef<[u8]>,
+        W: Write + Send + 'static,
+    {
+        RecordToWriter { inner: self, writer: FeedWriter::new(writer, framing) }
+    }
+
+    /// Передаёт не более **n** первых элементов
+    ///
+    /// После **n**-го элемента обработчик больше не вызывается, а подписка источника
+    /// освобождается в фоновом потоке, в том числе после [`Subscription::detach`]. При `n == 0`
+    /// обработчик не вызывается ни разу.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter(|buf| buf.xml_tag_name() == Some(b"server_status"))
+    ///     .take(1)
+    ///     .subscribe(|buf| println!("{buf}"))?;
+    /// ```
+    #[inline(always)]
+    fn take(self, n: usize) -> Take<Self> {
+        Take { inner: self, n }
+    }
+
+    /// Передаёт элементы, пока выполняется условие
+    ///
+    /// После первого элемента, не удовлетворяющего условию, ни условие, ни обработчик больше
+    /// не вызываются.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .take_while(|buf| buf.xml_attr(b"connected") != Some(b"false"))
+    ///     .subscribe(|buf| println!("{buf}"))?;
+    /// ```
+    #[inline(always)]
+    fn take_while<F>(self, f: F) -> TakeWhile<Self, F>
+    where
+        F: FnMut(&Self::Output) -> bool + Sync + Send,
+    {
+        TakeWhile { inner: self, f }
+    }
+
+    /// Пропускает элементы, пока выполняется условие
+    ///
+    /// Начиная с первого элемента, не удовлетворяющего условию, передаёт все элементы,
+    /// условие больше не вызывается.
+    #[inline(always)]
+    fn skip_while<F>(self, f: F) -> SkipWhile<Self, F>
+    where
+        F: FnMut(&Self::Output) -> bool + Sync + Send,
+    {
+        SkipWhile { inner: self, f }
+    }
+
+    /// Объединяет элементы в пакеты по **n** элементов
+    ///
+    /// При освобождении [`Subscription`] оставшиеся элементы передаются неполным пакетом.
+    /// Неполный пакет передаётся в потоке, освобождающем подписку, поэтому элементы должны
+    /// удовлетворять `Send + 'static`, например [`OwnedMessage`](crate::OwnedMessage).
+    /// При `n == 0` пакеты состоят из одного элемента.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let subscription = txc.input_stream()
+    ///     .map(OwnedMessage::from)
+    ///     .chunks(1 << 10)
+    ///     .subscribe_guarded(|batch| /* запись на диск */)?;
+    /// ```
+    #[inline(always)]
+    fn chunks(self, n: usize) -> Chunks<Self>
+    where
+        Self::Output: Send + 'static,
+    {
+        Chunks { inner: self, n: n.max(1), timeout: None }
+    }
+
+    /// Объединяет элементы в пакеты по **n** элементов, передавая неполный пакет по истечении
+    /// **timeout** с момента поступления его первого элемента
+    ///
+    /// Истечение **timeout** проверяется при поступлении очередного элемента, а не отдельным
+    /// таймером: это не требует дополнительного потока и сохраняет вызов обработчика в потоке
+    /// коннектора, но неполный пакет может быть передан позже **timeout**, если новые элементы
+    /// не поступают. Остальное поведение совпадает с [`Stream::chunks`].
+    #[inline(always)]
+    fn chunks_timeout(self, n: usize, timeout: Duration) -> Chunks<Self>
+    where
+        Self::Output: Send + 'static,
+    {
+        Chunks { inner: self, n: n.max(1), timeout: Some(timeout) }
+    }
+
+    /// Передаёт не более одного элемента за **period** - последний поступивший за период
+    ///
+    /// Первый период начинается с поступлением первого элемента. Элементы, поступившие в течение
+    /// периода, замещают друг друга, а последний из них передаётся при поступлении первого
+    /// элемента после окончания периода, который начинает следующий период. Таким образом
+    /// передаваемый элемент отстаёт на один период, а последний элемент не передаётся, пока не
+    /// поступит следующий.
+    ///
+    /// В отличие от [`Stream::throttle`], передающего первый элемент периода без задержки,
+    /// `sample` передаёт наиболее актуальное значение периода, например для обновления
+    /// интерфейса по частым изменениям стакана.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter(|buf| buf.xml_tag_name() == Some(b"quotes"))
+    ///     .map(OwnedMessage::from)
+    ///     .sample(Duration::from_millis(100))
+    ///     .subscribe(|msg| /* обновление интерфейса */)?;
+    /// ```
+    #[inline(always)]
+    fn sample(self, period: Duration) -> Sample<Self>
+    where
+        Self::Output: Send + Sync + 'static,
+    {
+        Sample { inner: self, period, now: Instant::now }
+    }
+
+    /// Передаёт первый элемент и пропускает остальные элементы в течение **period**
+    ///
+    /// Период начинается с переданного элемента, следующий элемент передаётся, если он поступил
+    /// не раньше, чем через **period**. В отличие от [`Stream::sample`], элемент передаётся без
+    /// задержки, но это не обязательно последнее значение периода.
+    #[inline(always)]
+    fn throttle(self, period: Duration) -> Throttle<Self> {
+        Throttle { inner: self, period, now: Instant::now }
+    }
+
+    /// Скользящее окно из **n** последних элементов
+    ///
+    /// Передаёт [`WindowView`] - окно после поступления каждого элемента, начиная с момента
+    /// заполнения окна. Окно хранится в состоянии комбинатора и не копируется, если обработчик
+    /// не сохраняет [`WindowView`] до поступления следующего элемента, иначе окно копируется
+    /// при поступлении следующего элемента. При `n == 0` окно состоит из одного элемента.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .filter_map(|buf| /* цена сделки */)
+    ///     .window(20)
+    ///     .map(|w| w.iter().sum::<Decimal>() / Decimal::from(w.len()))
+    ///     .subscribe(|mean| println!("{mean}"))?;
+    /// ```
+    #[inline(always)]
+    fn window(self, n: usize) -> Window<Self>
+    where
+        Self::Output: Clone + Send + Sync + 'static,
+    {
+        Window { inner: self, n: n.max(1), partial: false }
+    }
+
+    /// Скользящее окно из **n** последних элементов, передаваемое в том числе до заполнения
+    ///
+    /// См. [`Stream::window`].
+    #[inline(always)]
+    fn window_partial(self, n: usize) -> Window<Self>
+    where
+        Self::Output: Clone + Send + Sync + 'static,
+    {
+        Window { inner: self, n: n.max(1), partial: true }
+    }
+
+    /// Добавляет возможность приостановить доставку сообщений, см. [`FlowControl`]
+    ///
+    /// Пока доставка приостановлена, сообщения сохраняются в очереди размером **capacity**,
+    /// сообщения, не поместившиеся в очередь, отбрасываются. При `capacity == 0` все сообщения,
+    /// поступившие во время паузы, отбрасываются.
+    ///
+    /// Сообщения из очереди доставляются в вызывающем [`FlowControl::resume`] потоке, поэтому
+    /// элементы должны удовлетворять `Send + 'static`, например [`OwnedMessage`](crate::OwnedMessage).
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let (stream, flow) = txc.input_stream().map(OwnedMessage::from).with_flow_control(1 << 16);
+    /// stream.subscribe(|msg| /*..*/)?;
+    ///
+    /// flow.pause();
+    /// // ..
+    /// flow.resume();
+    /// println!("spilled: {}, dropped: {}", flow.spilled(), flow.dropped());
+    /// ```
+    #[inline(always)]
+    fn with_flow_control(
+        self,
+        capacity: usize,
+    ) -> (WithFlowControl<Self>, FlowControl<Self::Output>)
+    where
+        Self::Output: Send + 'static,
+    {
+        let state = Arc::new(FlowState {
+            paused: AtomicBool::new(false),
+            spilled: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            capacity,
+            inner: Mutex::new(FlowInner { spill: VecDeque::new(), sink: None }),
         });
+        (WithFlowControl { inner: self, state: Arc::clone(&state) }, FlowControl(state))
     }
-}
 
-pub struct FilterMap<S, F> {
-    inner: S,
-    f: F,
-}
-impl<S: Stream + Debug, F> Debug for FilterMap<S, F> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("FilterMap").field("inner", &self.inner).finish()
+    /// Вызывает **f**, если элементы не поступают в течение **timeout**
+    ///
+    /// **f** вызывается в отдельном потоке один раз за период простоя, следующий вызов возможен
+    /// только после поступления очередного элемента и нового периода простоя. Отсчёт первого
+    /// периода начинается в момент подписки. Элементы передаются дальше без изменений, поток
+    /// завершается при освобождении подписки.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .on_idle(Duration::from_secs(30), || eprintln!("нет данных 30 сек."))
+    ///     .subscribe(|buf| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn on_idle<F>(self, timeout: Duration, f: F) -> OnIdle<Self, F>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        OnIdle { inner: self, timeout, f }
     }
-}
-impl<S, F, T> Stream for FilterMap<S, F>
-where
-    S: Stream,
-    F: FnMut(S::Output) -> Option<T> + Sync + Send + 'static,
-{
-    type Output = T;
 
+    /// Перехватывает панику в последующих комбинаторах и обработчике
+    ///
+    /// Паника, возникшая при обработке элемента, передаётся в **on_panic**, после чего
+    /// обработка продолжается со следующего элемента. Перехваченная паника не достигает
+    /// функции обратного вызова коннектора, поэтому [`PanicPolicy`](crate::PanicPolicy) и опция
+    /// **catch_unwind** применяются только к панике вне `catching`, например в комбинаторах,
+    /// предшествующих ему в конвейере.
+    ///
+    /// Обработчик вызывается через [`AssertUnwindSafe`](std::panic::AssertUnwindSafe): после
+    /// паники его состояние может оказаться частично изменённым, поэтому `catching` следует
+    /// использовать для некритичных этапов конвейера, например журналирования. При сборке с
+    /// `panic = "abort"` паника не перехватывается.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .catching(|panic| eprintln!("logger panicked: {panic:?}"))
+    ///     .subscribe(|buf| /* журналирование */)?;
+    /// ```
     #[inline(always)]
-    fn subscribe<FSub: FnMut(Self::Output) + Sync + Send + 'static>(self, mut f: FSub) {
-        let mut fmapf = self.f;
-        self.inner.subscribe(move |x| {
-            if let Some(x) = (fmapf)(x) {
-                f(x);
-            }
-        })
+    fn catching<P>(self, on_panic: P) -> Catching<Self, P>
+    where
+        P: FnMut(Box<dyn Any + Send>) + Sync + Send,
+    {
+        Catching { inner: self, on_panic }
     }
-}
 
-pub struct Inspect<S, F> {
-    inner: S,
-    f: F,
-}
-impl<S: Stream + Debug, F> Debug for Inspect<S, F> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Inspect").field("inner", &self.inner).finish()
+    /// Направляет сообщения с корневым XML тэгом **tag** в обработчик **f**
+    ///
+    /// Возвращает [`Route`] для регистрации остальных обработчиков. Тэг сообщения извлекается
+    /// один раз, после чего последовательно сравнивается с тэгами обработчиков в порядке их
+    /// регистрации; сообщения без обработчика передаются в [`Route::default`].
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// txc.input_stream()
+    ///     .route("quotes", |buf| /* стакан */)
+    ///     .route("orders", |buf| /* заявки */)
+    ///     .default(|buf| println!("{buf}"))
+    ///     .subscribe_routed()?;
+    /// ```
+    #[inline(always)]
+    fn route<T, F>(self, tag: T, f: F) -> Route<Self, Handler<F, Unrouted>, Unrouted>
+    where
+        T: AsRef<[u8]>,
+        F: FnMut(Self::Output) + Sync + Send,
+        Self::Output: AsRef<[u8]>,
+    {
+        Route {
+            inner: self,
+            handlers: Handler { tag: tag.as_ref().into(), index: 0, f, next: Unrouted },
+            default: Unrouted,
+        }
     }
-}
-impl<S, F> Stream for Inspect<S, F>
-where
-    S: Stream,
-    F: FnMut(&S::Output) + Sync + Send + 'static,
-{
-    type Output = S::Output;
 
+    /// Разделяет поток на два независимых конвейера, см. [`Stream::fanout`]
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let (log, orders) = txc.input_stream().map(OwnedMessage::from).tee();
+    /// log.subscribe(|msg| println!("{msg}"))?;
+    /// orders.filter_tag("orders").subscribe(|msg| /*..*/)?;
+    /// ```
     #[inline(always)]
-    fn subscribe<FSub: FnMut(Self::Output) + Sync + Send + 'static>(self, mut f: FSub) {
-        let mut inspectf = self.f;
-        self.inner.subscribe(move |x| {
-            (inspectf)(&x);
-            f(x)
-        })
+    fn tee(self) -> (Tee<Self>, Tee<Self>)
+    where
+        Self::Output: Clone + 'static,
+    {
+        let mut branches = self.fanout(2);
+        let b = branches.pop().unwrap();
+        let a = branches.pop().unwrap();
+        (a, b)
+    }
+
+    /// Разделяет поток на **n** независимых конвейеров
+    ///
+    /// Каждый элемент передаётся во все подписанные ветви: копии - всем, кроме последней,
+    /// последняя получает исходное значение. Обработчик источника устанавливается после того,
+    /// как каждая из ветвей будет подписана или удалена, поэтому все ветви получают одни и те же
+    /// элементы, а удаление ветви без подписки не останавливает остальные. Обработчик источника
+    /// отключается после удаления [`Subscription`] всех ветвей.
+    ///
+    /// Ветви вызываются последовательно в потоке источника.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let mut branches = txc.input_stream().map(OwnedMessage::from).fanout(3).into_iter();
+    /// branches.next().unwrap().subscribe(|msg| /* журнал */)?;
+    /// branches.next().unwrap().filter_tag("quotes").subscribe(|msg| /* стакан */)?;
+    /// branches.next().unwrap().filter_tag("alltrades").subscribe(|msg| /* лента */)?;
+    /// ```
+    fn fanout(self, n: usize) -> Vec<Tee<Self>>
+    where
+        Self::Output: Clone + 'static,
+    {
+        let shared = Arc::new(TeeShared {
+            source: Mutex::new(TeeSource { source: Some(self), pending: n }),
+            control: Arc::new(Mutex::new(TeeControl { active: 0, subscription: None })),
+            branches: Arc::new(Mutex::new((0..n).map(|_| None).collect())),
+        });
+        (0..n).map(|index| Tee { shared: Some(Arc::clone(&shared)), index }).collect()
+    }
+
+    /// Объединяет поток с потоком **other** попарно: обработчик получает кортеж `(a, b)` из
+    /// очередного элемента каждого источника
+    ///
+    /// Элементы источника, опережающего другой, накапливаются в очереди до поступления парного
+    /// элемента, очередь не ограничена по размеру. Обработчик вызывается в потоке источника,
+    /// передавшего второй элемент пары, вызовы не пересекаются. Элементы должны удовлетворять
+    /// `Send + 'static`, поэтому буферы коннектора следует преобразовать, например в
+    /// [`OwnedMessage`](crate::OwnedMessage).
+    ///
+    /// ```no_run
+    /// let (primary, replica) = (/*..*/, /*..*/);
+    /// primary
+    ///     .map(OwnedMessage::from)
+    ///     .zip(replica.map(OwnedMessage::from))
+    ///     .filter(|(a, b)| a.as_ref() != b.as_ref())
+    ///     .subscribe(|(a, b)| eprintln!("mismatch: {a} != {b}"))?;
+    /// ```
+    #[inline(always)]
+    fn zip<O>(self, other: O) -> Zip<Self, O>
+    where
+        O: Stream,
+        Self::Output: Send + 'static,
+        O::Output: Send + 'static,
+    {
+        Zip { inner: self, other }
+    }
+
+    /// Передаёт элементы источника **other** после завершения текущего источника
+    ///
+    /// Источник считается завершённым после освобождения его обработчика, например по окончании
+    /// записи [`ReplayStream`](crate::ReplayStream), после [`Stream::take`] или вызова
+    /// [`CompletionHandle::complete`], см. [`Stream::completable`]. Обработчик **other**
+    /// устанавливается сразу, его элементы, поступившие до завершения текущего источника,
+    /// накапливаются в неограниченной очереди и передаются в обработчик при завершении, в потоке,
+    /// освобождающем обработчик текущего источника. Поэтому элементы должны удовлетворять
+    /// `Send + 'static`, а буферы коннектора следует преобразовать, например в
+    /// [`OwnedMessage`](crate::OwnedMessage). Вызовы обработчика не пересекаются.
+    ///
+    /// ```no_run
+    /// use libtxc::{Framing, ReplayStream};
+    ///
+    /// let mut txc = /*..*/;
+    /// ReplayStream::from_file("session.feed", Framing::LengthPrefixed)?
+    ///     .chain(txc.input_stream().map(OwnedMessage::from))
+    ///     .subscribe(|msg| /* сначала запись, затем сообщения коннектора */)?;
+    /// ```
+    #[inline(always)]
+    fn chain<O>(self, other: O) -> Chain<Self, O>
+    where
+        O: Stream<Output = Self::Output>,
+        Self::Output: Send + 'static,
+    {
+        Chain { inner: self, other }
+    }
+
+    /// Позволяет завершить источник явно, см. [`CompletionHandle::complete`]
+    ///
+    /// Предназначено для бесконечных источников, например для [`Stream::chain`]. Завершение
+    /// освобождает обработчик источника так же, как освобождение подписки.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let (warmup, handle) = txc.input_stream().map(OwnedMessage::from).completable();
+    /// warmup.chain(live).subscribe(|msg| /*..*/)?;
+    /// // ..
+    /// handle.complete();
+    /// ```
+    #[inline(always)]
+    fn completable(self) -> (Completable<Self>, CompletionHandle) {
+        let state =
+            Arc::new(ReleaseState { done: AtomicBool::new(false), subscription: Mutex::new(None) });
+        (Completable { inner: self, state: Arc::clone(&state) }, CompletionHandle(state))
+    }
+
+    /// Запускает обработчик **f** в отдельном потоке
+    ///
+    /// Элементы передаются в поток обработчика через ограниченную очередь ёмкостью **capacity**;
+    /// при заполнении очереди поток источника блокируется до освобождения места, см.
+    /// [`OverflowPolicy::Block`](crate::OverflowPolicy::Block). Элементы должны удовлетворять
+    /// `Send + 'static`, поэтому буферы коннектора следует преобразовать, например в
+    /// [`OwnedMessage`](crate::OwnedMessage).
+    ///
+    /// Обработчик источника отключается при вызове [`HandlerHandle::stop`] или удалении
+    /// [`HandlerHandle`], после чего поток обработчика обрабатывает оставшиеся в очереди
+    /// элементы и завершается.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let mut handle = txc.input_stream().map(OwnedMessage::from).spawn_handler(1024, |msg| {
+    ///     /* длительная обработка */
+    /// })?;
+    /// // ..
+    /// println!("lag: {}", handle.lag());
+    /// handle.stop();
+    /// handle.join().unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// - [`Error::Internal`](crate::Error::Internal) - не удалось запустить поток
+    /// - ошибки установки обработчика источника, см. [`Stream::subscribe_guarded`]
+    fn spawn_handler<F>(
+        self,
+        capacity: usize,
+        mut f: F,
+    ) -> crate::Result<HandlerHandle<Self::Output>>
+    where
+        F: FnMut(Self::Output) + Send + 'static,
+        Self::Output: Send + 'static,
+    {
+        let (tx, rx) = channel::bounded(capacity, OverflowPolicy::Block);
+        let rx = Arc::new(rx);
+        let processed = Arc::new(AtomicU64::new(0));
+
+        let worker = {
+            let rx = Arc::clone(&rx);
+            let processed = Arc::clone(&processed);
+            std::thread::Builder::new()
+                .name("txc-handler".into())
+                .spawn(move || {
+                    while let Ok(x) = rx.recv() {
+                        f(x);
+                        processed.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+                .map_err(|e| crate::Error::Internal(e.to_string().into()))?
+        };
+        // the worker exits once the sender is dropped alongside the subscription
+        let subscription = self.subscribe_guarded(move |x| tx.send(x))?;
+
+        Ok(HandlerHandle { subscription: Some(subscription), worker, rx, processed })
+    }
+
+    /// Устанавливает обработчик, возвращающий [`Result`], на всё время жизни источника
+    ///
+    /// Ошибки обработчика не прерывают обработку последующих элементов и передаются в
+    /// ограниченную очередь ёмкостью **capacity**; при её заполнении отбрасываются самые старые
+    /// ошибки, см. [`OverflowPolicy::DropOldest`]. Очередь закрывается при замене обработчика
+    /// или освобождении источника.
+    ///
+    /// Для сохранения исходного сообщения вместе с ошибкой см.
+    /// [`Stream::subscribe_result_with_message`].
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let errors = txc.input_stream().subscribe_result(64, |buf| {
+    ///     let status = ServerStatusResponse::try_from(&buf)?;
+    ///     /*..*/
+    ///     Ok(())
+    /// })?;
+    /// std::thread::spawn(move || errors.iter().for_each(|e| eprintln!("{}", e.error)));
+    /// ```
+    ///
+    /// # Errors
+    /// Ошибки установки обработчика, см. [`Stream::subscribe`]
+    fn subscribe_result<F, E>(
+        self,
+        capacity: usize,
+        mut f: F,
+    ) -> crate::Result<Receiver<ItemError<E>>>
+    where
+        F: FnMut(Self::Output) -> Result<(), E> + Sync + Send + 'static,
+        E: Send + 'static,
+    {
+        let (tx, rx) = channel::bounded(capacity, OverflowPolicy::DropOldest);
+        self.subscribe(move |x| {
+            if let Err(error) = f(x) {
+                tx.send(ItemError { error, message: None })
+            }
+        })?;
+        Ok(rx)
+    }
+
+    /// Как и [`Stream::subscribe_result`], но сохраняет копию сообщения, вызвавшего ошибку
+    ///
+    /// Обработчик получает сообщение по ссылке, копия создаётся только в случае ошибки.
+    ///
+    /// # Errors
+    /// Ошибки установки обработчика, см. [`Stream::subscribe`]
+    fn subscribe_result_with_message<F, E>(
+        self,
+        capacity: usize,
+        mut f: F,
+    ) -> crate::Result<Receiver<ItemError<E>>>
+    where
+        F: FnMut(&Self::Output) -> Result<(), E> + Sync + Send + 'static,
+        E: Send + 'static,
+        Self::Output: AsRef<[u8]>,
+    {
+        let (tx, rx) = channel::bounded(capacity, OverflowPolicy::DropOldest);
+        self.subscribe(move |x| {
+            if let Err(error) = f(&x) {
+                tx.send(ItemError { error, message: Some(OwnedMessage::from(x.as_ref())) })
+            }
+        })?;
+        Ok(rx)
+    }
+
+    /// Устанавливает обработчик, сохраняющий последний элемент, на всё время жизни источника
+    ///
+    /// Возвращает [`LatestHandle`], позволяющий прочитать последний элемент из любого потока,
+    /// не получая все элементы через канал, например последнее состояние подключения.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let status = txc.input_stream()
+    ///     .filter_map(|buf| ServerStatusResponse::try_from(&buf).ok())
+    ///     .latest()?;
+    ///
+    /// std::thread::spawn(move || loop {
+    ///     if status.changed(Duration::from_secs(1)) {
+    ///         println!("{:?}", status.get());
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    /// Ошибки установки обработчика, см. [`Stream::subscribe`]
+    fn latest(self) -> crate::Result<LatestHandle<Self::Output>>
+    where
+        Self::Output: Send + 'static,
+    {
+        let shared = Arc::new(LatestShared {
+            state: Mutex::new(LatestState { value: None, version: 0, closed: false }),
+            changed: Condvar::new(),
+        });
+        let publisher = LatestPublisher(Arc::clone(&shared));
+        self.subscribe(move |x| publisher.publish(x))?;
+        Ok(LatestHandle { shared, seen: AtomicU64::new(0) })
+    }
+
+    /// Ожидает первое сообщение, удовлетворяющее условию **pred**, в течение **timeout**
+    ///
+    /// Устанавливает временный обработчик, который освобождается по возвращении, и блокирует
+    /// текущий поток до поступления подходящего сообщения. Сообщение копируется в
+    /// [`OwnedMessage`](crate::OwnedMessage). Как и любая подписка, временный обработчик
+    /// заменяет установленный для источника обработчик.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let status = txc
+    ///     .input_stream()
+    ///     .first_matching(|buf| buf.xml_tag_name() == Some(b"server_status"), Duration::from_secs(30))?;
+    /// println!("{status}");
+    /// ```
+    ///
+    /// # Errors
+    /// - [`Error::Timeout`](crate::Error::Timeout) - подходящее сообщение не поступило в течение **timeout**
+    /// - ошибки установки обработчика, см. [`Stream::subscribe`]
+    fn first_matching<F>(self, mut pred: F, timeout: Duration) -> crate::Result<OwnedMessage>
+    where
+        F: FnMut(&Self::Output) -> bool + Sync + Send + 'static,
+        Self::Output: AsRef<[u8]>,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let _subscription = self.subscribe_guarded(move |x| {
+            if pred(&x) {
+                let _ = tx.try_send(OwnedMessage::from(x.as_ref()));
+            }
+        })?;
+        rx.recv_timeout(timeout).map_err(|_| crate::Error::Timeout)
+    }
+
+    /// Устанавливает обработчик, распределяющий сообщения по каналам в соответствии с корневым
+    /// XML тэгом, на всё время жизни источника
+    ///
+    /// Канал для сообщений с тэгом создаётся вызовом [`GroupReceiver::receiver_for`]; сообщения
+    /// без канала, в том числе поступившие до его создания, передаются в канал по-умолчанию,
+    /// возвращаемый вместе с [`GroupReceiver`]. Тэг сообщения извлекается один раз, без аллокаций,
+    /// сообщение копируется в [`OwnedMessage`](crate::OwnedMessage) для передачи в канал.
+    ///
+    /// Каналы ограничены ёмкостью **capacity**, поведение при переполнении по-умолчанию
+    /// [`OverflowPolicy::Block`](crate::OverflowPolicy::Block) может быть задано для каждого
+    /// канала, см. [`GroupReceiver::receiver_for_with`]. Каналы закрываются при замене
+    /// обработчика или освобождении источника.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let (groups, unmatched) = txc.input_stream().group_by_tag(1 << 10)?;
+    /// let quotes = groups.receiver_for("quotes");
+    /// let trades = groups.receiver_for_with("alltrades", OverflowPolicy::DropOldest);
+    ///
+    /// std::thread::spawn(move || quotes.iter().for_each(|msg| /*..*/));
+    /// ```
+    ///
+    /// # Errors
+    /// Ошибки установки обработчика, см. [`Stream::subscribe`]
+    fn group_by_tag(self, capacity: usize) -> crate::Result<(GroupReceiver, Receiver<OwnedMessage>)>
+    where
+        Self::Output: AsRef<[u8]>,
+    {
+        let (unmatched, rx) = channel::bounded(capacity, OverflowPolicy::default());
+        let groups: Arc<Groups> = Arc::new(Mutex::new(HashMap::new()));
+        let weak = Arc::downgrade(&groups);
+        self.subscribe(move |x| {
+            let bytes = x.as_ref();
+            let group = xml_root_tag_name(bytes).and_then(|tag| lock(&groups).get(tag).cloned());
+            match group {
+                Some(tx) => tx.send(OwnedMessage::from(bytes)),
+                None => unmatched.send(OwnedMessage::from(bytes)),
+            }
+        })?;
+        Ok((GroupReceiver { groups: weak, capacity }, rx))
+    }
+
+    /// Устанавливает обработчик, накапливающий состояние, начиная с **init**
+    ///
+    /// Каждый элемент передаётся в **f** вместе с текущим состоянием. При освобождении
+    /// обработчика, т.е. при освобождении возвращённой [`Subscription`], замене обработчика или
+    /// освобождении источника, итоговое состояние передаётся в **flush**, ровно один раз.
+    /// **flush** вызывается в потоке, освобождающем обработчик.
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let subscription = txc.input_stream().fold_stateful(
+    ///     (0u64, 0u64),
+    ///     |(buy, sell), buf| match TradesResponse::try_from(&buf) {
+    ///         /* подсчёт сделок по направлению */
+    ///     },
+    ///     |(buy, sell)| println!("buy: {buy}, sell: {sell}"),
+    /// )?;
+    /// ```
+    ///
+    /// # Errors
+    /// Ошибки установки обработчика, см. [`Stream::subscribe_guarded`]
+    fn fold_stateful<St, F, G>(self, init: St, mut f: F, flush: G) -> crate::Result<Subscription>
+    where
+        St: Send + Sync + 'static,
+        F: FnMut(&mut St, Self::Output) + Sync + Send + 'static,
+        G: FnOnce(St) + Sync + Send + 'static,
+    {
+        let mut fold = Fold { state: Some(init), flush: Some(flush) };
+        self.subscribe_guarded(move |x| {
+            if let Some(state) = fold.state.as_mut() {
+                f(state, x)
+            }
+        })
+    }
+
+    /// Устанавливает два обработчика: элементы, удовлетворяющие **pred**, передаются в
+    /// **subscribe_true**, остальные - в **subscribe_false**
+    ///
+    /// Подписка на источник выполняется один раз, элементы распределяются внутри единственного
+    /// обработчика, в отличие от двух цепочек [`Stream::filter`], требующих [`Stream::tee`].
+    ///
+    /// ```no_run
+    /// let mut txc = /*..*/;
+    /// let subscription = txc.input_stream().partition(
+    ///     |buf| buf.xml_tag_name() == Some(b"error"),
+    ///     |error| eprintln!("{error}"),
+    ///     |buf| /*..*/,
+    /// )?;
+    /// ```
+    ///
+    /// # Errors
+    /// Ошибки установки обработчика, см. [`Stream::subscribe_guarded`]
+    fn partition<P, T, F>(
+        self,
+        mut pred: P,
+        mut subscribe_true: T,
+        mut subscribe_false: F,
+    ) -> crate::Result<Subscription>
+    where
+        P: FnMut(&Self::Output) -> bool + Sync + Send + 'static,
+        T: FnMut(Self::Output) + Sync + Send + 'static,
+        F: FnMut(Self::Output) + Sync + Send + 'static,
+    {
+        self.subscribe_guarded(
+            move |x| if pred(&x) { subscribe_true(x) } else { subscribe_false(x) },
+        )
+    }
+
+    /// Стирает тип конвейера, см. [`BoxStream`]
+    ///
+    /// Элементы должны удовлетворять `'static`, поэтому буферы коннектора следует преобразовать
+    /// до стирания типа, например в [`OwnedMessage`](crate::OwnedMessage).
+    ///
+    /// ```no_run
+    /// struct Component<'a> {
+    ///     stream: BoxStream<'a, OwnedMessage>,
+    /// }
+    ///
+    /// let mut txc = /*..*/;
+    /// let stream = txc.input_stream().map(OwnedMessage::from).boxed();
+    /// stream.filter(|msg| msg.xml_tag_name() == Some(b"orders")).subscribe(|msg| /*..*/)?;
+    /// ```
+    #[inline(always)]
+    fn boxed<'a>(self) -> BoxStream<'a, Self::Output>
+    where
+        Self: 'a,
+        Self::Output: 'static,
+    {
+        BoxStream(Box::new(move |f| self.subscribe_guarded(f)))
+    }
+}
+
+/// Последний элемент источника, см. [`Stream::latest`]
+///
+/// Каждая копия `LatestHandle` отслеживает изменения независимо: [`LatestHandle::changed`]
+/// ожидает элемент, поступивший после последнего прочитанного этой копией.
+pub struct LatestHandle<T> {
+    shared: Arc<LatestShared<T>>,
+    // the version observed by this handle
+    seen: AtomicU64,
+}
+
+struct LatestShared<T> {
+    state: Mutex<LatestState<T>>,
+    changed: Condvar,
+}
+
+struct LatestState<T> {
+    value: Option<T>,
+    // the number of items published so far
+    version: u64,
+    closed: bool,
+}
+
+impl<T> LatestHandle<T> {
+    /// Возвращает копию последнего элемента
+    pub fn get(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        let state = lock(&self.shared.state);
+        self.seen.store(state.version, Ordering::Relaxed);
+        state.value.clone()
+    }
+
+    /// Забирает последний элемент, следующий вызов вернёт `None` до поступления нового элемента
+    pub fn take(&self) -> Option<T> {
+        let mut state = lock(&self.shared.state);
+        self.seen.store(state.version, Ordering::Relaxed);
+        state.value.take()
+    }
+
+    /// Ожидает поступления нового элемента не дольше **timeout**
+    ///
+    /// Возвращает `true`, если после последнего чтения этой копией поступил новый элемент.
+    /// Возвращает `false` по истечении **timeout** или если обработчик освобождён, например при
+    /// замене обработчика входящих сообщений.
+    pub fn changed(&self, timeout: Duration) -> bool {
+        let seen = self.seen.load(Ordering::Relaxed);
+        let state = lock(&self.shared.state);
+        let (state, _) = self
+            .shared
+            .changed
+            .wait_timeout_while(state, timeout, |state| state.version == seen && !state.closed)
+            .unwrap_or_else(|e| e.into_inner());
+        self.seen.store(state.version, Ordering::Relaxed);
+        state.version != seen
+    }
+
+    /// Обработчик освобождён, новые элементы не поступят
+    pub fn is_closed(&self) -> bool {
+        lock(&self.shared.state).closed
+    }
+}
+
+impl<T> Clone for LatestHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+            seen: AtomicU64::new(self.seen.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl<T: Debug> Debug for LatestHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = lock(&self.shared.state);
+        f.debug_struct("LatestHandle")
+            .field("value", &state.value)
+            .field("version", &state.version)
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+type Groups = Mutex<HashMap<Box<[u8]>, Arc<channel::ChannelSender<OwnedMessage>>>>;
+
+/// Распределение сообщений по каналам в соответствии с тэгом, см. [`Stream::group_by_tag`]
+pub struct GroupReceiver {
+    // the senders are owned by the handler and released along with it
+    groups: Weak<Groups>,
+    capacity: usize,
+}
+
+impl GroupReceiver {
+    /// Создаёт канал для сообщений с корневым тэгом **tag**
+    ///
+    /// При переполнении канала поток источника блокируется, см.
+    /// [`OverflowPolicy::Block`](crate::OverflowPolicy::Block). Повторный вызов для того же тэга
+    /// закрывает предыдущий канал.
+    pub fn receiver_for<T: AsRef<[u8]>>(&self, tag: T) -> Receiver<OwnedMessage> {
+        self.receiver_for_with(tag, OverflowPolicy::default())
+    }
+
+    /// Создаёт канал для сообщений с корневым тэгом **tag** с поведением при переполнении
+    /// **overflow**
+    ///
+    /// Если обработчик освобождён, возвращается закрытый канал.
+    pub fn receiver_for_with<T: AsRef<[u8]>>(
+        &self,
+        tag: T,
+        overflow: OverflowPolicy,
+    ) -> Receiver<OwnedMessage> {
+        let (tx, rx) = channel::bounded(self.capacity, overflow);
+        if let Some(groups) = self.groups.upgrade() {
+            lock(&groups).insert(tag.as_ref().into(), Arc::new(tx));
+        }
+        rx
+    }
+
+    /// Закрывает канал для сообщений с тэгом **tag**, последующие сообщения передаются в канал
+    /// по-умолчанию
+    pub fn remove<T: AsRef<[u8]>>(&self, tag: T) {
+        if let Some(groups) = self.groups.upgrade() {
+            lock(&groups).remove(tag.as_ref());
+        }
+    }
+}
+
+impl Debug for GroupReceiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tags = self.groups.upgrade().map(|groups| {
+            let groups = lock(&groups);
+            groups.keys().map(|tag| String::from_utf8_lossy(tag).into_owned()).collect::<Vec<_>>()
+        });
+        f.debug_struct("GroupReceiver")
+            .field("tags", &tags)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+// Publishing half owned by the handler, closes the handle once dropped
+struct LatestPublisher<T>(Arc<LatestShared<T>>);
+
+impl<T> LatestPublisher<T> {
+    fn publish(&self, x: T) {
+        let mut state = lock(&self.0.state);
+        state.value = Some(x);
+        state.version += 1;
+        drop(state);
+        self.0.changed.notify_all();
+    }
+}
+
+impl<T> Drop for LatestPublisher<T> {
+    fn drop(&mut self) {
+        lock(&self.0.state).closed = true;
+        self.0.changed.notify_all();
+    }
+}
+
+// The state of `Stream::fold_stateful`, flushed once the handler owning it is dropped
+struct Fold<St, G: FnOnce(St)> {
+    state: Option<St>,
+    flush: Option<G>,
+}
+
+impl<St, G: FnOnce(St)> Drop for Fold<St, G> {
+    fn drop(&mut self) {
+        if let (Some(state), Some(flush)) = (self.state.take(), self.flush.take()) {
+            flush(state)
+        }
+    }
+}
+
+type BoxSubscriber<T> = Box<dyn FnMut(T) + Sync + Send>;
+
+/// [`Stream`] со стёртым типом, см. [`Stream::boxed`]
+///
+/// Позволяет хранить конвейер в поле структуры или передавать его через границы API.
+/// Стоимость стирания типа - аллокация при подписке и динамический вызов обработчика на каждый
+/// элемент, комбинаторы, применённые до и после `boxed`, продолжают работать как обычно.
+pub struct BoxStream<'a, T>(
+    Box<dyn FnOnce(BoxSubscriber<T>) -> crate::Result<Subscription> + Send + 'a>,
+);
+impl<T> Debug for BoxStream<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxStream").finish_non_exhaustive()
+    }
+}
+impl<T> Stream for BoxStream<'_, T> {
+    type Output = T;
+
+    #[inline(always)]
+    fn subscribe_guarded<F: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: F,
+    ) -> crate::Result<Subscription> {
+        (self.0)(Box::new(f))
+    }
+}
+
+/// Ошибка обработки элемента, см. [`Stream::subscribe_result`]
+#[derive(Debug)]
+pub struct ItemError<E> {
+    /// Ошибка, возвращённая обработчиком
+    pub error: E,
+    /// Копия сообщения, вызвавшего ошибку, см. [`Stream::subscribe_result_with_message`]
+    pub message: Option<OwnedMessage>,
+}
+
+/// Поток обработчика, см. [`Stream::spawn_handler`]
+///
+/// Удаление `HandlerHandle` отключает обработчик источника, как и [`HandlerHandle::stop`], но
+/// не ожидает завершения потока обработчика.
+#[must_use = "удаление HandlerHandle отключает обработчик"]
+pub struct HandlerHandle<T> {
+    subscription: Option<Subscription>,
+    worker: std::thread::JoinHandle<()>,
+    rx: Arc<Receiver<T>>,
+    processed: Arc<AtomicU64>,
+}
+
+impl<T> HandlerHandle<T> {
+    /// Отключает обработчик источника
+    ///
+    /// Поток обработчика завершается после обработки оставшихся в очереди элементов.
+    pub fn stop(&mut self) {
+        drop(self.subscription.take());
+    }
+
+    /// Ожидает завершения потока обработчика
+    ///
+    /// Без вызова [`HandlerHandle::stop`] поток завершается только после освобождения источника.
+    /// Возвращает ошибку, если обработчик завершился паникой.
+    pub fn join(self) -> std::thread::Result<()> {
+        let HandlerHandle { subscription, worker, .. } = self;
+        let result = worker.join();
+        drop(subscription);
+        result
+    }
+
+    /// Количество элементов в очереди, ожидающих обработки
+    #[inline]
+    pub fn lag(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Количество обработанных элементов
+    #[inline]
+    pub fn processed(&self) -> u64 {
+        self.processed.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Debug for HandlerHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HandlerHandle")
+            .field("subscribed", &self.subscription.is_some())
+            .field("lag", &self.lag())
+            .field("processed", &self.processed())
+            .finish()
+    }
+}
+
+/// Подписка, отключающая обработчик при удалении, см. [`Stream::subscribe_guarded`]
+///
+/// Удаление `Subscription` отключает функцию обратного вызова в источнике сообщений и
+/// освобождает обработчик после завершения его текущего вызова, если такой выполняется.
+/// Для обработчиков, установленных [`TransaqConnector::input_stream()`](crate::TransaqConnector::input_stream),
+/// функция обратного вызова коннектора заменяется на освобождающую поступающие буферы без
+/// обработки.
+///
+/// [`Subscription::detach`] оставляет обработчик установленным до его замены или освобождения
+/// источника.
+///
+/// Не следует удалять `Subscription` внутри самого обработчика - это приведёт к взаимной
+/// блокировке или освобождению исполняемого обработчика.
+#[must_use = "удаление Subscription отключает обработчик"]
+pub struct Subscription(Option<Box<dyn FnOnce() + Send>>);
+
+impl Subscription {
+    /// Создаёт подписку с функцией отключения обработчика **release**
+    pub fn new<F: FnOnce() + Send + 'static>(release: F) -> Self {
+        Self(Some(Box::new(release)))
+    }
+
+    /// Подписка без функции отключения
+    pub fn detached() -> Self {
+        Self(None)
+    }
+
+    /// Оставляет обработчик установленным до его замены или освобождения источника
+    #[inline]
+    pub fn detach(mut self) {
+        self.0 = None;
+    }
+
+    // releases the subscriptions in order, detaching the composite subscription detaches each of
+    // them instead of releasing along with the dropped release function
+    pub(crate) fn join<I: IntoIterator<Item = Subscription>>(subscriptions: I) -> Self {
+        let joined = Joined(subscriptions.into_iter().collect());
+        Self::new(move || joined.release())
+    }
+
+    // runs `f` after the inner subscription is released
+    pub(crate) fn then<F: FnOnce() + Send + 'static>(mut self, f: F) -> Self {
+        match self.0.take() {
+            Some(release) => Self::new(move || {
+                release();
+                f()
+            }),
+            None => self,
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(release) = self.0.take() {
+            release()
+        }
+    }
+}
+
+impl Debug for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Subscription").field(&self.0.is_some()).finish()
+    }
+}
+
+// Subscriptions of the composite stream, see `Subscription::join`
+struct Joined(Vec<Subscription>);
+
+impl Joined {
+    fn release(mut self) {
+        drop(std::mem::take(&mut self.0));
+    }
+}
+
+impl Drop for Joined {
+    fn drop(&mut self) {
+        self.0.drain(..).for_each(Subscription::detach);
+    }
+}
+
+/// Создаёт [`Stream`] из функции **register**, получающей обработчик при подписке
+///
+/// Позволяет передавать произвольные элементы через те же комбинаторы, что и сообщения
+/// коннектора, например для проверки конвейера обработки без библиотеки коннектора.
+/// **register** вызывается однократно в потоке, устанавливающем обработчик; элементы передаются
+/// вызовом полученного обработчика из любого потока. Подписка не освобождает обработчик.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{from_fn, OwnedMessage, Stream};
+/// use std::sync::{Arc, Mutex};
+///
+/// let messages = ["<server_status connected=\"true\"/>", "<quotes/>"];
+/// let seen = Arc::new(Mutex::new(Vec::new()));
+/// let out = Arc::clone(&seen);
+///
+/// from_fn(move |mut subscriber| {
+///     for msg in messages {
+///         subscriber(OwnedMessage::from(msg.as_bytes()));
+///     }
+/// })
+/// .filter(|msg| msg.xml_tag_name() == Some(b"server_status"))
+/// .subscribe(move |msg| out.lock().unwrap().push(msg))?;
+///
+/// assert_eq!(seen.lock().unwrap().len(), 1);
+/// ```
+pub fn from_fn<T, R>(register: R) -> FromFn<T, R>
+where
+    R: FnOnce(Box<dyn FnMut(T) + Sync + Send>) + Send,
+{
+    FromFn { register, _item: PhantomData }
+}
+
+pub struct FromFn<T, R> {
+    register: R,
+    _item: PhantomData<fn(T)>,
+}
+impl<T, R> Debug for FromFn<T, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FromFn").finish()
+    }
+}
+impl<T, R> Stream for FromFn<T, R>
+where
+    R: FnOnce(Box<dyn FnMut(T) + Sync + Send>) + Send,
+{
+    type Output = T;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        (self.register)(Box::new(f));
+        Ok(Subscription::detached())
+    }
+}
+
+/// Объединяет источники **streams**, передавая в обработчик элементы только того источника,
+/// который первым передал элемент
+///
+/// Обработчики устанавливаются для всех источников; после первого элемента элементы остальных
+/// источников отбрасываются без вызова обработчика, их обработчики освобождаются вместе с
+/// подпиской. Например, для использования основного и резервного источника данных, в
+/// зависимости от того, какой из них начнёт передавать данные раньше.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{select_first, Stream};
+///
+/// let (primary, backup) = (/*..*/, /*..*/);
+/// select_first(vec![primary.boxed(), backup.boxed()]).subscribe(|msg| println!("{msg}"))?;
+/// ```
+pub fn select_first<T>(streams: Vec<BoxStream<'_, T>>) -> SelectFirst<'_, T> {
+    SelectFirst { streams }
+}
+
+pub struct SelectFirst<'a, T> {
+    streams: Vec<BoxStream<'a, T>>,
+}
+impl<T> Debug for SelectFirst<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelectFirst").field("streams", &self.streams.len()).finish()
+    }
+}
+impl<T> Stream for SelectFirst<'_, T> {
+    type Output = T;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        // index of the stream which delivered the first item
+        let winner = Arc::new(AtomicUsize::new(usize::MAX));
+        let f = Arc::new(Mutex::new(f));
+
+        let subscriptions = self
+            .streams
+            .into_iter()
+            .enumerate()
+            .map(|(i, stream)| {
+                let (winner, f) = (Arc::clone(&winner), Arc::clone(&f));
+                stream.subscribe_guarded(move |x| {
+                    let selected = match winner.compare_exchange(
+                        usize::MAX,
+                        i,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    ) {
+                        Ok(_) => true,
+                        Err(selected) => selected == i,
+                    };
+                    if selected {
+                        (*lock(&f))(x)
+                    }
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(Subscription::join(subscriptions))
+    }
+}
+
+#[cfg(feature = "encoding")]
+pub struct DecodeCp1251<S> {
+    inner: S,
+}
+#[cfg(feature = "encoding")]
+impl<S: Stream + Debug> Debug for DecodeCp1251<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecodeCp1251").field("inner", &self.inner).finish()
+    }
+}
+#[cfg(feature = "encoding")]
+impl<S> Stream for DecodeCp1251<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = String;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| f(decode_cp1251(x.as_ref()).into_owned()))
+    }
+}
+
+#[cfg(feature = "xml-events")]
+pub struct XmlEvents<S> {
+    inner: S,
+}
+#[cfg(feature = "xml-events")]
+impl<S: Stream + Debug> Debug for XmlEvents<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XmlEvents").field("inner", &self.inner).finish()
+    }
+}
+#[cfg(feature = "xml-events")]
+impl<S> Stream for XmlEvents<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = Result<ParsedMessage, XmlError>;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| f(ParsedMessage::parse(x.as_ref())))
+    }
+}
+
+pub struct ServerStatus<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for ServerStatus<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerStatus").field("inner", &self.inner).finish()
+    }
+}
+impl<S> Stream for ServerStatus<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = ServerStatusResponse;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| {
+            if let Ok(status) = ServerStatusResponse::parse(x.as_ref()) {
+                f(status)
+            }
+        })
+    }
+}
+
+pub struct OrderUpdates<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for OrderUpdates<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OrderUpdates").field("inner", &self.inner).finish()
+    }
+}
+impl<S> Stream for OrderUpdates<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = OrdersResponse;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| {
+            if let Ok(orders) = OrdersResponse::parse(x.as_ref()) {
+                f(orders)
+            }
+        })
+    }
+}
+
+pub struct BrokerMessages<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for BrokerMessages<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BrokerMessages").field("inner", &self.inner).finish()
+    }
+}
+impl<S> Stream for BrokerMessages<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = BrokerMessage;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| {
+            if let Ok(messages) = MessagesResponse::parse(x.as_ref()) {
+                messages.messages.into_iter().for_each(&mut f)
+            }
+        })
+    }
+}
+
+pub struct Messages<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for Messages<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Messages").field("inner", &self.inner).finish()
+    }
+}
+impl<S> Stream for Messages<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = Message;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| f(Message::parse_bytes(x.as_ref())))
+    }
+}
+
+// `type_name` with the module paths stripped and the closure types replaced by `_`
+fn short_type_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    while !rest.is_empty() {
+        let end = rest.find(&['<', '>', ',', '(', ')', '[', ']', ';', ' ', '&', '*'][..]);
+        let (path, tail) = match end {
+            Some(0) => rest.split_at(1),
+            Some(end) => rest.split_at(end),
+            None => (rest, ""),
+        };
+        if path.ends_with("{{closure}}") {
+            out.push('_');
+        } else {
+            out.push_str(path.rsplit("::").next().unwrap_or(path));
+        }
+        rest = tail;
+    }
+    out
+}
+
+// `Debug` of the closures as their type name, e.g. `app::main::{{closure}}`
+struct TypeName<T: ?Sized>(PhantomData<T>);
+
+impl<T: ?Sized> TypeName<T> {
+    fn new(_: &T) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: ?Sized> Debug for TypeName<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(std::any::type_name::<T>())
+    }
+}
+
+pub struct Map<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for Map<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Map")
+            .field("inner", &self.inner)
+            .field("f", &TypeName::new(&self.f))
+            .finish()
+    }
+}
+impl<S, F, R> Stream for Map<S, F>
+where
+    S: Stream,
+    F: FnMut(S::Output) -> R + Sync + Send + 'static,
+{
+    type Output = R;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut mapf = self.f;
+        self.inner.subscribe_guarded(move |x| f((mapf)(x)))
+    }
+}
+
+pub struct Filter<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for Filter<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Filter")
+            .field("inner", &self.inner)
+            .field("f", &TypeName::new(&self.f))
+            .finish()
+    }
+}
+impl<S, F> Stream for Filter<S, F>
+where
+    S: Stream,
+    F: FnMut(&S::Output) -> bool + Sync + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut filterf = self.f;
+        self.inner.subscribe_guarded(move |x| {
+            if (filterf)(&x) {
+                f(x)
+            }
+        })
+    }
+}
+
+pub struct FilterMap<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for FilterMap<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterMap")
+            .field("inner", &self.inner)
+            .field("f", &TypeName::new(&self.f))
+            .finish()
+    }
+}
+impl<S, F, T> Stream for FilterMap<S, F>
+where
+    S: Stream,
+    F: FnMut(S::Output) -> Option<T> + Sync + Send + 'static,
+{
+    type Output = T;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut fmapf = self.f;
+        self.inner.subscribe_guarded(move |x| {
+            if let Some(x) = (fmapf)(x) {
+                f(x);
+            }
+        })
+    }
+}
+
+pub struct Inspect<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for Inspect<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Inspect")
+            .field("inner", &self.inner)
+            .field("f", &TypeName::new(&self.f))
+            .finish()
+    }
+}
+impl<S, F> Stream for Inspect<S, F>
+where
+    S: Stream,
+    F: FnMut(&S::Output) + Sync + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut inspectf = self.f;
+        self.inner.subscribe_guarded(move |x| {
+            (inspectf)(&x);
+            f(x)
+        })
+    }
+}
+
+pub struct Dedup<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for Dedup<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dedup").field("inner", &self.inner).finish()
+    }
+}
+impl<S> Stream for Dedup<S>
+where
+    S: Stream,
+    S::Output: PartialEq + Clone + Send + Sync + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut prev: Option<S::Output> = None;
+        self.inner.subscribe_guarded(move |x| {
+            if prev.as_ref() != Some(&x) {
+                prev = Some(x.clone());
+                f(x)
+            }
+        })
+    }
+}
+
+pub struct DedupByKey<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for DedupByKey<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DedupByKey").field("inner", &self.inner).finish()
+    }
+}
+impl<S, F, K> Stream for DedupByKey<S, F>
+where
+    S: Stream,
+    F: FnMut(&S::Output) -> K + Sync + Send + 'static,
+    K: PartialEq + Send + Sync + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut key_fn = self.f;
+        let mut prev: Option<K> = None;
+        self.inner.subscribe_guarded(move |x| {
+            let key = (key_fn)(&x);
+            if prev.as_ref() != Some(&key) {
+                prev = Some(key);
+                f(x)
+            }
+        })
+    }
+}
+
+pub struct FilterTag<S> {
+    inner: S,
+    tags: Vec<Box<[u8]>>,
+}
+impl<S: Stream + Debug> Debug for FilterTag<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tags: Vec<_> = self.tags.iter().map(|tag| String::from_utf8_lossy(tag)).collect();
+        f.debug_struct("FilterTag").field("inner", &self.inner).field("tags", &tags).finish()
+    }
+}
+impl<S> Stream for FilterTag<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let tags = self.tags;
+        self.inner.subscribe_guarded(move |x| {
+            let matched = xml_root_tag_name(x.as_ref())
+                .map_or(false, |name| tags.iter().any(|tag| name == &tag[..]));
+            if matched {
+                f(x)
+            }
+        })
+    }
+}
+
+pub struct Enumerate<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for Enumerate<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Enumerate").field("inner", &self.inner).finish()
+    }
+}
+impl<S: Stream> Stream for Enumerate<S> {
+    type Output = (u64, S::Output);
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut seq = 0u64;
+        self.inner.subscribe_guarded(move |x| {
+            let n = seq;
+            seq = seq.wrapping_add(1);
+            f((n, x))
+        })
+    }
+}
+
+pub struct Timestamped<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for Timestamped<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Timestamped").field("inner", &self.inner).finish()
+    }
+}
+impl<S: Stream> Stream for Timestamped<S> {
+    type Output = (Instant, S::Output);
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| f((Instant::now(), x)))
+    }
+}
+
+pub struct TimestampedSystem<S> {
+    inner: S,
+}
+impl<S: Stream + Debug> Debug for TimestampedSystem<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimestampedSystem").field("inner", &self.inner).finish()
+    }
+}
+impl<S: Stream> Stream for TimestampedSystem<S> {
+    type Output = (SystemTime, S::Output);
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(move |x| f((SystemTime::now(), x)))
+    }
+}
+
+pub struct Metered<S> {
+    inner: S,
+    metrics: StreamMetrics,
+}
+impl<S: Stream + Debug> Debug for Metered<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metered").field("inner", &self.inner).finish()
+    }
+}
+impl<S> Stream for Metered<S>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let metrics = self.metrics;
+        self.inner.subscribe_guarded(move |x| {
+            metrics.record(x.as_ref().len());
+            f(x)
+        })
+    }
+}
+
+/// Счётчики сообщений, см. [`Stream::metered`]
+#[derive(Clone)]
+pub struct StreamMetrics(Arc<MetricsState>);
+
+struct MetricsState {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    // nanoseconds since `epoch` plus one, zero until the first message
+    last_arrival: AtomicU64,
+    epoch: Instant,
+}
+
+impl StreamMetrics {
+    #[inline]
+    fn record(&self, len: usize) {
+        let state = &*self.0;
+        let elapsed = state.epoch.elapsed().as_nanos() as u64;
+        state.messages.fetch_add(1, Ordering::Relaxed);
+        state.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        state.last_arrival.store(elapsed.saturating_add(1), Ordering::Relaxed);
+    }
+
+    /// Количество сообщений
+    pub fn messages(&self) -> u64 {
+        self.0.messages.load(Ordering::Relaxed)
+    }
+
+    /// Суммарный размер сообщений в байтах
+    pub fn bytes(&self) -> u64 {
+        self.0.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Момент поступления последнего сообщения
+    pub fn last_arrival(&self) -> Option<Instant> {
+        match self.0.last_arrival.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(self.0.epoch + Duration::from_nanos(nanos - 1)),
+        }
+    }
+}
+
+impl Debug for StreamMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamMetrics")
+            .field("messages", &self.messages())
+            .field("bytes", &self.bytes())
+            .field("last_arrival", &self.last_arrival())
+            .finish()
+    }
+}
+
+pub struct RatePerSecond<S, F> {
+    inner: S,
+    window: Duration,
+    threshold: f64,
+    observer: F,
+    now: fn() -> Instant,
+}
+impl<S: Stream + Debug, F> Debug for RatePerSecond<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RatePerSecond")
+            .field("inner", &self.inner)
+            .field("window", &self.window)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+impl<S, F> Stream for RatePerSecond<S, F>
+where
+    S: Stream,
+    F: FnMut(f64) + Sync + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let Self { inner, window, threshold, mut observer, now } = self;
+        let mut arrivals: VecDeque<Instant> = VecDeque::new();
+        let mut reported: Option<f64> = None;
+        inner.subscribe_guarded(move |x| {
+            let now = now();
+            while arrivals.front().map_or(false, |t| now.saturating_duration_since(*t) >= window) {
+                arrivals.pop_front();
+            }
+            arrivals.push_back(now);
+
+            let rate = arrivals.len() as f64 / window.as_secs_f64();
+            if reported.map_or(true, |reported| (rate - reported).abs() > threshold) {
+                reported = Some(rate);
+                observer(rate);
+            }
+            f(x)
+        })
+    }
+}
+
+/// Уровень журнала, см. [`Stream::log_each`]
+///
+/// С опцией **tracing** - `tracing::Level`.
+#[cfg(feature = "tracing")]
+pub use tracing::Level as TraceLevel;
+
+/// Уровень журнала, см. [`Stream::log_each`]
+///
+/// С опцией **tracing** - `tracing::Level`, без опции журнал не ведётся.
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceLevel(u8);
+
+#[cfg(not(feature = "tracing"))]
+impl TraceLevel {
+    /// Уровень `TRACE`
+    pub const TRACE: TraceLevel = TraceLevel(0);
+    /// Уровень `DEBUG`
+    pub const DEBUG: TraceLevel = TraceLevel(1);
+    /// Уровень `INFO`
+    pub const INFO: TraceLevel = TraceLevel(2);
+    /// Уровень `WARN`
+    pub const WARN: TraceLevel = TraceLevel(3);
+    /// Уровень `ERROR`
+    pub const ERROR: TraceLevel = TraceLevel(4);
+}
+
+pub struct LogEach<S> {
+    inner: S,
+    level: TraceLevel,
+    target: &'static str,
+}
+impl<S: Stream + Debug> Debug for LogEach<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogEach")
+            .field("inner", &self.inner)
+            .field("level", &self.level)
+            .field("target", &self.target)
+            .finish()
+    }
+}
+impl<S> Stream for LogEach<S>
+where
+    S: Stream,
+    S::Output: Display,
+{
+    type Output = S::Output;
+
+    #[cfg(feature = "tracing")]
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        // the callsite metadata is static, hence a callsite per level
+        macro_rules! event {
+            ($level:expr, $target:expr, $x:expr) => {
+                tracing::event!(target: "libtxc::stream", $level, target = $target, message = %$x)
+            };
+        }
+        let Self { inner, level, target } = self;
+        inner.subscribe_guarded(move |x| {
+            match level {
+                TraceLevel::TRACE => event!(TraceLevel::TRACE, target, x),
+                TraceLevel::DEBUG => event!(TraceLevel::DEBUG, target, x),
+                TraceLevel::INFO => event!(TraceLevel::INFO, target, x),
+                TraceLevel::WARN => event!(TraceLevel::WARN, target, x),
+                TraceLevel::ERROR => event!(TraceLevel::ERROR, target, x),
+            }
+            f(x)
+        })
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        self.inner.subscribe_guarded(f)
+    }
+}
+
+/// Порядок передачи результатов [`Stream::map_parallel`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParallelMode {
+    /// В порядке поступления элементов
+    ///
+    /// Готовые результаты ожидают результатов элементов, поступивших раньше.
+    Ordered,
+    /// По готовности результатов
+    Unordered,
+}
+
+pub struct MapParallel<S, F> {
+    inner: S,
+    workers: usize,
+    mode: ParallelMode,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for MapParallel<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MapParallel")
+            .field("inner", &self.inner)
+            .field("workers", &self.workers)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+impl<S, F, R> Stream for MapParallel<S, F>
+where
+    S: Stream,
+    S::Output: Send + 'static,
+    F: Fn(S::Output) -> R + Sync + Send + 'static,
+    R: Send + 'static,
+{
+    type Output = R;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let Self { inner, workers, mode, f: map } = self;
+        let (tx, rx) = mpsc::sync_channel::<(u64, S::Output)>(workers);
+        let rx = Arc::new(Mutex::new(rx));
+        let map = Arc::new(map);
+        let results = Arc::new(Mutex::new(Resequencer { next: 0, pending: BTreeMap::new(), f }));
+
+        let handles = (0..workers)
+            .map(|i| {
+                let (rx, map, results) = (Arc::clone(&rx), Arc::clone(&map), Arc::clone(&results));
+                std::thread::Builder::new().name(format!("txc-worker-{i}")).spawn(move || loop {
+                    // the queue is closed when the source releases the handler
+                    let (seq, x) = match lock(&rx).recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| map(x))).ok();
+                    match mode {
+                        ParallelMode::Ordered => lock(&results).push(seq, result),
+                        ParallelMode::Unordered => lock(&results).emit(result),
+                    }
+                })
+            })
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(|e| crate::Error::Internal(e.to_string().into()))?;
+
+        let mut seq = 0;
+        let subscription = inner.subscribe_guarded(move |x| {
+            let _ = tx.send((seq, x));
+            seq += 1;
+        })?;
+        Ok(subscription.then(move || {
+            for handle in handles {
+                // the handler may release the subscription from a worker
+                if handle.thread().id() != std::thread::current().id() {
+                    let _ = handle.join();
+                }
+            }
+        }))
+    }
+}
+
+// Restores the arrival order of the `map_parallel` results, `None` marks a skipped item
+struct Resequencer<T, F> {
+    next: u64,
+    pending: BTreeMap<u64, Option<T>>,
+    f: F,
+}
+
+impl<T, F: FnMut(T)> Resequencer<T, F> {
+    fn push(&mut self, seq: u64, result: Option<T>) {
+        self.pending.insert(seq, result);
+        while let Some(result) = self.pending.remove(&self.next) {
+            self.next += 1;
+            self.emit(result);
+        }
+    }
+
+    fn emit(&mut self, result: Option<T>) {
+        if let Some(x) = result {
+            (self.f)(x)
+        }
+    }
+}
+
+pub struct OnSubscribe<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for OnSubscribe<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OnSubscribe").field("inner", &self.inner).finish()
+    }
+}
+impl<S, F> Stream for OnSubscribe<S, F>
+where
+    S: Stream,
+    F: FnOnce() + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let subscription = self.inner.subscribe_guarded(f)?;
+        (self.f)();
+        Ok(subscription)
+    }
+}
+
+pub struct OnDrop<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for OnDrop<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OnDrop").field("inner", &self.inner).finish()
+    }
+}
+impl<S, F> Stream for OnDrop<S, F>
+where
+    S: Stream,
+    F: FnOnce() + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut guard = OnDropGuard { f, on_drop: Mutex::new(Some(self.f)) };
+        self.inner.subscribe_guarded(move |x| guard.call(x))
+    }
+}
+
+// Handler calling `on_drop` once it's released, see `Stream::on_drop`
+struct OnDropGuard<F, D: FnOnce()> {
+    f: F,
+    // the mutex makes the guard `Sync` for the `Send`-only cleanup
+    on_drop: Mutex<Option<D>>,
+}
+
+impl<F, D: FnOnce()> OnDropGuard<F, D> {
+    #[inline(always)]
+    fn call<T>(&mut self, x: T)
+    where
+        F: FnMut(T),
+    {
+        (self.f)(x)
+    }
+}
+
+impl<F, D: FnOnce()> Drop for OnDropGuard<F, D> {
+    fn drop(&mut self) {
+        if let Some(on_drop) = self.on_drop.get_mut().unwrap_or_else(|e| e.into_inner()).take() {
+            on_drop()
+        }
+    }
+}
+
+pub struct RecordToWriter<S, W: Write> {
+    inner: S,
+    writer: FeedWriter<W>,
+}
+impl<S: Stream + Debug, W: Write> Debug for RecordToWriter<S, W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordToWriter").field("inner", &self.inner).finish()
+    }
+}
+impl<S, W> Stream for RecordToWriter<S, W>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+    W: Write + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let writer = Arc::new(Mutex::new(Some(self.writer)));
+        let release = Arc::clone(&writer);
+        let subscription = self.inner.subscribe_guarded(move |x| {
+            let mut writer = lock(&writer);
+            if let Some(w) = writer.as_mut() {
+                if w.write_frame(x.as_ref()).is_err() {
+                    *writer = None;
+                }
+            }
+            drop(writer);
+            f(x)
+        })?;
+        Ok(subscription.then(move || {
+            if let Some(mut w) = lock(&release).take() {
+                let _ = w.flush();
+            }
+        }))
+    }
+}
+
+pub struct OnIdle<S, F> {
+    inner: S,
+    timeout: Duration,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for OnIdle<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OnIdle")
+            .field("inner", &self.inner)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+impl<S, F> Stream for OnIdle<S, F>
+where
+    S: Stream,
+    F: FnMut() + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let state = Arc::new(IdleState {
+            epoch: Instant::now(),
+            last_arrival: AtomicU64::new(0),
+            stopped: Mutex::new(false),
+            stop: Condvar::new(),
+        });
+
+        let (timer, timeout, on_idle) = (Arc::clone(&state), self.timeout, self.f);
+        std::thread::Builder::new()
+            .name("txc-idle".into())
+            .spawn(move || timer.run(timeout, on_idle))
+            .map_err(|e| crate::Error::Internal(e.to_string().into()))?;
+
+        let release = Arc::clone(&state);
+        let subscription = self.inner.subscribe_guarded(move |x| {
+            let elapsed = state.epoch.elapsed().as_nanos() as u64;
+            state.last_arrival.store(elapsed, Ordering::Relaxed);
+            f(x)
+        });
+        let stop = move || {
+            *lock(&release.stopped) = true;
+            release.stop.notify_one();
+        };
+        match subscription {
+            Ok(subscription) => Ok(subscription.then(stop)),
+            Err(e) => {
+                stop();
+                Err(e)
+            }
+        }
+    }
+}
+
+struct IdleState {
+    epoch: Instant,
+    // nanoseconds since `epoch`
+    last_arrival: AtomicU64,
+    stopped: Mutex<bool>,
+    stop: Condvar,
+}
+
+impl IdleState {
+    fn run<F: FnMut()>(&self, timeout: Duration, mut on_idle: F) {
+        // the arrival the last idle period was reported for
+        let mut reported = None;
+        let mut stopped = lock(&self.stopped);
+        while !*stopped {
+            let last = self.last_arrival.load(Ordering::Relaxed);
+            let idle = self.epoch.elapsed().saturating_sub(Duration::from_nanos(last));
+            let wait = if idle >= timeout {
+                if reported != Some(last) {
+                    reported = Some(last);
+                    drop(stopped);
+                    on_idle();
+                    stopped = lock(&self.stopped);
+                    continue;
+                }
+                timeout
+            } else {
+                timeout - idle
+            };
+            // spurious wakeups and arrivals are re-checked on the next iteration
+            stopped = self.stop.wait_timeout(stopped, wait).unwrap_or_else(|e| e.into_inner()).0;
+        }
+    }
+}
+
+pub struct Catching<S, P> {
+    inner: S,
+    on_panic: P,
+}
+impl<S: Stream + Debug, P> Debug for Catching<S, P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Catching").field("inner", &self.inner).finish()
+    }
+}
+impl<S, P> Stream for Catching<S, P>
+where
+    S: Stream,
+    P: FnMut(Box<dyn Any + Send>) + Sync + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut on_panic = self.on_panic;
+        self.inner.subscribe_guarded(move |x| {
+            if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| f(x))) {
+                on_panic(panic)
+            }
+        })
+    }
+}
+
+pub struct Take<S> {
+    inner: S,
+    n: usize,
+}
+impl<S: Stream + Debug> Debug for Take<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Take").field("inner", &self.inner).field("n", &self.n).finish()
+    }
+}
+impl<S: Stream> Stream for Take<S> {
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let state = Arc::new(ReleaseState {
+            done: AtomicBool::new(self.n == 0),
+            subscription: Mutex::new(None),
+        });
+
+        let mut remaining = self.n;
+        let handler_state = Arc::clone(&state);
+        let subscription = self.inner.subscribe_guarded(move |x| {
+            if remaining == 0 {
+                return;
+            }
+            remaining = remaining.saturating_sub(1);
+            f(x);
+            if remaining == 0 {
+                handler_state.done.store(true, Ordering::SeqCst);
+                // the subscription can't be released on the thread running the handler
+                let state = Arc::clone(&handler_state);
+                let _ = std::thread::Builder::new()
+                    .name("txc-take-release".into())
+                    .spawn(move || drop(state.take_subscription()));
+            }
+        })?;
+
+        let mut slot = state.subscription.lock().unwrap_or_else(|e| e.into_inner());
+        if state.done.load(Ordering::SeqCst) {
+            drop(slot);
+            drop(subscription);
+        } else {
+            *slot = Some(subscription);
+            drop(slot);
+        }
+        Ok(Subscription::new(move || drop(state.take_subscription())))
+    }
+}
+
+// subscription released once the stream is done, see `Take` and `Completable`
+struct ReleaseState {
+    done: AtomicBool,
+    subscription: Mutex<Option<Subscription>>,
+}
+
+impl ReleaseState {
+    fn take_subscription(&self) -> Option<Subscription> {
+        self.subscription.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+/// Набор обработчиков сообщений по тэгу, см. [`Stream::route`]
+pub struct Route<S, H, D> {
+    inner: S,
+    handlers: H,
+    default: D,
+}
+impl<S: Stream + Debug, H, D> Debug for Route<S, H, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Route").field("inner", &self.inner).finish()
+    }
+}
+impl<S, H, D> Route<S, H, D>
+where
+    S: Stream,
+    S::Output: AsRef<[u8]>,
+    H: Dispatch<S::Output>,
+    D: Dispatch<S::Output>,
+{
+    /// Направляет сообщения с корневым XML тэгом **tag** в обработчик **f**
+    #[inline(always)]
+    pub fn route<T, F>(self, tag: T, f: F) -> Route<S, Handler<F, H>, D>
+    where
+        T: AsRef<[u8]>,
+        F: FnMut(S::Output) + Sync + Send + 'static,
+    {
+        let index = self.handlers.count();
+        let handlers = Handler { tag: tag.as_ref().into(), index, f, next: self.handlers };
+        Route { inner: self.inner, handlers, default: self.default }
+    }
+
+    /// Обработчик сообщений, тэг которых не соответствует ни одному из обработчиков
+    ///
+    /// Без обработчика по-умолчанию такие сообщения отбрасываются.
+    #[inline(always)]
+    pub fn default<F>(self, f: F) -> Route<S, H, Fallback<F>>
+    where
+        F: FnMut(S::Output) + Sync + Send + 'static,
+    {
+        Route { inner: self.inner, handlers: self.handlers, default: Fallback(f) }
+    }
+
+    /// Устанавливает обработчики на всё время жизни источника, см. [`Stream::subscribe`]
+    #[inline(always)]
+    pub fn subscribe_routed(self) -> crate::Result {
+        self.subscribe_routed_guarded().map(Subscription::detach)
+    }
+
+    /// Устанавливает обработчики и возвращает [`Subscription`], см. [`Stream::subscribe_guarded`]
+    pub fn subscribe_routed_guarded(self) -> crate::Result<Subscription> {
+        let mut handlers = self.handlers;
+        let mut default = self.default;
+        self.inner.subscribe_guarded(move |x| {
+            match xml_root_tag_name(x.as_ref()).and_then(|tag| handlers.find(tag)) {
+                Some(index) => handlers.call(index, x),
+                None => default.call(0, x),
+            }
+        })
+    }
+}
+
+/// Обработчики сообщений в составе [`Route`]
+pub trait Dispatch<T>: Send + Sync + 'static {
+    /// Количество обработчиков
+    fn count(&self) -> usize;
+    /// Порядковый номер первого обработчика с тэгом **tag**
+    fn find(&self, tag: &[u8]) -> Option<usize>;
+    /// Передаёт **x** в обработчик с порядковым номером **index**
+    fn call(&mut self, index: usize, x: T);
+}
+
+/// Отсутствующий обработчик, сообщения отбрасываются
+#[derive(Debug)]
+pub struct Unrouted;
+impl<T> Dispatch<T> for Unrouted {
+    #[inline(always)]
+    fn count(&self) -> usize {
+        0
+    }
+
+    #[inline(always)]
+    fn find(&self, _: &[u8]) -> Option<usize> {
+        None
+    }
+
+    #[inline(always)]
+    fn call(&mut self, _: usize, _: T) {}
+}
+
+/// Обработчик сообщений с тэгом, см. [`Route::route`]
+pub struct Handler<F, N> {
+    tag: Box<[u8]>,
+    index: usize,
+    f: F,
+    next: N,
+}
+impl<T, F, N> Dispatch<T> for Handler<F, N>
+where
+    F: FnMut(T) + Send + Sync + 'static,
+    N: Dispatch<T>,
+{
+    #[inline(always)]
+    fn count(&self) -> usize {
+        self.index + 1
+    }
+
+    #[inline(always)]
+    fn find(&self, tag: &[u8]) -> Option<usize> {
+        // handlers registered earlier take precedence
+        self.next.find(tag).or_else(|| (tag == &self.tag[..]).then_some(self.index))
+    }
+
+    #[inline(always)]
+    fn call(&mut self, index: usize, x: T) {
+        if index == self.index {
+            (self.f)(x)
+        } else {
+            self.next.call(index, x)
+        }
+    }
+}
+
+/// Обработчик по-умолчанию, см. [`Route::default`]
+pub struct Fallback<F>(F);
+impl<T, F> Dispatch<T> for Fallback<F>
+where
+    F: FnMut(T) + Send + Sync + 'static,
+{
+    #[inline(always)]
+    fn count(&self) -> usize {
+        1
+    }
+
+    #[inline(always)]
+    fn find(&self, _: &[u8]) -> Option<usize> {
+        Some(0)
+    }
+
+    #[inline(always)]
+    fn call(&mut self, _: usize, x: T) {
+        (self.0)(x)
+    }
+}
+
+pub struct Tee<S: Stream>
+where
+    S::Output: Clone + 'static,
+{
+    // `None` once the branch is subscribed
+    shared: Option<Arc<TeeShared<S>>>,
+    index: usize,
+}
+impl<S: Stream> Debug for Tee<S>
+where
+    S::Output: Clone + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tee").field("index", &self.index).finish()
+    }
+}
+impl<S> Stream for Tee<S>
+where
+    S: Stream,
+    S::Output: Clone + 'static,
+{
+    type Output = S::Output;
+
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        mut self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let shared = self.shared.take().unwrap();
+        let index = self.index;
+        lock(&shared.branches)[index] = Some(Box::new(f));
+        lock(&shared.control).active += 1;
+
+        if let Err(e) = shared.settle() {
+            lock(&shared.control).active -= 1;
+            lock(&shared.branches)[index] = None;
+            return Err(e);
+        }
+
+        let branches = Arc::clone(&shared.branches);
+        let control = Arc::clone(&shared.control);
+        Ok(Subscription::new(move || {
+            lock(&branches)[index] = None;
+            let mut control = lock(&control);
+            control.active -= 1;
+            let subscription = if control.active == 0 { control.subscription.take() } else { None };
+            // the source is released outside of the lock
+            drop(control);
+            drop(subscription);
+        }))
+    }
+}
+impl<S: Stream> Drop for Tee<S>
+where
+    S::Output: Clone + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            // there is no one to report the error to, the subscribed branches stay silent
+            let _ = shared.settle();
+        }
+    }
+}
+
+type Branches<T> = Arc<Mutex<Vec<Option<BoxSubscriber<T>>>>>;
+
+struct TeeShared<S: Stream> {
+    source: Mutex<TeeSource<S>>,
+    control: Arc<Mutex<TeeControl>>,
+    branches: Branches<S::Output>,
+}
+
+struct TeeSource<S> {
+    source: Option<S>,
+    // branches neither subscribed nor dropped
+    pending: usize,
+}
+
+struct TeeControl {
+    // subscribed branches, whose subscriptions are not released yet
+    active: usize,
+    subscription: Option<Subscription>,
+}
+
+impl<S: Stream> TeeShared<S>
+where
+    S::Output: Clone + 'static,
+{
+    // called once per branch when it's subscribed or dropped, the source is subscribed after
+    // the last one
+    fn settle(&self) -> crate::Result {
+        let mut source = lock(&self.source);
+        source.pending -= 1;
+        if source.pending != 0 {
+            return Ok(());
+        }
+        let source = match source.source.take() {
+            Some(source) if lock(&self.control).active != 0 => source,
+            _ => return Ok(()),
+        };
+
+        let branches = Arc::clone(&self.branches);
+        let subscription = source.subscribe_guarded(move |x| {
+            let mut branches = lock(&branches);
+            if let Some(last) = branches.iter().rposition(Option::is_some) {
+                branches[..last].iter_mut().flatten().for_each(|f| f(x.clone()));
+                if let Some(f) = branches[last].as_mut() {
+                    f(x)
+                }
+            }
+        })?;
+
+        let mut control = lock(&self.control);
+        // the branches could have been released meanwhile
+        if control.active != 0 {
+            control.subscription = Some(subscription);
+        } else {
+            drop(control);
+            drop(subscription);
+        }
+        Ok(())
+    }
+}
+
+pub struct Zip<S1, S2> {
+    inner: S1,
+    other: S2,
+}
+impl<S1: Stream + Debug, S2: Stream + Debug> Debug for Zip<S1, S2> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Zip").field("inner", &self.inner).field("other", &self.other).finish()
+    }
+}
+impl<S1, S2> Stream for Zip<S1, S2>
+where
+    S1: Stream,
+    S2: Stream,
+    S1::Output: Send + 'static,
+    S2::Output: Send + 'static,
+{
+    type Output = (S1::Output, S2::Output);
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let state =
+            Arc::new(Mutex::new(ZipState { left: VecDeque::new(), right: VecDeque::new(), f }));
+
+        let left = Arc::clone(&state);
+        let inner = self.inner.subscribe_guarded(move |a| {
+            let mut state = lock(&left);
+            match state.right.pop_front() {
+                Some(b) => (state.f)((a, b)),
+                None => state.left.push_back(a),
+            }
+        })?;
+        let other = self.other.subscribe_guarded(move |b| {
+            let mut state = lock(&state);
+            match state.left.pop_front() {
+                Some(a) => (state.f)((a, b)),
+                None => state.right.push_back(b),
+            }
+        })?;
+        Ok(Subscription::join([inner, other]))
+    }
+}
+
+// items waiting for a pair, only one of the queues is non-empty at a time
+struct ZipState<A, B, F> {
+    left: VecDeque<A>,
+    right: VecDeque<B>,
+    f: F,
+}
+
+pub struct Chain<S1, S2> {
+    inner: S1,
+    other: S2,
+}
+impl<S1: Stream + Debug, S2: Stream + Debug> Debug for Chain<S1, S2> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Chain").field("inner", &self.inner).field("other", &self.other).finish()
+    }
+}
+impl<S1, S2> Stream for Chain<S1, S2>
+where
+    S1: Stream,
+    S2: Stream<Output = S1::Output>,
+    S1::Output: Send + 'static,
+{
+    type Output = S1::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let state = Arc::new(Mutex::new(ChainState {
+            f,
+            pending: VecDeque::new(),
+            first_done: false,
+            released: false,
+        }));
+
+        let mut guard = ChainGuard(Arc::clone(&state));
+        let first = self.inner.subscribe_guarded(move |x| guard.call(x))?;
+        let handler_state = Arc::clone(&state);
+        let second = self.other.subscribe_guarded(move |x| {
+            let mut state = lock(&handler_state);
+            if state.released {
+                return;
+            }
+            if state.first_done {
+                (state.f)(x)
+            } else {
+                state.pending.push_back(x)
+            }
+        });
+        // neither of the streams is forwarded once released, the first one doesn't flush
+        let released = Subscription::new(move || lock(&state).released = true);
+        match second {
+            Ok(second) => Ok(Subscription::join([released, first, second])),
+            Err(e) => {
+                drop((released, first));
+                Err(e)
+            }
+        }
+    }
+}
+
+struct ChainState<T, F> {
+    f: F,
+    // items of the second stream received before the first one is done
+    pending: VecDeque<T>,
+    first_done: bool,
+    // the subscription is released, neither of the streams is forwarded
+    released: bool,
+}
+
+// Handler of the first stream, switching to the second one once it's released
+struct ChainGuard<T, F: FnMut(T)>(Arc<Mutex<ChainState<T, F>>>);
+
+impl<T, F: FnMut(T)> ChainGuard<T, F> {
+    #[inline(always)]
+    fn call(&mut self, x: T) {
+        let mut state = lock(&self.0);
+        if !state.released {
+            (state.f)(x)
+        }
+    }
+}
+
+impl<T, F: FnMut(T)> Drop for ChainGuard<T, F> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.0);
+        state.first_done = true;
+        let pending = std::mem::take(&mut state.pending);
+        if !state.released {
+            pending.into_iter().for_each(|x| (state.f)(x));
+        }
+    }
+}
+
+pub struct Completable<S> {
+    inner: S,
+    state: Arc<ReleaseState>,
+}
+impl<S: Stream + Debug> Debug for Completable<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Completable").field("inner", &self.inner).finish()
+    }
+}
+impl<S: Stream> Stream for Completable<S> {
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let Self { inner, state } = self;
+        let subscription = inner.subscribe_guarded(f)?;
+
+        let mut slot = lock(&state.subscription);
+        if state.done.load(Ordering::SeqCst) {
+            drop(slot);
+            drop(subscription);
+        } else {
+            *slot = Some(subscription);
+            drop(slot);
+        }
+        Ok(Subscription::new(move || drop(state.take_subscription())))
+    }
+}
+
+/// Завершение источника, см. [`Stream::completable`]
+#[derive(Clone)]
+pub struct CompletionHandle(Arc<ReleaseState>);
+
+impl CompletionHandle {
+    /// Завершает источник, освобождая его обработчик
+    ///
+    /// Если подписка ещё не выполнена, обработчик будет освобождён сразу после установки.
+    /// Повторные вызовы не имеют эффекта. Как и подписку на
+    /// [`TransaqConnector`](crate::TransaqConnector), источник нельзя завершить в потоке,
+    /// выполняющем его обработчик.
+    pub fn complete(&self) {
+        self.0.done.store(true, Ordering::SeqCst);
+        drop(self.0.take_subscription());
+    }
+
+    /// Источник завершён вызовом [`CompletionHandle::complete`]
+    pub fn is_complete(&self) -> bool {
+        self.0.done.load(Ordering::SeqCst)
+    }
+}
+
+impl Debug for CompletionHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CompletionHandle").field(&self.is_complete()).finish()
+    }
+}
+
+#[inline]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub struct TakeWhile<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for TakeWhile<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TakeWhile").field("inner", &self.inner).finish()
+    }
+}
+impl<S, F> Stream for TakeWhile<S, F>
+where
+    S: Stream,
+    F: FnMut(&S::Output) -> bool + Sync + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut pred = self.f;
+        let mut done = false;
+        self.inner.subscribe_guarded(move |x| {
+            if !done && (pred)(&x) {
+                f(x)
+            } else {
+                done = true;
+            }
+        })
+    }
+}
+
+pub struct SkipWhile<S, F> {
+    inner: S,
+    f: F,
+}
+impl<S: Stream + Debug, F> Debug for SkipWhile<S, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SkipWhile").field("inner", &self.inner).finish()
+    }
+}
+impl<S, F> Stream for SkipWhile<S, F>
+where
+    S: Stream,
+    F: FnMut(&S::Output) -> bool + Sync + Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let mut pred = self.f;
+        let mut skipping = true;
+        self.inner.subscribe_guarded(move |x| {
+            if skipping && (pred)(&x) {
+                return;
+            }
+            skipping = false;
+            f(x)
+        })
+    }
+}
+
+pub struct Chunks<S> {
+    inner: S,
+    n: usize,
+    timeout: Option<Duration>,
+}
+impl<S: Stream + Debug> Debug for Chunks<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Chunks")
+            .field("inner", &self.inner)
+            .field("n", &self.n)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+impl<S> Stream for Chunks<S>
+where
+    S: Stream,
+    S::Output: Send + 'static,
+{
+    type Output = Vec<S::Output>;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let (n, timeout) = (self.n, self.timeout);
+        let state =
+            Arc::new(Mutex::new(ChunksState { batch: Vec::with_capacity(n), started: None, f }));
+        let release = Arc::clone(&state);
+        let subscription = self.inner.subscribe_guarded(move |x| {
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            let now = timeout.map(|_| Instant::now());
+            if state.batch.is_empty() {
+                state.started = now;
+            }
+            state.batch.push(x);
+
+            let expired = match (timeout, state.started, now) {
+                (Some(timeout), Some(started), Some(now)) => now - started >= timeout,
+                _ => false,
+            };
+            if state.batch.len() >= n || expired {
+                state.flush(n);
+            }
+        })?;
+        // deliver the trailing partial batch once the handler is released
+        Ok(subscription.then(move || release.lock().unwrap_or_else(|e| e.into_inner()).flush(0)))
+    }
+}
+
+pub struct Sample<S> {
+    inner: S,
+    period: Duration,
+    now: fn() -> Instant,
+}
+impl<S: Stream + Debug> Debug for Sample<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sample").field("inner", &self.inner).field("period", &self.period).finish()
+    }
+}
+impl<S> Stream for Sample<S>
+where
+    S: Stream,
+    S::Output: Send + Sync + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let (period, now) = (self.period, self.now);
+        let mut started: Option<Instant> = None;
+        let mut pending = None;
+        self.inner.subscribe_guarded(move |x| {
+            let now = now();
+            match started {
+                Some(start) if now.saturating_duration_since(start) < period => {}
+                _ => {
+                    started = Some(now);
+                    if let Some(latest) = pending.take() {
+                        f(latest)
+                    }
+                }
+            }
+            pending = Some(x);
+        })
+    }
+}
+
+pub struct Throttle<S> {
+    inner: S,
+    period: Duration,
+    now: fn() -> Instant,
+}
+impl<S: Stream + Debug> Debug for Throttle<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Throttle")
+            .field("inner", &self.inner)
+            .field("period", &self.period)
+            .finish()
+    }
+}
+impl<S: Stream> Stream for Throttle<S> {
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let (period, now) = (self.period, self.now);
+        let mut passed: Option<Instant> = None;
+        self.inner.subscribe_guarded(move |x| {
+            let now = now();
+            if passed.map_or(true, |passed| now.saturating_duration_since(passed) >= period) {
+                passed = Some(now);
+                f(x)
+            }
+        })
+    }
+}
+
+pub struct Window<S> {
+    inner: S,
+    n: usize,
+    partial: bool,
+}
+impl<S: Stream + Debug> Debug for Window<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Window")
+            .field("inner", &self.inner)
+            .field("n", &self.n)
+            .field("partial", &self.partial)
+            .finish()
+    }
+}
+impl<S> Stream for Window<S>
+where
+    S: Stream,
+    S::Output: Clone + Send + Sync + 'static,
+{
+    type Output = WindowView<S::Output>;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        mut f: FSub,
+    ) -> crate::Result<Subscription> {
+        let (n, partial) = (self.n, self.partial);
+        let mut window = Arc::new(VecDeque::with_capacity(n));
+        self.inner.subscribe_guarded(move |x| {
+            // the view passed on is usually dropped by now and the window is updated in place
+            let items = Arc::make_mut(&mut window);
+            if items.len() == n {
+                items.pop_front();
+            }
+            items.push_back(x);
+            if partial || items.len() == n {
+                f(WindowView(Arc::clone(&window)))
+            }
+        })
+    }
+}
+
+/// Скользящее окно элементов, см. [`Stream::window`]
+///
+/// Элементы упорядочены от старых к новым.
+#[derive(Clone)]
+pub struct WindowView<T>(Arc<VecDeque<T>>);
+
+impl<T> std::ops::Deref for WindowView<T> {
+    type Target = VecDeque<T>;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: Debug> Debug for WindowView<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
+
+struct ChunksState<T, F> {
+    batch: Vec<T>,
+    started: Option<Instant>,
+    f: F,
+}
+
+impl<T, F: FnMut(Vec<T>)> ChunksState<T, F> {
+    fn flush(&mut self, capacity: usize) {
+        if !self.batch.is_empty() {
+            let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(capacity));
+            (self.f)(batch)
+        }
+    }
+}
+
+pub struct WithFlowControl<S: Stream> {
+    inner: S,
+    state: Arc<FlowState<S::Output>>,
+}
+impl<S: Stream + Debug> Debug for WithFlowControl<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithFlowControl").field("inner", &self.inner).finish()
+    }
+}
+impl<S> Stream for WithFlowControl<S>
+where
+    S: Stream,
+    S::Output: Send + 'static,
+{
+    type Output = S::Output;
+
+    #[inline(always)]
+    fn subscribe_guarded<FSub: FnMut(Self::Output) + Sync + Send + 'static>(
+        self,
+        f: FSub,
+    ) -> crate::Result<Subscription> {
+        let state = self.state;
+        state.lock().sink = Some(Box::new(f));
+        let release = Arc::clone(&state);
+        let subscription = self.inner.subscribe_guarded(move |x| state.push(x))?;
+        Ok(subscription.then(move || {
+            let mut inner = release.lock();
+            inner.sink = None;
+            inner.spill.clear();
+        }))
+    }
+}
+
+/// Управление доставкой сообщений, см. [`Stream::with_flow_control`]
+pub struct FlowControl<T>(Arc<FlowState<T>>);
+
+struct FlowState<T> {
+    paused: AtomicBool,
+    spilled: AtomicU64,
+    dropped: AtomicU64,
+    capacity: usize,
+    inner: Mutex<FlowInner<T>>,
+}
+
+struct FlowInner<T> {
+    spill: VecDeque<T>,
+    sink: Option<Box<dyn FnMut(T) + Send>>,
+}
+
+impl<T> FlowState<T> {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, FlowInner<T>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[inline]
+    fn push(&self, x: T) {
+        let mut inner = self.lock();
+        if self.paused.load(Ordering::Relaxed) {
+            if inner.spill.len() < self.capacity {
+                inner.spill.push_back(x);
+                self.spilled.fetch_add(1, Ordering::Relaxed);
+            } else {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        } else if let Some(sink) = inner.sink.as_mut() {
+            sink(x)
+        }
+    }
+}
+
+impl<T> FlowControl<T> {
+    /// Приостанавливает доставку сообщений
+    pub fn pause(&self) {
+        let _inner = self.0.lock();
+        self.0.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Возобновляет доставку сообщений
+    ///
+    /// Сохранённые во время паузы сообщения доставляются в текущем потоке в порядке поступления,
+    /// до доставки новых сообщений.
+    pub fn resume(&self) {
+        let mut inner = self.0.lock();
+        self.0.paused.store(false, Ordering::Relaxed);
+        let FlowInner { spill, sink } = &mut *inner;
+        if let Some(sink) = sink.as_mut() {
+            spill.drain(..).for_each(sink);
+        }
+    }
+
+    /// Доставка сообщений приостановлена
+    pub fn is_paused(&self) -> bool {
+        self.0.paused.load(Ordering::Relaxed)
+    }
+
+    /// Количество сообщений, сохранённых в очереди во время пауз
+    pub fn spilled(&self) -> u64 {
+        self.0.spilled.load(Ordering::Relaxed)
+    }
+
+    /// Количество сообщений, отброшенных из-за переполнения очереди
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Количество сообщений в очереди
+    pub fn pending(&self) -> usize {
+        self.0.lock().spill.len()
+    }
+}
+
+impl<T> Clone for FlowControl<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> Debug for FlowControl<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlowControl")
+            .field("paused", &self.is_paused())
+            .field("spilled", &self.spilled())
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Slot<T> = Arc<Mutex<Option<Box<dyn FnMut(T) + Send + Sync>>>>;
+
+    // Scripted source, the items are pushed by the test and the handler is released along with
+    // the subscription
+    struct Source<T>(Slot<T>);
+
+    impl<T> Source<T> {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(None)))
+        }
+
+        fn handle(&self) -> Self {
+            Self(Arc::clone(&self.0))
+        }
+
+        // Delivers the item to the handler, returns `false` if there is no handler
+        fn push(&self, x: T) -> bool {
+            match lock(&self.0).as_mut() {
+                Some(f) => {
+                    f(x);
+                    true
+                }
+                None => false,
+            }
+        }
+
+        fn is_subscribed(&self) -> bool {
+            lock(&self.0).is_some()
+        }
+    }
+
+    impl<T: 'static> Stream for Source<T> {
+        type Output = T;
+
+        fn subscribe_guarded<F: FnMut(T) + Sync + Send + 'static>(
+            self,
+            f: F,
+        ) -> crate::Result<Subscription> {
+            *lock(&self.0) = Some(Box::new(f));
+            Ok(Subscription::new(move || drop(lock(&self.0).take())))
+        }
+    }
+
+    // Handler collecting the items
+    fn collect<T: Send + 'static>() -> (Arc<Mutex<Vec<T>>>, impl FnMut(T) + Sync + Send + 'static) {
+        let items = Arc::new(Mutex::new(Vec::new()));
+        let out = Arc::clone(&items);
+        (items, move |x| lock(&out).push(x))
+    }
+
+    fn wait_until(cond: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !cond() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Source failing to install the handler
+    #[derive(Debug)]
+    struct Failing;
+
+    impl Stream for Failing {
+        type Output = u8;
+
+        fn subscribe_guarded<F: FnMut(u8) + Sync + Send + 'static>(
+            self,
+            _: F,
+        ) -> crate::Result<Subscription> {
+            Err(crate::Error::Timeout)
+        }
+    }
+
+    #[test]
+    fn take_delivers_first_items_and_releases_source() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let subscription = source.handle().take(3).subscribe_guarded(f).unwrap();
+
+        // the source may be released before the last items are pushed
+        (0..5).for_each(|i| {
+            source.push(i);
+        });
+        wait_until(|| !source.is_subscribed());
+        assert_eq!(*lock(&items), [0, 1, 2]);
+        drop(subscription);
+    }
+
+    #[test]
+    fn take_releases_detached_source() {
+        let source = Source::new();
+        let (items, f) = collect();
+        source.handle().take(1).subscribe(f).unwrap();
+
+        assert!(source.push("first"));
+        wait_until(|| !source.is_subscribed());
+        assert!(!source.push("second"));
+        assert_eq!(*lock(&items), ["first"]);
+    }
+
+    #[test]
+    fn take_zero() {
+        let source = Source::<u8>::new();
+        let (items, f) = collect();
+        let _subscription = source.handle().take(0).subscribe_guarded(f).unwrap();
+
+        assert!(!source.is_subscribed());
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn take_while_stops_at_first_mismatch() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        source
+            .handle()
+            .take_while(move |x| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                *x < 3
+            })
+            .subscribe(f)
+            .unwrap();
+
+        [1, 2, 3, 1, 2].into_iter().for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&items), [1, 2]);
+        // the predicate is not called after the first mismatch
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn skip_while_passes_everything_after_first_mismatch() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        source
+            .handle()
+            .skip_while(move |x| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                *x < 3
+            })
+            .subscribe(f)
+            .unwrap();
+
+        [1, 2, 3, 1, 4].into_iter().for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&items), [3, 1, 4]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn chunks_delivers_full_batches_and_trailing_batch_on_release() {
+        let source = Source::new();
+        let (batches, f) = collect();
+        let subscription = source.handle().chunks(2).subscribe_guarded(f).unwrap();
+
+        (1..=5).for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&batches), [vec![1, 2], vec![3, 4]]);
+
+        drop(subscription);
+        assert!(!source.is_subscribed());
+        assert_eq!(*lock(&batches), [vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[test]
+    fn chunks_release_without_pending_items() {
+        let source = Source::new();
+        let (batches, f) = collect();
+        let subscription = source.handle().chunks(2).subscribe_guarded(f).unwrap();
+
+        (1..=4).for_each(|x| assert!(source.push(x)));
+        drop(subscription);
+        // no empty batch
+        assert_eq!(*lock(&batches), [vec![1, 2], vec![3, 4]]);
+    }
+
+    #[test]
+    fn chunks_of_zero_are_single_items() {
+        let source = Source::new();
+        let (batches, f) = collect();
+        source.handle().chunks(0).subscribe(f).unwrap();
+
+        (1..=3).for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&batches), [vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn chunks_timeout_flushes_expired_batch_on_next_item() {
+        let timeout = Duration::from_millis(20);
+        let source = Source::new();
+        let (batches, f) = collect();
+        let subscription =
+            source.handle().chunks_timeout(10, timeout).subscribe_guarded(f).unwrap();
+
+        assert!(source.push(1));
+        std::thread::sleep(timeout);
+        // the timeout is checked when the item arrives, the batch includes it
+        assert!(lock(&batches).is_empty());
+        assert!(source.push(2));
+        assert_eq!(*lock(&batches), [vec![1, 2]]);
+
+        // the next batch starts over and is not expired yet
+        assert!(source.push(3));
+        assert_eq!(lock(&batches).len(), 1);
+        drop(subscription);
+        assert_eq!(*lock(&batches), [vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn chunks_timeout_delivers_full_batches() {
+        let source = Source::new();
+        let (batches, f) = collect();
+        source.handle().chunks_timeout(2, Duration::from_secs(60)).subscribe(f).unwrap();
+
+        (1..=5).for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&batches), [vec![1, 2], vec![3, 4]]);
+    }
+
+    #[test]
+    fn dedup_skips_consecutive_duplicates() {
+        let source = Source::new();
+        let (items, f) = collect();
+        source.handle().dedup().subscribe(f).unwrap();
+
+        ["a", "a", "b", "a", "a", "c", "c"].into_iter().for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&items), ["a", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn dedup_owned_messages() {
+        let source = Source::new();
+        let (items, f) = collect();
+        source.handle().dedup().subscribe(f).unwrap();
+
+        let connected = "<server_status connected=\"true\"/>";
+        let disconnected = "<server_status connected=\"false\"/>";
+        [connected, connected, disconnected, connected]
+            .into_iter()
+            .for_each(|msg| assert!(source.push(OwnedMessage::from(msg.as_bytes()))));
+        let items = lock(&items).iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(items, [connected, disconnected, connected]);
+    }
+
+    #[test]
+    fn dedup_by_key_keeps_first_item_of_each_run() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        source
+            .handle()
+            .dedup_by_key(move |(key, _): &(u8, u8)| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                *key
+            })
+            .subscribe(f)
+            .unwrap();
+
+        [(1, 1), (1, 2), (2, 3), (2, 4), (1, 5)].into_iter().for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&items), [(1, 1), (2, 3), (1, 5)]);
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    // Writer shared with the test, counts the write calls and fails them if `fail` is set
+    #[derive(Clone, Default)]
+    struct SharedWriter {
+        buf: Arc<Mutex<Vec<u8>>>,
+        writes: Arc<AtomicUsize>,
+        fail: bool,
+    }
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            lock(&self.buf).extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_to_writer_round_trip() {
+        let messages = ["<server_status connected=\"true\"/>", "", "<quotes/>"];
+        for framing in [Framing::LengthPrefixed, Framing::NulDelimited] {
+            let writer = SharedWriter::default();
+            let source = Source::new();
+            let (items, f) = collect();
+            let subscription = source
+                .handle()
+                .record_to_writer(writer.clone(), framing)
+                .subscribe_guarded(f)
+                .unwrap();
+
+            messages.into_iter().for_each(|msg| assert!(source.push(msg)));
+            assert_eq!(*lock(&items), messages);
+            // flushed on release
+            drop(subscription);
+
+            let feed = lock(&writer.buf).clone();
+            let frames = crate::FeedReader::new(&feed[..], framing).map(Result::unwrap);
+            assert!(frames.eq(messages.map(|msg| msg.as_bytes().to_vec())));
+        }
+    }
+
+    #[test]
+    fn record_to_writer_stops_on_write_error() {
+        let writer = SharedWriter { fail: true, ..SharedWriter::default() };
+        let source = Source::new();
+        let (items, f) = collect();
+        let subscription = source
+            .handle()
+            .record_to_writer(writer.clone(), Framing::NulDelimited)
+            .subscribe_guarded(f)
+            .unwrap();
+
+        // larger than the write buffer, so the frames are written through
+        let frame = vec![b'x'; 1 << 16];
+        (0..3).for_each(|_| assert!(source.push(frame.clone())));
+        assert_eq!(lock(&items).len(), 3);
+        assert_eq!(writer.writes.load(Ordering::SeqCst), 1);
+
+        drop(subscription);
+        assert_eq!(writer.writes.load(Ordering::SeqCst), 1);
+        assert!(lock(&writer.buf).is_empty());
+    }
+
+    #[test]
+    fn fold_stateful_flushes_once_on_release() {
+        let source = Source::new();
+        let (flushed, flush) = collect();
+        let subscription = source
+            .handle()
+            .fold_stateful(
+                (0, Vec::new()),
+                |(sum, seen), x| {
+                    *sum += x;
+                    seen.push(x);
+                },
+                flush,
+            )
+            .unwrap();
+
+        (1..=4).for_each(|x| assert!(source.push(x)));
+        assert!(lock(&flushed).is_empty());
+
+        drop(subscription);
+        assert!(!source.is_subscribed());
+        assert_eq!(*lock(&flushed), [(10, vec![1, 2, 3, 4])]);
+    }
+
+    #[test]
+    fn fold_stateful_flushes_on_replacement() {
+        let source = Source::new();
+        let (flushed, flush) = collect();
+        source.handle().fold_stateful(0, |count, _: u8| *count += 1, flush).unwrap().detach();
+        (0..3).for_each(|x| assert!(source.push(x)));
+
+        // the replaced handler is released along with its state
+        let (items, f) = collect();
+        source.handle().subscribe(f).unwrap();
+        assert_eq!(*lock(&flushed), [3]);
+        assert!(source.push(7));
+        assert_eq!(*lock(&items), [7]);
+        assert_eq!(*lock(&flushed), [3]);
+    }
+
+    #[test]
+    fn fold_stateful_flushes_initial_state() {
+        let source = Source::<u8>::new();
+        let (flushed, flush) = collect();
+        drop(source.handle().fold_stateful("init", |_, _| {}, flush).unwrap());
+        assert_eq!(*lock(&flushed), ["init"]);
+    }
+
+    #[test]
+    fn from_fn_delivers_through_combinators() {
+        let messages = ["<server_status connected=\"true\"/>", "<quotes/>", "<server_status/>"];
+        let (items, f) = collect();
+        let subscription = from_fn(move |mut subscriber| {
+            messages.into_iter().for_each(|msg| subscriber(OwnedMessage::from(msg.as_bytes())))
+        })
+        .filter(|msg| msg.xml_tag_name() == Some(b"server_status"))
+        .map(|msg| msg.to_string())
+        .subscribe_guarded(f)
+        .unwrap();
+
+        assert_eq!(*lock(&items), [messages[0], messages[2]]);
+        assert_eq!(format!("{subscription:?}"), "Subscription(false)");
+    }
+
+    #[test]
+    fn from_fn_handler_outlives_subscription() {
+        let handler = Arc::new(Mutex::new(None));
+        let slot = Arc::clone(&handler);
+        let (items, f) = collect();
+        drop(from_fn(move |subscriber| *lock(&slot) = Some(subscriber)).subscribe_guarded(f));
+
+        (lock(&handler).as_mut().unwrap())(1);
+        assert_eq!(*lock(&items), [1]);
+    }
+
+    #[test]
+    fn select_first_follows_first_delivering_source() {
+        let (primary, backup) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        let subscription = select_first(vec![primary.handle().boxed(), backup.handle().boxed()])
+            .subscribe_guarded(f)
+            .unwrap();
+
+        assert!(backup.push("backup 1"));
+        // the items of the other sources are dropped, their handlers are kept
+        assert!(primary.push("primary 1"));
+        assert!(backup.push("backup 2"));
+        assert!(primary.push("primary 2"));
+        assert_eq!(*lock(&items), ["backup 1", "backup 2"]);
+
+        drop(subscription);
+        assert!(!primary.is_subscribed());
+        assert!(!backup.is_subscribed());
+    }
+
+    #[test]
+    fn select_first_releases_sources_on_subscribe_error() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let result = select_first(vec![source.handle().boxed(), Failing.boxed()]).subscribe(f);
+
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+        assert!(!source.is_subscribed());
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn select_first_of_no_sources() {
+        let (items, f) = collect::<u8>();
+        let subscription = select_first(Vec::new()).subscribe_guarded(f).unwrap();
+        drop(subscription);
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn partition_splits_items_by_predicate() {
+        let source = Source::new();
+        let (even, f_true) = collect();
+        let (odd, f_false) = collect();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let subscription = source
+            .handle()
+            .partition(
+                move |x: &i32| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    x % 2 == 0
+                },
+                f_true,
+                f_false,
+            )
+            .unwrap();
+
+        (0..6).for_each(|x| assert!(source.push(x)));
+        assert_eq!(*lock(&even), [0, 2, 4]);
+        assert_eq!(*lock(&odd), [1, 3, 5]);
+        // single handler, the predicate is called once per item
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+
+        drop(subscription);
+        assert!(!source.is_subscribed());
+    }
+
+    #[test]
+    fn partition_with_one_side_empty() {
+        let source = Source::new();
+        let (matched, f_true) = collect();
+        let (rest, f_false) = collect();
+        let _subscription = source.handle().partition(|_: &&str| true, f_true, f_false).unwrap();
+
+        assert!(source.push("a"));
+        assert!(source.push("b"));
+        assert_eq!(*lock(&matched), ["a", "b"]);
+        assert!(lock(&rest).is_empty());
+    }
+
+    #[test]
+    fn zip_pairs_items_in_order() {
+        let (left, right) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        let subscription = left.handle().zip(right.handle()).subscribe_guarded(f).unwrap();
+
+        // the leading source is queued until the pair arrives
+        assert!(left.push(1));
+        assert!(left.push(2));
+        assert!(left.push(3));
+        assert!(right.push("a"));
+        assert_eq!(*lock(&items), [(1, "a")]);
+        assert!(right.push("b"));
+        assert!(right.push("c"));
+        assert!(right.push("d"));
+        assert!(left.push(4));
+        assert_eq!(*lock(&items), [(1, "a"), (2, "b"), (3, "c"), (4, "d")]);
+
+        drop(subscription);
+        assert!(!left.is_subscribed());
+        assert!(!right.is_subscribed());
+    }
+
+    #[test]
+    fn zip_drops_unpaired_items_on_release() {
+        let (left, right) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        let subscription = left.handle().zip(right.handle()).subscribe_guarded(f).unwrap();
+
+        assert!(left.push(1));
+        drop(subscription);
+        assert!(!right.push(1));
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn zip_releases_first_source_on_subscribe_error() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let result = source.handle().zip(Failing).subscribe(f);
+
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+        assert!(!source.is_subscribed());
+        assert!(!source.push(1));
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn select_first_detached_keeps_sources_subscribed() {
+        let (primary, backup) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        select_first(vec![primary.handle().boxed(), backup.handle().boxed()]).subscribe(f).unwrap();
+
+        assert!(primary.push(1));
+        assert!(backup.push(2));
+        assert!(primary.push(3));
+        assert_eq!(*lock(&items), [1, 3]);
+    }
+
+    #[test]
+    fn zip_detached_keeps_sources_subscribed() {
+        let (left, right) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        left.handle().zip(right.handle()).subscribe(f).unwrap();
+
+        assert!(left.push(1));
+        assert!(right.push('a'));
+        assert_eq!(*lock(&items), [(1, 'a')]);
+    }
+
+    #[test]
+    fn chain_delivers_second_source_after_first_completes() {
+        let (first, second) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        let (warmup, handle) = first.handle().completable();
+        let subscription = warmup.chain(second.handle()).subscribe_guarded(f).unwrap();
+
+        assert!(first.push(1));
+        // the items of the second source are queued until the first one completes
+        assert!(second.push(10));
+        assert!(first.push(2));
+        assert!(second.push(11));
+        assert_eq!(*lock(&items), [1, 2]);
+
+        handle.complete();
+        assert!(!first.is_subscribed());
+        assert_eq!(*lock(&items), [1, 2, 10, 11]);
+        assert!(second.push(12));
+        assert_eq!(*lock(&items), [1, 2, 10, 11, 12]);
+
+        drop(subscription);
+        assert!(!second.is_subscribed());
+    }
+
+    #[test]
+    fn chain_after_take() {
+        let (first, second) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        first.handle().take(2).chain(second.handle()).subscribe(f).unwrap();
+
+        assert!(second.push("b1"));
+        assert!(first.push("a1"));
+        first.push("a2");
+        wait_until(|| lock(&items).len() == 3);
+        assert!(second.push("b2"));
+        assert_eq!(*lock(&items), ["a1", "a2", "b1", "b2"]);
+    }
+
+    #[test]
+    fn chain_release_drops_pending_items() {
+        let (first, second) = (Source::new(), Source::new());
+        let (items, f) = collect();
+        let subscription = first.handle().chain(second.handle()).subscribe_guarded(f).unwrap();
+
+        assert!(second.push(10));
+        assert!(first.push(1));
+        drop(subscription);
+        assert!(!first.is_subscribed());
+        assert!(!second.is_subscribed());
+        assert_eq!(*lock(&items), [1]);
+    }
+
+    #[test]
+    fn chain_releases_first_source_on_subscribe_error() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let result = source.handle().chain(Failing).subscribe(f);
+
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+        assert!(!source.is_subscribed());
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn completable_complete_releases_handler() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let (stream, handle) = source.handle().completable();
+        let subscription = stream.subscribe_guarded(f).unwrap();
+        assert!(!handle.is_complete());
+        assert_eq!(format!("{handle:?}"), "CompletionHandle(false)");
+
+        assert!(source.push(1));
+        handle.clone().complete();
+        assert!(handle.is_complete());
+        assert!(!source.is_subscribed());
+        assert!(!source.push(2));
+
+        // repeated completion and the release of the subscription have no effect
+        handle.complete();
+        drop(subscription);
+        assert_eq!(*lock(&items), [1]);
+    }
+
+    #[test]
+    fn completable_completed_before_subscribe() {
+        let source = Source::<u8>::new();
+        let (items, f) = collect();
+        let (stream, handle) = source.handle().completable();
+        handle.complete();
+
+        let _subscription = stream.subscribe_guarded(f).unwrap();
+        assert!(!source.is_subscribed());
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn completable_released_with_subscription() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let (stream, handle) = source.handle().completable();
+        let subscription = stream.subscribe_guarded(f).unwrap();
+
+        assert!(source.push(1));
+        drop(subscription);
+        assert!(!source.is_subscribed());
+        assert!(!handle.is_complete());
+        handle.complete();
+        assert_eq!(*lock(&items), [1]);
+    }
+
+    #[test]
+    fn subscribe_spsc_delivers_on_consumer_thread() {
+        let source = Source::new();
+        let (items, mut f) = collect();
+        let caller = std::thread::current().id();
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&threads);
+
+        source
+            .handle()
+            .subscribe_spsc(
+                move |x: u32| {
+                    lock(&seen).push(std::thread::current().id());
+                    f(x)
+                },
+                4,
+            )
+            .unwrap();
+
+        // the queue is smaller than the number of items, the producer waits for the consumer
+        (0..100).for_each(|i| assert!(source.push(i)));
+        wait_until(|| lock(&items).len() == 100);
+
+        assert_eq!(*lock(&items), (0..100).collect::<Vec<_>>());
+        assert!(lock(&threads).iter().all(|id| *id != caller));
+    }
+
+    #[test]
+    fn subscribe_spsc_propagates_subscribe_error() {
+        assert!(matches!(Failing.subscribe_spsc(drop, 4), Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn broker_messages_splits_documents() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription = source.handle().broker_messages().subscribe_guarded(f).unwrap();
+
+        assert!(source.push(
+            "<messages><message urgent=\"Y\">margin call</message>\
+             <message urgent=\"N\">maintenance</message></messages>"
+        ));
+        assert!(source.push("<server_status connected=\"true\"/>"));
+        // the malformed document is skipped
+        assert!(source.push("<messages><message urgent=\"maybe\">x</message></messages>"));
+        assert!(source.push("<messages><message><text>notice</text></message></messages>"));
+
+        let items = lock(&items);
+        let texts = items.iter().map(|m: &BrokerMessage| (m.urgent, m.text.as_str()));
+        assert_eq!(
+            texts.collect::<Vec<_>>(),
+            [(true, "margin call"), (false, "maintenance"), (false, "notice")]
+        );
+    }
+
+    #[cfg(feature = "xml-events")]
+    #[test]
+    fn xml_events_continue_after_malformed() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription = source.handle().xml_events().subscribe_guarded(f).unwrap();
+
+        assert!(source.push("<server_status connected=\"true\"/>"));
+        assert!(source.push("<quotes><quote>"));
+        assert!(source.push("<result success=\"true\"></result>"));
+
+        let items = lock(&items);
+        assert_eq!(items.len(), 3);
+        let parsed = items[0].as_ref().unwrap();
+        assert_eq!(parsed.tag, "server_status");
+        assert_eq!(parsed.events.len(), 1);
+        assert_eq!(items[1].as_ref().unwrap_err().message, crate::lang::XML_TRUNCATED);
+
+        let parsed = items[2].as_ref().unwrap();
+        assert_eq!(parsed.tag, "result");
+        assert!(matches!(&parsed.events[1], crate::OwnedEvent::End { name } if name == "result"));
+    }
+
+    #[test]
+    fn timestamped_is_monotonic() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription = source.handle().timestamped().subscribe_guarded(f).unwrap();
+
+        let started = Instant::now();
+        for i in 0..100 {
+            assert!(source.push(i));
+            if i == 50 {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        let finished = Instant::now();
+
+        let items = lock(&items);
+        assert_eq!(items.iter().map(|(_, x)| *x).collect::<Vec<_>>(), (0..100).collect::<Vec<_>>());
+        assert!(items.windows(2).all(|w| w[0].0 <= w[1].0));
+        assert!(items[0].0 >= started && items[99].0 <= finished);
+        // the moment of arrival, not of the subscription
+        assert!(items[51].0 - items[50].0 >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn timestamped_before_handler() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription = source
+            .handle()
+            .timestamped()
+            .map(|(at, x)| {
+                // a slow stage after `timestamped` doesn't shift the moment
+                std::thread::sleep(Duration::from_millis(5));
+                (at, Instant::now(), x)
+            })
+            .subscribe_guarded(f)
+            .unwrap();
+
+        assert!(source.push(1));
+        assert!(source.push(2));
+        let items = lock(&items);
+        assert!(items.iter().all(|(at, handled, _)| *handled - *at >= Duration::from_millis(5)));
+        assert!(items[1].0 >= items[0].1);
+    }
+
+    thread_local! {
+        static CLOCK: std::cell::Cell<Option<Instant>> = const { std::cell::Cell::new(None) };
+    }
+
+    // Fake clock of the current thread, starts at the first reading
+    fn fake_now() -> Instant {
+        CLOCK.with(|clock| *clock.get().get_or_insert_with(Instant::now))
+    }
+
+    fn advance(ms: u64) {
+        let now = fake_now();
+        CLOCK.with(|clock| clock.set(Some(now + Duration::from_millis(ms))));
+    }
+
+    #[test]
+    fn sample_window_boundaries() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let period = Duration::from_millis(100);
+        let sample = Sample { inner: source.handle(), period, now: fake_now };
+        let _subscription = sample.subscribe_guarded(f).unwrap();
+
+        // the first window starts with the first item, the latest item is held
+        assert!(source.push(1));
+        advance(50);
+        assert!(source.push(2));
+        advance(49);
+        assert!(source.push(3));
+        assert!(lock(&items).is_empty());
+
+        // the window is closed exactly at the period, the held item is passed
+        advance(1);
+        assert!(source.push(4));
+        assert_eq!(*lock(&items), [3]);
+
+        // 4 is held until the next arrival, however late it is
+        advance(1000);
+        assert!(source.push(5));
+        assert_eq!(*lock(&items), [3, 4]);
+        advance(99);
+        assert!(source.push(6));
+        advance(100);
+        assert!(source.push(7));
+        assert_eq!(*lock(&items), [3, 4, 6]);
+    }
+
+    #[test]
+    fn throttle_window_boundaries() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let period = Duration::from_millis(100);
+        let throttle = Throttle { inner: source.handle(), period, now: fake_now };
+        let _subscription = throttle.subscribe_guarded(f).unwrap();
+
+        assert!(source.push(1));
+        advance(99);
+        assert!(source.push(2));
+        // the period is counted from the passed item, not from the suppressed ones
+        advance(1);
+        assert!(source.push(3));
+        advance(50);
+        assert!(source.push(4));
+        advance(50);
+        assert!(source.push(5));
+        advance(250);
+        assert!(source.push(6));
+        assert!(source.push(7));
+        assert_eq!(*lock(&items), [1, 3, 5, 6]);
+    }
+
+    #[test]
+    fn sample_and_throttle_with_system_clock() {
+        let source = Source::new();
+        let (sampled, f) = collect();
+        let _sample = source.handle().sample(Duration::from_secs(60)).subscribe_guarded(f).unwrap();
+        (0..10).for_each(|i| assert!(source.push(i)));
+        assert!(lock(&sampled).is_empty());
+
+        let source = Source::new();
+        let (throttled, f) = collect();
+        let _throttle =
+            source.handle().throttle(Duration::from_secs(60)).subscribe_guarded(f).unwrap();
+        (0..10).for_each(|i| assert!(source.push(i)));
+        assert_eq!(*lock(&throttled), [0]);
+    }
+
+    #[test]
+    fn flow_control_spills_while_paused() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let (stream, flow) = source.handle().with_flow_control(2);
+        let _subscription = stream.subscribe_guarded(f).unwrap();
+
+        assert!(source.push(1));
+        flow.pause();
+        assert!(flow.is_paused());
+        (2..=5).for_each(|i| assert!(source.push(i)));
+        assert_eq!(*lock(&items), [1]);
+        assert_eq!((flow.spilled(), flow.dropped(), flow.pending()), (2, 2, 2));
+
+        // the spilled items are delivered before the new ones
+        flow.resume();
+        assert!(!flow.is_paused());
+        assert!(source.push(6));
+        assert_eq!(*lock(&items), [1, 2, 3, 6]);
+        assert_eq!(flow.pending(), 0);
+    }
+
+    #[test]
+    fn flow_control_zero_capacity_drops() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let (stream, flow) = source.handle().with_flow_control(0);
+        let _subscription = stream.subscribe_guarded(f).unwrap();
+
+        flow.clone().pause();
+        (0..3).for_each(|i| assert!(source.push(i)));
+        flow.resume();
+        assert!(lock(&items).is_empty());
+        assert_eq!((flow.spilled(), flow.dropped()), (0, 3));
+    }
+
+    #[test]
+    fn flow_control_release_clears_spill() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let (stream, flow) = source.handle().with_flow_control(4);
+        let subscription = stream.subscribe_guarded(f).unwrap();
+
+        flow.pause();
+        (0..3).for_each(|i| assert!(source.push(i)));
+        drop(subscription);
+        assert!(!source.is_subscribed());
+        assert_eq!(flow.pending(), 0);
+        flow.resume();
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn boxed_pipelines_share_a_type() {
+        let (first, second) = (Source::new(), Source::new());
+        let streams: Vec<BoxStream<'_, u32>> = vec![
+            first.handle().map(|x: u32| x * 10).boxed(),
+            second.handle().filter(|x: &u32| x % 2 == 0).boxed(),
+        ];
+        let (items, _) = collect::<u32>();
+        let subscriptions = streams
+            .into_iter()
+            .map(|stream| {
+                let items = Arc::clone(&items);
+                // the combinators apply after the type is erased
+                stream.map(|x| x + 1).subscribe_guarded(move |x| lock(&items).push(x)).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(first.push(1));
+        assert!(second.push(2));
+        assert!(second.push(3));
+        assert_eq!(*lock(&items), [11, 3]);
+
+        drop(subscriptions);
+        assert!(!first.is_subscribed() && !second.is_subscribed());
+    }
+
+    #[test]
+    fn boxed_propagates_subscribe_error() {
+        let ret = Failing.boxed().subscribe_guarded(|_| {});
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+        assert_eq!(format!("{:?}", Failing.boxed()), "BoxStream { .. }");
+    }
+
+    #[test]
+    fn filter_tag_matches_root_tag() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription = source.handle().filter_tag("quotes").subscribe_guarded(f).unwrap();
+
+        for msg in [
+            "<quotes><quote secid=\"3\"/></quotes>",
+            "<quote secid=\"3\"/>",
+            "<quotes_ext/>",
+            "<?xml version=\"1.0\"?>\n<quotes/>",
+            "<orders><quotes/></orders>",
+            "quotes",
+            "",
+        ] {
+            assert!(source.push(msg));
+        }
+        assert_eq!(
+            *lock(&items),
+            ["<quotes><quote secid=\"3\"/></quotes>", "<?xml version=\"1.0\"?>\n<quotes/>"]
+        );
+    }
+
+    #[test]
+    fn filter_tags_matches_any() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let stream = source.handle().map(String::from).filter_tags(["quotes", "alltrades"]);
+        let _subscription = stream.subscribe_guarded(f).unwrap();
+
+        for msg in ["<alltrades/>", "<ticks/>", "<quotes/>", "<quotations/>"] {
+            assert!(source.push(msg));
+        }
+        assert_eq!(*lock(&items), ["<alltrades/>", "<quotes/>"]);
+
+        let source = Source::<&str>::new();
+        let (items, f) = collect();
+        let _subscription =
+            source.handle().filter_tags(Vec::<&str>::new()).subscribe_guarded(f).unwrap();
+        assert!(source.push("<quotes/>"));
+        assert!(lock(&items).is_empty());
+    }
+
+    #[test]
+    fn tee_delivers_to_both_branches() {
+        let source = Source::new();
+        let (a, b) = source.handle().tee();
+        let (left, f) = collect();
+        let sub_a = a.map(|x: u32| x * 10).subscribe_guarded(f).unwrap();
+        // the source is subscribed once every branch is settled
+        assert!(!source.is_subscribed());
+        let (right, f) = collect();
+        let sub_b = b.subscribe_guarded(f).unwrap();
+        assert!(source.is_subscribed());
+
+        (1..=3).for_each(|i| assert!(source.push(i)));
+        assert_eq!(*lock(&left), [10, 20, 30]);
+        assert_eq!(*lock(&right), [1, 2, 3]);
+
+        // the remaining branch keeps receiving the items
+        drop(sub_b);
+        assert!(source.push(4));
+        assert_eq!(*lock(&left), [10, 20, 30, 40]);
+        assert_eq!(*lock(&right), [1, 2, 3]);
+        drop(sub_a);
+        assert!(!source.is_subscribed());
+    }
+
+    #[test]
+    fn fanout_with_dropped_branch() {
+        let source = Source::new();
+        let mut branches = source.handle().fanout(3).into_iter();
+        let (first, f) = collect();
+        let _first = branches.next().unwrap().subscribe_guarded(f).unwrap();
+        drop(branches.next());
+        assert!(!source.is_subscribed());
+        let (third, f) = collect();
+        let _third = branches.next().unwrap().subscribe_guarded(f).unwrap();
+        assert!(source.is_subscribed());
+
+        assert!(source.push(String::from("<a/>")));
+        assert_eq!(*lock(&first), ["<a/>"]);
+        assert_eq!(*lock(&third), ["<a/>"]);
+    }
+
+    #[test]
+    fn fanout_without_subscribed_branches() {
+        let source = Source::<u8>::new();
+        drop(source.handle().fanout(2));
+        assert!(!source.is_subscribed());
+        assert!(source.handle().fanout(0).is_empty());
+    }
+
+    #[test]
+    fn tee_propagates_subscribe_error() {
+        let (a, b) = Failing.tee();
+        // the error is reported by the branch subscribing the source
+        let sub_a = a.subscribe_guarded(|_| {}).unwrap();
+        assert!(matches!(b.subscribe_guarded(|_| {}), Err(crate::Error::Timeout)));
+        drop(sub_a);
+    }
+
+    #[test]
+    fn route_dispatches_by_tag() {
+        let source = Source::new();
+        let (quotes, on_quotes) = collect();
+        let (orders, on_orders) = collect();
+        let (first, on_first) = collect();
+        let (other, on_other) = collect();
+        let subscription = source
+            .handle()
+            .route("quotes", on_quotes)
+            .route("orders", on_orders)
+            // handlers registered earlier take precedence
+            .route("quotes", on_first)
+            .default(on_other)
+            .subscribe_routed_guarded()
+            .unwrap();
+
+        for msg in ["<quotes/>", "<orders><order/></orders>", "<trades/>", "<?xml?><quotes/>", ""] {
+            assert!(source.push(msg));
+        }
+        assert_eq!(*lock(&quotes), ["<quotes/>", "<?xml?><quotes/>"]);
+        assert_eq!(*lock(&orders), ["<orders><order/></orders>"]);
+        assert!(lock(&first).is_empty());
+        assert_eq!(*lock(&other), ["<trades/>", ""]);
+
+        drop(subscription);
+        assert!(!source.is_subscribed());
+    }
+
+    #[test]
+    fn route_without_default_drops_unrouted() {
+        let source = Source::new();
+        let (quotes, f) = collect();
+        let _subscription = source.handle().route("quotes", f).subscribe_routed_guarded().unwrap();
+        assert!(source.push("<orders/>"));
+        assert!(source.push("<quotes/>"));
+        assert_eq!(*lock(&quotes), ["<quotes/>"]);
+
+        let ret = Failing.map(|_| "").route("quotes", |_| {}).subscribe_routed();
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn spawn_handler_runs_on_worker_thread() {
+        let source = Source::new();
+        let (items, _) = collect();
+        let out = Arc::clone(&items);
+        let mut handle = source
+            .handle()
+            .spawn_handler(16, move |x: u32| {
+                let name = std::thread::current().name().map(String::from);
+                lock(&out).push((x, name));
+            })
+            .unwrap();
+
+        (0..10).for_each(|i| assert!(source.push(i)));
+        wait_until(|| handle.processed() == 10);
+        assert_eq!(handle.lag(), 0);
+        let items = std::mem::take(&mut *lock(&items));
+        assert_eq!(items.iter().map(|(x, _)| *x).collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+        assert!(items.iter().all(|(_, name)| name.as_deref() == Some("txc-handler")));
+
+        handle.stop();
+        assert!(!source.is_subscribed());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn spawn_handler_drains_queue_after_stop() {
+        let source = Source::new();
+        let (gate_tx, gate_rx) = std::sync::mpsc::channel::<()>();
+        let gate = Mutex::new(gate_rx);
+        let (items, _) = collect();
+        let out = Arc::clone(&items);
+        let mut handle = source
+            .handle()
+            .spawn_handler(4, move |x: u32| {
+                lock(&gate).recv().unwrap();
+                lock(&out).push(x);
+            })
+            .unwrap();
+
+        (0..4).for_each(|i| assert!(source.push(i)));
+        // the first item may be taken by the worker already
+        wait_until(|| handle.lag() == 3);
+        handle.stop();
+        assert!(!source.is_subscribed());
+
+        (0..4).for_each(|_| gate_tx.send(()).unwrap());
+        handle.join().unwrap();
+        assert_eq!(*lock(&items), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn spawn_handler_join_reports_panic() {
+        let source = Source::new();
+        let mut handle = source
+            .handle()
+            .spawn_handler(4, |x: u32| {
+                if x == 1 {
+                    panic!("boom")
+                }
+            })
+            .unwrap();
+        assert!(source.push(0));
+        assert!(source.push(1));
+        handle.stop();
+        assert!(handle.join().is_err());
+
+        let ret = Failing.spawn_handler(4, |_| {});
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn catching_contains_downstream_panics() {
+        let source = Source::new();
+        let (panics, mut on_panic) = collect();
+        let (items, f) = collect();
+        let _subscription = source
+            .handle()
+            .catching(move |panic: Box<dyn Any + Send>| {
+                on_panic(panic.downcast::<String>().map(|s| *s).unwrap_or_default())
+            })
+            .map(|x: u32| if x % 2 == 1 { panic!("odd {x}") } else { x })
+            .subscribe_guarded(f)
+            .unwrap();
+
+        // the pipeline keeps delivering after a panic
+        (0..5).for_each(|i| assert!(source.push(i)));
+        assert_eq!(*lock(&items), [0, 2, 4]);
+        assert_eq!(*lock(&panics), ["odd 1", "odd 3"]);
+    }
+
+    #[test]
+    fn catching_passes_items_unchanged() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription =
+            source.handle().catching(|_| unreachable!()).subscribe_guarded(f).unwrap();
+        assert!(source.push("<a/>"));
+        assert_eq!(*lock(&items), ["<a/>"]);
+
+        let ret = Failing.catching(|_| {}).subscribe_guarded(|_| {});
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn subscribe_result_queues_errors() {
+        let source = Source::new();
+        let errors = source
+            .handle()
+            .subscribe_result(2, |x: u32| if x % 2 == 0 { Ok(()) } else { Err(x) })
+            .unwrap();
+
+        // the errors don't stop the handler, the oldest ones are dropped on overflow
+        (0..8).for_each(|i| assert!(source.push(i)));
+        assert_eq!(errors.dropped(), 2);
+        let errors_seen = errors.try_iter().map(|e| (e.error, e.message)).collect::<Vec<_>>();
+        assert_eq!(errors_seen, [(5, None), (7, None)]);
+
+        // the queue is closed once the handler is replaced
+        let _subscription = source.handle().subscribe_guarded(|_| {}).unwrap();
+        assert!(matches!(errors.try_recv(), Err(std::sync::mpsc::TryRecvError::Disconnected)));
+    }
+
+    #[test]
+    fn subscribe_result_with_message_copies_failed_message() {
+        let source = Source::new();
+        let errors = source
+            .handle()
+            .subscribe_result_with_message(4, |buf: &&str| match buf.strip_prefix("<error>") {
+                Some(_) => Err("error"),
+                None => Ok(()),
+            })
+            .unwrap();
+
+        for msg in ["<a/>", "<error>1</error>", "<b/>"] {
+            assert!(source.push(msg));
+        }
+        let e = errors.try_recv().unwrap();
+        assert_eq!(e.error, "error");
+        assert_eq!(e.message.unwrap().to_string(), "<error>1</error>");
+        assert!(errors.is_empty());
+
+        assert!(matches!(
+            Failing.subscribe_result(1, |_| Ok::<_, ()>(())),
+            Err(crate::Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn metered_counts_messages_and_bytes() {
+        let source = Source::new();
+        let (stream, metrics) = source.handle().metered();
+        assert_eq!((metrics.messages(), metrics.bytes(), metrics.last_arrival()), (0, 0, None));
+        let (items, f) = collect();
+        // counted before the following stages
+        let _subscription = stream.filter_tag("quotes").subscribe_guarded(f).unwrap();
+
+        let before = Instant::now();
+        for msg in ["<quotes/>", "<orders/>", "<quotes></quotes>"] {
+            assert!(source.push(msg));
+        }
+        let after = Instant::now();
+
+        assert_eq!(lock(&items).len(), 2);
+        let reader = metrics.clone();
+        let (messages, bytes) =
+            std::thread::spawn(move || (reader.messages(), reader.bytes())).join().unwrap();
+        assert_eq!((messages, bytes), (3, 9 + 9 + 17));
+        let last = metrics.last_arrival().unwrap();
+        assert!(last >= before && last <= after);
+    }
+
+    #[test]
+    fn window_slides_once_full() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription = source
+            .handle()
+            .window(3)
+            .map(|w| w.iter().copied().collect::<Vec<u32>>())
+            .subscribe_guarded(f)
+            .unwrap();
+
+        (1..=5).for_each(|i| assert!(source.push(i)));
+        assert_eq!(*lock(&items), [vec![1, 2, 3], vec![2, 3, 4], vec![3, 4, 5]]);
+    }
+
+    #[test]
+    fn window_partial_and_retained_views() {
+        let source = Source::new();
+        let (views, f) = collect();
+        let _subscription = source.handle().window_partial(2).subscribe_guarded(f).unwrap();
+
+        // the retained views are not changed by the following items
+        (1..=4).for_each(|i| assert!(source.push(i)));
+        let views = lock(&views);
+        assert_eq!(format!("{:?}", *views), "[[1], [1, 2], [2, 3], [3, 4]]");
+        assert!(views.iter().all(|w| w.len() <= 2));
+
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription =
+            source.handle().window(0).map(|w| w.len()).subscribe_guarded(f).unwrap();
+        (0..3).for_each(|i| assert!(source.push(i)));
+        assert_eq!(*lock(&items), [1, 1, 1]);
+    }
+
+    #[test]
+    fn on_idle_fires_once_per_idle_period() {
+        let source = Source::new();
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let (items, f) = collect();
+        let timeout = Duration::from_millis(100);
+        let subscription = source
+            .handle()
+            .on_idle(timeout, move || {
+                assert_eq!(std::thread::current().name(), Some("txc-idle"));
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .subscribe_guarded(f)
+            .unwrap();
+
+        // the first period starts with the subscription
+        wait_until(|| calls.load(Ordering::SeqCst) == 1);
+        std::thread::sleep(timeout * 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // the arrivals postpone the next call
+        let started = Instant::now();
+        while started.elapsed() < timeout * 2 {
+            assert!(source.push(1));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        wait_until(|| calls.load(Ordering::SeqCst) == 2);
+        assert!(lock(&items).iter().all(|&x| x == 1));
+
+        // no calls after the release
+        assert!(source.push(2));
+        drop(subscription);
+        assert!(!source.is_subscribed());
+        std::thread::sleep(timeout * 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn on_idle_stops_on_subscribe_error() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&calls);
+        let timeout = Duration::from_millis(50);
+        let on_idle = move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        };
+        let ret = Failing.on_idle(timeout, on_idle).subscribe_guarded(|_| {});
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+        std::thread::sleep(timeout * 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn latest_keeps_most_recent_item() {
+        let source = Source::new();
+        let latest = source.handle().latest().unwrap();
+        assert_eq!(latest.get(), None);
+        assert!(!latest.changed(Duration::ZERO));
+
+        (1..=3).for_each(|i| assert!(source.push(i)));
+        assert!(latest.changed(Duration::ZERO));
+        assert_eq!(latest.get(), Some(3));
+        // nothing new since the last read
+        assert!(!latest.changed(Duration::from_millis(10)));
+
+        assert_eq!(latest.take(), Some(3));
+        assert_eq!(latest.get(), None);
+        assert!(source.push(4));
+        assert_eq!(latest.get(), Some(4));
+    }
+
+    #[test]
+    fn latest_handles_track_changes_independently() {
+        let source = Source::new();
+        let latest = source.handle().latest().unwrap();
+        let other = latest.clone();
+
+        assert!(source.push(1));
+        assert!(latest.changed(Duration::ZERO));
+        assert!(!latest.changed(Duration::ZERO));
+        assert!(other.changed(Duration::ZERO));
+
+        // a waiting reader is woken by a new item
+        let reader =
+            std::thread::spawn(move || (other.changed(Duration::from_secs(5)), other.get()));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(source.push(2));
+        assert_eq!(reader.join().unwrap(), (true, Some(2)));
+    }
+
+    #[test]
+    fn latest_closed_on_replacement() {
+        let source = Source::new();
+        let latest = source.handle().latest().unwrap();
+        assert!(source.push(1));
+        assert!(!latest.is_closed());
+
+        let reader = latest.clone();
+        let waiter = std::thread::spawn(move || {
+            reader.get();
+            reader.changed(Duration::from_secs(5))
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        let _subscription = source.handle().subscribe_guarded(|_| {}).unwrap();
+        assert!(!waiter.join().unwrap());
+        assert!(latest.is_closed());
+        // the last item is kept
+        assert_eq!(latest.get(), Some(1));
+
+        assert!(matches!(Failing.latest(), Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn rate_per_second_sliding_window() {
+        let source = Source::new();
+        let (rates, mut on_rate) = collect();
+        let (items, f) = collect();
+        let rate = RatePerSecond {
+            inner: source.handle(),
+            window: Duration::from_secs(1),
+            threshold: 1.5,
+            observer: move |rate| on_rate(rate),
+            now: fake_now,
+        };
+        let _subscription = rate.subscribe_guarded(f).unwrap();
+
+        // the first rate is always reported, the next ones on a change above the threshold
+        for i in 0..5 {
+            assert!(source.push(i));
+            advance(100);
+        }
+        assert_eq!(*lock(&rates), [1.0, 3.0, 5.0]);
+
+        // the arrivals older than the window are evicted, one exactly at its boundary as well
+        advance(850);
+        assert!(source.push(5));
+        assert_eq!(*lock(&rates), [1.0, 3.0, 5.0, 2.0]);
+        advance(50);
+        assert!(source.push(6));
+        assert_eq!(*lock(&rates), [1.0, 3.0, 5.0, 2.0]);
+        assert_eq!(*lock(&items), (0..7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn rate_per_second_scales_by_window() {
+        let source = Source::new();
+        let (rates, mut on_rate) = collect();
+        let rate = RatePerSecond {
+            inner: source.handle(),
+            window: Duration::from_millis(250),
+            threshold: 0.0,
+            observer: move |rate| on_rate(rate),
+            now: fake_now,
+        };
+        let _subscription = rate.subscribe_guarded(|_| {}).unwrap();
+
+        (0..3).for_each(|i| assert!(source.push(i)));
+        // the earlier arrivals are evicted exactly at the window boundary
+        advance(250);
+        assert!(source.push(3));
+        assert_eq!(*lock(&rates), [4.0, 8.0, 12.0, 4.0]);
+    }
+
+    #[test]
+    fn group_by_tag_dispatches_to_channels() {
+        let source = Source::new();
+        let (groups, unmatched) = source.handle().group_by_tag(4).unwrap();
+        // the messages before the channel is created go to the default one
+        assert!(source.push("<quotes/>"));
+        let quotes = groups.receiver_for("quotes");
+        let trades = groups.receiver_for_with("alltrades", OverflowPolicy::DropOldest);
+
+        for msg in ["<quotes>1</quotes>", "<alltrades/>", "<orders/>", "<?xml?><quotes>2</quotes>"]
+        {
+            assert!(source.push(msg));
+        }
+        let text =
+            |rx: &Receiver<OwnedMessage>| rx.try_iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        assert_eq!(text(&quotes), ["<quotes>1</quotes>", "<?xml?><quotes>2</quotes>"]);
+        assert_eq!(text(&trades), ["<alltrades/>"]);
+        assert_eq!(text(&unmatched), ["<quotes/>", "<orders/>"]);
+
+        // the overflow policy is per channel
+        (0..6).for_each(|_| assert!(source.push("<alltrades/>")));
+        assert_eq!((trades.len(), trades.dropped()), (4, 2));
+
+        // the removed tag goes to the default channel, its channel is closed
+        groups.remove("quotes");
+        assert!(source.push("<quotes>3</quotes>"));
+        assert!(matches!(quotes.try_recv(), Err(std::sync::mpsc::TryRecvError::Disconnected)));
+        assert_eq!(text(&unmatched), ["<quotes>3</quotes>"]);
+    }
+
+    #[test]
+    fn group_by_tag_closed_on_replacement() {
+        let source = Source::new();
+        let (groups, unmatched) = source.handle().group_by_tag(4).unwrap();
+        let quotes = groups.receiver_for("quotes");
+        assert!(source.push("<quotes/>"));
+
+        let _subscription = source.handle().subscribe_guarded(|_: &str| {}).unwrap();
+        assert_eq!(quotes.iter().count(), 1);
+        assert!(unmatched.recv().is_err());
+        // the channels created after the release are closed
+        assert!(groups.receiver_for("orders").recv().is_err());
+        assert_eq!(format!("{groups:?}"), "GroupReceiver { tags: None, capacity: 4 }");
+    }
+
+    #[test]
+    fn log_each_passes_items_unchanged() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let stream = source.handle().log_each(TraceLevel::DEBUG, "quotes");
+        let _subscription = stream.subscribe_guarded(f).unwrap();
+        assert!(source.push("<quotes/>"));
+        assert!(source.push("<orders/>"));
+        assert_eq!(*lock(&items), ["<quotes/>", "<orders/>"]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn log_each_emits_an_event_per_item() {
+        use tracing::{field::Field, span, Event, Metadata, Subscriber};
+
+        // (level, target of the event, `target` field, message)
+        type Record = (TraceLevel, String, String, String);
+
+        #[derive(Default)]
+        struct Fields(String, String);
+
+        impl tracing::field::Visit for Fields {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                if field.name() == "target" {
+                    self.0 = value.into()
+                }
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                match field.name() {
+                    "message" => self.1 = format!("{value:?}"),
+                    "target" => self.0 = format!("{value:?}"),
+                    _ => {}
+                }
+            }
+        }
+
+        struct Recorder(Arc<Mutex<Vec<Record>>>);
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                let meta = event.metadata();
+                lock(&self.0).push((*meta.level(), meta.target().into(), fields.0, fields.1));
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder(Arc::clone(&records));
+        tracing::subscriber::with_default(recorder, || {
+            let source = Source::new();
+            let _subscription = source
+                .handle()
+                .log_each(TraceLevel::INFO, "quotes")
+                .log_each(TraceLevel::WARN, "all")
+                .subscribe_guarded(|_| {})
+                .unwrap();
+            assert!(source.push("<quotes/>"));
+        });
+
+        let event = |level, target: &str| {
+            (level, "libtxc::stream".to_string(), target.to_string(), "<quotes/>".to_string())
+        };
+        assert_eq!(
+            *lock(&records),
+            [event(TraceLevel::INFO, "quotes"), event(TraceLevel::WARN, "all")]
+        );
+    }
+
+    #[test]
+    fn map_parallel_ordered() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let subscription = source
+            .handle()
+            .map_parallel(4, ParallelMode::Ordered, |x: u64| {
+                // the earlier items are the slower ones
+                std::thread::sleep(Duration::from_millis(20 - 2 * x));
+                if x == 3 {
+                    panic!("skipped")
+                }
+                (x * 10, std::thread::current().name().map(String::from))
+            })
+            .subscribe_guarded(f)
+            .unwrap();
+
+        (0..8).for_each(|i| assert!(source.push(i)));
+        // the release waits for the queued items
+        drop(subscription);
+        assert!(!source.is_subscribed());
+
+        let items = lock(&items);
+        let values = items.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+        assert_eq!(values, [0, 10, 20, 40, 50, 60, 70]);
+        assert!(items.iter().all(|(_, name)| name.as_ref().unwrap().starts_with("txc-worker-")));
+    }
+
+    #[test]
+    fn map_parallel_unordered() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let subscription = source
+            .handle()
+            .map_parallel(2, ParallelMode::Unordered, |x: u32| {
+                if x == 0 {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                x
+            })
+            .subscribe_guarded(f)
+            .unwrap();
+
+        (0..6).for_each(|i| assert!(source.push(i)));
+        drop(subscription);
+        let items = lock(&items);
+        // the slow item doesn't hold the others back
+        assert_eq!(items.last(), Some(&0));
+        let mut sorted = items.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn map_parallel_propagates_subscribe_error() {
+        let ret = Failing.map_parallel(0, ParallelMode::Ordered, |x| x).subscribe_guarded(|_| {});
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn on_subscribe_runs_once_handler_is_installed() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let probe = source.handle();
+        let (called, mut on_called) = collect();
+        let subscription = source
+            .handle()
+            .on_subscribe(move || {
+                // the handler already receives the items
+                on_called(probe.is_subscribed());
+                assert!(probe.push(1));
+            })
+            .map(|x: u32| x + 1)
+            .subscribe_guarded(f)
+            .unwrap();
+
+        assert_eq!(*lock(&called), [true]);
+        assert!(source.push(2));
+        assert_eq!(*lock(&items), [2, 3]);
+        drop(subscription);
+        assert_eq!(lock(&called).len(), 1);
+    }
+
+    #[test]
+    fn on_subscribe_skipped_on_subscribe_error() {
+        let ret = Failing.on_subscribe(|| panic!("not called")).subscribe_guarded(|_| {});
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn first_matching_returns_first_match() {
+        let source = Source::new();
+        let pusher = source.handle();
+        let pushing = std::thread::spawn(move || {
+            wait_until(|| pusher.is_subscribed());
+            for msg in ["<quotes/>", "<server_status connected=\"false\"/>", "<server_status/>"] {
+                pusher.push(msg);
+            }
+        });
+
+        let status = source
+            .handle()
+            .first_matching(|buf| buf.starts_with("<server_status"), Duration::from_secs(5))
+            .unwrap();
+        pushing.join().unwrap();
+        assert_eq!(status.to_string(), "<server_status connected=\"false\"/>");
+        // the temporary handler is released
+        assert!(!source.is_subscribed());
+    }
+
+    #[test]
+    fn first_matching_times_out() {
+        let source = Source::new();
+        let pusher = source.handle();
+        let pushing = std::thread::spawn(move || {
+            wait_until(|| pusher.is_subscribed());
+            pusher.push("<quotes/>");
+        });
+
+        let ret = source.handle().first_matching(|_| false, Duration::from_millis(200));
+        pushing.join().unwrap();
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+        assert!(!source.is_subscribed());
+
+        let ret = Failing.map(|_| "").first_matching(|_| true, Duration::from_secs(5));
+        assert!(matches!(ret, Err(crate::Error::Timeout)));
+    }
+
+    #[test]
+    fn messages_parses_each_item() {
+        let source = Source::new();
+        let (items, f) = collect();
+        let _subscription = source.handle().messages().subscribe_guarded(f).unwrap();
+
+        for msg in [
+            "<server_status connected=\"true\"/>",
+            "<result success=\"false\"><message>Ошибка</message></result>",
+            "<overnight status=\"true\"/>",
+            "<server_status/>",
+            "text",
+        ] {
+            assert!(source.push(msg));
+        }
+
+        let items = lock(&items);
+        assert!(matches!(&items[0], Message::ServerStatus(_)));
+        assert!(matches!(&items[1], Message::Result { success: false, body } if body == "Ошибка"));
+        // the unknown and malformed messages are kept as is
+        let unknown = items[2..].iter().map(|msg| match msg {
+            Message::Unknown { tag, raw } => (tag.as_str(), raw.as_slice()),
+            msg => panic!("{msg:?}"),
+        });
+        assert_eq!(
+            unknown.collect::<Vec<_>>(),
+            [
+                ("overnight", &b"<overnight status=\"true\"/>"[..]),
+                ("server_status", b"<server_status/>"),
+                ("", b"text"),
+            ]
+        );
+    }
+
+    #[test]
+    fn pipeline_description_strips_paths_and_closures() {
+        let stream = Source::<u32>::new().filter(|x| x % 2 == 0).map(|x| x.to_string());
+        assert_eq!(stream.pipeline_description(), "Map<Filter<Source<u32>, _>, _>");
+        assert_eq!(Failing.enumerate().pipeline_description(), "Enumerate<Failing>");
+
+        assert_eq!(
+            short_type_name(
+                "core::option::Option<alloc::vec::Vec<(u8, &[alloc::string::String; 2])>>"
+            ),
+            "Option<Vec<(u8, &[String; 2])>>"
+        );
+        assert_eq!(short_type_name("app::main::{{closure}}"), "_");
+        assert_eq!(short_type_name("*const u8"), "*const u8");
+    }
+
+    #[test]
+    fn debug_shows_closure_types() {
+        let stream = Failing.filter(|_| true).map(|x| x + 1).inspect(|_| {});
+        let debug = format!("{stream:?}");
+        assert!(debug.starts_with("Inspect { inner: Map { inner: Filter { inner: Failing, f: "));
+        // the closures are named after the function defining them
+        let closure = "stream::tests::debug_shows_closure_types::{{closure}}";
+        assert_eq!(debug.matches(closure).count(), 3, "{debug}");
     }
 }
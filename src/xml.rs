@@ -0,0 +1,346 @@
+use super::{lang, Message, ParseError, SecuritiesResponse, Security, TCStr};
+use quick_xml::{
+    events::{attributes::Attributes, BytesStart, Event},
+    Reader,
+};
+use std::{borrow::Cow, fmt};
+
+/// Событие разбора XML документа
+///
+/// Имена, значения атрибутов и текст приведены к строкам, XML сущности(`&amp;` etc.) раскрыты.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum OwnedEvent {
+    /// Открывающий тэг `<name ..>`
+    Start {
+        /// Имя тэга
+        name: String,
+        /// Атрибуты тэга в порядке следования
+        attributes: Vec<(String, String)>,
+    },
+    /// Пустой элемент `<name ../>`
+    Empty {
+        /// Имя тэга
+        name: String,
+        /// Атрибуты тэга в порядке следования
+        attributes: Vec<(String, String)>,
+    },
+    /// Закрывающий тэг `</name>`
+    End {
+        /// Имя тэга
+        name: String,
+    },
+    /// Текстовое содержимое элемента, без начальных и конечных пробельных символов
+    Text(String),
+    /// Содержимое секции `<![CDATA[..]]>`
+    CData(String),
+}
+
+/// Разобранное сообщение коннектора, см. [`Stream::xml_events`](crate::Stream::xml_events)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParsedMessage {
+    /// Имя корневого тэга
+    pub tag: String,
+    /// События разбора в порядке следования, без объявления XML и комментариев
+    pub events: Vec<OwnedEvent>,
+}
+
+impl ParsedMessage {
+    /// Разбирает сообщение **bytes**
+    ///
+    /// # Errors
+    /// Некорректный или неполный XML документ, см. [`XmlError`]
+    pub fn parse(bytes: &[u8]) -> Result<Self, XmlError> {
+        let mut events = Vec::new();
+        visit_events(bytes, |event| events.push(event))?;
+        let tag = match events.first() {
+            Some(OwnedEvent::Start { name, .. } | OwnedEvent::Empty { name, .. }) => name.clone(),
+            _ => return Err(XmlError { position: 0, message: lang::XML_NO_ROOT.to_string() }),
+        };
+        Ok(Self { tag, events })
+    }
+}
+
+/// Ошибка разбора XML документа
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct XmlError {
+    /// Смещение в байтах, на котором обнаружена ошибка
+    pub position: usize,
+    /// Описание ошибки
+    pub message: String,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", lang::XML_MALFORMED, self.position, self.message)
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+impl From<XmlError> for ParseError {
+    fn from(e: XmlError) -> Self {
+        ParseError::Malformed { position: e.position, message: e.message }
+    }
+}
+
+/// Разбирает сообщение коннектора с проверкой корректности XML документа
+///
+/// В отличие от [`Message::parse`], документ предварительно читается
+/// [`quick-xml`](https://docs.rs/quick-xml/latest/quick_xml/), а некорректный документ и
+/// сообщение, которое не удалось разобрать, возвращаются как ошибка. Сообщения неизвестного
+/// типа возвращаются как [`Message::Unknown`].
+///
+/// Документ читается потоково без построения дерева элементов, инструменты сообщения
+/// `<securities>` разбираются по мере чтения элементов `<security>`.
+///
+/// # Пример
+/// ```no_run
+/// use libtxc::{parse_message, Message};
+///
+/// let mut txc = /*..*/;
+/// txc.input_stream().subscribe(|buf| match parse_message(&buf) {
+///     Ok(Message::Securities(securities)) => println!("{}", securities.securities.len()),
+///     Ok(_) => {}
+///     Err(e) => eprintln!("{e}"),
+/// })?;
+/// ```
+///
+/// # Errors
+/// - [`ParseError::Malformed`] - некорректный или неполный XML документ
+/// - ошибки разбора сообщения, см. [`ParseError`]
+pub fn parse_message(buf: &TCStr<'_>) -> Result<Message, ParseError> {
+    parse_message_bytes(buf.to_bytes())
+}
+
+/// Разбирает сообщение **bytes**, см. [`parse_message`]
+///
+/// # Errors
+/// См. [`parse_message`]
+pub fn parse_message_bytes(bytes: &[u8]) -> Result<Message, ParseError> {
+    let mut reader = Reader::from_reader(bytes);
+    let error = |reader: &Reader<&[u8]>, message: String| XmlError {
+        position: reader.buffer_position(),
+        message,
+    };
+
+    let mut securities: Option<Vec<Security>> = None;
+    let mut depth = 0usize;
+    // start of the `<security>` element being read
+    let mut record = 0usize;
+    loop {
+        let start = reader.buffer_position();
+        match reader.read_event().map_err(|e| error(&reader, e.to_string()))? {
+            Event::Start(element) => {
+                depth += 1;
+                if depth == 1 && element.name().as_ref() == b"securities" {
+                    securities = Some(Vec::new());
+                } else if depth == 2 && element.name().as_ref() == b"security" {
+                    record = start;
+                }
+            }
+            Event::Empty(element) => {
+                if let (Some(securities), 1, b"security") =
+                    (&mut securities, depth, element.name().as_ref())
+                {
+                    securities.push(Security::parse(&bytes[start..reader.buffer_position()])?);
+                }
+            }
+            Event::End(element) => {
+                if let (Some(securities), 2, b"security") =
+                    (&mut securities, depth, element.name().as_ref())
+                {
+                    securities.push(Security::parse(&bytes[record..reader.buffer_position()])?);
+                }
+                depth = depth.saturating_sub(1);
+            }
+            Event::Eof if depth > 0 => {
+                return Err(error(&reader, lang::XML_TRUNCATED.to_string()).into());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    match securities {
+        Some(securities) => Ok(Message::Securities(SecuritiesResponse { securities })),
+        None => Message::decode(bytes),
+    }
+}
+
+/// Вызывает **visitor** для каждого события разбора XML документа **bytes**
+///
+/// В отличие от [`ParsedMessage::parse`] не накапливает события. События, предшествующие
+/// ошибке, передаются в **visitor**.
+///
+/// # Errors
+/// Некорректный или неполный XML документ, например не закрытый элемент
+pub fn visit_events<F: FnMut(OwnedEvent)>(bytes: &[u8], mut visitor: F) -> Result<(), XmlError> {
+    let mut reader = Reader::from_reader(bytes);
+    reader.trim_text(true);
+    let error = |reader: &Reader<&[u8]>, message: String| XmlError {
+        position: reader.buffer_position(),
+        message,
+    };
+
+    let mut depth = 0usize;
+    loop {
+        let event = reader.read_event().map_err(|e| error(&reader, e.to_string()))?;
+        let event = match event {
+            Event::Start(start) => {
+                depth += 1;
+                let (name, attributes) = element(&start).map_err(|e| error(&reader, e))?;
+                OwnedEvent::Start { name, attributes }
+            }
+            Event::Empty(start) => {
+                let (name, attributes) = element(&start).map_err(|e| error(&reader, e))?;
+                OwnedEvent::Empty { name, attributes }
+            }
+            Event::End(end) => {
+                depth = depth.saturating_sub(1);
+                OwnedEvent::End { name: utf8(end.name().as_ref()).into_owned() }
+            }
+            Event::Text(text) => {
+                OwnedEvent::Text(text.unescape().map_err(|e| error(&reader, e.to_string()))?.into())
+            }
+            Event::CData(data) => OwnedEvent::CData(utf8(&data).into_owned()),
+            Event::Eof if depth > 0 => {
+                return Err(error(&reader, lang::XML_TRUNCATED.to_string()));
+            }
+            Event::Eof => return Ok(()),
+            Event::Decl(_) | Event::PI(_) | Event::DocType(_) | Event::Comment(_) => continue,
+        };
+        visitor(event);
+    }
+}
+
+type Element = (String, Vec<(String, String)>);
+
+fn element(start: &BytesStart<'_>) -> Result<Element, String> {
+    Ok((utf8(start.name().as_ref()).into_owned(), attributes(start.attributes())?))
+}
+
+fn attributes(attributes: Attributes<'_>) -> Result<Vec<(String, String)>, String> {
+    attributes
+        .map(|attr| {
+            let attr = attr.map_err(|e| e.to_string())?;
+            let value = attr.unescape_value().map_err(|e| e.to_string())?;
+            Ok((utf8(attr.key.as_ref()).into_owned(), value.into_owned()))
+        })
+        .collect()
+}
+
+#[inline]
+fn utf8(bytes: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(name: &str, attributes: &[(&str, &str)]) -> OwnedEvent {
+        let attributes = attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        OwnedEvent::Start { name: name.into(), attributes }
+    }
+
+    fn end(name: &str) -> OwnedEvent {
+        OwnedEvent::End { name: name.into() }
+    }
+
+    #[test]
+    fn event_sequence() {
+        let msg = "<?xml version=\"1.0\"?><!-- quotes --><quotes><quote secid=\"3\">\
+                   <board>TQBR</board><price> 265.31 </price><source>A &amp; B</source>\
+                   <buy/><text><![CDATA[<raw>]]></text></quote></quotes>";
+        let parsed = ParsedMessage::parse(msg.as_bytes()).unwrap();
+        assert_eq!(parsed.tag, "quotes");
+        assert_eq!(
+            parsed.events,
+            [
+                start("quotes", &[]),
+                start("quote", &[("secid", "3")]),
+                start("board", &[]),
+                OwnedEvent::Text("TQBR".into()),
+                end("board"),
+                start("price", &[]),
+                OwnedEvent::Text("265.31".into()),
+                end("price"),
+                start("source", &[]),
+                OwnedEvent::Text("A & B".into()),
+                end("source"),
+                OwnedEvent::Empty { name: "buy".into(), attributes: Vec::new() },
+                start("text", &[]),
+                OwnedEvent::CData("<raw>".into()),
+                end("text"),
+                end("quote"),
+                end("quotes"),
+            ]
+        );
+
+        let parsed = ParsedMessage::parse(b"<server_status connected=\"a&lt;b\"/>").unwrap();
+        assert_eq!(parsed.tag, "server_status");
+        assert_eq!(
+            parsed.events,
+            [OwnedEvent::Empty {
+                name: "server_status".into(),
+                attributes: vec![("connected".into(), "a<b".into())]
+            }]
+        );
+    }
+
+    #[test]
+    fn malformed_documents() {
+        let truncated = ParsedMessage::parse(b"<quotes><quote>").unwrap_err();
+        assert_eq!(truncated.message, lang::XML_TRUNCATED);
+        assert_eq!(truncated.position, 15);
+
+        assert!(ParsedMessage::parse(b"<quotes></orders>").is_err());
+        assert!(ParsedMessage::parse(b"<quote secid=\"3>").is_err());
+        assert!(ParsedMessage::parse(b"<quotes>&unknown;</quotes>").is_err());
+
+        let no_root = ParsedMessage::parse(b"  ").unwrap_err();
+        assert_eq!(no_root, XmlError { position: 0, message: lang::XML_NO_ROOT.into() });
+
+        // the events preceding the error are visited
+        let mut events = Vec::new();
+        assert!(visit_events(b"<a><b>text</b><c>", |e| events.push(e)).is_err());
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn parse_message_streams_securities() {
+        let msg = b"<securities><security secid=\"3\" active=\"true\"><seccode>SBER</seccode>\
+                    <board>TQBR</board></security><security secid=\"4\" active=\"false\"/>\
+                    </securities>";
+        match parse_message_bytes(msg).unwrap() {
+            Message::Securities(response) => {
+                let ids = response.securities.iter().map(|s| (s.secid, s.active));
+                assert_eq!(ids.collect::<Vec<_>>(), [(3, true), (4, false)]);
+                assert_eq!(response.securities[0].seccode, "SBER");
+            }
+            msg => panic!("{msg:?}"),
+        }
+
+        assert!(matches!(
+            parse_message_bytes(b"<server_status connected=\"true\"/>"),
+            Ok(Message::ServerStatus(_))
+        ));
+        assert!(matches!(
+            parse_message_bytes(b"<overnight status=\"true\"/>"),
+            Ok(Message::Unknown { .. })
+        ));
+        assert!(matches!(
+            parse_message_bytes(b"<securities><security secid=\"3\">"),
+            Err(ParseError::Malformed { .. })
+        ));
+        assert_eq!(
+            parse_message_bytes(b"<securities><security/></securities>"),
+            Err(ParseError::MissingAttribute("secid"))
+        );
+        // the document is valid, the message is not
+        assert_eq!(
+            parse_message_bytes(b"<server_status/>"),
+            Err(ParseError::MissingAttribute("connected"))
+        );
+    }
+}